@@ -0,0 +1,76 @@
+//! Benchmarks for `format_tables` on a large generated report
+//!
+//! Run with `cargo bench`. The document mimics a generated report: many tables
+//! of a few dozen rows, unaligned, with column formulas, a running total that
+//! reads the previous row, a totals row and a cross-table reference in each.
+//! A second, templated document repeats one table and directive many times.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mdutils::table::format_tables;
+
+/// Builds a document of `tables` tables with `rows` data rows each
+fn report(tables: usize, rows: usize) -> String {
+    let mut doc = String::from("# Report\n\n");
+    for table in 0..tables {
+        doc.push_str(&format!("## Section {}\n\n", table + 1));
+        doc.push_str(
+            "| Item | Qty | Price | Total | Share | Running |\n|---|---|---|---|---|---|\n",
+        );
+        for row in 0..rows {
+            doc.push_str(&format!(
+                "| item {} | {} | {}.{:02} | | | |\n",
+                row,
+                (row * 7 + table) % 40 + 1,
+                (row * 13 + table * 3) % 90 + 5,
+                (row * 37) % 100
+            ));
+        }
+        doc.push_str(&format!(
+            "<!-- md-table: id=\"t{}\"; totals=D:sum; D_ = B_ * C_; E_ = D_ / sum(D_) * 100; F_ = F^ + D_ -->\n",
+            table
+        ));
+        if table > 0 {
+            doc.push_str(&format!(
+                "<!-- md-table: B1 = from(\"t{}\", B2) -->\n",
+                table - 1
+            ));
+        }
+        doc.push('\n');
+    }
+    doc
+}
+
+/// Builds a templated document: `copies` of the same small table and formulas
+fn templated(copies: usize) -> String {
+    let table = "| Quarter | Revenue | Costs | Profit | Margin | Share |\n\
+                 |---|---|---|---|---|---|\n\
+                 | Q1 | 1200 | 800 | | | |\n\
+                 | Q2 | 1500 | 900 | | | |\n\
+                 | Q3 | 1100 | 950 | | | |\n\
+                 | Q4 | 1800 | 1000 | | | |\n\
+                 <!-- md-table: totals=B:sum,C:sum,D:sum; D_ = B_ - C_; E_ = (D_ / B_) * 100 // 1; \
+                 let total = sum(D_); F_ = D_ / total * 100 -->\n\n";
+    table.repeat(copies)
+}
+
+fn bench_format_tables(c: &mut Criterion) {
+    let small = report(20, 20);
+    let large = report(200, 50);
+    assert!(!format_tables(&large).has_errors());
+
+    c.bench_function("format_tables 20x20", |b| {
+        b.iter(|| format_tables(black_box(&small)))
+    });
+    c.bench_function("format_tables 200x50", |b| {
+        b.iter(|| format_tables(black_box(&large)))
+    });
+
+    let templated = templated(100);
+    assert!(!format_tables(&templated).has_errors());
+    c.bench_function("format_tables 100 identical tables", |b| {
+        b.iter(|| format_tables(black_box(&templated)))
+    });
+}
+
+criterion_group!(benches, bench_format_tables);
+criterion_main!(benches);
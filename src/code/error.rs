@@ -30,6 +30,14 @@ pub enum CodeError {
     #[error("Code block at line {line} is missing required field: {field}")]
     MissingField { line: usize, field: String },
 
+    /// No bin, and no program is known for the block's fence language
+    #[error("Code block at line {line} is missing required field: bin (no program is known for the fence language '{language}'; add bin=\"...\" or map it with <!-- md-code-config: {language}=\"...\" -->)")]
+    UnknownLanguage { line: usize, language: String },
+
+    /// bin="shebang" on a block whose code has no `#!` line to run it with
+    #[error("bin=\"shebang\" runs the code as a script, but its first line is not a #! line naming the interpreter")]
+    MissingShebang,
+
     /// Empty bin specification
     #[error("Empty bin specification at line {line}")]
     EmptyBin { line: usize },
@@ -42,6 +50,62 @@ pub enum CodeError {
     #[error("Process execution failed: {0}")]
     ProcessError(String),
 
+    /// The directory given with cwd= does not exist
+    #[error("Working directory '{path}' does not exist or is not a directory")]
+    MissingDirectory { path: String },
+
+    /// stdin-from names a block that does not exist
+    #[error("Code block '{id}' at line {line} reads stdin from unknown code block '{producer}'")]
+    UnknownStdinSource {
+        id: String,
+        line: usize,
+        producer: String,
+    },
+
+    /// A block to run was asked for by an id no block has
+    #[error("Unknown code block ID '{id}' (available IDs: {available})")]
+    UnknownId { id: String, available: String },
+
+    /// depends-on names a block that does not exist
+    #[error("Code block '{id}' at line {line} depends on unknown code block '{dependency}'")]
+    UnknownDependency {
+        id: String,
+        line: usize,
+        dependency: String,
+    },
+
+    /// Blocks feed each other's stdin in a cycle; `line` is that of the first
+    /// block in the cycle
+    #[error("Code blocks {ids} read stdin from each other in a cycle")]
+    StdinCycle { ids: String, line: usize },
+
+    /// Blocks depend on each other in a cycle, through depends-on and possibly
+    /// stdin-from; `line` is that of the first block in the cycle
+    #[error("Code blocks {ids} depend on each other in a cycle")]
+    DependencyCycle { ids: String, line: usize },
+
+    /// stdin-from names a block whose run failed
+    #[error(
+        "Code block '{id}' was not run because code block '{producer}' it reads stdin from failed"
+    )]
+    StdinSourceFailed { id: String, producer: String },
+
+    /// depends-on names a block whose run failed
+    #[error(
+        "Code block '{id}' was not run because code block '{dependency}' it depends on failed"
+    )]
+    DependencyFailed { id: String, dependency: String },
+
+    /// The program exited with a non-zero status in a block with
+    /// require-success="true"; `detail` is ": " and the last line of its
+    /// stderr, or empty
+    #[error("Program exited with status {status}{detail}")]
+    NonZeroExit { status: String, detail: String },
+
+    /// The output could not be written to the file given with output-file=
+    #[error("Failed to write output file '{path}': {reason}")]
+    OutputFileError { path: String, reason: String },
+
     /// Timeout error
     #[error("Code execution timed out after {seconds} seconds")]
     Timeout { seconds: u64 },
@@ -70,6 +134,22 @@ impl CodeError {
         }
     }
 
+    /// The line of the directive the error is about, if it is about one
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            CodeError::DuplicateId { line, .. }
+            | CodeError::DuplicateOutputId { line, .. }
+            | CodeError::MissingField { line, .. }
+            | CodeError::UnknownLanguage { line, .. }
+            | CodeError::EmptyBin { line }
+            | CodeError::UnknownStdinSource { line, .. }
+            | CodeError::UnknownDependency { line, .. }
+            | CodeError::StdinCycle { line, .. }
+            | CodeError::DependencyCycle { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
     /// Create a missing field error
     pub fn missing_field(line: usize, field: impl Into<String>) -> Self {
         CodeError::MissingField {
@@ -1,61 +1,321 @@
 use crate::code::error::CodeError;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+/// Seconds a program may run when its directive gives no `timeout`
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+
+/// The `bin` that runs a block as a script of its own, whose `#!` line
+/// names the interpreter
+pub const SHEBANG_BIN: &str = "shebang";
 
 pub struct ExecutionResult {
-    #[allow(dead_code)]
     pub success: bool,
+    /// The program's exit status, or `None` if a signal ended it
+    pub exit_code: Option<i32>,
     pub output: String,
+    /// The program's stderr with `separate_stderr`, and empty otherwise
+    pub stderr: String,
+    /// Wall-clock time from starting the program until it exited
+    pub elapsed: Duration,
 }
 
-/// Executes code with the specified interpreter and timeout
-pub fn execute_code(
-    code: &str,
-    bin: &str,
-    timeout_seconds: Option<u64>,
-) -> Result<ExecutionResult, CodeError> {
-    let timeout = timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+/// How to run a code block: the program and what it gets besides the code
+#[derive(Debug, Default)]
+pub struct ExecutionOptions<'a> {
+    /// The command and its own arguments (`python3 -u`)
+    pub bin: &'a str,
+    /// Arguments after those in `bin`
+    pub args: &'a [String],
+    /// Environment variables added to the inherited environment
+    pub env: &'a [(String, String)],
+    /// Directory to run in, which must exist
+    pub cwd: Option<&'a Path>,
+    /// Input for the program's stdin; the code then goes in a script file
+    pub input: Option<&'a str>,
+    /// Seconds to wait before giving up (default: 30)
+    pub timeout_seconds: Option<u64>,
+    /// Keep stderr apart: the output is stdout even on failure, and stderr
+    /// goes to `ExecutionResult::stderr`
+    pub separate_stderr: bool,
+    /// Leave terminal escape sequences (colors, cursor moves, titles) in the
+    /// output instead of stripping them
+    pub keep_ansi: bool,
+    /// Lines of output (and of stderr) to keep; any more are replaced by a
+    /// line saying how many were left out
+    pub max_lines: Option<usize>,
+}
 
-    // Parse the bin string into command and args
-    let parts: Vec<&str> = bin.split_whitespace().collect();
-    if parts.is_empty() {
-        return Err(CodeError::DirectiveParseError(
-            "Empty bin specification".to_string(),
-        ));
-    }
+/// Executes code with the interpreter and settings in `options`
+///
+/// Without `input` or `args`, the code is written to the program's stdin.
+/// Otherwise the code is written to a temporary script file whose path
+/// follows the arguments given in `bin` (so `bin="jq -f"` or `bin="python3"`
+/// run it), `args` come after it as the script's own arguments, and stdin
+/// gets `input`, if any. A `bin` that already reads its script from stdin
+/// (`python3 -`, `bash -s`) is given the code there even with `args`. Stdin
+/// is fed from another thread so the timeout applies even if the program
+/// never reads it.
+///
+/// With `bin` set to [`SHEBANG_BIN`], the code must start with a `#!` line;
+/// it is written to an executable script file that is run directly, so the
+/// `#!` line picks the interpreter, and stdin gets only `input`.
+pub fn execute_code(code: &str, options: &ExecutionOptions) -> Result<ExecutionResult, CodeError> {
+    let bin = options.bin;
+    let timeout = options.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+
+    let (mut command, script) = if bin == SHEBANG_BIN {
+        if !code.starts_with("#!") {
+            return Err(CodeError::MissingShebang);
+        }
+        let script = ScriptFile::create(code)?;
+        script.make_executable()?;
+        (Command::new(&script.path), Some(script))
+    } else {
+        // Parse the bin string into command and args
+        let parts: Vec<&str> = bin.split_whitespace().collect();
+        if parts.is_empty() {
+            return Err(CodeError::DirectiveParseError(
+                "Empty bin specification".to_string(),
+            ));
+        }
+
+        let mut command = Command::new(parts[0]);
+        command.args(&parts[1..]);
 
-    let command = parts[0];
-    let args = &parts[1..];
+        // With input for stdin, or args that must follow the script rather
+        // than go to the interpreter, the code is run from a script file
+        let from_stdin =
+            options.input.is_none() && (options.args.is_empty() || reads_script_from_stdin(&parts));
+        let script = if from_stdin {
+            None
+        } else {
+            let script = ScriptFile::create(code)?;
+            command.arg(&script.path);
+            Some(script)
+        };
+        (command, script)
+    };
+
+    if let Some(cwd) = options.cwd {
+        if !cwd.is_dir() {
+            return Err(CodeError::MissingDirectory {
+                path: cwd.display().to_string(),
+            });
+        }
+        command.current_dir(cwd);
+    }
 
     // Spawn the process
-    let mut child = Command::new(command)
-        .args(args)
+    let started = Instant::now();
+    command
+        .args(options.args)
+        .envs(options.env.iter().map(|(key, value)| (key, value)))
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
+        .stderr(Stdio::piped());
+    let mut child = spawn(&mut command)
         .map_err(|e| CodeError::ProcessError(format!("Failed to execute '{}': {}", bin, e)))?;
 
-    // Write code to stdin
+    // Write the input (or the code) to stdin; a program that exits without
+    // reading all of it is not an error. A program run with a script file
+    // has already been given its code.
     if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(code.as_bytes())
-            .map_err(|e| CodeError::IoError(format!("Failed to write to stdin: {}", e)))?;
+        let input = match options.input {
+            Some(input) => input,
+            None if script.is_some() => "",
+            None => code,
+        }
+        .to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
     }
 
     // Wait for the process with timeout
-    let result = wait_with_timeout(child, Duration::from_secs(timeout))?;
+    let result = wait_with_timeout(
+        child,
+        started,
+        Duration::from_secs(timeout),
+        options.separate_stderr,
+    );
+    drop(script);
+    result.map(|result| {
+        let clean = |text: String| {
+            let text = if options.keep_ansi {
+                text
+            } else {
+                strip_ansi(&text)
+            };
+            match options.max_lines {
+                Some(max_lines) => truncate_lines(&text, max_lines),
+                None => text,
+            }
+        };
+        ExecutionResult {
+            output: clean(result.output),
+            stderr: clean(result.stderr),
+            ..result
+        }
+    })
+}
+
+/// Whether a `bin` ends by telling its interpreter to read the script from
+/// stdin, so that any arguments after it belong to the script
+fn reads_script_from_stdin(parts: &[&str]) -> bool {
+    parts.len() > 1 && matches!(parts.last(), Some(&"-" | &"-s"))
+}
 
-    Ok(result)
+/// Keeps the first `max_lines` lines of `text`, replacing the rest with a
+/// line such as `… (3950 more lines truncated)`
+///
+/// Only the line count matters, so the same output is always cut the same way.
+pub fn truncate_lines(text: &str, max_lines: usize) -> String {
+    let total = text.lines().count();
+    if total <= max_lines {
+        return text.to_string();
+    }
+    let mut kept: String = text
+        .lines()
+        .take(max_lines)
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    let left_out = total - max_lines;
+    kept.push_str(&format!(
+        "… ({} more {} truncated)\n",
+        left_out,
+        if left_out == 1 { "line" } else { "lines" }
+    ));
+    kept
 }
 
-/// Waits for a child process with a timeout
+/// Removes terminal escape sequences, such as colors, from program output
+///
+/// Handles CSI sequences (`ESC [ ... m` for colors, cursor moves, erasing),
+/// OSC sequences such as window titles (`ESC ] ... BEL`), the other string
+/// sequences ended by `ESC \`, and two-character escapes. Only text that
+/// starts with ESC is removed, so brackets in the output itself are kept.
+pub fn strip_ansi(text: &str) -> String {
+    const ESC: char = '\u{1b}';
+    const BEL: char = '\u{7}';
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ESC {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates, then one final character
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC, DCS, SOS, PM and APC: a string ended by BEL or ESC \
+            Some(']' | 'P' | 'X' | '^' | '_') => {
+                while let Some(c) = chars.next() {
+                    if c == BEL {
+                        break;
+                    }
+                    if c == ESC {
+                        chars.next_if_eq(&'\\');
+                        break;
+                    }
+                }
+            }
+            // Other escapes: intermediates such as `(`, then one final character
+            Some(c) if ('\u{20}'..='\u{2f}').contains(&c) => {
+                while chars
+                    .next_if(|c| ('\u{20}'..='\u{2f}').contains(c))
+                    .is_some()
+                {}
+                chars.next();
+            }
+            Some(_) | None => {}
+        }
+    }
+    stripped
+}
+
+/// A temporary file holding a code block, removed when dropped
+struct ScriptFile {
+    path: PathBuf,
+}
+
+impl ScriptFile {
+    fn create(code: &str) -> Result<Self, CodeError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "mdutils-code-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, code).map_err(|e| {
+            CodeError::IoError(format!(
+                "Failed to write script file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(ScriptFile { path })
+    }
+
+    /// Lets the script be run directly, for its `#!` line to pick the interpreter
+    fn make_executable(&self) -> Result<(), CodeError> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(0o700)).map_err(
+                |e| {
+                    CodeError::IoError(format!(
+                        "Failed to make script file {} executable: {}",
+                        self.path.display(),
+                        e
+                    ))
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ScriptFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Starts a command, retrying while its program is busy
+///
+/// A script file that was just written can still be open for writing in a
+/// process another thread forked before the file was closed, until that
+/// process execs; running the script meanwhile fails with "text file busy".
+fn spawn(command: &mut Command) -> std::io::Result<std::process::Child> {
+    let mut attempts = 0;
+    loop {
+        match command.spawn() {
+            Err(e) if e.kind() == std::io::ErrorKind::ExecutableFileBusy && attempts < 10 => {
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Waits for a child process, started at `started`, with a timeout
 fn wait_with_timeout(
     child: std::process::Child,
+    started: Instant,
     timeout: Duration,
+    separate_stderr: bool,
 ) -> Result<ExecutionResult, CodeError> {
     use std::sync::mpsc;
     use std::thread;
@@ -71,18 +331,26 @@ fn wait_with_timeout(
     // Wait for the result with timeout
     match rx.recv_timeout(timeout) {
         Ok(Ok(output)) => {
+            let elapsed = started.elapsed();
             let success = output.status.success();
-            let output_str = if success {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            let (output_str, stderr) = if separate_stderr {
+                (stdout, stderr)
+            } else if success {
                 // On success, capture stdout
-                String::from_utf8_lossy(&output.stdout).to_string()
+                (stdout, String::new())
             } else {
                 // On failure, capture stderr
-                String::from_utf8_lossy(&output.stderr).to_string()
+                (stderr, String::new())
             };
 
             Ok(ExecutionResult {
                 success,
+                exit_code: output.status.code(),
                 output: output_str,
+                stderr,
+                elapsed,
             })
         }
         Ok(Err(e)) => Err(CodeError::ProcessError(format!(
@@ -102,7 +370,14 @@ mod tests {
     #[test]
     fn test_execute_simple_code() {
         let code = "print('hello world')";
-        let result = execute_code(code, "python3", Some(5));
+        let result = execute_code(
+            code,
+            &ExecutionOptions {
+                bin: "python3",
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
 
         if let Ok(exec_result) = result {
             assert!(exec_result.success);
@@ -114,7 +389,14 @@ mod tests {
     #[test]
     fn test_execute_with_args() {
         let code = "console.log('hello from node')";
-        let result = execute_code(code, "node -e", Some(5));
+        let result = execute_code(
+            code,
+            &ExecutionOptions {
+                bin: "node -e",
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
 
         // This test might fail if node is not installed, which is okay
         // We're just testing the parsing logic
@@ -126,10 +408,249 @@ mod tests {
     #[test]
     fn test_execute_failing_code() {
         let code = "import sys; sys.exit(1)";
-        let result = execute_code(code, "python3", Some(5));
+        let result = execute_code(
+            code,
+            &ExecutionOptions {
+                bin: "python3",
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
 
         if let Ok(exec_result) = result {
             assert!(!exec_result.success);
+            assert_eq!(exec_result.exit_code, Some(1));
+        }
+    }
+
+    #[test]
+    fn test_execute_passes_extra_args() {
+        let code = "import sys; print(sys.argv[1:])";
+        let args = vec!["--format".to_string(), "my plot".to_string()];
+        let run = |bin: &str, code: &str| {
+            execute_code(
+                code,
+                &ExecutionOptions {
+                    bin,
+                    args: &args,
+                    timeout_seconds: Some(5),
+                    ..Default::default()
+                },
+            )
+        };
+
+        // The args reach the script, not the interpreter, whether the code is
+        // run from a script file or read from stdin
+        for bin in ["python3", "python3 -"] {
+            if let Ok(exec_result) = run(bin, code) {
+                assert!(exec_result.success, "{}: {}", bin, exec_result.output);
+                assert_eq!(exec_result.output.trim(), "['--format', 'my plot']");
+            }
+        }
+        if let Ok(exec_result) = run("bash", "echo \"$# arguments: $2\"") {
+            assert!(exec_result.success, "{}", exec_result.output);
+            assert_eq!(exec_result.output, "2 arguments: my plot\n");
+        }
+    }
+
+    #[test]
+    fn test_execute_in_cwd() {
+        let dir = std::env::temp_dir().join(format!("mdutils-cwd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("data.txt"), "from the data file\n").unwrap();
+
+        let result = execute_code(
+            "cat data.txt",
+            &ExecutionOptions {
+                bin: "bash",
+                cwd: Some(&dir),
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
+        let missing = dir.join("missing");
+        let error = execute_code(
+            "pwd",
+            &ExecutionOptions {
+                bin: "bash",
+                cwd: Some(&missing),
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        if let Ok(exec_result) = result {
+            assert!(exec_result.success);
+            assert_eq!(exec_result.output, "from the data file\n");
+        }
+        let message = error.err().unwrap().to_string();
+        assert!(
+            message.contains(&missing.display().to_string()),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_execute_shebang_script() {
+        let code = "#!/usr/bin/env python3\nimport sys\nprint(sys.argv[1:], sys.stdin.read())\n";
+        let args = vec!["--flag".to_string()];
+        let result = execute_code(
+            code,
+            &ExecutionOptions {
+                bin: SHEBANG_BIN,
+                args: &args,
+                input: Some("input"),
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
+        if let Ok(exec_result) = result {
+            assert!(exec_result.success, "{}", exec_result.output);
+            assert_eq!(exec_result.output, "['--flag'] input\n");
+        }
+
+        // Without a #! line nothing says which interpreter to use
+        let error = execute_code(
+            "print('hello')\n",
+            &ExecutionOptions {
+                bin: SHEBANG_BIN,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(error, Err(CodeError::MissingShebang)));
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        // Colors and styles
+        assert_eq!(
+            strip_ansi("\x1b[0;32mok\x1b[0m \x1b[1;38;5;196merror\x1b[m"),
+            "ok error"
+        );
+        // Cursor moves and erasing, as progress bars print them
+        assert_eq!(strip_ansi("50%\x1b[2K\x1b[1G100%\x1b[?25h\n"), "50%100%\n");
+        // Window titles and hyperlinks, ended by BEL or ESC \
+        assert_eq!(strip_ansi("\x1b]0;my title\x07done"), "done");
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        // Character set selection and other two-character escapes
+        assert_eq!(strip_ansi("\x1b(Bplain\x1b7\x1b8"), "plain");
+        // Text that only looks like a sequence is kept
+        assert_eq!(
+            strip_ansi("[0;32m array[1] [[x]] ]0; m"),
+            "[0;32m array[1] [[x]] ]0; m"
+        );
+        // An escape cut off at the end is dropped
+        assert_eq!(strip_ansi("text\x1b["), "text");
+        assert_eq!(strip_ansi("text\x1b"), "text");
+    }
+
+    #[test]
+    fn test_truncate_lines() {
+        let text = "one\ntwo\nthree\n";
+        // Exactly the limit, or under it, is left alone
+        assert_eq!(truncate_lines(text, 3), text);
+        assert_eq!(truncate_lines(text, 10), text);
+        assert_eq!(truncate_lines("", 1), "");
+        // One line over is replaced by the marker
+        assert_eq!(
+            truncate_lines(text, 2),
+            "one\ntwo\n… (1 more line truncated)\n"
+        );
+        assert_eq!(
+            truncate_lines("one\ntwo\nthree", 1),
+            "one\n… (2 more lines truncated)\n"
+        );
+    }
+
+    #[test]
+    fn test_execute_strips_ansi_unless_kept() {
+        let code = "printf '\\033[31mred\\033[0m\\n'";
+        let options = ExecutionOptions {
+            bin: "sh",
+            timeout_seconds: Some(5),
+            ..Default::default()
+        };
+        if let Ok(exec_result) = execute_code(code, &options) {
+            assert_eq!(exec_result.output, "red\n");
+        }
+
+        let kept = ExecutionOptions {
+            keep_ansi: true,
+            ..options
+        };
+        if let Ok(exec_result) = execute_code(code, &kept) {
+            assert_eq!(exec_result.output, "\x1b[31mred\x1b[0m\n");
+        }
+    }
+
+    #[test]
+    fn test_execute_with_input() {
+        // The code runs from a script file while the input arrives on stdin
+        let result = execute_code(
+            "import sys\nprint(sorted(sys.stdin.read().split()), sys.argv[1:])",
+            &ExecutionOptions {
+                bin: "python3",
+                args: &["--flag".to_string()],
+                input: Some("pear\napple\n"),
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
+
+        if let Ok(exec_result) = result {
+            assert!(exec_result.success);
+            assert_eq!(exec_result.output.trim(), "['apple', 'pear'] ['--flag']");
+        }
+    }
+
+    #[test]
+    fn test_execute_times_out_with_unread_input() {
+        // A program that never reads its input still times out
+        let input = "x".repeat(1 << 20);
+        let result = execute_code(
+            "sleep 5",
+            &ExecutionOptions {
+                bin: "bash",
+                input: Some(&input),
+                timeout_seconds: Some(1),
+                ..Default::default()
+            },
+        );
+
+        if let Err(error) = result {
+            assert!(matches!(error, CodeError::Timeout { seconds: 1 }));
+        }
+    }
+
+    #[test]
+    fn test_execute_sets_env() {
+        let code = "echo \"$API_URL|$GREETING\"";
+        let env = vec![
+            ("API_URL".to_string(), "http://localhost:8080".to_string()),
+            ("GREETING".to_string(), "hello, world".to_string()),
+        ];
+        let result = execute_code(
+            code,
+            &ExecutionOptions {
+                bin: "bash",
+                env: &env,
+                timeout_seconds: Some(5),
+                ..Default::default()
+            },
+        );
+
+        if let Ok(exec_result) = result {
+            assert!(exec_result.success);
+            assert_eq!(
+                exec_result.output.trim(),
+                "http://localhost:8080|hello, world"
+            );
         }
     }
 }
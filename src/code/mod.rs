@@ -4,13 +4,157 @@ mod parser;
 
 pub use error::CodeError;
 
-use crate::common::{get_fence_type, is_code_fence, ProcessingError, ProcessingResult};
-use executor::execute_code;
+use crate::common::{
+    get_fence_type, is_code_fence, with_line_endings, ProcessingError, ProcessingResult,
+};
+use executor::{
+    execute_code, ExecutionOptions, ExecutionResult, DEFAULT_TIMEOUT_SECONDS, SHEBANG_BIN,
+};
 use parser::{
     is_md_code_comment, is_md_code_output_comment, parse_document, parse_md_code_output_directive,
-    validate_unique_ids, CodeBlock, OutputBlock,
+    validate_unique_ids, CodeBlock, CodeBlockDirective, OutputBlock, OutputDirective,
+    ParsedDocument,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Options controlling how md-code blocks are processed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeOptions {
+    /// Directory that relative `cwd` directories are resolved from
+    pub base_dir: PathBuf,
+    /// Run every block, even those whose output was produced by the same code
+    pub force: bool,
+    /// Run only the blocks with these ids, leaving the others' output as it
+    /// is; when empty, every block runs
+    pub only: Vec<String>,
+    /// Record a failing block's error and go on with the rest instead of
+    /// returning the document unchanged
+    pub keep_going: bool,
+    /// How many blocks may run at the same time; a block still waits for the
+    /// blocks it names with `stdin-from` or `depends-on`
+    pub jobs: usize,
+    /// Lines of output to keep for blocks without their own `max-lines`
+    pub max_lines: Option<usize>,
+}
+
+impl Default for CodeOptions {
+    fn default() -> Self {
+        CodeOptions {
+            base_dir: PathBuf::from("."),
+            force: false,
+            only: Vec::new(),
+            keep_going: false,
+            jobs: 1,
+            max_lines: None,
+        }
+    }
+}
+
+/// What `md code` would run for one md-code block, as listed by [`list_code_blocks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlockInfo {
+    /// The block's id
+    pub id: String,
+    /// 1-based line of the block's opening fence
+    pub line: usize,
+    /// The program to run, or `None` if the directive has no `bin` and none
+    /// is known for the fence language
+    pub bin: Option<String>,
+    /// Arguments passed after those in `bin`
+    pub args: Vec<String>,
+    /// Seconds before the run is stopped (the default if none is given)
+    pub timeout_seconds: u64,
+    /// Id of the block whose output is piped to stdin, if any
+    pub stdin_from: Option<String>,
+    /// Ids of the blocks that run before this one
+    pub depends_on: Vec<String>,
+}
+
+/// Lists the md-code blocks of a document without running any of them
+///
+/// Blocks are listed in document order.
+///
+/// # Errors
+///
+/// Returns the error [`process_code_blocks`] would stop at before running
+/// anything: a malformed directive, an unclosed code block or a duplicate id.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::code::list_code_blocks;
+///
+/// let doc = "```python\nprint(1)\n```\n<!-- md-code: id=\"one\"; bin=\"python3\"; timeout=5 -->\n";
+/// let blocks = list_code_blocks(doc).unwrap();
+/// assert_eq!(blocks[0].id, "one");
+/// assert_eq!(blocks[0].line, 1);
+/// assert_eq!(blocks[0].bin.as_deref(), Some("python3"));
+/// assert_eq!(blocks[0].timeout_seconds, 5);
+/// ```
+pub fn list_code_blocks(text: &str) -> Result<Vec<CodeBlockInfo>, CodeError> {
+    let document = parse_document(text)?;
+    validate_unique_ids(&document.code_blocks)?;
+    Ok(document
+        .code_blocks
+        .iter()
+        .filter_map(|block| {
+            let directive = block.directive.as_ref()?;
+            Some(CodeBlockInfo {
+                id: directive.id.clone(),
+                line: block.start_line + 1,
+                bin: resolve_bin(block, directive, &document)
+                    .ok()
+                    .map(str::to_string),
+                args: directive.args.clone(),
+                timeout_seconds: directive.timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS),
+                stdin_from: directive.stdin_from.clone(),
+                depends_on: directive.depends_on.clone(),
+            })
+        })
+        .collect())
+}
+
+/// The program a block runs: its `bin`, or else the program for its fence
+/// language, from the document's md-code-config comments or [`default_bin`]
+///
+/// Only the first word of the fence's info string is the language, so a
+/// fence opened with `python title="demo"` maps like one with `python`.
+fn resolve_bin<'a>(
+    block: &CodeBlock,
+    directive: &'a CodeBlockDirective,
+    document: &'a ParsedDocument,
+) -> Result<&'a str, CodeError> {
+    if let Some(bin) = directive.bin.as_deref() {
+        return Ok(bin);
+    }
+    let line = block.start_line + 1;
+    let language = match block.language.split_whitespace().next() {
+        Some(language) => language.to_lowercase(),
+        None => return Err(CodeError::missing_field(line, "bin")),
+    };
+    match document.bins.get(&language) {
+        Some(bin) => Ok(bin),
+        None => default_bin(&language).ok_or(CodeError::UnknownLanguage { line, language }),
+    }
+}
+
+/// The program that runs code in a fence language when a block gives no `bin`
+fn default_bin(language: &str) -> Option<&'static str> {
+    match language {
+        "python" | "python3" | "py" => Some("python3"),
+        "sh" | "bash" | "shell" => Some("bash"),
+        "zsh" => Some("zsh"),
+        "ruby" | "rb" => Some("ruby"),
+        "node" | "javascript" | "js" => Some("node"),
+        "perl" | "pl" => Some("perl"),
+        "php" => Some("php"),
+        _ => None,
+    }
+}
 
 /// Processes markdown code blocks with md-code directives
 ///
@@ -23,82 +167,847 @@ use std::collections::HashMap;
 /// Note: Unlike other modules, code processing errors are often fatal (e.g., duplicate IDs,
 /// missing bin specification). In these cases, the original input is returned unchanged
 /// and the error is reported.
+///
+/// Relative `cwd` directories are resolved from the current directory; use
+/// [`process_code_blocks_in`] to resolve them from the document's directory.
+#[allow(dead_code)]
 pub fn process_code_blocks(text: &str) -> ProcessingResult {
-    let mut errors = Vec::new();
+    process_code_blocks_in(text, Path::new("."))
+}
+
+/// Processes markdown code blocks like [`process_code_blocks`], resolving
+/// relative `cwd` directories from `base_dir`
+#[allow(dead_code)]
+pub fn process_code_blocks_in(text: &str, base_dir: &Path) -> ProcessingResult {
+    let options = CodeOptions {
+        base_dir: base_dir.to_path_buf(),
+        ..CodeOptions::default()
+    };
+    process_code_blocks_with_options(text, &options)
+}
+
+/// Processes markdown code blocks like [`process_code_blocks`] with the given options
+///
+/// Each output comment records a hash of the code, program, arguments,
+/// environment, working directory and stdin that produced it. A block whose
+/// output comment already holds the current hash is not run again and its
+/// output is left as it is, unless `options.force` is set.
+///
+/// With `options.only`, blocks that are not named keep their output as it is.
+/// One that feeds a named block through `stdin-from` passes on its existing
+/// output, and is only run (without writing its output) if it has none.
+pub fn process_code_blocks_with_options(text: &str, options: &CodeOptions) -> ProcessingResult {
+    with_line_endings(text, |text| process_code_blocks_lf(text, options))
+}
 
+/// Processes the code blocks of a document with `\n` line endings
+fn process_code_blocks_lf(text: &str, options: &CodeOptions) -> ProcessingResult {
     // Parse the document to find all code blocks and output blocks
-    let (code_blocks, mut output_blocks) = match parse_document(text) {
-        Ok(result) => result,
+    let document = match parse_document(text) {
+        Ok(document) => document,
         Err(e) => {
-            errors.push(ProcessingError::code(0, e.to_string()));
+            let errors = vec![ProcessingError::code(0, e.to_string())];
             return ProcessingResult::with_errors(text.to_string(), errors);
         }
     };
 
-    // Validate that all code block IDs are unique
-    if let Err(e) = validate_unique_ids(&code_blocks) {
-        errors.push(ProcessingError::code(0, e.to_string()));
+    let Runs {
+        results: execution_results,
+        mut errors,
+        stopped,
+    } = run_blocks(&document, options);
+    if stopped {
         return ProcessingResult::with_errors(text.to_string(), errors);
     }
 
-    // Execute code blocks and collect results
+    // Reconstruct the document
+    match reconstruct_document(text, &document, &execution_results) {
+        Ok(output) => ProcessingResult::with_errors(output, errors),
+        Err(e) => {
+            errors.push(ProcessingError::code(0, e.to_string()));
+            ProcessingResult::with_errors(text.to_string(), errors)
+        }
+    }
+}
+
+/// A block whose output differs from the output recorded in the document,
+/// as found by [`verify_code_blocks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedOutput {
+    /// The block's id
+    pub id: String,
+    /// 1-based line of the block's opening fence
+    pub line: usize,
+    /// Whether this is the block's stderr (`stderr="separate"`) rather than its output
+    pub stderr: bool,
+    /// The output recorded in the document, empty if there is none
+    pub expected: String,
+    /// The output the block gives now
+    pub actual: String,
+}
+
+/// What [`verify_code_blocks`] found
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// The outputs that changed, in document order
+    pub changed: Vec<ChangedOutput>,
+    /// Blocks that could not run, and problems that stopped the run
+    pub errors: Vec<ProcessingError>,
+}
+
+/// Runs the code blocks of a document and compares their output with the
+/// output blocks already in it, without changing the document
+///
+/// Every block runs, whether or not its code changed since the output was
+/// recorded. Output is compared after removing trailing whitespace from each
+/// line and trailing blank lines, unless `strict` is set. Blocks with
+/// `verify="skip"` or `output="none"` are run but not compared, and with
+/// `options.only` only the named blocks are compared.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::code::{verify_code_blocks, CodeOptions};
+///
+/// let doc = "```bash\necho hi\n```\n<!-- md-code: id=\"greet\"; bin=\"bash\" -->\n\n\
+///            Output:\n```\nhello\n```\n<!-- md-code-output: id=\"greet\" -->\n";
+/// let report = verify_code_blocks(doc, &CodeOptions::default(), false);
+/// assert_eq!(report.changed[0].id, "greet");
+/// assert_eq!(report.changed[0].actual, "hi\n");
+/// ```
+pub fn verify_code_blocks(text: &str, options: &CodeOptions, strict: bool) -> VerifyReport {
+    let text = text.replace("\r\n", "\n");
+    let document = match parse_document(&text) {
+        Ok(document) => document,
+        Err(e) => {
+            return VerifyReport {
+                changed: Vec::new(),
+                errors: vec![ProcessingError::code(0, e.to_string())],
+            }
+        }
+    };
+
+    let options = CodeOptions {
+        force: true,
+        ..options.clone()
+    };
+    let Runs {
+        results, errors, ..
+    } = run_blocks(&document, &options);
+
+    let same = |expected: &str, actual: &str| {
+        if strict {
+            expected == actual
+        } else {
+            normalize_output(expected) == normalize_output(actual)
+        }
+    };
+    let mut changed = Vec::new();
+    for block in &document.code_blocks {
+        let Some(directive) = &block.directive else {
+            continue;
+        };
+        let Some(run) = results.get(&directive.id) else {
+            continue;
+        };
+        if directive.skip_verify || directive.hide_output {
+            continue;
+        }
+
+        let expected = document
+            .output_blocks
+            .get(&directive.id)
+            .and_then(|output| recorded_output(output, &options.base_dir))
+            .unwrap_or_default();
+        let expected_stderr = document
+            .stderr_blocks
+            .get(&directive.id)
+            .map_or(String::new(), |output| output.content.clone());
+        for (stderr, expected, actual) in [
+            (false, expected, &run.output),
+            (true, expected_stderr, &run.stderr),
+        ] {
+            if !same(&expected, actual) {
+                changed.push(ChangedOutput {
+                    id: directive.id.clone(),
+                    line: block.start_line + 1,
+                    stderr,
+                    expected,
+                    actual: actual.clone(),
+                });
+            }
+        }
+    }
+
+    VerifyReport { changed, errors }
+}
+
+/// Output with trailing whitespace removed from each line and trailing
+/// blank lines dropped, for comparisons that ignore them
+fn normalize_output(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_end_matches('\n').to_string()
+}
+
+/// What running the blocks of a document gave
+struct Runs {
+    /// The outputs of the blocks that were asked for and ran, by id
+    results: HashMap<String, RunOutput>,
+    errors: Vec<ProcessingError>,
+    /// Whether an error stopped the run, so the document must be left as it is
+    stopped: bool,
+}
+
+/// Runs the blocks of a parsed document in execution order
+fn run_blocks(document: &ParsedDocument, options: &CodeOptions) -> Runs {
+    let mut errors = Vec::new();
+    let code_blocks = &document.code_blocks;
+    let stop = |line: usize, error: CodeError| Runs {
+        results: HashMap::new(),
+        errors: vec![ProcessingError::code(line, error.to_string())],
+        stopped: true,
+    };
+
+    // Validate that all code block IDs are unique
+    if let Err(e) = validate_unique_ids(code_blocks) {
+        return stop(0, e);
+    }
+
+    if let Err(e) = validate_selected_ids(code_blocks, &options.only) {
+        return stop(0, e);
+    }
+    let producers: Vec<&str> = code_blocks
+        .iter()
+        .filter_map(|block| block.directive.as_ref()?.stdin_from.as_deref())
+        .collect();
+
+    // Blocks reading another block's output with stdin-from, or depending on
+    // it with depends-on, run after it
+    let order = match execution_order(code_blocks) {
+        Ok(order) => order,
+        Err(e) => return stop(e.line().unwrap_or(0), e),
+    };
+
+    // Execute code blocks and collect results; every output is kept for stdin-from.
+    // Up to `jobs` blocks run at once, each starting once the blocks it needs have run.
+    let jobs = options.jobs.max(1);
     let mut execution_results = HashMap::new();
+    let mut captured: HashMap<&str, String> = HashMap::new();
+    let mut settled: HashSet<&str> = HashSet::new();
+    let mut failed: HashSet<&str> = HashSet::new();
+    let mut failures: Vec<(usize, ProcessingError)> = Vec::new();
+    let mut stopped = false;
+    let mut pending: Vec<(usize, &CodeBlock, &CodeBlockDirective)> = order
+        .into_iter()
+        .map(|idx| &code_blocks[idx])
+        .filter_map(|block| Some((block, block.directive.as_ref()?)))
+        .enumerate()
+        .map(|(position, (block, directive))| (position, block, directive))
+        .collect();
 
-    for block in &code_blocks {
-        if let Some(ref directive) = block.directive {
-            // Validate that bin is specified
-            let bin = match directive.bin.as_ref() {
-                Some(bin) => bin,
-                None => {
-                    let err = CodeError::missing_field(block.start_line + 1, "bin");
-                    errors.push(ProcessingError::code(block.start_line + 1, err.to_string()));
-                    return ProcessingResult::with_errors(text.to_string(), errors);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        let mut running = 0;
+        loop {
+            let mut next = 0;
+            while !stopped && running < jobs && next < pending.len() {
+                let (position, block, directive) = pending[next];
+                let waiting = directive
+                    .stdin_from
+                    .iter()
+                    .chain(&directive.depends_on)
+                    .any(|needed| !settled.contains(needed.as_str()));
+                if waiting {
+                    next += 1;
+                    continue;
                 }
-            };
+                pending.remove(next);
+
+                match prepare_block(
+                    block, directive, options, document, &producers, &captured, &failed,
+                ) {
+                    Prepared::Skipped => {
+                        settled.insert(&directive.id);
+                    }
+                    Prepared::Settled(output) => {
+                        captured.insert(&directive.id, output);
+                        settled.insert(&directive.id);
+                    }
+                    Prepared::Failed(error) => {
+                        failures.push((position, error));
+                        failed.insert(&directive.id);
+                        settled.insert(&directive.id);
+                        stopped |= !(options.keep_going || directive.continue_on_error);
+                    }
+                    Prepared::Run(run) => {
+                        let sender = sender.clone();
+                        running += 1;
+                        scope.spawn(move || {
+                            let result = execute_code(&block.content, &run.execution())
+                                .and_then(|result| check_exit_status(directive, result));
+                            sender.send((position, directive, run, result)).ok();
+                        });
+                    }
+                }
+            }
 
-            // Execute the code
-            match execute_code(&block.content, bin, directive.timeout) {
+            if running == 0 {
+                break;
+            }
+            let (position, directive, run, result) = receiver.recv().expect("a block is running");
+            running -= 1;
+            settled.insert(&directive.id);
+
+            // Output for a file is written there, unless it is empty
+            let result = result.and_then(|result| {
+                match &directive.output_file {
+                    Some(file) if run.selected && !result.output.trim().is_empty() => {
+                        write_output_file(file, &options.base_dir, &result.output)?
+                    }
+                    _ => {}
+                }
+                Ok(result)
+            });
+            match result {
                 Ok(result) => {
-                    // Only store non-empty outputs
-                    if !result.output.trim().is_empty() {
-                        execution_results.insert(directive.id.clone(), result.output);
+                    // Only store the outputs of the blocks asked for; a block with
+                    // output="none" drops them unless its program failed
+                    if run.selected {
+                        let hidden = directive.hide_output && result.success;
+                        let output = RunOutput {
+                            output: if hidden {
+                                String::new()
+                            } else {
+                                result.output.clone()
+                            },
+                            stderr: if hidden { String::new() } else { result.stderr },
+                            hash: run.hash,
+                            hidden,
+                            time: directive
+                                .show_timing
+                                .then(|| format_elapsed(result.elapsed)),
+                        };
+                        execution_results.insert(directive.id.clone(), output);
                     }
+                    captured.insert(&directive.id, result.output);
                 }
                 Err(e) => {
-                    errors.push(ProcessingError::code(block.start_line + 1, e.to_string()));
-                    return ProcessingResult::with_errors(text.to_string(), errors);
+                    // A failed block keeps whatever output it had
+                    let line = run.block_line;
+                    failures.push((position, ProcessingError::code(line, e.to_string())));
+                    failed.insert(&directive.id);
+                    stopped |= !(options.keep_going || directive.continue_on_error);
                 }
             }
         }
+    });
+
+    // Report failures in the order the blocks would run one at a time
+    failures.sort_by_key(|(position, _)| *position);
+    errors.extend(failures.into_iter().map(|(_, error)| error));
+    Runs {
+        results: execution_results,
+        errors,
+        stopped,
     }
+}
 
-    // Reconstruct the document
-    match reconstruct_document(text, &code_blocks, &mut output_blocks, &execution_results) {
-        Ok(output) => ProcessingResult::with_errors(output, errors),
-        Err(e) => {
-            errors.push(ProcessingError::code(0, e.to_string()));
-            ProcessingResult::with_errors(text.to_string(), errors)
+/// What a block's run wrote, for its output and stderr blocks
+struct RunOutput {
+    output: String,
+    /// Empty unless the block asks for `stderr="separate"`
+    stderr: String,
+    hash: String,
+    /// Whether the block's output blocks are removed (`output="none"`)
+    hidden: bool,
+    /// How long the program ran, for blocks with `timing="true"`
+    time: Option<String>,
+}
+
+/// What to do with a block whose stdin source, if any, has run
+enum Prepared<'a> {
+    /// Nothing; the block was not asked for and nothing reads its output
+    Skipped,
+    /// The block's output is already known, from an unselected block or an
+    /// unchanged one
+    Settled(String),
+    /// The block cannot run
+    Failed(ProcessingError),
+    /// The block has to be executed
+    Run(BlockRun<'a>),
+}
+
+/// Everything needed to execute a block, owned so it can move to another thread
+struct BlockRun<'a> {
+    directive: &'a CodeBlockDirective,
+    max_lines: Option<usize>,
+    bin: &'a str,
+    cwd: Option<PathBuf>,
+    input: Option<String>,
+    hash: String,
+    selected: bool,
+    block_line: usize,
+}
+
+impl BlockRun<'_> {
+    fn execution(&self) -> ExecutionOptions<'_> {
+        ExecutionOptions {
+            bin: self.bin,
+            args: &self.directive.args,
+            env: &self.directive.env,
+            cwd: self.cwd.as_deref(),
+            input: self.input.as_deref().or(self.directive.stdin.as_deref()),
+            timeout_seconds: self.directive.timeout,
+            separate_stderr: self.directive.separate_stderr,
+            keep_ansi: self.directive.keep_ansi,
+            max_lines: self.max_lines,
         }
     }
 }
 
+/// Decides how to handle a block given the outputs captured so far
+fn prepare_block<'a>(
+    block: &CodeBlock,
+    directive: &'a CodeBlockDirective,
+    options: &CodeOptions,
+    document: &'a ParsedDocument,
+    producers: &[&str],
+    captured: &HashMap<&str, String>,
+    failed: &HashSet<&str>,
+) -> Prepared<'a> {
+    // Blocks that were not asked for keep their output
+    let selected = options.only.is_empty() || options.only.contains(&directive.id);
+    let output_blocks = &document.output_blocks;
+    if !selected {
+        let recorded = output_blocks
+            .get(&directive.id)
+            .and_then(|output| recorded_output(output, &options.base_dir));
+        match recorded {
+            Some(output) => return Prepared::Settled(output),
+            None if !producers.contains(&directive.id.as_str()) => return Prepared::Skipped,
+            None => {}
+        }
+    }
+
+    // Validate that bin is specified, or known for the fence language
+    let block_line = block.start_line + 1;
+    let bin = match resolve_bin(block, directive, document) {
+        Ok(bin) => bin,
+        Err(err) => return Prepared::Failed(ProcessingError::code(block_line, err.to_string())),
+    };
+
+    // A block whose stdin source or dependency failed cannot run either
+    if let Some(dependency) = directive
+        .depends_on
+        .iter()
+        .find(|dependency| failed.contains(dependency.as_str()))
+    {
+        let err = CodeError::DependencyFailed {
+            id: directive.id.clone(),
+            dependency: dependency.clone(),
+        };
+        return Prepared::Failed(ProcessingError::code(block_line, err.to_string()));
+    }
+    let input = match directive.stdin_from.as_ref() {
+        Some(producer) => match captured.get(producer.as_str()) {
+            Some(output) => Some(output.clone()),
+            None => {
+                let err = CodeError::StdinSourceFailed {
+                    id: directive.id.clone(),
+                    producer: producer.clone(),
+                };
+                return Prepared::Failed(ProcessingError::code(block_line, err.to_string()));
+            }
+        },
+        None => None,
+    };
+
+    let run = BlockRun {
+        directive,
+        max_lines: directive.max_lines.or(options.max_lines),
+        bin,
+        cwd: directive.cwd.as_ref().map(|cwd| options.base_dir.join(cwd)),
+        input,
+        hash: String::new(),
+        selected,
+        block_line,
+    };
+
+    // Reuse the existing output if the same code produced it
+    let hash = code_hash(&block.content, &run.execution(), directive);
+    // (a block with no output but some stderr records its hash with the stderr)
+    let cached = [output_blocks, &document.stderr_blocks]
+        .iter()
+        .filter_map(|blocks| blocks.get(&directive.id))
+        .any(|output| !options.force && output.hash.as_ref() == Some(&hash));
+    // (output written to a file is only reused while the file is there)
+    let recorded = match output_blocks.get(&directive.id) {
+        Some(output) => recorded_output(output, &options.base_dir),
+        None => Some(String::new()),
+    };
+    match recorded {
+        Some(output) if cached => Prepared::Settled(output),
+        _ => Prepared::Run(BlockRun { hash, ..run }),
+    }
+}
+
+/// The output recorded for a block: the content of its output block, or of
+/// the file it wrote with `output-file`, if that file can be read
+fn recorded_output(output: &OutputBlock, base_dir: &Path) -> Option<String> {
+    match &output.file {
+        Some(file) => std::fs::read_to_string(base_dir.join(file)).ok(),
+        None => Some(output.content.clone()),
+    }
+}
+
+/// Writes a block's output to the file given with `output-file`, resolved
+/// from `base_dir`, creating the directories it needs
+fn write_output_file(file: &str, base_dir: &Path, output: &str) -> Result<(), CodeError> {
+    let path = base_dir.join(file);
+    let error = |e: std::io::Error| CodeError::OutputFileError {
+        path: path.display().to_string(),
+        reason: e.to_string(),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(error)?;
+    }
+    std::fs::write(&path, output).map_err(error)
+}
+
+/// Applies a block's `require-success` and `exit-code` settings to its run
+///
+/// A non-zero exit status is an error with `require-success="true"`, and is
+/// otherwise added to the output as `[exit status: N]` with `exit-code="show"`.
+fn check_exit_status(
+    directive: &CodeBlockDirective,
+    mut result: ExecutionResult,
+) -> Result<ExecutionResult, CodeError> {
+    if result.success {
+        return Ok(result);
+    }
+    let status = result
+        .exit_code
+        .map_or("unknown (ended by a signal)".to_string(), |code| {
+            code.to_string()
+        });
+
+    if directive.require_success {
+        let stderr = if directive.separate_stderr {
+            &result.stderr
+        } else {
+            &result.output
+        };
+        let detail = stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map_or(String::new(), |line| format!(": {}", line.trim()));
+        return Err(CodeError::NonZeroExit { status, detail });
+    }
+    if directive.show_exit_code {
+        if !result.output.is_empty() && !result.output.ends_with('\n') {
+            result.output.push('\n');
+        }
+        result
+            .output
+            .push_str(&format!("[exit status: {}]\n", status));
+    }
+    Ok(result)
+}
+
+/// Checks that every id given to run exists, listing the ids that do if not
+fn validate_selected_ids(code_blocks: &[CodeBlock], only: &[String]) -> Result<(), CodeError> {
+    let ids: Vec<&str> = code_blocks
+        .iter()
+        .filter_map(|block| block.directive.as_ref().map(|d| d.id.as_str()))
+        .collect();
+    match only.iter().find(|id| !ids.contains(&id.as_str())) {
+        Some(id) => Err(CodeError::UnknownId {
+            id: id.clone(),
+            available: if ids.is_empty() {
+                "none".to_string()
+            } else {
+                ids.join(", ")
+            },
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Counts the code blocks that have an md-code directive, for `md stats`
+pub(crate) fn count_code_blocks(text: &str) -> Result<usize, CodeError> {
+    parse_document(text).map(|document| document.code_blocks.len())
+}
+
+/// Finds problems with the md-code directives without running any code
+///
+/// Reports the problems that would stop [`process_code_blocks`] before it runs
+/// anything: a malformed directive or output comment, a duplicate id, a
+/// `stdin-from` or `depends-on` naming an unknown block or forming a cycle,
+/// and blocks without
+/// a `bin`.
+pub(crate) fn lint_code_blocks(text: &str) -> Vec<ProcessingError> {
+    let finding = |e: CodeError| ProcessingError::code(e.line().unwrap_or(0), e.to_string());
+
+    let document = match parse_document(text) {
+        Ok(document) => document,
+        Err(e) => return vec![finding(e)],
+    };
+    let mut findings = Vec::new();
+    if let Err(e) = validate_unique_ids(&document.code_blocks) {
+        findings.push(finding(e));
+    }
+    if let Err(e) = execution_order(&document.code_blocks) {
+        findings.push(finding(e));
+    }
+    for block in &document.code_blocks {
+        let Some(directive) = &block.directive else {
+            continue;
+        };
+        match resolve_bin(block, directive, &document) {
+            Ok(SHEBANG_BIN) if !block.content.starts_with("#!") => {
+                let line = block.start_line + 1;
+                findings.push(ProcessingError::code(
+                    line,
+                    CodeError::MissingShebang.to_string(),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => findings.push(finding(e)),
+        }
+    }
+    findings
+}
+
+/// Hashes everything that determines a block's output, as stored in its output comment
+///
+/// The working directory is hashed as written in the directive, so the hash
+/// does not depend on where `md` runs. This is 64-bit FNV-1a, printed as 12
+/// hex digits; it only has to tell whether the code changed, not resist tampering.
+/// `exit-code="show"`, `stderr="separate"`, `ansi="keep"`, a line limit,
+/// an output file and `output="none"` change the output or where it goes, so
+/// they change the hash too, as does `timing="true"` so that turning it on
+/// records a time. The time itself is never hashed.
+fn code_hash(code: &str, execution: &ExecutionOptions, directive: &CodeBlockDirective) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |text: &str| {
+        // Length-prefix each field so that moving text between fields changes the hash
+        for byte in (text.len() as u64)
+            .to_le_bytes()
+            .iter()
+            .chain(text.as_bytes())
+        {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+
+    feed(execution.bin);
+    for arg in execution.args {
+        feed(arg);
+    }
+    feed("");
+    for (key, value) in execution.env {
+        feed(key);
+        feed(value);
+    }
+    feed("");
+    feed(directive.cwd.as_deref().unwrap_or(""));
+    feed(execution.input.unwrap_or(""));
+    feed(code);
+    if directive.show_exit_code {
+        feed("exit-code=show");
+    }
+    if execution.separate_stderr {
+        feed("stderr=separate");
+    }
+    if execution.keep_ansi {
+        feed("ansi=keep");
+    }
+    if let Some(max_lines) = execution.max_lines {
+        feed(&format!("max-lines={}", max_lines));
+    }
+    if let Some(file) = &directive.output_file {
+        feed(&format!("output-file={}", file));
+    }
+    if directive.hide_output {
+        feed("output=none");
+    }
+    if directive.show_timing {
+        feed("timing=true");
+    }
+    format!("{:016x}", hash)[..12].to_string()
+}
+
+/// Formats the comment that stands for output written to `file`
+fn file_output_comment(id: &str, file: &str, hash: &str, time: Option<&str>) -> String {
+    format!(
+        r#"<!-- md-code-output: id="{}"; file="{}"; hash="{}"{} -->"#,
+        id,
+        file,
+        hash,
+        time_attribute(time)
+    )
+}
+
+/// Formats the comment that ends an output block, or a stderr block
+fn output_comment(id: &str, hash: &str, stderr: bool, time: Option<&str>) -> String {
+    let stream = if stderr { r#"; stream="stderr""# } else { "" };
+    format!(
+        r#"<!-- md-code-output: id="{}"{}; hash="{}"{} -->"#,
+        id,
+        stream,
+        hash,
+        time_attribute(time)
+    )
+}
+
+/// The `time` attribute of an output comment, for blocks with `timing="true"`
+///
+/// It comes after the hash and is not read back: the time of a run is
+/// informational, so it never decides whether a block runs again or whether
+/// its output changed.
+fn time_attribute(time: Option<&str>) -> String {
+    time.map_or(String::new(), |time| format!(r#"; time="{}""#, time))
+}
+
+/// Formats how long a program ran, as seconds with two decimals (`1.24s`)
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.2}s", elapsed.as_secs_f64())
+}
+
+/// Orders the blocks with directives for execution
+///
+/// Blocks run in document order, except that a block naming others with
+/// `stdin-from` or `depends-on` runs after them. Unknown blocks and blocks
+/// that depend on each other in a cycle are errors.
+fn execution_order(code_blocks: &[CodeBlock]) -> Result<Vec<usize>, CodeError> {
+    let id_of = |idx: usize| {
+        code_blocks[idx]
+            .directive
+            .as_ref()
+            .map_or("", |d| d.id.as_str())
+    };
+    let by_id: HashMap<&str, usize> = (0..code_blocks.len())
+        .filter(|&idx| code_blocks[idx].directive.is_some())
+        .map(|idx| (id_of(idx), idx))
+        .collect();
+
+    // prerequisites[idx] are the blocks that run before block idx, its stdin
+    // source first
+    let mut prerequisites = vec![Vec::new(); code_blocks.len()];
+    for (idx, block) in code_blocks.iter().enumerate() {
+        let Some(directive) = block.directive.as_ref() else {
+            continue;
+        };
+        if let Some(producer_id) = &directive.stdin_from {
+            let producer_idx =
+                by_id
+                    .get(producer_id.as_str())
+                    .ok_or_else(|| CodeError::UnknownStdinSource {
+                        id: directive.id.clone(),
+                        line: block.start_line + 1,
+                        producer: producer_id.clone(),
+                    })?;
+            prerequisites[idx].push(*producer_idx);
+        }
+        for dependency in &directive.depends_on {
+            let dependency_idx =
+                by_id
+                    .get(dependency.as_str())
+                    .ok_or_else(|| CodeError::UnknownDependency {
+                        id: directive.id.clone(),
+                        line: block.start_line + 1,
+                        dependency: dependency.clone(),
+                    })?;
+            prerequisites[idx].push(*dependency_idx);
+        }
+    }
+
+    let mut order = Vec::with_capacity(by_id.len());
+    let mut done = vec![false; code_blocks.len()];
+    for idx in 0..code_blocks.len() {
+        if code_blocks[idx].directive.is_none() {
+            continue;
+        }
+        if let Err(cycle) = visit_block(idx, &prerequisites, &mut done, &mut Vec::new(), &mut order)
+        {
+            let ids: Vec<String> = cycle
+                .iter()
+                .map(|&idx| format!("'{}'", id_of(idx)))
+                .collect();
+            let ids = ids.join(", ");
+            let line = code_blocks[cycle[0]].start_line + 1;
+            // Each block in a stdin cycle reads the output of the next one
+            let reads_next = |(pos, &idx): (usize, &usize)| {
+                let next = cycle[(pos + 1) % cycle.len()];
+                code_blocks[idx]
+                    .directive
+                    .as_ref()
+                    .and_then(|d| d.stdin_from.as_deref())
+                    == Some(id_of(next))
+            };
+            return Err(if cycle.iter().enumerate().all(reads_next) {
+                CodeError::StdinCycle { ids, line }
+            } else {
+                CodeError::DependencyCycle { ids, line }
+            });
+        }
+    }
+    Ok(order)
+}
+
+/// Adds block `idx` to `order` after the blocks it needs, depth first
+///
+/// `path` holds the blocks being visited; reaching one of them again means
+/// the blocks from it onward form a cycle, which is returned as the error.
+fn visit_block(
+    idx: usize,
+    prerequisites: &[Vec<usize>],
+    done: &mut [bool],
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), Vec<usize>> {
+    if done[idx] {
+        return Ok(());
+    }
+    if let Some(pos) = path.iter().position(|&seen| seen == idx) {
+        return Err(path[pos..].to_vec());
+    }
+    path.push(idx);
+    for &prerequisite in &prerequisites[idx] {
+        visit_block(prerequisite, prerequisites, done, path, order)?;
+    }
+    path.pop();
+    done[idx] = true;
+    order.push(idx);
+    Ok(())
+}
+
 /// Reconstructs the document with updated/new output blocks
+///
+/// A block's output and stderr blocks are updated independently: an empty
+/// output leaves the output block as it is, while an empty stderr removes
+/// the stderr block. A new stderr block goes after the block's output block.
+/// A block with `output="none"` has its output block removed as well.
 fn reconstruct_document(
     text: &str,
-    code_blocks: &[CodeBlock],
-    output_blocks: &mut HashMap<String, OutputBlock>,
-    execution_results: &HashMap<String, String>,
+    document: &ParsedDocument,
+    execution_results: &HashMap<String, RunOutput>,
 ) -> Result<String, CodeError> {
     let lines: Vec<&str> = text.lines().collect();
     let mut output_lines = Vec::new();
     let mut i = 0;
 
-    // Track which output blocks we've updated
+    // Track which output and stderr blocks we've updated (`None` to remove),
+    // and which stderr blocks we have to add after an output block
     let mut updated_output_blocks = HashMap::new();
+    let mut updated_stderr_blocks = HashMap::new();
+    let mut pending_stderr_blocks = HashMap::new();
 
     while i < lines.len() {
         // Check if this is a code block
-        if let Some(block) = find_code_block_at_line(code_blocks, i) {
+        if let Some(block) = find_code_block_at_line(&document.code_blocks, i) {
             // Output the code block
             output_lines.push(lines[i].to_string()); // Opening fence
             i += 1;
@@ -116,39 +1025,89 @@ fn reconstruct_document(
 
                 // Check if we need to add/update output block
                 if let Some(ref directive) = block.directive {
-                    if let Some(output) = execution_results.get(&directive.id) {
+                    if let Some(run) = execution_results.get(&directive.id) {
                         // Determine which fence to use: directive override or code block's fence
                         let output_fence = directive.fence.as_ref().unwrap_or(&block.fence);
                         // Determine which syntax to use: directive syntax or empty string (default)
                         let output_syntax = directive.syntax.as_deref().unwrap_or("");
+                        let fenced = |content: &str, stderr: bool| {
+                            vec![
+                                format!("{}{}", output_fence, output_syntax),
+                                content.to_string(),
+                                output_fence.clone(),
+                                output_comment(
+                                    &directive.id,
+                                    &run.hash,
+                                    stderr,
+                                    run.time.as_deref().filter(|_| !stderr),
+                                ),
+                            ]
+                        };
 
-                        // Check if output block already exists
-                        if output_blocks.contains_key(&directive.id) {
-                            // Mark it as updated (we'll replace it when we encounter it)
-                            updated_output_blocks.insert(
+                        let has_stderr = !run.stderr.trim().is_empty();
+                        let new_stderr_block = if document.stderr_blocks.contains_key(&directive.id)
+                        {
+                            // Replace it (or remove it) when we encounter it
+                            updated_stderr_blocks.insert(
                                 directive.id.clone(),
-                                (
-                                    output.clone(),
-                                    output_fence.clone(),
-                                    output_syntax.to_string(),
-                                ),
+                                has_stderr.then(|| fenced(&run.stderr, true)),
                             );
+                            None
+                        } else if has_stderr {
+                            let mut new_block = vec![String::new(), "Stderr:".to_string()];
+                            new_block.extend(fenced(&run.stderr, true));
+                            Some(new_block)
                         } else {
-                            // Create new output block immediately after code block
-                            output_lines.push(String::new());
-                            output_lines.push("Output:".to_string());
-                            output_lines.push(format!("{}{}", output_fence, output_syntax));
-                            output_lines.push(output.clone());
-                            output_lines.push(output_fence.clone());
-                            output_lines
-                                .push(format!(r#"<!-- md-code-output: id="{}" -->"#, directive.id));
+                            None
+                        };
+
+                        let existing_output = document
+                            .output_blocks
+                            .get(&directive.id)
+                            .filter(|output| output.start_line > block.end_line);
+                        if !run.output.trim().is_empty() {
+                            // Output written to a file is replaced by a comment naming it
+                            let new_block = match &directive.output_file {
+                                Some(file) => {
+                                    vec![file_output_comment(
+                                        &directive.id,
+                                        file,
+                                        &run.hash,
+                                        run.time.as_deref(),
+                                    )]
+                                }
+                                None => fenced(&run.output, false),
+                            };
+                            // Check if output block already exists
+                            if document.output_blocks.contains_key(&directive.id) {
+                                // Mark it as updated (we'll replace it when we encounter it)
+                                updated_output_blocks.insert(directive.id.clone(), Some(new_block));
+                            } else {
+                                // Create new output block immediately after code block
+                                output_lines.push(String::new());
+                                if directive.output_file.is_none() {
+                                    output_lines.push("Output:".to_string());
+                                }
+                                output_lines.extend(new_block);
+                            }
+                        } else if run.hidden && document.output_blocks.contains_key(&directive.id) {
+                            // Remove it when we encounter it
+                            updated_output_blocks.insert(directive.id.clone(), None);
+                        }
+
+                        // A new stderr block follows the output block
+                        if let Some(new_block) = new_stderr_block {
+                            if existing_output.is_some() {
+                                pending_stderr_blocks.insert(directive.id.clone(), new_block);
+                            } else {
+                                output_lines.extend(new_block);
+                            }
                         }
                     }
                 }
             }
         } else if is_code_fence(lines[i]) {
             // This might be an output block or a regular code fence
-            let _fence_start_line = i;
             let opening_fence_type = get_fence_type(lines[i]);
             output_lines.push(lines[i].to_string());
             i += 1;
@@ -176,31 +1135,80 @@ fn reconstruct_document(
 
                 // Check for md-code-output directive
                 if i < lines.len() && is_md_code_output_comment(lines[i]) {
-                    let id = parse_md_code_output_directive(lines[i])?;
+                    let OutputDirective { id, stderr, .. } =
+                        parse_md_code_output_directive(lines[i])?;
 
                     // If we have an updated output for this ID, use it
-                    if let Some((new_output, new_fence, new_syntax)) =
+                    let replacement = if stderr {
+                        updated_stderr_blocks.get(&id)
+                    } else {
                         updated_output_blocks.get(&id)
-                    {
-                        // Replace the content with new output
-                        output_lines.pop(); // Remove closing fence we just added
-                        for _ in content_lines.iter() {
-                            output_lines.pop(); // Remove old content
-                        }
-                        output_lines.pop(); // Remove opening fence
+                    };
+                    if let Some(replacement) = replacement {
+                        // Remove the closing fence, the old content and the opening fence
+                        output_lines.truncate(output_lines.len() - content_lines.len() - 2);
 
-                        // Add new output block with the correct fence and syntax
-                        output_lines.push(format!("{}{}", new_fence, new_syntax));
-                        output_lines.push(new_output.clone());
-                        output_lines.push(new_fence.clone());
+                        match replacement {
+                            // Output now written to a file leaves only its comment
+                            Some(new_block) if new_block.len() == 1 => {
+                                if output_lines.last().is_some_and(|line| line == "Output:") {
+                                    output_lines.pop();
+                                }
+                                output_lines.extend(new_block.iter().cloned());
+                            }
+                            Some(new_block) => output_lines.extend(new_block.iter().cloned()),
+                            None => {
+                                // The block goes, along with its heading
+                                let heading = if stderr { "Stderr:" } else { "Output:" };
+                                if output_lines.last().is_some_and(|line| line == heading) {
+                                    output_lines.pop();
+                                    if output_lines.last().is_some_and(|line| line.is_empty()) {
+                                        output_lines.pop();
+                                    }
+                                }
+                            }
+                        }
+                    } else {
+                        // Output the directive comment
+                        output_lines.push(lines[i].to_string());
                     }
-
-                    // Output the directive comment
-                    output_lines.push(lines[i].to_string());
                     i += 1;
+
+                    if !stderr {
+                        if let Some(new_block) = pending_stderr_blocks.remove(&id) {
+                            output_lines.extend(new_block);
+                        }
+                    }
                 }
                 // If not an output block, content is already output, just continue
             }
+        } else if is_md_code_output_comment(lines[i]) {
+            // The comment standing for output written to a file is replaced
+            // like an output block
+            let OutputDirective { id, file, .. } = parse_md_code_output_directive(lines[i])?;
+            match updated_output_blocks.get(&id).filter(|_| file.is_some()) {
+                Some(Some(new_block)) => {
+                    // Output no longer written to a file gets its heading back
+                    if new_block.len() > 1 {
+                        output_lines.push("Output:".to_string());
+                    }
+                    output_lines.extend(new_block.iter().cloned());
+                }
+                Some(None) => {
+                    // The comment goes, along with the blank line before it
+                    if output_lines.last().is_some_and(|line| line.is_empty()) {
+                        output_lines.pop();
+                    }
+                }
+                None => output_lines.push(lines[i].to_string()),
+            }
+            i += 1;
+
+            if file.is_some() {
+                if let Some(new_block) = pending_stderr_blocks.remove(&id) {
+                    output_lines.extend(new_block);
+                }
+            }
         } else {
             // Regular line
             output_lines.push(lines[i].to_string());
@@ -279,7 +1287,7 @@ print("world")
 
     #[test]
     fn test_execute_without_bin_error() {
-        let input = r#"```python
+        let input = r#"```
 print("hello")
 ```
 <!-- md-code: id="test" -->"#;
@@ -289,6 +1297,17 @@ print("hello")
         assert!(result.errors[0]
             .message
             .contains("missing required field: bin"));
+
+        // A fence language without a known program is named in the error
+        let result = process_code_blocks(&input.replacen("```", "```cobol", 1));
+        assert!(result.has_errors());
+        let message = &result.errors[0].message;
+        assert!(
+            message.contains("missing required field: bin"),
+            "{}",
+            message
+        );
+        assert!(message.contains("fence language 'cobol'"), "{}", message);
     }
 
     #[test]
@@ -6,17 +6,32 @@ use std::collections::HashMap;
 pub struct CodeBlockDirective {
     pub id: String,
     pub bin: Option<String>,
+    pub args: Vec<String>, // Arguments passed to the program after those in bin
+    pub env: Vec<(String, String)>, // Extra environment variables for the program
+    pub cwd: Option<String>, // Working directory, relative to the document's directory
+    pub stdin: Option<String>, // Literal input for the program's stdin
+    pub stdin_from: Option<String>, // Id of the block whose output is piped to stdin
+    pub depends_on: Vec<String>, // Ids of the blocks that must run before this one
     pub timeout: Option<u64>,
+    pub max_lines: Option<usize>, // Lines of output to keep; the rest is replaced by a marker
+    pub output_file: Option<String>, // File for the output instead of an output block, relative to the document
     pub fence: Option<String>, // Optional fence override for output block (e.g., "```", "~~~", "````")
     pub syntax: Option<String>, // Optional syntax language for output block (e.g., "json", "text")
+    pub continue_on_error: bool, // on-error="continue": a failure here does not stop the run
+    pub show_exit_code: bool,  // exit-code="show": end the output with a non-zero exit status
+    pub require_success: bool, // require-success="true": a non-zero exit status is an error
+    pub separate_stderr: bool, // stderr="separate": stderr goes to its own output block
+    pub keep_ansi: bool,       // ansi="keep": leave terminal escape sequences in the output
+    pub hide_output: bool,     // output="none": run for side effects only, without output blocks
+    pub skip_verify: bool,     // verify="skip": md code --verify does not compare the output
+    pub show_timing: bool, // timing="true": record how long the program ran in the output comment
 }
 
 #[derive(Debug)]
 pub struct CodeBlock {
     pub start_line: usize,
     pub end_line: usize,
-    #[allow(dead_code)]
-    pub language: String,
+    pub language: String, // The opening fence's info string, such as python
     pub content: String,
     pub directive: Option<CodeBlockDirective>,
     pub fence: String, // The fence used for this code block (e.g., "```", "~~~")
@@ -30,8 +45,18 @@ pub struct OutputBlock {
     pub end_line: usize,
     #[allow(dead_code)]
     pub id: String,
-    #[allow(dead_code)]
     pub content: String,
+    pub hash: Option<String>, // Hash of the code that produced the output, if recorded
+    pub file: Option<String>, // The file holding the output, for a file="..." comment
+}
+
+/// The attributes of an md-code-output comment
+#[derive(Debug, PartialEq)]
+pub struct OutputDirective {
+    pub id: String,
+    pub hash: Option<String>, // Hash of the code that produced the output, if recorded
+    pub stderr: bool,         // stream="stderr": the block holds the program's stderr
+    pub file: Option<String>, // file="...": the output was written to this file
 }
 
 // Re-export from common for backward compatibility
@@ -52,8 +77,10 @@ fn extract_fence(line: &str) -> String {
 /// Extracts the language from a code fence line
 pub fn extract_language(fence_line: &str) -> String {
     let trimmed = fence_line.trim();
-    if let Some(lang) = trimmed.strip_prefix("```") {
-        lang.trim().to_string()
+    if trimmed.starts_with("```") {
+        trimmed.trim_start_matches('`').trim().to_string()
+    } else if trimmed.starts_with("~~~") {
+        trimmed.trim_start_matches('~').trim().to_string()
     } else {
         String::new()
     }
@@ -71,8 +98,59 @@ pub fn is_md_code_output_comment(line: &str) -> bool {
     trimmed.starts_with("<!--") && trimmed.contains("md-code-output:")
 }
 
+/// Checks if a line is an md-code-config comment
+pub fn is_md_code_config_comment(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with("<!--") && trimmed.contains("md-code-config:")
+}
+
+/// Parses an md-code-config comment into (fence language, bin) pairs
+/// Format: <!-- md-code-config: python="python3.12"; sh="zsh" -->
+///
+/// Languages are lowercased, so `Python="..."` maps ```python fences too.
+pub fn parse_md_code_config(line: &str) -> Result<Vec<(String, String)>, CodeError> {
+    let content = line
+        .trim()
+        .strip_prefix("<!--")
+        .and_then(|content| content.strip_suffix("-->"))
+        .and_then(|content| content.trim().strip_prefix("md-code-config:"))
+        .ok_or_else(|| {
+            CodeError::DirectiveParseError(format!("Invalid md-code-config comment: {}", line))
+        })?;
+
+    let mut bins = Vec::new();
+    for part in content
+        .split(';')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (language, value) = part.split_once('=').ok_or_else(|| {
+            CodeError::DirectiveParseError(format!(
+                "Invalid md-code-config entry: '{}'. Expected LANGUAGE=\"bin\"",
+                part
+            ))
+        })?;
+        let language = language.trim();
+        if language.is_empty() || language.contains(char::is_whitespace) {
+            return Err(CodeError::DirectiveParseError(format!(
+                "Invalid md-code-config language: '{}'",
+                language
+            )));
+        }
+        let bin = extract_quoted_value(value.trim())?;
+        if bin.trim().is_empty() {
+            return Err(CodeError::DirectiveParseError(format!(
+                "md-code-config bin for '{}' cannot be empty",
+                language
+            )));
+        }
+        bins.push((language.to_lowercase(), bin));
+    }
+    Ok(bins)
+}
+
 /// Parses an md-code directive comment into a CodeBlockDirective
-/// Format: <!-- md-code: id="foo"; bin="python3"; timeout=60 -->
+/// Format: <!-- md-code: id="foo"; bin="python3"; args="--dpi 120"; env="DEBUG=1"; cwd="./scripts"; stdin="a\nb"; stdin-from="bar"; depends-on="setup"; timeout=60; on-error="continue"; exit-code="show"; require-success="true" -->
 pub fn parse_md_code_directive(line: &str) -> Result<CodeBlockDirective, CodeError> {
     let trimmed = line.trim();
 
@@ -92,9 +170,25 @@ pub fn parse_md_code_directive(line: &str) -> Result<CodeBlockDirective, CodeErr
 
     let mut id = None;
     let mut bin = None;
+    let mut args = Vec::new();
+    let mut env = Vec::new();
+    let mut cwd = None;
+    let mut stdin = None;
+    let mut stdin_from = None;
+    let mut depends_on = Vec::new();
     let mut timeout = None;
+    let mut max_lines = None;
+    let mut output_file = None;
     let mut fence = None;
     let mut syntax = None;
+    let mut continue_on_error = false;
+    let mut show_exit_code = false;
+    let mut require_success = false;
+    let mut separate_stderr = false;
+    let mut keep_ansi = false;
+    let mut hide_output = false;
+    let mut skip_verify = false;
+    let mut show_timing = false;
 
     // Split by semicolons
     for part in content.split(';') {
@@ -108,12 +202,67 @@ pub fn parse_md_code_directive(line: &str) -> Result<CodeBlockDirective, CodeErr
             // Extract bin value from quotes
             let value = part.strip_prefix("bin=").unwrap().trim();
             bin = Some(extract_quoted_value(value)?);
+        } else if part.starts_with("args=") {
+            // Extract args value from quotes and split it like a shell would
+            let value = part.strip_prefix("args=").unwrap().trim();
+            args = split_args(&extract_quoted_value(value)?)?;
+        } else if part.starts_with("env=") {
+            // Extract env value from quotes; repeated env= attributes add up
+            let value = part.strip_prefix("env=").unwrap().trim();
+            env.extend(parse_env(&extract_quoted_value(value)?)?);
+        } else if part.starts_with("cwd=") {
+            // Extract cwd value from quotes
+            let value = part.strip_prefix("cwd=").unwrap().trim();
+            cwd = Some(extract_quoted_value(value)?);
+        } else if part.starts_with("stdin=") {
+            // Extract stdin value from quotes, with \n, \t and \\ escapes
+            let value = part.strip_prefix("stdin=").unwrap().trim();
+            stdin = Some(unescape_stdin(&extract_quoted_value(value)?)?);
+        } else if part.starts_with("stdin-from=") {
+            // Extract the producing block's id from quotes
+            let value = part.strip_prefix("stdin-from=").unwrap().trim();
+            stdin_from = Some(extract_quoted_value(value)?);
+        } else if part.starts_with("depends-on=") {
+            // Extract comma-separated block ids from quotes; repeated depends-on= attributes add up
+            let value = part.strip_prefix("depends-on=").unwrap().trim();
+            for dependency in extract_quoted_value(value)?.split(',') {
+                let dependency = dependency.trim();
+                validate_id(dependency).map_err(|e| {
+                    CodeError::DirectiveParseError(format!("Invalid depends-on: {}", e))
+                })?;
+                depends_on.push(dependency.to_string());
+            }
         } else if part.starts_with("timeout=") {
             // Extract timeout value (no quotes)
             let value = part.strip_prefix("timeout=").unwrap().trim();
             timeout = Some(value.parse::<u64>().map_err(|_| {
                 CodeError::DirectiveParseError(format!("Invalid timeout value: {}", value))
             })?);
+        } else if part.starts_with("max-lines=") {
+            // Extract the line limit (no quotes), which must be at least 1
+            let value = part.strip_prefix("max-lines=").unwrap().trim();
+            max_lines = Some(
+                value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| {
+                        CodeError::DirectiveParseError(format!(
+                            "Invalid max-lines value: {}",
+                            value
+                        ))
+                    })?,
+            );
+        } else if part.starts_with("output-file=") {
+            // Extract output file path from quotes
+            let value = part.strip_prefix("output-file=").unwrap().trim();
+            let path = extract_quoted_value(value)?;
+            if path.trim().is_empty() {
+                return Err(CodeError::DirectiveParseError(
+                    "output-file cannot be empty".to_string(),
+                ));
+            }
+            output_file = Some(path);
         } else if part.starts_with("fence=") {
             // Extract fence value from quotes
             let value = part.strip_prefix("fence=").unwrap().trim();
@@ -122,6 +271,110 @@ pub fn parse_md_code_directive(line: &str) -> Result<CodeBlockDirective, CodeErr
             // Extract syntax value from quotes
             let value = part.strip_prefix("syntax=").unwrap().trim();
             syntax = Some(extract_quoted_value(value)?);
+        } else if part.starts_with("on-error=") {
+            // Extract whether a failure stops the run from quotes
+            let value = part.strip_prefix("on-error=").unwrap().trim();
+            continue_on_error = match extract_quoted_value(value)?.as_str() {
+                "continue" => true,
+                "stop" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid on-error value: '{}'. Must be \"continue\" or \"stop\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("exit-code=") {
+            // Extract whether to show a non-zero exit status from quotes
+            let value = part.strip_prefix("exit-code=").unwrap().trim();
+            show_exit_code = match extract_quoted_value(value)?.as_str() {
+                "show" => true,
+                "hide" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid exit-code value: '{}'. Must be \"show\" or \"hide\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("require-success=") {
+            // Extract whether a non-zero exit status is an error from quotes
+            let value = part.strip_prefix("require-success=").unwrap().trim();
+            require_success = match extract_quoted_value(value)?.as_str() {
+                "true" => true,
+                "false" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid require-success value: '{}'. Must be \"true\" or \"false\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("stderr=") {
+            // Extract whether stderr gets its own output block from quotes
+            let value = part.strip_prefix("stderr=").unwrap().trim();
+            separate_stderr = match extract_quoted_value(value)?.as_str() {
+                "separate" => true,
+                "merge" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid stderr value: '{}'. Must be \"separate\" or \"merge\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("ansi=") {
+            // Extract whether to keep terminal escape sequences from quotes
+            let value = part.strip_prefix("ansi=").unwrap().trim();
+            keep_ansi = match extract_quoted_value(value)?.as_str() {
+                "keep" => true,
+                "strip" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid ansi value: '{}'. Must be \"keep\" or \"strip\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("output=") {
+            // Extract whether the block gets output blocks from quotes
+            let value = part.strip_prefix("output=").unwrap().trim();
+            hide_output = match extract_quoted_value(value)?.as_str() {
+                "none" => true,
+                "show" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid output value: '{}'. Must be \"none\" or \"show\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("verify=") {
+            // Extract whether md code --verify compares the output from quotes
+            let value = part.strip_prefix("verify=").unwrap().trim();
+            skip_verify = match extract_quoted_value(value)?.as_str() {
+                "skip" => true,
+                "check" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid verify value: '{}'. Must be \"skip\" or \"check\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("timing=") {
+            // Extract whether to record the run time from quotes
+            let value = part.strip_prefix("timing=").unwrap().trim();
+            show_timing = match extract_quoted_value(value)?.as_str() {
+                "true" => true,
+                "false" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid timing value: '{}'. Must be \"true\" or \"false\"",
+                        other
+                    )))
+                }
+            };
         }
     }
 
@@ -132,6 +385,22 @@ pub fn parse_md_code_directive(line: &str) -> Result<CodeBlockDirective, CodeErr
     // Validate ID format
     validate_id(&id).map_err(|e| CodeError::DirectiveParseError(format!("Invalid ID: {}", e)))?;
 
+    if let Some(ref producer) = stdin_from {
+        validate_id(producer)
+            .map_err(|e| CodeError::DirectiveParseError(format!("Invalid stdin-from: {}", e)))?;
+        if stdin.is_some() {
+            return Err(CodeError::DirectiveParseError(
+                "stdin and stdin-from cannot both be given".to_string(),
+            ));
+        }
+    }
+
+    if hide_output && output_file.is_some() {
+        return Err(CodeError::DirectiveParseError(
+            "output=\"none\" and output-file cannot both be given".to_string(),
+        ));
+    }
+
     // Validate fence if specified (must be ``` or ~~~ with optional repetitions)
     if let Some(ref f) = fence {
         if !f.chars().all(|c| c == '`' || c == '~') || f.len() < 3 {
@@ -145,15 +414,34 @@ pub fn parse_md_code_directive(line: &str) -> Result<CodeBlockDirective, CodeErr
     Ok(CodeBlockDirective {
         id,
         bin,
+        args,
+        env,
+        cwd,
+        stdin,
+        stdin_from,
+        depends_on,
         timeout,
+        max_lines,
+        output_file,
         fence,
         syntax,
+        continue_on_error,
+        show_exit_code,
+        require_success,
+        separate_stderr,
+        keep_ansi,
+        hide_output,
+        skip_verify,
+        show_timing,
     })
 }
 
-/// Parses an md-code-output directive comment to extract the id
-/// Format: <!-- md-code-output: id="foo" -->
-pub fn parse_md_code_output_directive(line: &str) -> Result<String, CodeError> {
+/// Parses an md-code-output directive comment to extract the id, the
+/// optional hash of the code that produced the output, whether the block
+/// holds stderr rather than the output, and the file the output went to
+/// Format: <!-- md-code-output: id="foo"; stream="stderr"; hash="0123456789ab" -->
+/// or, on a line of its own: <!-- md-code-output: id="foo"; file="out.svg"; hash="..." -->
+pub fn parse_md_code_output_directive(line: &str) -> Result<OutputDirective, CodeError> {
     let trimmed = line.trim();
 
     // Remove <!-- and -->
@@ -172,21 +460,51 @@ pub fn parse_md_code_output_directive(line: &str) -> Result<String, CodeError> {
         })?
         .trim();
 
-    // Extract id value
-    if content.starts_with("id=") {
-        let value = content.strip_prefix("id=").unwrap().trim();
-        let id = extract_quoted_value(value)?;
-
-        // Validate ID format
-        validate_id(&id)
-            .map_err(|e| CodeError::DirectiveParseError(format!("Invalid ID: {}", e)))?;
-
-        Ok(id)
-    } else {
-        Err(CodeError::DirectiveParseError(
+    // Extract id value, which comes first, then the optional stream, file and hash
+    if !content.starts_with("id=") {
+        return Err(CodeError::DirectiveParseError(
             "Missing id attribute in md-code-output".to_string(),
-        ))
+        ));
     }
+    let mut id = String::new();
+    let mut hash = None;
+    let mut stderr = false;
+    let mut file = None;
+    for part in content.split(';') {
+        let part = part.trim();
+        if part.starts_with("id=") {
+            let value = part.strip_prefix("id=").unwrap().trim();
+            id = extract_quoted_value(value)?;
+        } else if part.starts_with("hash=") {
+            let value = part.strip_prefix("hash=").unwrap().trim();
+            hash = Some(extract_quoted_value(value)?);
+        } else if part.starts_with("stream=") {
+            let value = part.strip_prefix("stream=").unwrap().trim();
+            stderr = match extract_quoted_value(value)?.as_str() {
+                "stderr" => true,
+                "stdout" => false,
+                other => {
+                    return Err(CodeError::DirectiveParseError(format!(
+                        "Invalid stream value: '{}'. Must be \"stdout\" or \"stderr\"",
+                        other
+                    )))
+                }
+            };
+        } else if part.starts_with("file=") {
+            let value = part.strip_prefix("file=").unwrap().trim();
+            file = Some(extract_quoted_value(value)?);
+        }
+    }
+
+    // Validate ID format
+    validate_id(&id).map_err(|e| CodeError::DirectiveParseError(format!("Invalid ID: {}", e)))?;
+
+    Ok(OutputDirective {
+        id,
+        hash,
+        stderr,
+        file,
+    })
 }
 
 /// Extracts a quoted value from a string (removes surrounding quotes)
@@ -201,14 +519,158 @@ fn extract_quoted_value(s: &str) -> Result<String, CodeError> {
     }
 }
 
+/// Splits an `args` value into separate arguments, shell-words style
+///
+/// Arguments are separated by whitespace. Single or double quotes group text
+/// containing spaces into one argument, and a backslash outside single quotes
+/// escapes the next character.
+fn split_args(value: &str) -> Result<Vec<String>, CodeError> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"') | None, '\\') => {
+                let escaped = chars.next().ok_or_else(|| {
+                    CodeError::DirectiveParseError(format!("Trailing backslash in args: {}", value))
+                })?;
+                current.push(escaped);
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(CodeError::DirectiveParseError(format!(
+            "Unterminated quote in args: {}",
+            value
+        )));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// Expands the escapes of a `stdin` value: `\n` (newline), `\t` (tab) and `\\`
+fn unescape_stdin(value: &str) -> Result<String, CodeError> {
+    let mut input = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            input.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => input.push('\n'),
+            Some('t') => input.push('\t'),
+            Some('\\') => input.push('\\'),
+            other => {
+                return Err(CodeError::DirectiveParseError(format!(
+                    "Invalid escape '\\{}' in stdin (expected \\n, \\t or \\\\)",
+                    other.map(String::from).unwrap_or_default()
+                )))
+            }
+        }
+    }
+    Ok(input)
+}
+
+/// Parses an `env` value: comma-separated `KEY=VALUE` entries
+///
+/// A value in single or double quotes may contain commas; the quotes are removed.
+fn parse_env(value: &str) -> Result<Vec<(String, String)>, CodeError> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    for (idx, c) in value.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                entries.push(&value[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return Err(CodeError::DirectiveParseError(format!(
+            "Unterminated quote in env: {}",
+            value
+        )));
+    }
+    entries.push(&value[start..]);
+
+    entries
+        .into_iter()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (key, value) = entry
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, _)| is_env_name(key))
+                .ok_or_else(|| {
+                    CodeError::DirectiveParseError(format!(
+                        "Invalid env entry '{}': expected KEY=VALUE with a name of letters, digits and underscores",
+                        entry
+                    ))
+                })?;
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q))
+                .unwrap_or(value);
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Whether `name` can be an environment variable name (`DEBUG`, `API_URL`)
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The md-code blocks of a document and the output blocks they wrote
+#[derive(Debug)]
+pub struct ParsedDocument {
+    pub code_blocks: Vec<CodeBlock>,
+    pub output_blocks: HashMap<String, OutputBlock>, // Output blocks by id
+    pub stderr_blocks: HashMap<String, OutputBlock>, // stream="stderr" output blocks by id
+    pub bins: HashMap<String, String>, // Programs by lowercased fence language, from md-code-config
+}
+
 /// Parses the entire markdown document to find code blocks and output blocks
 /// Skips code blocks that are nested inside other code fences
-pub fn parse_document(
-    text: &str,
-) -> Result<(Vec<CodeBlock>, HashMap<String, OutputBlock>), CodeError> {
+pub fn parse_document(text: &str) -> Result<ParsedDocument, CodeError> {
     let lines: Vec<&str> = text.lines().collect();
     let mut code_blocks = Vec::new();
     let mut output_blocks = HashMap::new();
+    let mut stderr_blocks = HashMap::new();
+    let mut bins = HashMap::new();
     let mut output_block_lines = HashMap::new(); // Track line numbers for duplicate detection
     let mut i = 0;
     let mut active_fence_type: Option<FenceType> = None; // Track fence type
@@ -253,9 +715,11 @@ pub fn parse_document(
                     Some(parse_md_code_directive(lines[i])?)
                 } else if i < lines.len() && is_md_code_output_comment(lines[i]) {
                     // This is an output block
-                    let id = parse_md_code_output_directive(lines[i])?;
+                    let OutputDirective {
+                        id, hash, stderr, ..
+                    } = parse_md_code_output_directive(lines[i])?;
 
-                    if let Some(&prev_line) = output_block_lines.get(&id) {
+                    if let Some(&prev_line) = output_block_lines.get(&(id.clone(), stderr)) {
                         return Err(CodeError::duplicate_output_id(
                             &id,
                             start_line + 1,
@@ -263,14 +727,21 @@ pub fn parse_document(
                         ));
                     }
 
-                    output_block_lines.insert(id.clone(), start_line);
-                    output_blocks.insert(
+                    output_block_lines.insert((id.clone(), stderr), start_line);
+                    let blocks = if stderr {
+                        &mut stderr_blocks
+                    } else {
+                        &mut output_blocks
+                    };
+                    blocks.insert(
                         id.clone(),
                         OutputBlock {
                             start_line,
                             end_line,
                             id,
                             content: content.clone(),
+                            hash,
+                            file: None,
                         },
                     );
 
@@ -293,12 +764,43 @@ pub fn parse_document(
                 // We're inside a fence, just skip this line
                 i += 1;
             }
+        } else if is_md_code_output_comment(lines[i]) {
+            // An output comment on its own line stands for output written to a file
+            let directive = parse_md_code_output_directive(lines[i])?;
+            if let Some(file) = directive.file {
+                let id = directive.id;
+                if let Some(&prev_line) = output_block_lines.get(&(id.clone(), false)) {
+                    return Err(CodeError::duplicate_output_id(&id, i + 1, prev_line + 1));
+                }
+                output_block_lines.insert((id.clone(), false), i);
+                output_blocks.insert(
+                    id.clone(),
+                    OutputBlock {
+                        start_line: i,
+                        end_line: i,
+                        id,
+                        content: String::new(),
+                        hash: directive.hash,
+                        file: Some(file),
+                    },
+                );
+            }
+            i += 1;
+        } else if is_md_code_config_comment(lines[i]) {
+            // Later comments override earlier ones, wherever the blocks are
+            bins.extend(parse_md_code_config(lines[i])?);
+            i += 1;
         } else {
             i += 1;
         }
     }
 
-    Ok((code_blocks, output_blocks))
+    Ok(ParsedDocument {
+        code_blocks,
+        output_blocks,
+        stderr_blocks,
+        bins,
+    })
 }
 
 /// Validates that all code block IDs are unique
@@ -340,6 +842,8 @@ mod tests {
         assert_eq!(extract_language("```rust"), "rust");
         assert_eq!(extract_language("```"), "");
         assert_eq!(extract_language("  ```  javascript  "), "javascript");
+        assert_eq!(extract_language("~~~ruby"), "ruby");
+        assert_eq!(extract_language("````sh"), "sh");
     }
 
     #[test]
@@ -361,11 +865,295 @@ mod tests {
         assert_eq!(directive.timeout, Some(60));
     }
 
+    #[test]
+    fn test_parse_md_code_directive_with_max_lines() {
+        let line = r#"<!-- md-code: id="test"; bin="python3"; max-lines=20 -->"#;
+        let directive = parse_md_code_directive(line).unwrap();
+        assert_eq!(directive.max_lines, Some(20));
+
+        for invalid in ["max-lines=0", "max-lines=-3", "max-lines=many"] {
+            let line = format!(r#"<!-- md-code: id="test"; bin="python3"; {} -->"#, invalid);
+            assert!(parse_md_code_directive(&line).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_output_file() {
+        let line = r#"<!-- md-code: id="plot"; bin="python3"; output-file="assets/plot.svg" -->"#;
+        let directive = parse_md_code_directive(line).unwrap();
+        assert_eq!(directive.output_file.as_deref(), Some("assets/plot.svg"));
+
+        let line = r#"<!-- md-code: id="plot"; bin="python3"; output-file="" -->"#;
+        assert!(parse_md_code_directive(line).is_err());
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_args() {
+        let directive = parse_md_code_directive(
+            r#"<!-- md-code: id="plot"; bin="python3 -"; args="--format svg --dpi 120" -->"#,
+        )
+        .unwrap();
+        assert_eq!(directive.bin, Some("python3 -".to_string()));
+        assert_eq!(directive.args, vec!["--format", "svg", "--dpi", "120"]);
+
+        let directive = parse_md_code_directive(r#"<!-- md-code: id="test" -->"#).unwrap();
+        assert!(directive.args.is_empty());
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_env() {
+        let directive = parse_md_code_directive(
+            r#"<!-- md-code: id="api"; bin="bash"; env="API_URL=http://localhost:8080,DEBUG=1"; env="GREETING='hello, world'" -->"#,
+        )
+        .unwrap();
+        let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+        assert_eq!(
+            directive.env,
+            vec![
+                pair("API_URL", "http://localhost:8080"),
+                pair("DEBUG", "1"),
+                pair("GREETING", "hello, world"),
+            ]
+        );
+
+        let directive = parse_md_code_directive(r#"<!-- md-code: id="test" -->"#).unwrap();
+        assert!(directive.env.is_empty());
+        assert_eq!(directive.cwd, None);
+
+        let directive =
+            parse_md_code_directive(r#"<!-- md-code: id="test"; cwd="./scripts" -->"#).unwrap();
+        assert_eq!(directive.cwd, Some("./scripts".to_string()));
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_stdin() {
+        let directive = parse_md_code_directive(
+            r#"<!-- md-code: id="sort"; bin="sort"; stdin="pear\napple\tred\\" -->"#,
+        )
+        .unwrap();
+        assert_eq!(directive.stdin, Some("pear\napple\tred\\".to_string()));
+        assert_eq!(directive.stdin_from, None);
+
+        let directive = parse_md_code_directive(
+            r#"<!-- md-code: id="count"; bin="wc -l"; stdin-from="sort" -->"#,
+        )
+        .unwrap();
+        assert_eq!(directive.stdin_from, Some("sort".to_string()));
+
+        for invalid in [
+            r#"<!-- md-code: id="a"; stdin="x"; stdin-from="b" -->"#,
+            r#"<!-- md-code: id="a"; stdin-from="" -->"#,
+            r#"<!-- md-code: id="a"; stdin="\q" -->"#,
+        ] {
+            assert!(parse_md_code_directive(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_depends_on() {
+        let directive = parse_md_code_directive(
+            r#"<!-- md-code: id="test"; bin="sh"; depends-on="setup, build"; depends-on="db" -->"#,
+        )
+        .unwrap();
+        assert_eq!(directive.depends_on, vec!["setup", "build", "db"]);
+
+        let directive = parse_md_code_directive(r#"<!-- md-code: id="a"; bin="sh" -->"#).unwrap();
+        assert!(directive.depends_on.is_empty());
+
+        for invalid in [
+            r#"<!-- md-code: id="a"; depends-on="" -->"#,
+            r#"<!-- md-code: id="a"; depends-on="b,,c" -->"#,
+        ] {
+            assert!(parse_md_code_directive(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_on_error() {
+        let parse = |line| parse_md_code_directive(line).map(|d| d.continue_on_error);
+        assert!(!parse(r#"<!-- md-code: id="a"; bin="sh" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; on-error="continue" -->"#).unwrap());
+        assert!(!parse(r#"<!-- md-code: id="a"; on-error="stop" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; on-error="ignore" -->"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_output() {
+        let parse = |line| parse_md_code_directive(line).map(|d| d.hide_output);
+        assert!(!parse(r#"<!-- md-code: id="a"; bin="sh" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; output="none" -->"#).unwrap());
+        assert!(!parse(r#"<!-- md-code: id="a"; output="show" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; output="hidden" -->"#).is_err());
+        assert!(parse(r#"<!-- md-code: id="a"; output="none"; output-file="a.txt" -->"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_verify() {
+        let parse = |line| parse_md_code_directive(line).map(|d| d.skip_verify);
+        assert!(!parse(r#"<!-- md-code: id="a"; bin="sh" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; verify="skip" -->"#).unwrap());
+        assert!(!parse(r#"<!-- md-code: id="a"; verify="check" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; verify=skip -->"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_md_code_config() {
+        assert_eq!(
+            parse_md_code_config(r#"<!-- md-code-config: Python="python3.12"; sh="zsh -e" -->"#)
+                .unwrap(),
+            vec![
+                ("python".to_string(), "python3.12".to_string()),
+                ("sh".to_string(), "zsh -e".to_string()),
+            ]
+        );
+        for invalid in [
+            r#"<!-- md-code-config: python -->"#,
+            r#"<!-- md-code-config: python=python3 -->"#,
+            r#"<!-- md-code-config: python="" -->"#,
+            r#"<!-- md-code-config: ="bash" -->"#,
+        ] {
+            assert!(parse_md_code_config(invalid).is_err(), "{}", invalid);
+        }
+
+        // The last comment wins, and comments inside code fences are ignored
+        let doc = "<!-- md-code-config: sh=\"zsh\" -->\n<!-- md-code-config: sh=\"dash\" -->\n\
+                   ```markdown\n<!-- md-code-config: sh=\"ksh\" -->\n```\n";
+        let bins = parse_document(doc).unwrap().bins;
+        assert_eq!(bins.get("sh").map(String::as_str), Some("dash"));
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_timing() {
+        let parse = |line| parse_md_code_directive(line).map(|d| d.show_timing);
+        assert!(!parse(r#"<!-- md-code: id="a"; bin="sh" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; timing="true" -->"#).unwrap());
+        assert!(!parse(r#"<!-- md-code: id="a"; timing="false" -->"#).unwrap());
+        assert!(parse(r#"<!-- md-code: id="a"; timing="yes" -->"#).is_err());
+
+        // The time in an output comment is informational and ignored when read back
+        let output = parse_md_code_output_directive(
+            r#"<!-- md-code-output: id="a"; hash="abc"; time="1.24s" -->"#,
+        )
+        .unwrap();
+        assert_eq!(output.hash.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_parse_md_code_directive_with_exit_status_settings() {
+        let directive = parse_md_code_directive(r#"<!-- md-code: id="a"; bin="sh" -->"#).unwrap();
+        assert!(!directive.show_exit_code);
+        assert!(!directive.require_success);
+
+        let directive = parse_md_code_directive(
+            r#"<!-- md-code: id="a"; bin="sh"; exit-code="show"; require-success="true" -->"#,
+        )
+        .unwrap();
+        assert!(directive.show_exit_code);
+        assert!(directive.require_success);
+
+        let directive =
+            parse_md_code_directive(r#"<!-- md-code: id="a"; bin="sh"; stderr="separate" -->"#)
+                .unwrap();
+        assert!(directive.separate_stderr);
+        assert!(!directive.keep_ansi);
+
+        let directive =
+            parse_md_code_directive(r#"<!-- md-code: id="a"; bin="sh"; ansi="keep" -->"#).unwrap();
+        assert!(directive.keep_ansi);
+
+        for invalid in [
+            r#"<!-- md-code: id="a"; exit-code="always" -->"#,
+            r#"<!-- md-code: id="a"; require-success="yes" -->"#,
+            r#"<!-- md-code: id="a"; require-success=true -->"#,
+            r#"<!-- md-code: id="a"; stderr="apart" -->"#,
+            r#"<!-- md-code: id="a"; ansi="color" -->"#,
+        ] {
+            assert!(parse_md_code_directive(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_parse_env() {
+        let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+        assert_eq!(parse_env("").unwrap(), vec![]);
+        assert_eq!(
+            parse_env(" A = 1 , B=\"x,y\", C=, D=a=b").unwrap(),
+            vec![
+                pair("A", "1"),
+                pair("B", "x,y"),
+                pair("C", ""),
+                pair("D", "a=b")
+            ]
+        );
+
+        for malformed in ["DEBUG", "=1", "1X=1", "MY-VAR=1", "A='unterminated"] {
+            let error = parse_env(malformed).unwrap_err().to_string();
+            assert!(
+                error.contains(malformed.split(',').next().unwrap()),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("").unwrap(), Vec::<String>::new());
+        assert_eq!(split_args("  a   b ").unwrap(), vec!["a", "b"]);
+        assert_eq!(
+            split_args(r#"--title 'My plot' --label "x axis" it\'s"#).unwrap(),
+            vec!["--title", "My plot", "--label", "x axis", "it's"]
+        );
+        assert_eq!(
+            split_args(r#"'' "a\"b" 'c\d'"#).unwrap(),
+            vec!["", "a\"b", "c\\d"]
+        );
+        assert!(split_args("'unterminated").is_err());
+        assert!(split_args("trailing\\").is_err());
+    }
+
     #[test]
     fn test_parse_md_code_output_directive() {
+        let output =
+            |id: &str, hash: Option<&str>, stderr: bool, file: Option<&str>| OutputDirective {
+                id: id.to_string(),
+                hash: hash.map(str::to_string),
+                stderr,
+                file: file.map(str::to_string),
+            };
+
         let result = parse_md_code_output_directive(r#"<!-- md-code-output: id="test" -->"#);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "test");
+        assert_eq!(result.unwrap(), output("test", None, false, None));
+
+        let result = parse_md_code_output_directive(
+            r#"<!-- md-code-output: id="test"; hash="0123456789ab" -->"#,
+        );
+        assert_eq!(
+            result.unwrap(),
+            output("test", Some("0123456789ab"), false, None)
+        );
+
+        let result = parse_md_code_output_directive(
+            r#"<!-- md-code-output: id="test"; stream="stderr"; hash="0123456789ab" -->"#,
+        );
+        assert_eq!(
+            result.unwrap(),
+            output("test", Some("0123456789ab"), true, None)
+        );
+
+        let result = parse_md_code_output_directive(
+            r#"<!-- md-code-output: id="plot"; file="assets/plot.svg"; hash="0123456789ab" -->"#,
+        );
+        assert_eq!(
+            result.unwrap(),
+            output("plot", Some("0123456789ab"), false, Some("assets/plot.svg"))
+        );
+
+        assert!(parse_md_code_output_directive(r#"<!-- md-code-output: hash="ab" -->"#).is_err());
+        assert!(parse_md_code_output_directive(
+            r#"<!-- md-code-output: id="test"; stream="both" -->"#
+        )
+        .is_err());
     }
 
     #[test]
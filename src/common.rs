@@ -16,6 +16,8 @@ use thiserror::Error;
 pub enum ExitCode {
     /// Successful termination (0)
     Success,
+    /// The check failed - e.g. `md table --check` found changes to make (1)
+    Failure,
     /// Command line usage error - invalid arguments or options (64)
     Usage,
     /// Data format error - input data was incorrect in some way (65)
@@ -29,6 +31,7 @@ impl ExitCode {
     pub fn code(self) -> i32 {
         match self {
             ExitCode::Success => 0,
+            ExitCode::Failure => 1,
             ExitCode::Usage => 64,
             ExitCode::DataErr => 65,
             ExitCode::IoErr => 74,
@@ -228,6 +231,11 @@ impl ProcessingError {
     pub fn code(line: usize, message: impl Into<String>) -> Self {
         Self::new(ErrorOrigin::Code, line, message)
     }
+
+    /// Create a TOC error
+    pub fn toc(line: usize, message: impl Into<String>) -> Self {
+        Self::new(ErrorOrigin::Toc, line, message)
+    }
 }
 
 /// Result of processing a markdown document
@@ -288,6 +296,45 @@ impl Default for ProcessingResult {
     }
 }
 
+// ============================================================================
+// Line Endings
+// ============================================================================
+
+/// Runs a line-based processor so that its output keeps the input's line endings
+///
+/// Processors split their input with `lines()` and join the result with `\n`,
+/// which would turn a `\r\n` document into a `\n` one. The processor is given
+/// the input with every `\r\n` replaced by `\n`, and if `\r\n` is the more
+/// common line ending in the input, every `\n` in the output becomes `\r\n`.
+/// A file with mixed line endings is thus normalized to its dominant style
+/// (`\n` when both are equally common).
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::common::{with_line_endings, ProcessingResult};
+///
+/// let shout = |text: &str| ProcessingResult::success(text.to_uppercase());
+/// assert_eq!(with_line_endings("a\r\nb\r\n", shout).output, "A\r\nB\r\n");
+/// assert_eq!(with_line_endings("a\nb\r\nc\n", shout).output, "A\nB\nC\n");
+/// ```
+pub fn with_line_endings<F>(text: &str, process: F) -> ProcessingResult
+where
+    F: FnOnce(&str) -> ProcessingResult,
+{
+    if !text.contains('\r') {
+        return process(text);
+    }
+
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let mut result = process(&text.replace("\r\n", "\n"));
+    if crlf > lf {
+        result.output = result.output.replace('\n', "\r\n");
+    }
+    result
+}
+
 /// Validates that an ID is not empty.
 ///
 /// Valid IDs must:
@@ -321,6 +368,21 @@ pub fn validate_id(id: &str) -> Result<(), String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_with_line_endings() {
+        let echo = |text: &str| ProcessingResult::success(text.to_string());
+        assert_eq!(with_line_endings("a\nb\n", echo).output, "a\nb\n");
+        assert_eq!(with_line_endings("a\r\nb", echo).output, "a\r\nb");
+        // Mixed endings follow the majority, and LF wins a tie
+        assert_eq!(
+            with_line_endings("a\r\nb\nc\r\n", echo).output,
+            "a\r\nb\r\nc\r\n"
+        );
+        assert_eq!(with_line_endings("a\r\nb\n", echo).output, "a\nb\n");
+        // A lone carriage return is not a line ending
+        assert_eq!(with_line_endings("a\rb\n", echo).output, "a\rb\n");
+    }
+
     #[test]
     fn test_get_fence_type() {
         assert_eq!(get_fence_type("```"), Some(FenceType::Backtick));
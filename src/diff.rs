@@ -0,0 +1,260 @@
+//! Line-based unified diffs, as printed by `md table --diff`
+
+/// Number of unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Above this many line pairs in the changed region, the region is shown as
+/// one block of removals followed by one of additions instead of being aligned
+const MAX_ALIGNED_PAIRS: usize = 4_000_000;
+
+/// One step of an edit script turning the old lines into the new ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    /// The line is in both (old index, new index)
+    Keep(usize, usize),
+    /// The old line at this index is removed
+    Remove(usize),
+    /// The new line at this index is added
+    Add(usize),
+}
+
+/// Renders the differences between two texts as a unified diff
+///
+/// Lines are compared exactly, and each hunk shows up to three unchanged lines
+/// of context on either side. The result starts with `--- old_name` and
+/// `+++ new_name` header lines, and is empty if the texts have the same lines.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::diff::unified_diff;
+///
+/// let diff = unified_diff("a\nb\nc\n", "a\nB\nc\n", "old", "new");
+/// assert_eq!(diff, "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+/// ```
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = edit_script(&old_lines, &new_lines);
+
+    if edits.iter().all(|edit| matches!(edit, Edit::Keep(..))) {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for hunk in hunks(&edits) {
+        output.push_str(&render_hunk(&edits[hunk], &old_lines, &new_lines));
+    }
+    output
+}
+
+/// Computes a shortest edit script between two lists of lines
+///
+/// Lines shared at the start and end are kept as they are; the region between
+/// them is aligned by a longest common subsequence.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|idx| Edit::Keep(idx, idx)).collect();
+
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_ALIGNED_PAIRS {
+        edits.extend((0..old_middle.len()).map(|idx| Edit::Remove(prefix + idx)));
+        edits.extend((0..new_middle.len()).map(|idx| Edit::Add(prefix + idx)));
+    } else {
+        edits.extend(
+            align(old_middle, new_middle)
+                .into_iter()
+                .map(|edit| match edit {
+                    Edit::Keep(i, j) => Edit::Keep(prefix + i, prefix + j),
+                    Edit::Remove(i) => Edit::Remove(prefix + i),
+                    Edit::Add(j) => Edit::Add(prefix + j),
+                }),
+        );
+    }
+
+    let old_start = old.len() - suffix;
+    let new_start = new.len() - suffix;
+    edits.extend((0..suffix).map(|idx| Edit::Keep(old_start + idx, new_start + idx)));
+    edits
+}
+
+/// Aligns two lists of lines by their longest common subsequence
+///
+/// Removals are placed before additions when both are possible, so a changed
+/// line reads as `-old` then `+new`.
+fn align(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let width = new.len() + 1;
+    // common[i * width + j] = length of the LCS of old[i..] and new[j..]
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Keep(i, j));
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && common[(i + 1) * width + j] >= common[i * width + j + 1])
+        {
+            edits.push(Edit::Remove(i));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(j));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// Groups the changes into hunks, returned as ranges of `edits`
+///
+/// Each hunk includes up to [`CONTEXT_LINES`] kept lines before its first and
+/// after its last change. Changes separated by no more than twice that many
+/// kept lines share a hunk.
+fn hunks(edits: &[Edit]) -> Vec<std::ops::Range<usize>> {
+    let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+
+    for (idx, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Keep(..)) {
+            continue;
+        }
+        let start = idx.saturating_sub(CONTEXT_LINES);
+        let end = (idx + 1 + CONTEXT_LINES).min(edits.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.end => hunk.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    hunks
+}
+
+/// Renders one hunk with its `@@ -start,count +start,count @@` header
+fn render_hunk(edits: &[Edit], old: &[&str], new: &[&str]) -> String {
+    // Where the hunk starts in each file (0-indexed), found from its first line
+    // that exists in that file. Hunks carry context, so a hunk without any line
+    // of one file can only come from that file being empty
+    let old_start = edits
+        .iter()
+        .find_map(|edit| match *edit {
+            Edit::Keep(i, _) | Edit::Remove(i) => Some(i),
+            Edit::Add(_) => None,
+        })
+        .unwrap_or(0);
+    let new_start = edits
+        .iter()
+        .find_map(|edit| match *edit {
+            Edit::Keep(_, j) | Edit::Add(j) => Some(j),
+            Edit::Remove(_) => None,
+        })
+        .unwrap_or(0);
+
+    let old_count = edits
+        .iter()
+        .filter(|edit| !matches!(edit, Edit::Add(_)))
+        .count();
+    let new_count = edits
+        .iter()
+        .filter(|edit| !matches!(edit, Edit::Remove(_)))
+        .count();
+
+    let mut output = format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(old_start, old_count),
+        hunk_range(new_start, new_count)
+    );
+    for edit in edits {
+        let line = match *edit {
+            Edit::Keep(i, _) => format!(" {}", old[i]),
+            Edit::Remove(i) => format!("-{}", old[i]),
+            Edit::Add(j) => format!("+{}", new[j]),
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+/// Formats a hunk's line range as `start,count`
+///
+/// Following GNU diff, an empty range is given as the line before it.
+fn hunk_range(start: usize, count: usize) -> String {
+    if count == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_identical() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "old", "new"), "");
+        assert_eq!(unified_diff("", "", "old", "new"), "");
+    }
+
+    #[test]
+    fn test_unified_diff_context_and_hunks() {
+        let old: String = (1..=12).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=12)
+            .filter(|&n| n != 11)
+            .map(|n| match n {
+                2 => "two\n".to_string(),
+                n => format!("{}\n", n),
+            })
+            .collect();
+
+        assert_eq!(
+            unified_diff(&old, &new, "a", "b"),
+            "--- a\n+++ b\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -8,5 +8,4 @@\n 8\n 9\n 10\n-11\n 12\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_nearby_changes_share_hunk() {
+        let diff = unified_diff("a\nb\nc\nd\ne\nf\n", "A\nb\nc\nd\ne\nF\n", "a", "b");
+        assert_eq!(
+            diff,
+            "--- a\n+++ b\n@@ -1,6 +1,6 @@\n-a\n+A\n b\n c\n d\n e\n-f\n+F\n"
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_insertions_and_removals() {
+        assert_eq!(
+            unified_diff("", "x\ny\n", "a", "b"),
+            "--- a\n+++ b\n@@ -0,0 +1,2 @@\n+x\n+y\n"
+        );
+        assert_eq!(
+            unified_diff("x\n", "", "a", "b"),
+            "--- a\n+++ b\n@@ -1,1 +0,0 @@\n-x\n"
+        );
+        assert_eq!(
+            unified_diff("a\nc\n", "a\nb\nc\n", "a", "b"),
+            "--- a\n+++ b\n@@ -1,2 +1,3 @@\n a\n+b\n c\n"
+        );
+    }
+}
@@ -27,7 +27,7 @@
 /// assert!(result.output.contains("- [x] Walk the dog")); // unchanged, already checked
 /// assert!(!result.has_errors());
 /// ```
-use crate::common::{CodeFenceTracker, ProcessingResult};
+use crate::common::{with_line_endings, CodeFenceTracker, ProcessingResult};
 use chrono::Local;
 
 /// Process markdown and mark checklist items as done
@@ -59,6 +59,11 @@ pub fn process_done(input: &str) -> ProcessingResult {
 /// A [`ProcessingResult`] containing the updated document with checklist items marked as done.
 /// This operation is infallible, so the result will never contain errors.
 pub fn process_done_with_timestamp(input: &str, timestamp: &str) -> ProcessingResult {
+    with_line_endings(input, |input| process_done_lf(input, timestamp))
+}
+
+/// Marks the open checklist items of a document with `\n` line endings as done
+fn process_done_lf(input: &str, timestamp: &str) -> ProcessingResult {
     let lines: Vec<&str> = input.lines().collect();
     let mut result: Vec<String> = Vec::new();
     let mut fence_tracker = CodeFenceTracker::new();
@@ -97,8 +102,8 @@ fn process_line(line: &str, timestamp: &str) -> String {
         return line.to_string();
     }
 
-    // Try to parse as a checklist item
-    if let Some(parsed) = parse_checklist_item(line) {
+    // Try to parse as an open checklist item
+    if let Some(parsed) = parse_checklist_item(line).filter(|item| !item.checked) {
         format!(
             "{}- [x] ~~{}~~ `COMPLETED: {}`",
             parsed.indent, parsed.text, timestamp
@@ -108,28 +113,68 @@ fn process_line(line: &str, timestamp: &str) -> String {
     }
 }
 
+/// Counts the open and the checked checklist items outside code blocks
+///
+/// Returns `(open, checked)`, as used by `md stats`.
+pub(crate) fn count_checklist_items(input: &str) -> (usize, usize) {
+    let mut fence_tracker = CodeFenceTracker::new();
+    let mut open = 0;
+    let mut checked = 0;
+    for line in input.lines() {
+        fence_tracker.process_line(line);
+        if fence_tracker.is_inside_code_block() || crate::common::is_code_fence(line) {
+            continue;
+        }
+        match parse_checklist_item(line) {
+            Some(item) if item.checked => checked += 1,
+            Some(_) => open += 1,
+            None => {}
+        }
+    }
+    (open, checked)
+}
+
 /// Parsed checklist item
 struct ChecklistItem<'a> {
     indent: &'a str,
     text: &'a str,
+    /// Whether the box is ticked (`- [x]` or `- [X]`)
+    checked: bool,
 }
 
-/// Parse a line as an open (unchecked) checklist item
+/// Parse a line as a checklist item, open (`- [ ]`) or checked (`- [x]`)
 ///
-/// Returns None if the line is not an open checklist item.
-/// Already checked items (`- [x]`) are not matched.
+/// Returns None if the line is not a checklist item.
 fn parse_checklist_item(line: &str) -> Option<ChecklistItem<'_>> {
     // Find leading whitespace
     let trimmed = line.trim_start();
     let indent_len = line.len() - trimmed.len();
     let indent = &line[..indent_len];
 
-    // Only match unchecked items: - [ ]
-    if let Some(text) = trimmed.strip_prefix("- [ ] ") {
-        Some(ChecklistItem { indent, text })
-    } else if trimmed == "- [ ]" {
-        // Empty unchecked checklist item
-        Some(ChecklistItem { indent, text: "" })
+    let (checked, rest) = if let Some(rest) = trimmed.strip_prefix("- [ ]") {
+        (false, rest)
+    } else if let Some(rest) = trimmed
+        .strip_prefix("- [x]")
+        .or_else(|| trimmed.strip_prefix("- [X]"))
+    {
+        (true, rest)
+    } else {
+        return None;
+    };
+
+    if let Some(text) = rest.strip_prefix(' ') {
+        Some(ChecklistItem {
+            indent,
+            text,
+            checked,
+        })
+    } else if rest.is_empty() {
+        // Empty checklist item
+        Some(ChecklistItem {
+            indent,
+            text: "",
+            checked,
+        })
     } else {
         None
     }
@@ -141,6 +186,17 @@ mod tests {
 
     const TEST_TIMESTAMP: &str = "2024-01-15 14:30:00";
 
+    #[test]
+    fn test_count_checklist_items() {
+        let input = "- [ ] open\n- [x] done\n  - [X] ~~done~~ `COMPLETED: 2024-01-15 14:30:00`\n\
+                     - [ ]\n- [x]not an item\n```\n- [ ] in code\n```\n";
+        assert_eq!(count_checklist_items(input), (2, 2));
+        assert_eq!(
+            count_checklist_items(&process_done_with_timestamp(input, TEST_TIMESTAMP).output),
+            (0, 4)
+        );
+    }
+
     #[test]
     fn test_unchecked_item() {
         let input = "- [ ] Buy groceries";
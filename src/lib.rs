@@ -1,6 +1,6 @@
 //! Library interface for mdutils functionality
 //!
-//! Exposes table, code, toc, and done processing modules for use in tests and as a library.
+//! Exposes table, code, toc, done, lint, and stats modules for use in tests and as a library.
 //!
 //! All processing functions return a [`ProcessingResult`] which contains:
 //! - The processed output (always produced, even with errors)
@@ -10,7 +10,10 @@
 
 pub mod code;
 pub mod common;
+pub mod diff;
 pub mod done;
+pub mod lint;
+pub mod stats;
 pub mod table;
 pub mod toc;
 
@@ -18,7 +21,17 @@ pub mod toc;
 pub use common::{ExitCode, ProcessingError, ProcessingResult};
 
 // Re-export commonly used functions for convenience
-pub use code::process_code_blocks;
+pub use code::{
+    list_code_blocks, process_code_blocks, process_code_blocks_in,
+    process_code_blocks_with_options, verify_code_blocks, ChangedOutput, CodeBlockInfo,
+    CodeOptions, VerifyReport,
+};
 pub use done::{process_done, process_done_with_timestamp};
-pub use table::format_tables;
+pub use lint::lint_document;
+pub use stats::{document_stats, DocumentStats};
+pub use table::{
+    export_csv, export_csv_selected, export_json, export_json_selected, format_tables,
+    format_tables_with_options, import_csv, import_csv_with_delimiter, import_json,
+    transpose_tables, FormatOptions, Table, TableSelection,
+};
 pub use toc::process_toc;
@@ -0,0 +1,37 @@
+//! Read-only checks over a whole document, as run by `md lint`
+//!
+//! Each module contributes the problems its own command would run into, so
+//! a document can be checked without formatting it or running any code.
+
+use crate::code::lint_code_blocks;
+use crate::common::ProcessingError;
+use crate::table::lint_tables;
+use crate::toc::lint_toc;
+
+/// Finds problems in a document without changing it or running any code
+///
+/// Reports table rows whose cell count differs from their header's, formula
+/// and directive errors (as [`crate::table::format_tables`] reports them), TOC
+/// start markers without an end marker or without any entries, and md-code
+/// directives that `md code` would reject, such as duplicate ids. Findings are
+/// ordered by line; those that apply to the whole document have line 0.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::lint::lint_document;
+///
+/// let findings = lint_document("| A | B |\n|---|---|\n| 1 |\n");
+/// assert_eq!(findings.len(), 1);
+/// assert_eq!(
+///     findings[0].to_string(),
+///     "[table] line 3: row has 1 cell, but the header has 2"
+/// );
+/// ```
+pub fn lint_document(text: &str) -> Vec<ProcessingError> {
+    let mut findings = lint_tables(text);
+    findings.extend(lint_toc(text));
+    findings.extend(lint_code_blocks(text));
+    findings.sort_by_key(|finding| finding.line);
+    findings
+}
@@ -1,15 +1,30 @@
 mod code;
 mod common;
+mod diff;
 mod done;
+mod lint;
+mod stats;
 mod table;
 mod toc;
 
-use clap::{Parser, Subcommand};
-use code::process_code_blocks;
+use clap::{Parser, Subcommand, ValueEnum};
+use code::{
+    list_code_blocks, process_code_blocks_with_options, verify_code_blocks, CodeBlockInfo,
+    CodeOptions,
+};
 use common::{ExitCode, ProcessingResult};
+use diff::unified_diff;
 use done::process_done;
+use lint::lint_document;
+use stats::document_stats;
 use std::io::{self, Read};
-use table::{create_table, format_tables, parse_table_spec};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use table::{
+    count_changed_tables, create_table_with_headers, export_csv_selected, export_json_selected,
+    format_tables_with_options, import_csv_with_delimiter, import_json, parse_table_spec,
+    transpose_tables, FormatOptions, TableSelection,
+};
 use toc::process_toc;
 
 #[derive(Parser)]
@@ -24,20 +39,217 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Format and align markdown tables in the input
-    Table,
+    Table {
+        /// Markdown file to read instead of stdin
+        file: Option<PathBuf>,
+        /// Swap the rows and columns of every table instead of formatting it
+        #[arg(long)]
+        transpose: bool,
+        /// Separate cells with single spaces instead of aligning columns
+        #[arg(long, conflicts_with = "transpose")]
+        compact: bool,
+        /// Print nothing but a summary, and exit with status 1 if formatting
+        /// (including updating formula results) would change the input
+        #[arg(long, conflicts_with = "transpose")]
+        check: bool,
+        /// Like --check, but print a unified diff of the changes instead of a summary
+        #[arg(long, conflicts_with = "transpose")]
+        diff: bool,
+        /// Keep running, and format FILE in place each time it changes (stop with Ctrl-C)
+        #[arg(long, requires = "file", conflicts_with_all = ["check", "diff"])]
+        watch: bool,
+    },
     /// Execute code blocks with md-code directives
-    Code,
+    Code {
+        /// Markdown file to read instead of stdin; relative cwd= directories
+        /// are resolved from its directory
+        file: Option<PathBuf>,
+        /// Run every block, even those whose output comment shows the code is unchanged
+        #[arg(long)]
+        force: bool,
+        /// Run only the block with this id, keeping the other blocks' output
+        /// (repeat to run several)
+        #[arg(long = "id", value_name = "ID")]
+        ids: Vec<String>,
+        /// Keep running the other blocks when one fails, then print the
+        /// document and exit with the error status
+        #[arg(long)]
+        keep_going: bool,
+        /// Run up to N blocks at the same time; blocks still wait for those
+        /// they read with stdin-from or name with depends-on
+        #[arg(short, long, value_name = "N", default_value_t = 1,
+              value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+        /// Keep at most N lines of each block's output, for blocks without
+        /// their own max-lines=
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        max_lines: Option<u32>,
+        /// List the md-code blocks that would run, one per line, without
+        /// running them or printing the document
+        #[arg(long, visible_alias = "list",
+              conflicts_with_all = ["force", "ids", "keep_going", "jobs", "max_lines", "verify"])]
+        dry_run: bool,
+        /// Run every block and compare its output with the output block in the
+        /// document, printing the blocks whose output changed instead of the
+        /// document; exits with status 1 if any did
+        #[arg(long)]
+        verify: bool,
+        /// With --verify, compare output exactly instead of ignoring trailing
+        /// whitespace and blank lines
+        #[arg(long, requires = "verify")]
+        strict: bool,
+    },
     /// Generate or update table of contents
     Toc,
     /// Mark checklist items as done with strikethrough and timestamp
     Done,
-    /// Create a new markdown table
+    /// Report malformed tables, formulas, TOC markers and code directives
+    /// without changing anything; exits with status 1 if any are found
+    Lint {
+        /// Markdown file to read instead of stdin
+        file: Option<PathBuf>,
+    },
+    /// Count the headers, tables, code blocks and checklist items in a document
+    Stats {
+        /// Markdown file to read instead of stdin
+        file: Option<PathBuf>,
+        /// Print the counts as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Create a new markdown table, checklist, or document skeleton
     New {
-        /// Table specification in format "table:R:C" (e.g., "table:2:3")
+        /// What to create: "table:R:C" (e.g., "table:2:3"), "table:R:HEADERS"
+        /// (e.g., "table:3:Name,Price,Qty"), "csv" or "json" to build a table from CSV or
+        /// from a JSON array of objects read on stdin, "checklist:N" or "checklist:ITEM,ITEM", or "doc:TITLE"
         spec: String,
+        /// Field separator for the csv spec: a single character, or "tab" for TSV
+        #[arg(long, value_parser = parse_delimiter)]
+        delimiter: Option<char>,
+    },
+    /// Export markdown tables in the input to another format
+    Export {
+        /// Output format
+        #[arg(value_enum)]
+        format: ExportFormat,
+        /// Export only the Nth table in the input (1-based)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(usize))]
+        nth: Option<usize>,
+        /// Export only the table with this id
+        #[arg(long, value_name = "ID", conflicts_with = "nth")]
+        table_id: Option<String>,
     },
 }
 
+/// Formats supported by the export command
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// Comma-separated values (RFC 4180), one block per table
+    Csv,
+    /// A JSON array of row objects keyed by header, one per table
+    Json,
+}
+
+/// Element requested by the `md new` spec
+#[derive(Debug, PartialEq)]
+enum NewSpec {
+    /// `table:R:C` or `table:R:HEADERS`
+    Table { rows: usize, headers: Vec<String> },
+    /// `csv`: a table built from CSV read on stdin
+    Csv,
+    /// `json`: a table built from a JSON array of objects read on stdin
+    Json,
+    /// `checklist:N` (empty items) or `checklist:ITEM,ITEM,...`
+    Checklist(Vec<String>),
+    /// `doc:TITLE`
+    Doc(String),
+}
+
+/// Spec kinds accepted by `md new`, listed in error messages
+const NEW_SPEC_KINDS: &str =
+    "table:R:C, table:R:HEADERS, csv, json, checklist:N, checklist:ITEM,ITEM, doc:TITLE";
+
+/// Parses the spec argument of `md new` by its kind prefix
+fn parse_new_spec(spec: &str) -> Result<NewSpec, String> {
+    let (kind, value) = spec.split_once(':').unwrap_or((spec, ""));
+    match kind {
+        "table" => {
+            let (rows, headers) = parse_table_spec(spec)?;
+            Ok(NewSpec::Table { rows, headers })
+        }
+        "csv" if !spec.contains(':') => Ok(NewSpec::Csv),
+        "json" if !spec.contains(':') => Ok(NewSpec::Json),
+        "checklist" => parse_checklist_spec(value).map(NewSpec::Checklist),
+        "doc" if !value.trim().is_empty() => Ok(NewSpec::Doc(value.trim().to_string())),
+        "doc" => Err("Invalid doc spec: expected a title (e.g., doc:Meeting Notes)".to_string()),
+        _ => Err(format!(
+            "Unknown spec '{}'. Supported kinds: {}",
+            spec, NEW_SPEC_KINDS
+        )),
+    }
+}
+
+/// Parses the `--delimiter` argument of `md new`
+fn parse_delimiter(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+    let delimiter = match (value, chars.next(), chars.next()) {
+        ("tab" | "\\t", _, _) => '\t',
+        (_, Some(c), None) => c,
+        _ => {
+            return Err(format!(
+                "expected a single character or \"tab\", got '{}'",
+                value
+            ))
+        }
+    };
+    if matches!(delimiter, '"' | '\n' | '\r') {
+        return Err(format!("{:?} cannot be used as a delimiter", delimiter));
+    }
+    Ok(delimiter)
+}
+
+/// Parses the value of a `checklist:` spec into item texts (empty for `checklist:N`)
+fn parse_checklist_spec(value: &str) -> Result<Vec<String>, String> {
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        let count = value
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid item count '{}'", value))?;
+        if count == 0 {
+            return Err("Item count must be greater than 0".to_string());
+        }
+        return Ok(vec![String::new(); count]);
+    }
+
+    let items: Vec<String> = value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .collect();
+    if items.iter().any(|item| item.is_empty()) {
+        return Err(format!(
+            "Invalid checklist spec 'checklist:{}': expected an item count (e.g., checklist:5) or comma-separated items (e.g., checklist:Buy milk,Call bank)",
+            value
+        ));
+    }
+    Ok(items)
+}
+
+/// Renders checklist items as open `- [ ]` lines
+fn create_checklist(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("- [ ] {}\n", item))
+        .collect()
+}
+
+/// Renders a document skeleton: an H1 title, a table of contents, and a first section
+fn create_document(title: &str) -> String {
+    let skeleton = format!(
+        "# {}\n\n<!-- md-toc: -->\n<!-- md-toc: end -->\n\n## Introduction\n\n",
+        title
+    );
+    process_toc(&skeleton).output
+}
+
 /// Reads all input from stdin into a String
 fn read_stdin() -> Result<String, String> {
     let stdin = io::stdin();
@@ -64,6 +276,214 @@ fn handle_result(result: ProcessingResult) -> ExitCode {
     }
 }
 
+/// Reports whether formatting would change the input, for `md table --check`
+///
+/// Prints a summary of the tables that would change (or, with `show_diff`, a
+/// unified diff) instead of the formatted document. Formula errors are
+/// reported and take precedence over changes in the exit status.
+fn check_tables(input: &str, options: &FormatOptions, show_diff: bool) -> ExitCode {
+    let result = format_tables_with_options(input, options);
+    let changed = result.output != input;
+
+    if show_diff {
+        print!(
+            "{}",
+            unified_diff(input, &result.output, "input", "formatted")
+        );
+    } else if changed {
+        match count_changed_tables(input, &result.output) {
+            0 => println!("the input would be reformatted"),
+            1 => println!("1 table would be reformatted"),
+            n => println!("{} tables would be reformatted", n),
+        }
+    }
+
+    for error in &result.errors {
+        eprintln!("error: {}", error);
+    }
+
+    if result.has_errors() {
+        ExitCode::DataErr
+    } else if changed {
+        ExitCode::Failure
+    } else {
+        ExitCode::Success
+    }
+}
+
+/// Prints the problems `md lint` finds in the input, one per line
+fn lint(input: &str) -> ExitCode {
+    let findings = lint_document(input);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    match findings.len() {
+        0 => ExitCode::Success,
+        1 => {
+            println!("1 problem found");
+            ExitCode::Failure
+        }
+        n => {
+            println!("{} problems found", n);
+            ExitCode::Failure
+        }
+    }
+}
+
+/// Reports the blocks whose output changed, for `md code --verify`
+///
+/// Prints a unified diff of each changed output and a summary instead of the
+/// document. Errors take precedence over changes in the exit status.
+fn verify_blocks(input: &str, options: &CodeOptions, strict: bool) -> ExitCode {
+    let report = verify_code_blocks(input, options, strict);
+
+    for change in &report.changed {
+        let stream = if change.stderr { "stderr" } else { "output" };
+        println!(
+            "{} of block '{}' (line {}) changed:",
+            stream, change.id, change.line
+        );
+        print!(
+            "{}",
+            unified_diff(&change.expected, &change.actual, "expected", "actual")
+        );
+    }
+    match report.changed.len() {
+        0 => {}
+        1 => println!("1 output changed"),
+        n => println!("{} outputs changed", n),
+    }
+
+    for error in &report.errors {
+        eprintln!("error: {}", error);
+    }
+
+    if !report.errors.is_empty() {
+        ExitCode::DataErr
+    } else if !report.changed.is_empty() {
+        ExitCode::Failure
+    } else {
+        ExitCode::Success
+    }
+}
+
+/// Prints one line per md-code block for `md code --dry-run`
+fn list_blocks(input: &str) -> ExitCode {
+    let blocks = match list_code_blocks(input) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::DataErr;
+        }
+    };
+    for block in &blocks {
+        println!("{}", describe_block(block));
+    }
+    ExitCode::Success
+}
+
+/// Describes a block as `line N: id="..."; bin="..."; timeout=S`, plus its
+/// args, stdin source and dependencies when it has them
+fn describe_block(block: &CodeBlockInfo) -> String {
+    let mut line = format!("line {}: id=\"{}\"; ", block.line, block.id);
+    match &block.bin {
+        Some(bin) => line.push_str(&format!("bin=\"{}\"", bin)),
+        None => line.push_str("bin missing"),
+    }
+    if !block.args.is_empty() {
+        line.push_str(&format!("; args={:?}", block.args));
+    }
+    line.push_str(&format!("; timeout={}", block.timeout_seconds));
+    if let Some(producer) = &block.stdin_from {
+        line.push_str(&format!("; stdin-from=\"{}\"", producer));
+    }
+    if !block.depends_on.is_empty() {
+        line.push_str(&format!("; depends-on=\"{}\"", block.depends_on.join(",")));
+    }
+    line
+}
+
+/// Reads the named file, or stdin if there is none, into a String
+fn read_input(file: Option<PathBuf>) -> Result<String, String> {
+    match file {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e)),
+        None => read_stdin(),
+    }
+}
+
+/// How often `--watch` checks whether the file has changed
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Processes the file again each time it changes, writing the result back,
+/// until the process is interrupted
+fn watch_file<F>(path: &Path, processor: F) -> ExitCode
+where
+    F: Fn(&str) -> ProcessingResult,
+{
+    if let Err(e) = std::fs::metadata(path) {
+        eprintln!("Error reading {}: {}", path.display(), e);
+        return ExitCode::IoErr;
+    }
+    eprintln!("Watching {} (press Ctrl-C to stop)", path.display());
+
+    let mut last_seen = None;
+    loop {
+        // Errors are reported but do not stop watching, since editors that
+        // save by replacing the file can make it briefly disappear
+        match watch_step(path, last_seen, &processor) {
+            Ok(seen) => last_seen = seen,
+            Err(e) => eprintln!("{}", e),
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Processes the file if its modification time is not `last_seen`, and
+/// returns the modification time to compare against next time
+///
+/// The result is only written if it differs from the file, and the time
+/// returned is that of the write, so the write does not trigger another pass.
+/// It is written to a temporary file that then replaces the original, so
+/// interrupting `--watch` never leaves a half-written file.
+fn watch_step<F>(
+    path: &Path,
+    last_seen: Option<SystemTime>,
+    processor: &F,
+) -> Result<Option<SystemTime>, String>
+where
+    F: Fn(&str) -> ProcessingResult,
+{
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))
+    };
+
+    let seen = modified(path)?;
+    if last_seen == Some(seen) {
+        return Ok(last_seen);
+    }
+
+    let input = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    let result = processor(&input);
+    for error in &result.errors {
+        eprintln!("error: {}", error);
+    }
+    if result.output == input {
+        return Ok(Some(seen));
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.md-watch", file_name));
+    std::fs::write(&temp, &result.output)
+        .and_then(|_| std::fs::rename(&temp, path))
+        .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+    eprintln!("Updated {}", path.display());
+    modified(path).map(Some)
+}
+
 /// Reads stdin and processes it with the given function
 fn process_stdin<F>(processor: F) -> ExitCode
 where
@@ -82,14 +502,165 @@ fn run() -> ExitCode {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Table => process_stdin(format_tables),
-        Commands::Code => process_stdin(process_code_blocks),
+        Commands::Table {
+            file,
+            transpose,
+            compact,
+            check,
+            diff,
+            watch,
+        } => {
+            let options = FormatOptions {
+                compact,
+                ..FormatOptions::default()
+            };
+            let processor = |input: &str| {
+                if transpose {
+                    transpose_tables(input)
+                } else {
+                    format_tables_with_options(input, &options)
+                }
+            };
+            match (file, watch) {
+                (Some(path), true) => watch_file(&path, processor),
+                (file, _) => match read_input(file) {
+                    Ok(input) if check || diff => check_tables(&input, &options, diff),
+                    Ok(input) => handle_result(processor(&input)),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        ExitCode::IoErr
+                    }
+                },
+            }
+        }
+        Commands::Code {
+            file,
+            dry_run: true,
+            ..
+        } => match read_input(file) {
+            Ok(input) => list_blocks(&input),
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::IoErr
+            }
+        },
+        Commands::Code {
+            file,
+            verify: true,
+            strict,
+            ids,
+            keep_going,
+            jobs,
+            max_lines,
+            ..
+        } => {
+            let options = CodeOptions {
+                base_dir: file
+                    .as_deref()
+                    .and_then(Path::parent)
+                    .unwrap_or(Path::new("."))
+                    .to_path_buf(),
+                only: ids,
+                keep_going,
+                jobs: jobs.into(),
+                max_lines: max_lines.map(|n| n as usize),
+                ..CodeOptions::default()
+            };
+            match read_input(file) {
+                Ok(input) => verify_blocks(&input, &options, strict),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ExitCode::IoErr
+                }
+            }
+        }
+        Commands::Code {
+            file: None,
+            force,
+            ids,
+            keep_going,
+            jobs,
+            max_lines,
+            ..
+        } => {
+            let options = CodeOptions {
+                force,
+                only: ids,
+                keep_going,
+                jobs: jobs.into(),
+                max_lines: max_lines.map(|n| n as usize),
+                ..CodeOptions::default()
+            };
+            process_stdin(|input| process_code_blocks_with_options(input, &options))
+        }
+        Commands::Code {
+            file: Some(path),
+            force,
+            ids,
+            keep_going,
+            jobs,
+            max_lines,
+            ..
+        } => match std::fs::read_to_string(&path) {
+            Ok(input) => {
+                let options = CodeOptions {
+                    base_dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+                    force,
+                    only: ids,
+                    keep_going,
+                    jobs: jobs.into(),
+                    max_lines: max_lines.map(|n| n as usize),
+                };
+                handle_result(process_code_blocks_with_options(&input, &options))
+            }
+            Err(e) => {
+                eprintln!("Error reading {}: {}", path.display(), e);
+                ExitCode::IoErr
+            }
+        },
         Commands::Toc => process_stdin(process_toc),
         Commands::Done => process_stdin(process_done),
-        Commands::New { spec } => match parse_table_spec(&spec) {
-            Ok((rows, cols)) => {
-                let table = create_table(rows, cols);
-                print!("{}", table);
+        Commands::Lint { file } => match read_input(file) {
+            Ok(input) => lint(&input),
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::IoErr
+            }
+        },
+        Commands::Stats { file, json } => match read_input(file) {
+            Ok(input) => {
+                let stats = document_stats(&input);
+                if json {
+                    print!("{}", stats.to_json());
+                } else {
+                    print!("{}", stats.to_text());
+                }
+                ExitCode::Success
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::IoErr
+            }
+        },
+        Commands::New { spec, delimiter } => match parse_new_spec(&spec) {
+            Ok(NewSpec::Csv) => {
+                process_stdin(|input| import_csv_with_delimiter(input, delimiter.unwrap_or(',')))
+            }
+            Ok(_) if delimiter.is_some() => {
+                eprintln!("Error: --delimiter only applies to the csv spec");
+                ExitCode::Usage
+            }
+            Ok(NewSpec::Json) => process_stdin(import_json),
+            Ok(NewSpec::Table { rows, headers }) => {
+                print!("{}", create_table_with_headers(rows, &headers));
+                ExitCode::Success
+            }
+            Ok(NewSpec::Checklist(items)) => {
+                print!("{}", create_checklist(&items));
+                ExitCode::Success
+            }
+            Ok(NewSpec::Doc(title)) => {
+                print!("{}", create_document(&title));
                 ExitCode::Success
             }
             Err(e) => {
@@ -97,9 +668,159 @@ fn run() -> ExitCode {
                 ExitCode::Usage
             }
         },
+        Commands::Export {
+            format,
+            nth,
+            table_id,
+        } => {
+            let selection = match (nth, table_id) {
+                (Some(n), _) => TableSelection::Nth(n),
+                (None, Some(id)) => TableSelection::Id(id),
+                (None, None) => TableSelection::All,
+            };
+            match format {
+                ExportFormat::Csv => process_stdin(|input| export_csv_selected(input, &selection)),
+                ExportFormat::Json => {
+                    process_stdin(|input| export_json_selected(input, &selection))
+                }
+            }
+        }
     }
 }
 
 fn main() -> ExitCode {
     run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_new_spec_table() {
+        assert_eq!(
+            parse_new_spec("table:2:3"),
+            Ok(NewSpec::Table {
+                rows: 2,
+                headers: vec![String::new(); 3],
+            })
+        );
+        assert_eq!(
+            parse_new_spec("table:1:Name,Qty"),
+            Ok(NewSpec::Table {
+                rows: 1,
+                headers: vec!["Name".to_string(), "Qty".to_string()],
+            })
+        );
+        assert!(parse_new_spec("table:0:3").is_err());
+    }
+
+    #[test]
+    fn test_watch_step_processes_only_changes() {
+        let path = std::env::temp_dir().join(format!("mdutils-watch-{}.md", std::process::id()));
+        std::fs::write(&path, "| a | bb |\n|---|---|\n| ccc | d |\n").unwrap();
+        let format = |input: &str| format_tables_with_options(input, &FormatOptions::default());
+
+        // The first pass formats the file in place
+        let seen = watch_step(&path, None, &format).unwrap();
+        let formatted = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(formatted, "| a   | bb  |\n| --- | --- |\n| ccc | d   |\n");
+        assert_eq!(
+            seen,
+            Some(std::fs::metadata(&path).unwrap().modified().unwrap())
+        );
+
+        // Its own write is not processed again
+        let panics = |_: &str| -> ProcessingResult { panic!("unchanged file was processed") };
+        assert_eq!(watch_step(&path, seen, &panics).unwrap(), seen);
+
+        // A file that changed but is already formatted is left alone
+        let seen = watch_step(&path, None, &format).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), formatted);
+        assert_eq!(
+            seen,
+            Some(std::fs::metadata(&path).unwrap().modified().unwrap())
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watch_step(&path, seen, &format).is_err());
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter(","), Ok(','));
+        assert_eq!(parse_delimiter(";"), Ok(';'));
+        assert_eq!(parse_delimiter("tab"), Ok('\t'));
+        assert_eq!(parse_delimiter("\\t"), Ok('\t'));
+        assert_eq!(parse_delimiter("\t"), Ok('\t'));
+        assert!(parse_delimiter("").is_err());
+        assert!(parse_delimiter(";;").is_err());
+        assert!(parse_delimiter("\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_new_spec_csv() {
+        assert_eq!(parse_new_spec("csv"), Ok(NewSpec::Csv));
+        assert!(parse_new_spec("csv:x").is_err());
+        assert_eq!(parse_new_spec("json"), Ok(NewSpec::Json));
+        assert!(parse_new_spec("json:x").is_err());
+    }
+
+    #[test]
+    fn test_parse_new_spec_checklist() {
+        assert_eq!(
+            parse_new_spec("checklist:3"),
+            Ok(NewSpec::Checklist(vec![String::new(); 3]))
+        );
+        assert_eq!(
+            parse_new_spec("checklist:Buy milk, Call bank"),
+            Ok(NewSpec::Checklist(vec![
+                "Buy milk".to_string(),
+                "Call bank".to_string()
+            ]))
+        );
+        // A lone non-numeric item is an item, not a count
+        assert_eq!(
+            parse_new_spec("checklist:2nd draft"),
+            Ok(NewSpec::Checklist(vec!["2nd draft".to_string()]))
+        );
+        assert!(parse_new_spec("checklist:0").is_err());
+        assert!(parse_new_spec("checklist:").is_err());
+        assert!(parse_new_spec("checklist:a,,b").is_err());
+
+        assert_eq!(
+            create_checklist(&[String::new(), String::new()]),
+            "- [ ] \n- [ ] \n"
+        );
+        assert_eq!(
+            create_checklist(&["Buy milk".to_string(), "Call bank".to_string()]),
+            "- [ ] Buy milk\n- [ ] Call bank\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_new_spec_doc() {
+        assert_eq!(
+            parse_new_spec("doc:Meeting Notes: Q3"),
+            Ok(NewSpec::Doc("Meeting Notes: Q3".to_string()))
+        );
+        assert!(parse_new_spec("doc:").is_err());
+        assert!(parse_new_spec("doc").is_err());
+
+        let doc = create_document("Meeting Notes");
+        assert!(doc.starts_with("# Meeting Notes\n\n<!-- md-toc: -->\n"));
+        assert!(doc.contains("- [Introduction](#introduction)\n<!-- md-toc: end -->"));
+        assert!(doc.ends_with("## Introduction\n\n"));
+        // The table of contents is already up to date
+        assert_eq!(process_toc(&doc).output, doc);
+    }
+
+    #[test]
+    fn test_parse_new_spec_unknown_kind() {
+        for spec in ["list:3", "tabel:2:3", ""] {
+            let error = parse_new_spec(spec).unwrap_err();
+            assert!(error.starts_with("Unknown spec"), "spec {:?}", spec);
+            assert!(error.contains(NEW_SPEC_KINDS));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Read-only summary of a document's structure, as printed by `md stats`
+
+use crate::code::count_code_blocks;
+use crate::common::CodeFenceTracker;
+use crate::done::count_checklist_items;
+use crate::table::count_tables;
+use crate::toc::count_headers;
+
+/// Counts of the elements in a markdown document
+///
+/// Elements inside code fences are not counted, except by `code_blocks`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentStats {
+    /// Number of ATX headers per level, `headers[0]` being H1
+    pub headers: [usize; 6],
+    /// Number of tables
+    pub tables: usize,
+    /// Number of fenced code blocks
+    pub code_blocks: usize,
+    /// Number of code blocks with an md-code directive, or `None` if the
+    /// directives cannot be parsed (`md lint` reports why)
+    pub md_code_blocks: Option<usize>,
+    /// Number of open checklist items (`- [ ]`)
+    pub open_items: usize,
+    /// Number of checked checklist items (`- [x]`)
+    pub done_items: usize,
+}
+
+impl DocumentStats {
+    /// Renders the counts as an aligned summary, one count per line
+    ///
+    /// Header levels with no headers are left out.
+    pub fn to_text(&self) -> String {
+        let mut entries = vec![("Headers".to_string(), self.headers.iter().sum())];
+        for (idx, &count) in self.headers.iter().enumerate() {
+            if count > 0 {
+                entries.push((format!("  H{}", idx + 1), count));
+            }
+        }
+        entries.push(("Tables".to_string(), self.tables));
+        entries.push(("Code blocks".to_string(), self.code_blocks));
+        if let Some(count) = self.md_code_blocks {
+            entries.push(("  with md-code".to_string(), count));
+        }
+        entries.push((
+            "Checklist items".to_string(),
+            self.open_items + self.done_items,
+        ));
+        entries.push(("  done".to_string(), self.done_items));
+        entries.push(("  open".to_string(), self.open_items));
+
+        let label_width = entries
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0);
+        let count_width = entries
+            .iter()
+            .map(|(_, count)| count.to_string().len())
+            .max()
+            .unwrap_or(0);
+        entries
+            .iter()
+            .map(|(label, count)| {
+                format!(
+                    "{:<label_width$}  {:>count_width$}\n",
+                    format!("{}:", label),
+                    count,
+                    label_width = label_width + 1,
+                    count_width = count_width
+                )
+            })
+            .collect()
+    }
+
+    /// Renders the counts as a JSON object
+    pub fn to_json(&self) -> String {
+        let levels: Vec<String> = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(idx, count)| format!("\"h{}\": {}", idx + 1, count))
+            .collect();
+        format!(
+            "{{\n  \"headers\": {{\"total\": {}, {}}},\n  \"tables\": {},\n  \
+             \"code_blocks\": {{\"total\": {}, \"md_code\": {}}},\n  \
+             \"checklist\": {{\"total\": {}, \"done\": {}, \"open\": {}}}\n}}\n",
+            self.headers.iter().sum::<usize>(),
+            levels.join(", "),
+            self.tables,
+            self.code_blocks,
+            self.md_code_blocks
+                .map_or_else(|| "null".to_string(), |count| count.to_string()),
+            self.open_items + self.done_items,
+            self.done_items,
+            self.open_items
+        )
+    }
+}
+
+/// Counts the headers, tables, code blocks and checklist items in a document
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::stats::document_stats;
+///
+/// let stats = document_stats("# Plan\n\n- [x] Draft\n- [ ] Review\n");
+/// assert_eq!(stats.headers[0], 1);
+/// assert_eq!((stats.done_items, stats.open_items), (1, 1));
+/// ```
+pub fn document_stats(text: &str) -> DocumentStats {
+    let (open_items, done_items) = count_checklist_items(text);
+    DocumentStats {
+        headers: count_headers(text),
+        tables: count_tables(text),
+        code_blocks: count_fenced_blocks(text),
+        md_code_blocks: count_code_blocks(text).ok(),
+        open_items,
+        done_items,
+    }
+}
+
+/// Counts the fenced code blocks; fences inside another block's content are
+/// part of that block and not counted
+fn count_fenced_blocks(text: &str) -> usize {
+    let mut fence_tracker = CodeFenceTracker::new();
+    text.lines()
+        .filter(|line| fence_tracker.process_line(line) && fence_tracker.is_inside_code_block())
+        .count()
+}
@@ -0,0 +1,131 @@
+//! Comparing a document with its formatted version for `md table --check`,
+//! and finding problems in its tables for `md lint`
+
+use super::format_tables;
+use super::parser::{
+    collect_tables, is_formula_comment, is_md_table_comment, skip_generated_comments,
+};
+use crate::common::ProcessingError;
+
+/// Counts the tables that formatting changed between `original` and `formatted`
+///
+/// A table counts as changed if any of its lines differ, including its
+/// `md-table` directive comments and the error and warning comments generated
+/// after them. `formatted` must be the output of formatting `original`, which
+/// keeps every table in place, so tables are matched up by position.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::{count_changed_tables, format_tables};
+///
+/// let doc = "| A   |\n| --- |\n| 1   |\n\n| B |\n|---|\n| 2 |\n";
+/// let formatted = format_tables(doc).output;
+/// assert_eq!(count_changed_tables(doc, &formatted), 1);
+/// ```
+pub fn count_changed_tables(original: &str, formatted: &str) -> usize {
+    let original_lines: Vec<&str> = original.lines().collect();
+    let formatted_lines: Vec<&str> = formatted.lines().collect();
+    let original_blocks = table_blocks(&original_lines);
+    let formatted_blocks = table_blocks(&formatted_lines);
+
+    let changed = original_blocks
+        .iter()
+        .zip(&formatted_blocks)
+        .filter(|(before, after)| before != after)
+        .count();
+    // Unmatched tables (which formatting should never produce) count as changed
+    changed + original_blocks.len().abs_diff(formatted_blocks.len())
+}
+
+/// Finds problems in the document's tables without changing it
+///
+/// Reports rows whose cell count differs from the header's, followed by the
+/// errors [`format_tables`] collects, such as formulas that fail to parse or
+/// evaluate and invalid directives.
+pub(crate) fn lint_tables(text: &str) -> Vec<ProcessingError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut findings = Vec::new();
+    for (start, rows) in collect_tables(&lines) {
+        let columns = rows[0].len();
+        for (idx, row) in rows.iter().enumerate().skip(1) {
+            if row.len() != columns {
+                findings.push(ProcessingError::table(
+                    start + idx + 1,
+                    format!(
+                        "row has {} cell{}, but the header has {}",
+                        row.len(),
+                        if row.len() == 1 { "" } else { "s" },
+                        columns
+                    ),
+                ));
+            }
+        }
+    }
+    findings.extend(format_tables(text).errors);
+    findings
+}
+
+/// The lines of each table in the document together with its directive comments
+fn table_blocks<'a>(lines: &[&'a str]) -> Vec<Vec<&'a str>> {
+    collect_tables(lines)
+        .into_iter()
+        .map(|(start, rows)| {
+            let mut end = start + rows.len();
+            if end < lines.len() && is_md_table_comment(lines[end]) {
+                end = skip_generated_comments(lines, end + 1);
+                while end < lines.len() && is_formula_comment(lines[end]) {
+                    end = skip_generated_comments(lines, end + 1);
+                }
+            } else {
+                end = skip_generated_comments(lines, end);
+            }
+            lines[start..end].to_vec()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_changed_tables() {
+        let formatted = "| A   |\n| --- |\n| 1   |\n";
+        assert_eq!(count_changed_tables(formatted, formatted), 0);
+
+        let doc = "# Title\n\n| A |\n|---|\n| 1 |\n\ntext\n\n| B   |\n| --- |\n| 2   |\n";
+        assert_eq!(count_changed_tables(doc, &format_tables(doc).output), 1);
+    }
+
+    #[test]
+    fn test_count_changed_tables_includes_comments() {
+        // The table is aligned, but its formula result is stale
+        let stale = "| A   | B   |\n| --- | --- |\n| 2   | 0   |\n<!-- md-table: B1 = A1 * 2 -->\n";
+        assert_eq!(count_changed_tables(stale, &format_tables(stale).output), 1);
+
+        // A leftover error comment is dropped once the formula is fixed
+        let fixed = "| A   | B   |\n| --- | --- |\n| 2   | 4   |\n\
+                     <!-- md-table: B1 = A1 * 2 -->\n<!-- md-error: old -->\n";
+        assert_eq!(count_changed_tables(fixed, &format_tables(fixed).output), 1);
+    }
+
+    #[test]
+    fn test_lint_tables() {
+        let doc = "| A | B |\n|---|---|\n| 1 |\n| 2 | 3 | 4 |\n\n\
+                   | C |\n|---|\n| 5 |\n<!-- md-table: C1 = C1 + -->\n";
+        let findings: Vec<(usize, String)> = lint_tables(doc)
+            .into_iter()
+            .map(|finding| (finding.line, finding.message))
+            .collect();
+        assert_eq!(findings.len(), 3, "{:?}", findings);
+        assert_eq!(
+            findings[0],
+            (3, "row has 1 cell, but the header has 2".to_string())
+        );
+        assert_eq!(findings[1].0, 4);
+        assert_eq!(findings[2].0, 6);
+
+        assert!(lint_tables("| A | B |\n|---|---|\n| 1 | 2 |\n").is_empty());
+    }
+}
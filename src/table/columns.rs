@@ -0,0 +1,185 @@
+//! Structural column edits requested by `addcol=`, `dropcol=` and `movecol=`
+
+use super::formula::col_index_to_letter;
+use super::parser::{pad_rows, ColumnEdit, ColumnPosition};
+
+/// Applies column edits to parsed table rows (including the separator row)
+///
+/// Edits run in order, and each one's column letters refer to the table as
+/// left by the edit before it. Ragged rows are padded first. The edits are
+/// all-or-nothing: if any of them names a column outside the table, `rows` is
+/// left unchanged and the error is returned.
+pub(crate) fn apply_column_edits(
+    rows: &mut Vec<Vec<String>>,
+    edits: &[ColumnEdit],
+) -> Result<(), String> {
+    let mut edited = rows.clone();
+    pad_rows(&mut edited);
+
+    for edit in edits {
+        let width = edited.first().map_or(0, Vec::len);
+        match edit {
+            ColumnEdit::Add { header, position } => {
+                let index = insert_index(*position, width)?;
+                for (row_idx, row) in edited.iter_mut().enumerate() {
+                    let cell = match row_idx {
+                        0 => header.clone(),
+                        1 => "---".to_string(),
+                        _ => String::new(),
+                    };
+                    row.insert(index, cell);
+                }
+            }
+            ColumnEdit::Drop { column } => {
+                check_column(*column, width)?;
+                if width == 1 {
+                    return Err(format!(
+                        "Cannot drop column {}: it is the only column",
+                        col_index_to_letter(*column)
+                    ));
+                }
+                for row in &mut edited {
+                    row.remove(*column);
+                }
+            }
+            ColumnEdit::Move { column, position } => {
+                check_column(*column, width)?;
+                let index = insert_index(*position, width)?;
+                // Removing the column shifts everything after it one place left
+                let index = if index > *column { index - 1 } else { index };
+                for row in &mut edited {
+                    let cell = row.remove(*column);
+                    row.insert(index, cell);
+                }
+            }
+        }
+    }
+
+    *rows = edited;
+    Ok(())
+}
+
+/// Resolves a position to the index a new column is inserted at
+fn insert_index(position: ColumnPosition, width: usize) -> Result<usize, String> {
+    match position {
+        ColumnPosition::Before(column) => check_column(column, width).map(|_| column),
+        ColumnPosition::After(column) => check_column(column, width).map(|_| column + 1),
+        ColumnPosition::End => Ok(width),
+    }
+}
+
+/// Checks that a 0-indexed column exists in a table `width` columns wide
+fn check_column(column: usize, width: usize) -> Result<(), String> {
+    if column < width {
+        Ok(())
+    } else {
+        Err(format!(
+            "Column {} does not exist (table has {} columns)",
+            col_index_to_letter(column),
+            width
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_add_column() {
+        let mut rows = table(&[&["A", "B"], &["---", ":---:"], &["1", "2"]]);
+        let edits = [
+            ColumnEdit::Add {
+                header: "Mid".to_string(),
+                position: ColumnPosition::After(0),
+            },
+            ColumnEdit::Add {
+                header: "Last".to_string(),
+                position: ColumnPosition::End,
+            },
+            ColumnEdit::Add {
+                header: "First".to_string(),
+                position: ColumnPosition::Before(0),
+            },
+        ];
+
+        apply_column_edits(&mut rows, &edits).unwrap();
+        assert_eq!(
+            rows,
+            table(&[
+                &["First", "A", "Mid", "B", "Last"],
+                &["---", "---", "---", ":---:", "---"],
+                &["", "1", "", "2", ""],
+            ])
+        );
+    }
+
+    #[test]
+    fn test_drop_column() {
+        let mut rows = table(&[
+            &["A", "B", "C"],
+            &["---", "---", "---:"],
+            &["1", "2", "3"],
+            &["4"],
+        ]);
+
+        apply_column_edits(&mut rows, &[ColumnEdit::Drop { column: 1 }]).unwrap();
+        assert_eq!(
+            rows,
+            table(&[&["A", "C"], &["---", "---:"], &["1", "3"], &["4", ""]])
+        );
+
+        let mut single = table(&[&["A"], &["---"], &["1"]]);
+        let error = apply_column_edits(&mut single, &[ColumnEdit::Drop { column: 0 }]);
+        assert_eq!(
+            error,
+            Err("Cannot drop column A: it is the only column".to_string())
+        );
+    }
+
+    #[test]
+    fn test_move_column() {
+        let rows = table(&[
+            &["A", "B", "C", "D"],
+            &["---", "---", "---", "---"],
+            &["1", "2", "3", "4"],
+        ]);
+        let moved = |column, position| {
+            let mut rows = rows.clone();
+            apply_column_edits(&mut rows, &[ColumnEdit::Move { column, position }]).unwrap();
+            rows[0].join("")
+        };
+
+        assert_eq!(moved(3, ColumnPosition::Before(1)), "ADBC");
+        assert_eq!(moved(0, ColumnPosition::After(2)), "BCAD");
+        assert_eq!(moved(0, ColumnPosition::Before(3)), "BCAD");
+        assert_eq!(moved(3, ColumnPosition::After(0)), "ADBC");
+        assert_eq!(moved(1, ColumnPosition::Before(1)), "ABCD");
+        assert_eq!(moved(1, ColumnPosition::End), "ACDB");
+    }
+
+    #[test]
+    fn test_edits_are_all_or_nothing() {
+        let mut rows = table(&[&["A", "B"], &["---", "---"], &["1", "2"]]);
+        let edits = [
+            ColumnEdit::Drop { column: 0 },
+            ColumnEdit::Move {
+                column: 0,
+                position: ColumnPosition::After(1),
+            },
+        ];
+
+        let error = apply_column_edits(&mut rows, &edits);
+        assert_eq!(
+            error,
+            Err("Column B does not exist (table has 1 columns)".to_string())
+        );
+        assert_eq!(rows, table(&[&["A", "B"], &["---", "---"], &["1", "2"]]));
+    }
+}
@@ -0,0 +1,455 @@
+//! Conversion between markdown tables and CSV (RFC 4180)
+
+use super::format_tables;
+use super::formatter::{column_widths, push_table_row};
+use super::parser::{collect_tables, find_table_id, is_separator_row};
+use crate::common::{ProcessingError, ProcessingResult};
+
+/// Which tables of a document an export such as [`export_csv_selected`] covers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TableSelection {
+    /// Every table, in document order
+    #[default]
+    All,
+    /// The `n`th table (1-based), counting only tables outside code fences
+    Nth(usize),
+    /// The table whose `md-table` directive declares this `id`
+    Id(String),
+}
+
+/// Exports every markdown table in the document as CSV
+///
+/// Formulas are evaluated first (as by [`format_tables`]), so computed cells
+/// export their values. Each table is converted with its header row first and
+/// the separator row removed. Escaped pipes (`\|`) become plain `|`. Fields
+/// containing commas, double quotes, or line breaks are quoted (with embedded
+/// quotes doubled) as described in RFC 4180. When the document contains
+/// several tables, their CSV blocks are separated by a blank line. Tables
+/// inside code fences are ignored.
+///
+/// # Returns
+///
+/// A [`ProcessingResult`] containing the CSV text, along with any formula
+/// errors. If the document has no tables, the output is empty and an error is
+/// reported.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::export_csv;
+///
+/// let result = export_csv("| Name | Note |\n|---|---|\n| Bob | a, b |\n");
+/// assert_eq!(result.output, "Name,Note\nBob,\"a, b\"\n");
+/// ```
+#[allow(dead_code)]
+pub fn export_csv(text: &str) -> ProcessingResult {
+    export_csv_selected(text, &TableSelection::All)
+}
+
+/// Exports the tables picked by `selection` as CSV
+///
+/// Works like [`export_csv`]. Formula errors in tables that are not selected
+/// are not reported. If no table matches the selection, the output is empty
+/// and an error is reported.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::{export_csv_selected, TableSelection};
+///
+/// let doc = "| A |\n|---|\n| 1 |\n\n| B |\n|---|\n| 2 |\n<!-- md-table: id=\"b\" -->\n";
+/// let selection = TableSelection::Id("b".to_string());
+/// assert_eq!(export_csv_selected(doc, &selection).output, "B\n2\n");
+/// ```
+pub fn export_csv_selected(text: &str, selection: &TableSelection) -> ProcessingResult {
+    export_tables(text, selection, table_to_csv)
+}
+
+/// Converts the tables picked by `selection` with `convert`, after evaluating formulas
+///
+/// The converted blocks are joined with newlines. Shared by the export formats.
+pub(super) fn export_tables(
+    text: &str,
+    selection: &TableSelection,
+    convert: fn(&[Vec<String>]) -> String,
+) -> ProcessingResult {
+    let lines: Vec<&str> = text.lines().collect();
+    let tables = collect_tables(&lines);
+
+    if tables.is_empty() {
+        return ProcessingResult::with_errors(
+            String::new(),
+            vec![ProcessingError::table(
+                0,
+                "no markdown tables found in input",
+            )],
+        );
+    }
+
+    // Pick tables in the input, where errors are reported against their first lines
+    let selected: Vec<usize> = match selection {
+        TableSelection::All => (0..tables.len()).collect(),
+        TableSelection::Nth(n) if (1..=tables.len()).contains(n) => vec![n - 1],
+        TableSelection::Nth(n) => {
+            let message = format!(
+                "table {} not found (the input has {} tables)",
+                n,
+                tables.len()
+            );
+            return ProcessingResult::with_errors(
+                String::new(),
+                vec![ProcessingError::table(0, message)],
+            );
+        }
+        TableSelection::Id(id) => {
+            match tables.iter().position(|(start, rows)| {
+                find_table_id(&lines, start + rows.len()).as_ref() == Some(id)
+            }) {
+                Some(idx) => vec![idx],
+                None => {
+                    return ProcessingResult::with_errors(
+                        String::new(),
+                        vec![ProcessingError::table(
+                            0,
+                            format!("no table with id '{}' found in input", id),
+                        )],
+                    )
+                }
+            }
+        }
+    };
+
+    // Formatting never adds or removes tables, so they keep their positions
+    let formatted = format_tables(text);
+    let formatted_lines: Vec<&str> = formatted.output.lines().collect();
+    let formatted_tables = collect_tables(&formatted_lines);
+
+    let blocks: Vec<String> = selected
+        .iter()
+        .map(|&idx| convert(&formatted_tables[idx].1))
+        .collect();
+
+    let table_lines: Vec<usize> = tables.iter().map(|(start, _)| start + 1).collect();
+    let errors = formatted
+        .errors
+        .into_iter()
+        .filter(
+            |error| match table_lines.iter().position(|&line| line == error.line) {
+                Some(idx) => selected.contains(&idx),
+                None => true,
+            },
+        )
+        .collect();
+
+    ProcessingResult::with_errors(blocks.join("\n"), errors)
+}
+
+/// Converts parsed table rows to CSV, skipping the separator row
+fn table_to_csv(rows: &[Vec<String>]) -> String {
+    let mut csv = String::new();
+
+    for (idx, row) in rows.iter().enumerate() {
+        if idx == 1 && is_separator_row(row) {
+            continue;
+        }
+
+        let fields: Vec<String> = row
+            .iter()
+            .map(|cell| escape_csv_field(&cell.replace("\\|", "|")))
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Quotes a CSV field if it contains a comma, double quote, or line break
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Converts CSV text into an aligned markdown table
+///
+/// The first CSV record becomes the header row and a separator row is generated
+/// below it. Quoted fields may contain commas, doubled quotes, and line breaks;
+/// line breaks are folded into spaces and pipes are escaped so every record stays
+/// on a single table row. Short records are padded with empty cells.
+///
+/// # Returns
+///
+/// A [`ProcessingResult`] containing the formatted table. If the input has no
+/// records or contains an unterminated quoted field, the output is empty and an
+/// error is reported.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::import_csv;
+///
+/// let result = import_csv("Name,Note\nBob,\"a, b\"\n");
+/// assert_eq!(
+///     result.output,
+///     "| Name | Note |\n| ---- | ---- |\n| Bob  | a, b |\n"
+/// );
+/// ```
+#[allow(dead_code)]
+pub fn import_csv(text: &str) -> ProcessingResult {
+    import_csv_with_delimiter(text, ',')
+}
+
+/// Converts delimited text into an aligned markdown table
+///
+/// Works like [`import_csv`], but fields are separated by `delimiter` instead
+/// of a comma, so `'\t'` reads TSV. Quoting rules are unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::import_csv_with_delimiter;
+///
+/// let result = import_csv_with_delimiter("Name\tNote\nBob\ta, b\n", '\t');
+/// assert_eq!(
+///     result.output,
+///     "| Name | Note |\n| ---- | ---- |\n| Bob  | a, b |\n"
+/// );
+/// ```
+pub fn import_csv_with_delimiter(text: &str, delimiter: char) -> ProcessingResult {
+    let records = match parse_csv(text, delimiter) {
+        Ok(records) => records,
+        Err(e) => {
+            return ProcessingResult::with_errors(String::new(), vec![ProcessingError::table(0, e)])
+        }
+    };
+
+    if records.is_empty() {
+        return ProcessingResult::with_errors(
+            String::new(),
+            vec![ProcessingError::table(0, "no CSV records found in input")],
+        );
+    }
+
+    ProcessingResult::success(records_to_table(records))
+}
+
+/// Renders records of raw fields as an aligned table, the first record as header
+///
+/// Fields are made safe for a table cell with [`field_to_cell`] and short
+/// records are padded with empty cells.
+pub(super) fn records_to_table(records: Vec<Vec<String>>) -> String {
+    let cols = records.iter().map(|record| record.len()).max().unwrap_or(0);
+
+    let mut rows: Vec<Vec<String>> = Vec::with_capacity(records.len() + 1);
+    for (idx, record) in records.into_iter().enumerate() {
+        let mut row: Vec<String> = record.iter().map(|field| field_to_cell(field)).collect();
+        row.resize(cols, String::new());
+        rows.push(row);
+
+        if idx == 0 {
+            rows.push(vec!["---".to_string(); cols]);
+        }
+    }
+
+    let col_widths = column_widths(&rows);
+    let mut output = String::new();
+    for row in &rows {
+        push_table_row(&mut output, row, &col_widths, &[]);
+        output.push('\n');
+    }
+    output
+}
+
+/// Splits CSV text into records of unescaped fields separated by `delimiter`
+///
+/// Blank lines between records are ignored.
+pub(super) fn parse_csv(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                if record.len() == 1 && record[0].is_empty() {
+                    record.clear();
+                } else {
+                    records.push(std::mem::take(&mut record));
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("unterminated quoted field in CSV input".to_string());
+    }
+
+    // Final record without a trailing newline
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Makes a CSV field safe to place in a single markdown table cell
+pub(super) fn field_to_cell(field: &str) -> String {
+    field
+        .split(['\r', '\n'])
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_csv_field() {
+        assert_eq!(escape_csv_field("plain"), "plain");
+        assert_eq!(escape_csv_field(""), "");
+        assert_eq!(escape_csv_field("a, b"), "\"a, b\"");
+        assert_eq!(escape_csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_export_csv_multiple_tables() {
+        let input = "| A | B |\n|---|---|\n| 1 | 2 |\n\ntext\n\n| C |\n|---|\n| 3 |\n";
+        let result = export_csv(input);
+        assert!(!result.has_errors());
+        assert_eq!(result.output, "A,B\n1,2\n\nC\n3\n");
+    }
+
+    #[test]
+    fn test_export_csv_unescapes_pipes() {
+        let result = export_csv("| Cmd | Note |\n|---|---|\n| a \\| b | \"x\", y |\n");
+        assert!(!result.has_errors());
+        assert_eq!(result.output, "Cmd,Note\na | b,\"\"\"x\"\", y\"\n");
+    }
+
+    #[test]
+    fn test_export_csv_selected() {
+        let input = "| A |\n|---|\n| 1 |\n<!-- md-table: A1 = nope(1) -->\n\n\
+                     | B | C |\n|---|---|\n| 2 | |\n<!-- md-table: id=\"second\"; B1 = A1 * 2 -->\n";
+
+        let result = export_csv_selected(input, &TableSelection::Nth(2));
+        assert!(!result.has_errors());
+        assert_eq!(result.output, "B,C\n2,4\n");
+
+        let selection = TableSelection::Id("second".to_string());
+        assert_eq!(export_csv_selected(input, &selection).output, "B,C\n2,4\n");
+
+        // Errors in the first table only count when it is exported
+        let result = export_csv_selected(input, &TableSelection::Nth(1));
+        assert_eq!(result.output, "A\n1\n");
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line, 1);
+        assert_eq!(export_csv(input).errors.len(), 1);
+
+        for selection in [
+            TableSelection::Nth(0),
+            TableSelection::Nth(3),
+            TableSelection::Id("missing".to_string()),
+        ] {
+            let result = export_csv_selected(input, &selection);
+            assert!(result.has_errors());
+            assert_eq!(result.output, "");
+        }
+    }
+
+    #[test]
+    fn test_parse_csv_quoted_fields() {
+        let records =
+            parse_csv("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\n1,,\"two\nlines\"", ',').unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["a", "b, c", "say \"hi\""], vec!["1", "", "two\nlines"],]
+        );
+
+        assert!(parse_csv("a,\"unterminated\n", ',').is_err());
+    }
+
+    #[test]
+    fn test_import_csv_aligns_table() {
+        let result = import_csv("Item,Price,Note\nApple,1.25,\"red, sweet\"\nKiwi,0.5\n");
+        assert!(!result.has_errors());
+        assert_eq!(
+            result.output,
+            "| Item  | Price | Note       |\n\
+             | ----- | ----- | ---------- |\n\
+             | Apple | 1.25  | red, sweet |\n\
+             | Kiwi  | 0.5   |            |\n"
+        );
+    }
+
+    #[test]
+    fn test_import_csv_escapes_cells() {
+        let result = import_csv("A\n\"x | y\"\n\"multi\nline\"\n");
+        assert_eq!(
+            result.output,
+            "| A          |\n| ---------- |\n| x \\| y     |\n| multi line |\n"
+        );
+    }
+
+    #[test]
+    fn test_import_csv_with_delimiter() {
+        let tsv = "Item\tNote\nApple\tred, sweet\nKiwi\t\"say \"\"hi\"\"\ntwice\"\n";
+        let result = import_csv_with_delimiter(tsv, '\t');
+        assert!(!result.has_errors());
+        assert_eq!(
+            result.output,
+            "| Item  | Note           |\n\
+             | ----- | -------------- |\n\
+             | Apple | red, sweet     |\n\
+             | Kiwi  | say \"hi\" twice |\n"
+        );
+
+        let result = import_csv_with_delimiter("a;b\n1;2,5\n", ';');
+        assert_eq!(
+            result.output,
+            "| a   | b   |\n| --- | --- |\n| 1   | 2,5 |\n"
+        );
+    }
+
+    #[test]
+    fn test_import_csv_empty_input() {
+        let result = import_csv("\n");
+        assert!(result.has_errors());
+        assert_eq!(result.output, "");
+    }
+
+    #[test]
+    fn test_export_csv_no_tables() {
+        let result = export_csv("# Just text\n");
+        assert!(result.has_errors());
+        assert_eq!(result.output, "");
+    }
+}
@@ -91,6 +91,13 @@ pub enum FormulaError {
     /// Runtime evaluation error (generic message without prefix)
     #[error("{0}")]
     RuntimeError(String),
+
+    /// Error annotated with the span of the expression node that produced it
+    #[error("{error}")]
+    Spanned {
+        error: Box<FormulaError>,
+        span: crate::table::formula::Span,
+    },
 }
 
 impl FormulaError {
@@ -118,6 +125,28 @@ impl FormulaError {
         }
     }
 
+    /// Attach the span of the expression node that produced this error
+    ///
+    /// The innermost span wins: errors that already carry a span are returned unchanged,
+    /// so the position always points at the node that actually failed.
+    pub fn at(self, span: crate::table::formula::Span) -> Self {
+        match self {
+            FormulaError::Spanned { .. } => self,
+            error => FormulaError::Spanned {
+                error: Box::new(error),
+                span,
+            },
+        }
+    }
+
+    /// Returns the span attached to this error, if any
+    pub fn span(&self) -> Option<crate::table::formula::Span> {
+        match self {
+            FormulaError::Spanned { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
     /// Format error with visual context showing position in expression
     pub fn with_context(&self, expression: &str, span: crate::table::formula::Span) -> String {
         let mut result = format!("{}\n", self);
@@ -163,6 +192,21 @@ mod tests {
         assert!(result.contains("     ^^^^")); // 5 spaces then 4 carets
     }
 
+    #[test]
+    fn test_error_at_keeps_innermost_span() {
+        use crate::table::formula::Span;
+
+        let error = FormulaError::DivisionByZero.at(Span::new(5, 7));
+        assert_eq!(error.span(), Some(Span::new(5, 7)));
+        assert_eq!(error.to_string(), "division by zero");
+
+        // Re-attaching from an outer node must not move the span
+        let error = error.at(Span::new(0, 12));
+        assert_eq!(error.span(), Some(Span::new(5, 7)));
+
+        assert_eq!(FormulaError::DivisionByZero.span(), None);
+    }
+
     #[test]
     fn test_error_with_context_at_start() {
         let error = FormulaError::RuntimeError("invalid token".to_string());
@@ -1,35 +1,243 @@
-use super::parser::is_separator_cell;
-
-/// Formats a table row with proper padding based on column widths
-pub fn format_table_row(cells: &[String], col_widths: &[usize]) -> String {
-    let formatted_cells: Vec<String> = cells
-        .iter()
-        .enumerate()
-        .map(|(idx, cell)| {
-            let width = col_widths.get(idx).copied().unwrap_or(0);
-
-            // Check if this is a separator row (contains only dashes, colons, and spaces)
-            if is_separator_cell(cell) {
-                format_separator_cell(cell, width)
-            } else {
-                // Regular cell - left-aligned with padding
-                format!("{:<width$}", cell, width = width)
-            }
-        })
-        .collect();
-
-    format!("| {} |", formatted_cells.join(" | "))
-}
-
-/// Formats a separator cell with the appropriate width
-fn format_separator_cell(cell: &str, width: usize) -> String {
+use super::parser::{is_separator_cell, Alignment};
+use unicode_width::UnicodeWidthStr;
+
+/// Number of columns a cell occupies in a monospaced editor
+///
+/// Wide characters (CJK, most emoji) count as two columns and combining marks
+/// as zero, so widths line up visually rather than by byte or char count.
+pub fn display_width(cell: &str) -> usize {
+    cell.width()
+}
+
+/// Appends a table row to `out`, padding each cell to its column's width
+///
+/// Cells are padded according to their column's alignment; columns without an
+/// entry in `alignments` are left-aligned. Cells are written straight into
+/// `out`, so rendering a whole table into one buffer allocates nothing per cell.
+pub fn push_table_row(
+    out: &mut String,
+    cells: &[String],
+    col_widths: &[usize],
+    alignments: &[Alignment],
+) {
+    out.push('|');
+    for (idx, cell) in cells.iter().enumerate() {
+        let width = col_widths.get(idx).copied().unwrap_or(0);
+        out.push(' ');
+
+        // Check if this is a separator row (contains only dashes, colons, and spaces)
+        if is_separator_cell(cell) {
+            push_separator_cell(out, cell, width);
+        } else {
+            let alignment = alignments.get(idx).copied().unwrap_or_default();
+            push_aligned_cell(out, cell, width, alignment);
+        }
+        out.push_str(" |");
+    }
+    if cells.is_empty() {
+        out.push_str("  |");
+    }
+}
+
+/// Display width of each column: the width of its widest cell
+pub fn column_widths(rows: &[Vec<String>]) -> Vec<usize> {
+    let mut col_widths = Vec::new();
+    for row in rows {
+        if col_widths.len() < row.len() {
+            col_widths.resize(row.len(), 0);
+        }
+        for (width, cell) in col_widths.iter_mut().zip(row) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    col_widths
+}
+
+/// Formats a separator row without padding or spaces (`|---|:---:|`)
+///
+/// Each cell keeps its alignment colons around three dashes.
+pub fn format_compact_separator_row(cells: &[String]) -> String {
+    let mut line = String::from("|");
+    for cell in cells {
+        let colons = cell.matches(':').count().min(2);
+        push_separator_cell(&mut line, cell, 3 + colons);
+        line.push('|');
+    }
+    line
+}
+
+/// Appends a regular cell padded to the column width according to its alignment
+///
+/// Centered cells put the odd space of padding, if any, on the right.
+fn push_aligned_cell(out: &mut String, cell: &str, width: usize, alignment: Alignment) {
+    let padding = width.saturating_sub(display_width(cell));
+    let (left, right) = match alignment {
+        Alignment::Left => (0, padding),
+        Alignment::Center => (padding / 2, padding - padding / 2),
+        Alignment::Right => (padding, 0),
+    };
+    push_repeated(out, ' ', left);
+    out.push_str(cell);
+    push_repeated(out, ' ', right);
+}
+
+/// Appends a separator cell of the given width, keeping its alignment colons
+fn push_separator_cell(out: &mut String, cell: &str, width: usize) {
     let has_left_colon = cell.starts_with(':');
     let has_right_colon = cell.ends_with(':');
+    let colons = usize::from(has_left_colon) + usize::from(has_right_colon);
+
+    if has_left_colon {
+        out.push(':');
+    }
+    push_repeated(out, '-', width.saturating_sub(colons));
+    if has_right_colon {
+        out.push(':');
+    }
+}
+
+/// Appends `count` copies of `ch`
+fn push_repeated(out: &mut String, ch: char, count: usize) {
+    out.extend(std::iter::repeat_n(ch, count));
+}
+
+/// Truncates a cell to at most `max_width` display columns, marking the cut with an ellipsis
+pub fn truncate_cell(cell: &str, max_width: usize) -> String {
+    use unicode_width::UnicodeWidthChar;
+
+    if display_width(cell) <= max_width {
+        return cell.to_string();
+    }
+
+    // Leave one column for the ellipsis; a wide character that would straddle
+    // the limit is dropped rather than split
+    let budget = max_width.saturating_sub(1);
+    let mut used = 0;
+    let mut truncated = String::new();
+    for ch in cell.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        if used + ch_width > budget {
+            break;
+        }
+        used += ch_width;
+        truncated.push(ch);
+    }
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn format_table_row(
+        cells: &[String],
+        col_widths: &[usize],
+        alignments: &[Alignment],
+    ) -> String {
+        let mut line = String::new();
+        push_table_row(&mut line, cells, col_widths, alignments);
+        line
+    }
+
+    #[test]
+    fn test_push_rows_into_one_buffer() {
+        let rows = [
+            cells(&["Name", "Qty"]),
+            cells(&["---", "---:"]),
+            cells(&["日本", "12"]),
+        ];
+        let widths = column_widths(&rows);
+        assert_eq!(widths, [4, 4]);
+
+        let mut out = String::new();
+        for row in &rows {
+            push_table_row(&mut out, row, &widths, &[Alignment::Left, Alignment::Right]);
+            out.push('\n');
+        }
+        assert_eq!(out, "| Name |  Qty |\n| ---- | ---: |\n| 日本 |   12 |\n");
+    }
+
+    #[test]
+    fn test_format_left_aligned() {
+        let row = format_table_row(&cells(&["ab"]), &[5], &[Alignment::Left]);
+        assert_eq!(row, "| ab    |");
+    }
+
+    #[test]
+    fn test_format_center_aligned() {
+        // Odd padding puts the extra space on the right
+        let row = format_table_row(&cells(&["ab", "abc"]), &[5, 6], &[Alignment::Center; 2]);
+        assert_eq!(row, "|  ab   |  abc   |");
+    }
+
+    #[test]
+    fn test_format_right_aligned() {
+        let row = format_table_row(&cells(&["ab"]), &[5], &[Alignment::Right]);
+        assert_eq!(row, "|    ab |");
+    }
+
+    #[test]
+    fn test_format_separator_keeps_colons() {
+        let row = format_table_row(
+            &cells(&[":-", ":-:", "-:", "-"]),
+            &[5, 5, 5, 5],
+            &[Alignment::Left, Alignment::Center, Alignment::Right],
+        );
+        assert_eq!(row, "| :---- | :---: | ----: | ----- |");
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("🎉"), 2);
+        // "e" followed by a combining acute accent is one column
+        assert_eq!(display_width("e\u{301}"), 1);
+    }
+
+    #[test]
+    fn test_format_wide_and_combining_characters() {
+        let widths = [6];
+        let left = [Alignment::Left];
+        assert_eq!(
+            format_table_row(&cells(&["日本語"]), &widths, &left),
+            "| 日本語 |"
+        );
+        assert_eq!(
+            format_table_row(&cells(&["abc"]), &widths, &left),
+            "| abc    |"
+        );
+        assert_eq!(
+            format_table_row(&cells(&["🎉 ok"]), &widths, &left),
+            "| 🎉 ok  |"
+        );
+        assert_eq!(
+            format_table_row(&cells(&["cafe\u{301}"]), &widths, &left),
+            "| cafe\u{301}   |"
+        );
+        assert_eq!(
+            format_table_row(&cells(&["日本"]), &widths, &[Alignment::Right]),
+            "|   日本 |"
+        );
+    }
+
+    #[test]
+    fn test_truncate_wide_characters() {
+        assert_eq!(truncate_cell("日本語テキスト", 6), "日本…");
+        // A wide character never straddles the limit
+        assert_eq!(truncate_cell("日本語テキスト", 5), "日本…");
+        assert_eq!(truncate_cell("abcdefgh", 5), "abcd…");
+        assert_eq!(truncate_cell("short", 5), "short");
+    }
 
-    match (has_left_colon, has_right_colon) {
-        (true, true) => format!(":{:-<width$}:", "", width = width.saturating_sub(2)),
-        (true, false) => format!(":{:-<width$}", "", width = width.saturating_sub(1)),
-        (false, true) => format!("{:-<width$}:", "", width = width.saturating_sub(1)),
-        (false, false) => format!("{:-<width$}", "", width = width),
+    #[test]
+    fn test_format_missing_alignment_defaults_to_left() {
+        let row = format_table_row(&cells(&["a", "b"]), &[3, 3], &[]);
+        assert_eq!(row, "| a   | b   |");
     }
 }
@@ -56,12 +56,13 @@ impl Expr {
 /// Binary operators supported in expressions
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum BinaryOperator {
-    Add,    // +
-    Sub,    // -
-    Mul,    // *
-    Div,    // /
-    Pow,    // ^
-    MatMul, // @
+    Add,      // +
+    Sub,      // -
+    Mul,      // *
+    Div,      // /
+    FloorDiv, // //
+    Pow,      // ^
+    MatMul,   // @
 }
 
 impl BinaryOperator {
@@ -72,6 +73,7 @@ impl BinaryOperator {
             "-" => Some(BinaryOperator::Sub),
             "*" => Some(BinaryOperator::Mul),
             "/" => Some(BinaryOperator::Div),
+            "//" => Some(BinaryOperator::FloorDiv),
             "^" => Some(BinaryOperator::Pow),
             "@" => Some(BinaryOperator::MatMul),
             _ => None,
@@ -80,13 +82,13 @@ impl BinaryOperator {
 }
 
 /// Recursive descent parser for converting tokens to AST
-pub(crate) struct Parser {
-    tokens: Vec<crate::table::formula::tokenizer::Token>,
+pub(crate) struct Parser<'a> {
+    tokens: Vec<crate::table::formula::tokenizer::Token<'a>>,
     pos: usize,
 }
 
-impl Parser {
-    pub(crate) fn new(tokens: Vec<crate::table::formula::tokenizer::Token>) -> Self {
+impl<'a> Parser<'a> {
+    pub(crate) fn new(tokens: Vec<crate::table::formula::tokenizer::Token<'a>>) -> Self {
         Parser { tokens, pos: 0 }
     }
 
@@ -94,10 +96,12 @@ impl Parser {
     pub(crate) fn parse(&mut self) -> Result<Expr, FormulaError> {
         let expr = self.parse_expression()?;
         if self.pos < self.tokens.len() {
+            let token = &self.tokens[self.pos];
             return Err(FormulaError::UnexpectedToken {
-                token: self.tokens[self.pos].value.clone(),
-                position: self.pos,
-            });
+                token: token.value.to_string(),
+                position: token.span.start,
+            }
+            .at(token.span));
         }
         Ok(expr)
     }
@@ -127,16 +131,16 @@ impl Parser {
         Ok(left)
     }
 
-    /// Parse term: factor (('*' | '/' | '@') factor)*
+    /// Parse term: signed (('*' | '/' | '//' | '@') signed)*
     fn parse_term(&mut self) -> Result<Expr, FormulaError> {
-        let mut left = self.parse_factor()?;
+        let mut left = self.parse_signed()?;
 
         while self.pos < self.tokens.len() {
             let token = &self.tokens[self.pos];
-            if token.value == "*" || token.value == "/" || token.value == "@" {
+            if matches!(&*token.value, "*" | "/" | "//" | "@") {
                 let op = BinaryOperator::from_token(&token.value).unwrap();
                 self.pos += 1;
-                let right = self.parse_factor()?;
+                let right = self.parse_signed()?;
                 let span = left.span().merge(&right.span());
                 left = Expr::BinaryOp {
                     left: Box::new(left),
@@ -152,14 +156,36 @@ impl Parser {
         Ok(left)
     }
 
-    /// Parse factor: unary ('^' unary)*
-    /// Right-associative for exponentiation
+    /// Parse signed: '-' signed | factor
+    ///
+    /// A `-` with no left operand negates what follows. Negation binds more
+    /// loosely than `^`, so `-2 ^ 2` is `-(2 ^ 2)`, and is desugared to
+    /// `0 - operand`, which broadcasts over vectors and matrices.
+    fn parse_signed(&mut self) -> Result<Expr, FormulaError> {
+        if self.pos < self.tokens.len() && self.tokens[self.pos].value == "-" {
+            let minus_span = self.tokens[self.pos].span;
+            self.pos += 1;
+            let operand = self.parse_signed()?;
+            let span = minus_span.merge(&operand.span());
+            return Ok(Expr::BinaryOp {
+                left: Box::new(Expr::Literal(Decimal::ZERO, minus_span)),
+                op: BinaryOperator::Sub,
+                right: Box::new(operand),
+                span,
+            });
+        }
+
+        self.parse_factor()
+    }
+
+    /// Parse factor: unary ('^' signed)?
+    /// Right-associative for exponentiation; the exponent may be negated (`2 ^ -1`)
     fn parse_factor(&mut self) -> Result<Expr, FormulaError> {
         let mut left = self.parse_unary()?;
 
         if self.pos < self.tokens.len() && self.tokens[self.pos].value == "^" {
             self.pos += 1;
-            let right = self.parse_factor()?; // Right-associative recursion
+            let right = self.parse_signed()?; // Right-associative recursion
             let span = left.span().merge(&right.span());
             left = Expr::BinaryOp {
                 left: Box::new(left),
@@ -205,7 +231,8 @@ impl Parser {
             if self.pos >= self.tokens.len() || self.tokens[self.pos].value != ")" {
                 return Err(FormulaError::RuntimeError(
                     "unmatched opening parenthesis '(' - missing closing ')'".to_string(),
-                ));
+                )
+                .at(token.span));
             }
             self.pos += 1;
             return Ok(expr);
@@ -213,7 +240,7 @@ impl Parser {
 
         // Check for function call
         if self.pos + 1 < self.tokens.len() && self.tokens[self.pos + 1].value == "(" {
-            let func_name = token.value.clone();
+            let func_name = token.value.to_string();
             let func_span = token.span;
             self.pos += 2; // Skip function name and '('
 
@@ -371,7 +398,7 @@ impl Parser {
         // Otherwise, treat as a variable identifier
         // Variables are identifiers that aren't cell references, strings, or numbers
         let span = token.span;
-        let var_name = token.value.clone();
+        let var_name = token.value.to_string();
         self.pos += 1;
         Ok(Expr::Variable(var_name, span))
     }
@@ -0,0 +1,489 @@
+//! Dependency ordering of the formulas of one table
+//!
+//! Formulas are applied in dependency order rather than written order, so
+//! `D1 = E1 * 2` may come before `E1 = A1 + B1`. A formula that reads the very
+//! cell it assigns (such as `C1 = C1 + 1`) or formulas whose results feed each
+//! other would give results that depend on how often the document has been
+//! formatted. Such formulas are reported as errors instead of being applied.
+//! A single formula whose vectors or ranges merely overlap its own target
+//! (`C1 = _1 @ A_`) reads the cells as they were before it runs, as it always
+//! has, and is not an error.
+
+use super::ast::Expr;
+use super::types::{formula_row_to_table_index, Assignment, CellReference, Statement};
+use super::ParsedFormula;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+/// A rectangle of cells as inclusive table-row and column bounds
+///
+/// `None` means the region is unbounded in that direction, e.g. `A_` covers
+/// every row of column A.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Region {
+    rows: Option<(usize, usize)>,
+    cols: Option<(usize, usize)>,
+}
+
+impl Region {
+    /// Whether the cell at table row `row` and column `col` lies in the region
+    pub(crate) fn contains(&self, row: usize, col: usize) -> bool {
+        fn span_contains(span: Option<(usize, usize)>, idx: usize) -> bool {
+            span.is_none_or(|(start, end)| start <= idx && idx <= end)
+        }
+        span_contains(self.rows, row) && span_contains(self.cols, col)
+    }
+
+    fn overlaps(&self, other: &Region) -> bool {
+        fn spans_overlap(a: Option<(usize, usize)>, b: Option<(usize, usize)>) -> bool {
+            match (a, b) {
+                (Some((a_start, a_end)), Some((b_start, b_end))) => {
+                    a_start <= b_end && b_start <= a_end
+                }
+                _ => true,
+            }
+        }
+        spans_overlap(self.rows, other.rows) && spans_overlap(self.cols, other.cols)
+    }
+
+    fn from_reference(cell_ref: &CellReference) -> Self {
+        match *cell_ref {
+            CellReference::Scalar { row, col } => Region {
+                rows: Some((row, row)),
+                cols: Some((col, col)),
+            },
+            CellReference::ColumnVector { col } | CellReference::PreviousRow { col } => Region {
+                rows: None,
+                cols: Some((col, col)),
+            },
+            CellReference::RowVector { row } => {
+                let row = formula_row_to_table_index(row);
+                Region {
+                    rows: Some((row, row)),
+                    cols: None,
+                }
+            }
+            CellReference::Range {
+                start_row,
+                start_col,
+                end_row,
+                end_col,
+            } => Region {
+                rows: Some((start_row, end_row)),
+                cols: Some((start_col, end_col)),
+            },
+            CellReference::ColumnRange { start_col, end_col } => Region {
+                rows: None,
+                cols: Some((start_col, end_col)),
+            },
+            CellReference::RowRange { start_row, end_row } => Region {
+                rows: Some((
+                    formula_row_to_table_index(start_row),
+                    formula_row_to_table_index(end_row),
+                )),
+                cols: None,
+            },
+        }
+    }
+
+    pub(crate) fn from_assignment(assignment: &Assignment) -> Self {
+        // Assignment targets use the same coordinates as cell references
+        Region::from_reference(&match *assignment {
+            Assignment::Scalar { row, col } => CellReference::Scalar { row, col },
+            Assignment::ColumnVector { col } => CellReference::ColumnVector { col },
+            Assignment::RowVector { row } => CellReference::RowVector { row },
+            Assignment::Range {
+                start_row,
+                start_col,
+                end_row,
+                end_col,
+            } => CellReference::Range {
+                start_row,
+                start_col,
+                end_row,
+                end_col,
+            },
+            Assignment::ColumnRange { start_col, end_col } => {
+                CellReference::ColumnRange { start_col, end_col }
+            }
+            Assignment::RowRange { start_row, end_row } => {
+                CellReference::RowRange { start_row, end_row }
+            }
+        })
+    }
+}
+
+/// Collects the regions of the current table and the variables that `expr` reads
+///
+/// Ranges given to `from()` address another table or a variable's matrix, so
+/// they are skipped. Previous-row references (`C^`) go to `node.previous_rows`
+/// because a column may read the rows above itself.
+fn collect_reads(expr: &Expr, node: &mut FormulaNode) {
+    match expr {
+        Expr::Literal(..) | Expr::String(..) => {}
+        Expr::CellRef(cell_ref @ CellReference::PreviousRow { .. }, _) => {
+            node.previous_rows.push(Region::from_reference(cell_ref))
+        }
+        Expr::CellRef(cell_ref, _) => {
+            if let CellReference::Scalar { .. } = cell_ref {
+                node.cells.push(Region::from_reference(cell_ref));
+            }
+            node.reads.push(Region::from_reference(cell_ref))
+        }
+        Expr::Variable(name, _) => node.variables.push(name.clone()),
+        Expr::BinaryOp { left, right, .. } => {
+            collect_reads(left, node);
+            collect_reads(right, node);
+        }
+        Expr::Transpose(inner, _) => collect_reads(inner, node),
+        Expr::FunctionCall { name, args, .. } if name.eq_ignore_ascii_case("from") => {
+            if let Some(Expr::Variable(name, _)) = args.first() {
+                node.variables.push(name.clone());
+            }
+        }
+        Expr::FunctionCall { args, .. } => {
+            for arg in args {
+                collect_reads(arg, node);
+            }
+        }
+    }
+}
+
+/// What one formula reads and writes
+#[derive(Debug, Default)]
+struct FormulaNode {
+    /// Cells of this table assigned by the formula (none for `let` and `to()`)
+    targets: Vec<Region>,
+    /// Variable defined by a `let` statement
+    defines: Option<String>,
+    reads: Vec<Region>,
+    /// Single cells named in the expression, such as `D3`
+    cells: Vec<Region>,
+    /// Columns read through previous-row references
+    previous_rows: Vec<Region>,
+    variables: Vec<String>,
+    /// Whether the formula names the cell it assigns (`D3 = D3 + 1`), or a
+    /// `fill` reads its own column's cell in each row (`fill D = D + 1`)
+    reads_own_cell: bool,
+}
+
+impl FormulaNode {
+    /// Formulas that fail to parse become nodes without dependencies, so they
+    /// keep their place and report their error when applied
+    fn from_parsed(parsed: Option<&ParsedFormula>) -> Self {
+        let Some(ParsedFormula {
+            statements,
+            ast: Ok(ast),
+            ..
+        }) = parsed
+        else {
+            return FormulaNode::default();
+        };
+
+        let mut node = FormulaNode::default();
+        collect_reads(ast, &mut node);
+        for statement in statements {
+            match statement {
+                Statement::Let { name, span: _ } => node.defines = Some(name.clone()),
+                Statement::Assignment(assignment) => {
+                    let target = Region::from_assignment(assignment);
+                    if let Assignment::Scalar { .. } = assignment {
+                        node.reads_own_cell |= node.cells.contains(&target);
+                    }
+                    node.targets.push(target)
+                }
+                Statement::TableAssignment { .. } => {}
+                Statement::Fill { col } => {
+                    node.targets
+                        .push(Region::from_assignment(&Assignment::ColumnVector {
+                            col: *col,
+                        }));
+                    // Bare column letters read the column, one cell per row
+                    let columns: Vec<Region> = node
+                        .variables
+                        .iter()
+                        .filter_map(|name| match name.as_bytes() {
+                            [letter @ b'A'..=b'Z'] => {
+                                Some(Region::from_reference(&CellReference::ColumnVector {
+                                    col: (letter - b'A') as usize,
+                                }))
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    let own_column =
+                        Region::from_reference(&CellReference::ColumnVector { col: *col });
+                    node.reads_own_cell |= columns.contains(&own_column);
+                    node.reads.extend(columns);
+                }
+            }
+        }
+        node
+    }
+}
+
+/// The order to apply a table's formulas in
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FormulaOrder {
+    /// Indices of the formulas to apply, in order; circular formulas are left out
+    pub(crate) order: Vec<usize>,
+    /// `circular[i]` is Some(message) if formula `i` is part of a circular reference
+    pub(crate) circular: Vec<Option<String>>,
+}
+
+/// Orders formulas so each one runs after the formulas that produce its inputs
+///
+/// A formula depends on every formula that assigns a cell it reads, and on the
+/// `let` that defines each variable it uses (the closest one before it, or the
+/// first one after it if none comes before). Independent formulas keep their
+/// written order. Formulas that name the cell they assign, and formulas whose
+/// results feed each other's inputs, cannot be ordered and are reported as
+/// circular instead. A formula whose ranges overlap its own target, directly
+/// or through variables (`let x = A_` and `A1 = sum(x)`), reads that target
+/// as it was before it runs.
+///
+/// `parsed[i]` is formula `i` as parsed by [`ParsedFormula::parse`].
+pub(crate) fn order_formulas(
+    formulas: &[String],
+    parsed: &[Option<Rc<ParsedFormula>>],
+) -> FormulaOrder {
+    let nodes: Vec<FormulaNode> = parsed
+        .iter()
+        .map(|parsed| FormulaNode::from_parsed(parsed.as_deref()))
+        .collect();
+    let count = nodes.len();
+
+    // feeds[j] lists the formulas that read something formula j produces
+    let mut feeds: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for (k, reader) in nodes.iter().enumerate() {
+        for (j, writer) in nodes.iter().enumerate() {
+            // A formula may read the rows above its own target (`C_ = C^ + B_`)
+            let reads_target = writer.targets.iter().any(|target| {
+                let reads_previous_rows = j != k
+                    && reader
+                        .previous_rows
+                        .iter()
+                        .any(|read| read.overlaps(target));
+                reads_previous_rows || reader.reads.iter().any(|read| read.overlaps(target))
+            });
+            if reads_target {
+                feeds[j].push(k);
+            }
+        }
+        for name in &reader.variables {
+            let defined_by = |j: &usize| nodes[*j].defines.as_ref() == Some(name);
+            let definition = (0..k)
+                .rev()
+                .find(defined_by)
+                .or_else(|| (k + 1..count).find(defined_by));
+            if let Some(j) = definition {
+                feeds[j].push(k);
+            }
+        }
+    }
+
+    // A formula reads the cells it assigns as they were before it runs, and so
+    // does a variable feeding only that formula (through other variables at
+    // most). Neither is a cycle; naming the assigned cell itself is caught by
+    // `reads_own_cell` instead.
+    let is_let = |j: usize| nodes[j].defines.is_some();
+    let through_lets: Vec<Vec<bool>> = (0..count)
+        .map(|k| reachable_from(&feeds, k, is_let))
+        .collect();
+    for (j, readers) in feeds.iter_mut().enumerate() {
+        readers.retain(|&k| k != j && (is_let(j) || !is_let(k) || !through_lets[k][j]));
+    }
+
+    let reachable: Vec<Vec<bool>> = (0..count)
+        .map(|start| reachable_from(&feeds, start, |_| true))
+        .collect();
+
+    let circular: Vec<Option<String>> = (0..count)
+        .map(|i| {
+            if !nodes[i].reads_own_cell && !reachable[i][i] {
+                return None;
+            }
+            let others: Vec<String> = (0..count)
+                .filter(|&j| j != i && reachable[i][j] && reachable[j][i])
+                .map(|j| (j + 1).to_string())
+                .collect();
+            let formula = formulas[i].trim();
+            Some(if others.is_empty() {
+                format!(
+                    "Circular reference in '{}': the formula reads the cells it assigns",
+                    formula
+                )
+            } else {
+                format!(
+                    "Circular reference in '{}': the formula depends on its own result through formula{} {}",
+                    formula,
+                    if others.len() > 1 { "s" } else { "" },
+                    others.join(", ")
+                )
+            })
+        })
+        .collect();
+
+    // Kahn's algorithm over the remaining (acyclic) formulas, always taking the
+    // earliest ready formula so independent formulas keep their written order
+    let mut pending = vec![0; count];
+    for (j, readers) in feeds.iter().enumerate() {
+        if circular[j].is_none() {
+            for &k in readers {
+                pending[k] += 1;
+            }
+        }
+    }
+    let mut ready: BTreeSet<usize> = (0..count)
+        .filter(|&i| circular[i].is_none() && pending[i] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(count);
+    while let Some(next) = ready.pop_first() {
+        order.push(next);
+        for &k in &feeds[next] {
+            pending[k] -= 1;
+            if pending[k] == 0 && circular[k].is_none() {
+                ready.insert(k);
+            }
+        }
+    }
+
+    FormulaOrder { order, circular }
+}
+
+/// Marks the formulas reachable from `start` along `feeds`, going on past
+/// only the formulas for which `through` holds
+fn reachable_from(
+    feeds: &[Vec<usize>],
+    start: usize,
+    through: impl Fn(usize) -> bool,
+) -> Vec<bool> {
+    let mut seen = vec![false; feeds.len()];
+    let mut stack = feeds[start].clone();
+    while let Some(next) = stack.pop() {
+        if !seen[next] {
+            seen[next] = true;
+            if through(next) {
+                stack.extend(&feeds[next]);
+            }
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_of(formulas: &[&str]) -> FormulaOrder {
+        let formulas: Vec<String> = formulas.iter().map(|f| f.to_string()).collect();
+        let parsed: Vec<_> = formulas
+            .iter()
+            .map(|f| ParsedFormula::parse(f).map(Rc::new))
+            .collect();
+        order_formulas(&formulas, &parsed)
+    }
+
+    fn circular(formulas: &[&str]) -> Vec<bool> {
+        order_of(formulas)
+            .circular
+            .iter()
+            .map(Option::is_some)
+            .collect()
+    }
+
+    fn order(formulas: &[&str]) -> Vec<usize> {
+        order_of(formulas).order
+    }
+
+    #[test]
+    fn test_direct_self_reference() {
+        let result = order_of(&["C1 = C1 + 1"]);
+        assert_eq!(
+            result.circular,
+            vec![Some(
+                "Circular reference in 'C1 = C1 + 1': the formula reads the cells it assigns"
+                    .to_string()
+            )]
+        );
+        assert!(result.order.is_empty());
+
+        assert_eq!(circular(&["A1 = 1", "D3 = D3 + A1"]), vec![false, true]);
+        assert_eq!(circular(&["fill D = D + 1"]), vec![true]);
+        assert_eq!(circular(&["fill D = C + 1"]), vec![false]);
+
+        // Vectors and ranges that merely contain the target read it as it was
+        assert_eq!(circular(&["A4 = sum(A_)"]), vec![false]);
+        assert_eq!(circular(&["B2 = sum(A1:C3)"]), vec![false]);
+        assert_eq!(circular(&["C1 = _1 @ A_"]), vec![false]);
+        assert_eq!(order(&["C1 = _1 @ A_"]), vec![0]);
+    }
+
+    #[test]
+    fn test_indirect_cycles() {
+        assert_eq!(
+            circular(&["A1 = B1 + 1", "B1 = A1 * 2", "C1 = A1 + B1"]),
+            vec![true, true, false]
+        );
+        // Reads through variables are followed
+        assert_eq!(
+            circular(&["let x = B_", "A1 = sum(x)", "B1 = A1"]),
+            vec![true, true, true]
+        );
+        // A variable that reads the target of the only assignment it feeds is
+        // taken before that assignment runs
+        assert_eq!(
+            circular(&["let x = A_", "A3 = x.T @ x"]),
+            vec![false, false]
+        );
+        let transpose = ["let col = A_", "let row = col.T", "_1 = row"];
+        assert_eq!(circular(&transpose), vec![false, false, false]);
+        assert_eq!(order(&transpose), vec![0, 1, 2]);
+        // Reading the previous row of a column only cycles through other formulas
+        assert_eq!(circular(&["C_ = C^ + B_"]), vec![false]);
+        assert_eq!(circular(&["B_ = C^", "C_ = B_"]), vec![true, true]);
+    }
+
+    #[test]
+    fn test_sequential_formulas_are_not_circular() {
+        assert_eq!(
+            circular(&["C_ = A_ * B_", "D1 = sum(C_)", "C1 = 0"]),
+            vec![false, false, false]
+        );
+        // from() ranges and to() targets address other tables
+        assert_eq!(
+            circular(&[
+                "A1 = sum(from(\"other\", A_))",
+                "to(\"other\", A1) = A1",
+                "C3 = A1 + B2"
+            ]),
+            vec![false, false, false]
+        );
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        // Already in dependency order, or independent: written order is kept
+        assert_eq!(order(&["E1 = A1 + B1", "D1 = E1 * 2"]), vec![0, 1]);
+        assert_eq!(order(&["A1 = 1", "B1 = 2", "C1 = 3"]), vec![0, 1, 2]);
+
+        // Written in reverse dependency order
+        assert_eq!(order(&["D1 = E1 * 2", "E1 = A1 + B1"]), vec![1, 0]);
+        assert_eq!(
+            order(&["C1 = B1 + 1", "B1 = A1 + 1", "A1 = 1", "D1 = 0"]),
+            vec![2, 1, 0, 3]
+        );
+
+        // Variables are defined before they are used
+        assert_eq!(order(&["B1 = x * 2", "let x = A1"]), vec![1, 0]);
+
+        // Circular formulas are left out; the rest still run
+        assert_eq!(order(&["A1 = B1", "B1 = A1", "C1 = 1"]), vec![2]);
+
+        // Unparseable formulas keep their place
+        assert_eq!(
+            order(&["not a formula", "B1 = C1", "C1 = 1"]),
+            vec![0, 2, 1]
+        );
+    }
+}
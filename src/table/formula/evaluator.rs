@@ -1,10 +1,30 @@
 use crate::table::error::FormulaError;
 use crate::table::formula::ast::{BinaryOperator, Expr};
 use crate::table::formula::reference::{self, resolve_reference};
-use crate::table::formula::types::Value;
+use crate::table::formula::types::{Span, Value};
+use crate::table::parser::Comparison;
 use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
 use rust_decimal::Decimal;
 
+/// Looks up a built-in mathematical constant by name, ignoring case (`pi`, `e`)
+///
+/// Values are rounded to the 28 decimal places a `Decimal` can hold.
+fn builtin_constant(name: &str) -> Option<Decimal> {
+    if name.eq_ignore_ascii_case("pi") {
+        Some(Decimal::from_i128_with_scale(
+            31_415_926_535_897_932_384_626_433_833,
+            28,
+        ))
+    } else if name.eq_ignore_ascii_case("e") {
+        Some(Decimal::from_i128_with_scale(
+            27_182_818_284_590_452_353_602_874_714,
+            28,
+        ))
+    } else {
+        None
+    }
+}
+
 /// Evaluates an AST expression node to a Value with access to other tables and variables
 pub(crate) fn eval_ast_with_tables(
     expr: &Expr,
@@ -15,45 +35,52 @@ pub(crate) fn eval_ast_with_tables(
     match expr {
         Expr::Literal(d, _span) => Ok(Value::Scalar(*d)),
 
-        Expr::String(_s, _span) => Err(FormulaError::RuntimeError(
+        Expr::String(_s, span) => Err(FormulaError::RuntimeError(
             "string literals can only be used as arguments to functions like from()".to_string(),
-        )),
+        )
+        .at(*span)),
 
-        Expr::Variable(name, _span) => variable_map
+        // Variables and document constants shadow the built-in `pi` and `e`
+        Expr::Variable(name, span) => variable_map
             .get(name)
             .cloned()
-            .ok_or_else(|| FormulaError::RuntimeError(format!("undefined variable: '{}'", name))),
+            .or_else(|| builtin_constant(name).map(Value::Scalar))
+            .ok_or_else(|| {
+                FormulaError::RuntimeError(format!("undefined variable: '{}'", name)).at(*span)
+            }),
 
-        Expr::CellRef(cell_ref, _span) => resolve_reference(cell_ref, rows),
+        Expr::CellRef(cell_ref, span) => {
+            resolve_reference(cell_ref, rows).map_err(|error| error.at(*span))
+        }
 
         Expr::BinaryOp {
             left,
             op,
             right,
-            span: _span,
+            span,
         } => {
             let left_val = eval_ast_with_tables(left, rows, table_map, variable_map)?;
             let right_val = eval_ast_with_tables(right, rows, table_map, variable_map)?;
-            eval_binary_op(*op, left_val, right_val)
+            eval_binary_op(*op, left_val, right_val).map_err(|error| error.at(*span))
         }
 
-        Expr::Transpose(inner, _span) => {
+        Expr::Transpose(inner, span) => {
             let val = eval_ast_with_tables(inner, rows, table_map, variable_map)?;
             match val {
                 Value::Scalar(_) => Err(FormulaError::RuntimeError(
                     "cannot transpose a scalar value - only matrices can be transposed".to_string(),
-                )),
+                )
+                .at(*span)),
                 Value::Matrix { .. } => val.transpose().ok_or_else(|| {
-                    FormulaError::RuntimeError("transpose operation failed".to_string())
+                    FormulaError::RuntimeError("transpose operation failed".to_string()).at(*span)
                 }),
             }
         }
 
-        Expr::FunctionCall {
-            name,
-            args,
-            span: _span,
-        } => eval_function_call_with_tables(name, args, rows, table_map, variable_map),
+        Expr::FunctionCall { name, args, span } => {
+            eval_function_call_with_tables(name, args, *span, rows, table_map, variable_map)
+                .map_err(|error| error.at(*span))
+        }
     }
 }
 
@@ -75,6 +102,7 @@ pub(crate) fn eval_binary_op(
         BinaryOperator::Sub => evaluate_operation('-', left, right),
         BinaryOperator::Mul => evaluate_operation('*', left, right),
         BinaryOperator::Div => evaluate_operation('/', left, right),
+        BinaryOperator::FloorDiv => evaluate_operation(FLOOR_DIV, left, right),
         BinaryOperator::Pow => evaluate_operation('^', left, right),
         BinaryOperator::MatMul => evaluate_operation('@', left, right),
     }
@@ -84,6 +112,7 @@ pub(crate) fn eval_binary_op(
 fn eval_function_call_with_tables(
     name: &str,
     args: &[Expr],
+    span: Span,
     rows: &Vec<Vec<String>>,
     table_map: &std::collections::HashMap<String, Vec<Vec<String>>>,
     variable_map: &std::collections::HashMap<String, Value>,
@@ -120,9 +149,10 @@ fn eval_function_call_with_tables(
 
                     // If two arguments, second must be a cell reference or range
                     let range_value = match &args[1] {
-                        Expr::CellRef(cell_ref, _) => {
+                        Expr::CellRef(cell_ref, span) => {
                             // Resolve the reference from the target table
-                            resolve_reference(cell_ref, target_rows)?
+                            resolve_reference(cell_ref, target_rows)
+                                .map_err(|error| error.at(*span))?
                         }
                         _ => {
                             return Err(FormulaError::RuntimeError(
@@ -153,11 +183,25 @@ fn eval_function_call_with_tables(
                         return Ok(var_value.clone());
                     }
 
-                    // If two arguments with a variable, we need to handle range selection from the matrix
-                    // For now, return an error as this is complex to implement
-                    Err(FormulaError::RuntimeError(
-                        "from(variable, range) is not yet supported - use from(variable) to get the entire matrix".to_string()
-                    ))
+                    // If two arguments, select the range using the matrix's own coordinates
+                    match &args[1] {
+                        Expr::CellRef(cell_ref, span) => {
+                            reference::slice_matrix(var_value, cell_ref).ok_or_else(|| {
+                                let (rows, cols) = match var_value {
+                                    Value::Matrix { rows, cols, .. } => (*rows, *cols),
+                                    Value::Scalar(_) => (1, 1),
+                                };
+                                FormulaError::RuntimeError(format!(
+                                    "range is out of bounds for variable '{}' with shape ({}×{})",
+                                    var_name, rows, cols
+                                ))
+                                .at(*span)
+                            })
+                        }
+                        _ => Err(FormulaError::RuntimeError(
+                            "from() second argument must be a cell reference or range".to_string(),
+                        )),
+                    }
                 }
                 _ => {
                     Err(FormulaError::RuntimeError(
@@ -166,8 +210,239 @@ fn eval_function_call_with_tables(
                 }
             }
         }
+        "sort" => {
+            // sort(expr) sorts ascending; sort(expr, "desc") sorts descending
+            if args.is_empty() || args.len() > 2 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function 'sort' expects 1 or 2 arguments, got {}",
+                    args.len()
+                )));
+            }
+
+            let descending = match args.get(1) {
+                None => false,
+                Some(Expr::String(order, order_span)) => match order.as_str() {
+                    "asc" => false,
+                    "desc" => true,
+                    _ => {
+                        return Err(FormulaError::RuntimeError(format!(
+                            "sort() order must be \"asc\" or \"desc\", got \"{}\"",
+                            order
+                        ))
+                        .at(*order_span))
+                    }
+                },
+                Some(_) => {
+                    return Err(FormulaError::RuntimeError(
+                        "sort() second argument must be \"asc\" or \"desc\"".to_string(),
+                    ))
+                }
+            };
+
+            let arg = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?;
+            let sorted = eval_function(name, arg)?;
+            if descending {
+                eval_function("reverse", sorted)
+            } else {
+                Ok(sorted)
+            }
+        }
+        "rank" => {
+            // rank(expr) ranks the largest value 1; rank(expr, "asc") ranks the smallest 1
+            if args.is_empty() || args.len() > 2 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function 'rank' expects 1 or 2 arguments, got {}",
+                    args.len()
+                )));
+            }
+
+            let ascending = match args.get(1) {
+                None => false,
+                Some(Expr::String(order, order_span)) => match order.as_str() {
+                    "asc" => true,
+                    "desc" => false,
+                    _ => {
+                        return Err(FormulaError::RuntimeError(format!(
+                            "rank() order must be \"asc\" or \"desc\", got \"{}\"",
+                            order
+                        ))
+                        .at(*order_span))
+                    }
+                },
+                Some(_) => {
+                    return Err(FormulaError::RuntimeError(
+                        "rank() second argument must be \"asc\" or \"desc\"".to_string(),
+                    ))
+                }
+            };
+
+            let arg = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?;
+            if ascending {
+                // Ranking the negated values ranks the smallest first
+                let negated = evaluate_operation('*', arg, Value::Scalar(-Decimal::ONE))?;
+                eval_function(name, negated)
+            } else {
+                eval_function(name, arg)
+            }
+        }
+        "percentile" => {
+            // percentile(expr, p) interpolates between the two nearest ranks
+            if args.len() != 2 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function 'percentile' expects exactly 2 arguments, got {}",
+                    args.len()
+                )));
+            }
+
+            let data = match eval_ast_with_tables(&args[0], rows, table_map, variable_map)? {
+                Value::Scalar(s) => vec![s],
+                Value::Matrix { data, .. } => data,
+            };
+            let p = eval_ast_with_tables(&args[1], rows, table_map, variable_map)?
+                .as_scalar()
+                .ok_or_else(|| {
+                    FormulaError::RuntimeError(
+                        "percentile() second argument must be a scalar between 0 and 100"
+                            .to_string(),
+                    )
+                    .at(args[1].span())
+                })?;
+            percentile(data, p).map(Value::Scalar)
+        }
+        "head" | "tail" => {
+            // head(v, n)/tail(v, n) keep the first/last n elements of a vector
+            if args.len() != 2 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function '{}' expects exactly 2 arguments, got {}",
+                    name,
+                    args.len()
+                )));
+            }
+
+            let value = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?;
+            let n = eval_ast_with_tables(&args[1], rows, table_map, variable_map)?
+                .as_scalar()
+                .filter(|n| n.fract().is_zero() && !n.is_sign_negative())
+                .and_then(|n| n.to_usize())
+                .ok_or_else(|| {
+                    FormulaError::RuntimeError(format!(
+                        "{}() second argument must be a non-negative whole number",
+                        name.to_lowercase()
+                    ))
+                    .at(args[1].span())
+                })?;
+            Ok(slice_vector(value, n, name.eq_ignore_ascii_case("head")))
+        }
+        "vmin" | "vmax" => {
+            // vmin(a, b)/vmax(a, b) compare element by element, unlike min/max which reduce
+            if args.len() != 2 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function '{}' expects exactly 2 arguments, got {}",
+                    name,
+                    args.len()
+                )));
+            }
+
+            let left = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?;
+            let right = eval_ast_with_tables(&args[1], rows, table_map, variable_map)?;
+            let pick = if name.eq_ignore_ascii_case("vmin") {
+                Decimal::min
+            } else {
+                Decimal::max
+            };
+            elementwise(name, left, right, pick).map_err(|error| error.at(span))
+        }
+        "sumif" => {
+            // sumif(range, "criterion", sum_range) sums sum_range where range matches
+            if args.len() != 2 && args.len() != 3 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function 'sumif' expects 2 or 3 arguments, got {}",
+                    args.len()
+                )));
+            }
+
+            let (criterion, cells) =
+                criterion_and_cells("sumif", args, rows, table_map, variable_map)?;
+            let sum_data = match args.get(2) {
+                Some(expr) => match eval_ast_with_tables(expr, rows, table_map, variable_map)? {
+                    Value::Scalar(s) => vec![s],
+                    Value::Matrix { data, .. } => data,
+                },
+                None => cells
+                    .iter()
+                    .map(|cell| reference::parse_cell_number(cell).unwrap_or(Decimal::ZERO))
+                    .collect(),
+            };
+
+            if cells.len() != sum_data.len() {
+                return Err(FormulaError::RuntimeError(format!(
+                    "sumif() criteria range has {} elements but sum range has {}",
+                    cells.len(),
+                    sum_data.len()
+                ))
+                .at(span));
+            }
+
+            let total = cells
+                .iter()
+                .zip(sum_data)
+                .filter(|(cell, _)| criterion.matches(cell))
+                .map(|(_, value)| value)
+                .sum();
+            Ok(Value::Scalar(total))
+        }
+        "countif" => {
+            // countif(range, "criterion") counts the elements of range that match
+            if args.len() != 2 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function 'countif' expects exactly 2 arguments, got {}",
+                    args.len()
+                )));
+            }
+
+            let (criterion, cells) =
+                criterion_and_cells("countif", args, rows, table_map, variable_map)?;
+            let count = cells.iter().filter(|cell| criterion.matches(cell)).count();
+            Ok(Value::Scalar(Decimal::from(count)))
+        }
+        "identity" => {
+            // identity(n) builds an n×n identity matrix
+            if args.len() != 1 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function 'identity' expects exactly 1 argument, got {}",
+                    args.len()
+                )));
+            }
+
+            let n = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?
+                .as_scalar()
+                .filter(|n| n.fract().is_zero() && *n >= Decimal::ONE)
+                .and_then(|n| n.to_usize())
+                .ok_or_else(|| {
+                    FormulaError::RuntimeError(
+                        "identity() argument must be a positive whole number".to_string(),
+                    )
+                    .at(args[0].span())
+                })?;
+            Ok(identity_matrix(n))
+        }
+        "rowsum" | "colsum" => {
+            // rowsum(m) totals each row into a column vector, colsum(m) each column into a row vector
+            if args.len() != 1 {
+                return Err(FormulaError::RuntimeError(format!(
+                    "function '{}' expects exactly 1 argument, got {}",
+                    name,
+                    args.len()
+                )));
+            }
+
+            let arg = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?;
+            Ok(axis_sums(arg, name.eq_ignore_ascii_case("rowsum")))
+        }
         // All other functions expect exactly one argument
-        "sum" | "avg" | "min" | "max" | "count" | "prod" => {
+        "sum" | "avg" | "min" | "max" | "count" | "prod" | "cumsum" | "cumprod" | "diff"
+        | "reverse" | "argmax" | "argmin" | "range" | "diag" | "det" | "normalize" | "zscore"
+        | "first" | "last" | "sign" | "trunc" => {
             if args.len() != 1 {
                 return Err(FormulaError::RuntimeError(format!(
                     "function '{}' expects exactly 1 argument, got {}",
@@ -179,13 +454,344 @@ fn eval_function_call_with_tables(
             let arg = eval_ast_with_tables(&args[0], rows, table_map, variable_map)?;
             eval_function(name, arg)
         }
-        _ => Err(FormulaError::RuntimeError(format!(
-            "unknown function: '{}' (supported functions: sum, avg, min, max, count, prod, from)",
+        _ => {
+            // Point at the function name rather than the whole call
+            let name_span = Span::new(span.start, span.start + name.len());
+            Err(FormulaError::RuntimeError(format!(
+                "unknown function: '{}' (supported functions: sum, avg, min, max, count, prod, cumsum, cumprod, diff, sort, reverse, argmax, argmin, range, rank, normalize, zscore, first, last, head, tail, sign, trunc, percentile, sumif, countif, vmin, vmax, identity, diag, det, rowsum, colsum, from)",
+                name
+            ))
+            .at(name_span))
+        }
+    }
+}
+
+/// Sums a matrix along one axis: each row into a column vector (`by_row`),
+/// or each column into a row vector
+///
+/// A scalar is a 1×1 matrix, so it is its own total either way.
+fn axis_sums(value: Value, by_row: bool) -> Value {
+    match value {
+        Value::Scalar(s) => Value::Scalar(s),
+        Value::Matrix { cols, data, .. } if by_row => Value::column_vector(
+            data.chunks(cols.max(1))
+                .map(|row| row.iter().sum())
+                .collect(),
+        ),
+        Value::Matrix { cols, data, .. } => Value::row_vector(
+            (0..cols)
+                .map(|col| data.iter().skip(col).step_by(cols).sum())
+                .collect(),
+        ),
+    }
+}
+
+/// Keeps the first (`from_start`) or last `n` elements of a value, or all of them if it has fewer
+///
+/// A row vector gives a row vector; anything else, including a matrix read
+/// row by row, gives a column vector. A scalar is a vector of one element.
+fn slice_vector(value: Value, n: usize, from_start: bool) -> Value {
+    let (data, row) = match value {
+        Value::Scalar(s) => (vec![s], false),
+        Value::Matrix { rows, cols, data } => (data, rows == 1 && cols > 1),
+    };
+    let n = n.min(data.len());
+    let data = if from_start {
+        data[..n].to_vec()
+    } else {
+        data[data.len() - n..].to_vec()
+    };
+    if row {
+        Value::row_vector(data)
+    } else {
+        Value::column_vector(data)
+    }
+}
+
+/// Computes the `p`th percentile (0-100) of `data` by linear interpolation
+///
+/// The values are sorted and the percentile falls at rank `p / 100 * (n - 1)`
+/// (counting from 0). A fractional rank interpolates linearly between the two
+/// values around it, the same method as Excel's `PERCENTILE.INC` and NumPy's
+/// default, so `p = 50` is the median. `p` is clamped to 0-100.
+fn percentile(mut data: Vec<Decimal>, p: Decimal) -> Result<Decimal, FormulaError> {
+    if data.is_empty() {
+        return Err(FormulaError::RuntimeError(
+            "percentile() of an empty vector is undefined".to_string(),
+        ));
+    }
+    data.sort();
+
+    let p = p.clamp(Decimal::ZERO, Decimal::ONE_HUNDRED);
+    let rank = p / Decimal::ONE_HUNDRED * Decimal::from(data.len() - 1);
+    let lower = rank.floor();
+    let fraction = rank - lower;
+    // rank lies within 0..=n-1, so the conversion cannot fail
+    let lower_idx = lower.to_usize().unwrap_or(0);
+
+    match data.get(lower_idx + 1) {
+        Some(&upper) if !fraction.is_zero() => {
+            Ok(data[lower_idx] + fraction * (upper - data[lower_idx]))
+        }
+        _ => Ok(data[lower_idx]),
+    }
+}
+
+/// Standardizes `data` to z-scores: `(x - mean) / stdev` for each element
+///
+/// Uses the sample standard deviation (dividing by `n - 1`), like Excel's
+/// `STANDARDIZE` with `STDEV.S`. Fewer than two values, or values that are
+/// all the same, have no spread to divide by and are an error.
+fn zscores(data: &[Decimal]) -> Result<Vec<Decimal>, FormulaError> {
+    if data.len() < 2 {
+        return Err(FormulaError::RuntimeError(format!(
+            "zscore() needs at least 2 values, got {}",
+            data.len()
+        )));
+    }
+    let count = Decimal::from(data.len());
+    let mean = data.iter().sum::<Decimal>() / count;
+    let variance = data
+        .iter()
+        .map(|x| (x - mean) * (x - mean))
+        .sum::<Decimal>()
+        / (count - Decimal::ONE);
+    let stdev = sqrt(variance);
+    if stdev.is_zero() {
+        return Err(FormulaError::RuntimeError(
+            "division by zero in zscore(): the standard deviation is 0".to_string(),
+        ));
+    }
+    Ok(data.iter().map(|x| (x - mean) / stdev).collect())
+}
+
+/// The square root of a non-negative decimal, by Newton's method
+///
+/// Starts from the `f64` square root and refines it to full decimal precision.
+fn sqrt(value: Decimal) -> Decimal {
+    if value <= Decimal::ZERO {
+        return Decimal::ZERO;
+    }
+    let mut root = value
+        .to_f64()
+        .and_then(|v| Decimal::from_f64(v.sqrt()))
+        .filter(|root| !root.is_zero())
+        .unwrap_or(Decimal::ONE);
+    for _ in 0..20 {
+        let next = (root + value / root) / Decimal::TWO;
+        if next == root {
+            break;
+        }
+        root = next;
+    }
+    root
+}
+
+/// Reads the `range` and `"criterion"` arguments of `sumif()` and `countif()`
+///
+/// Text criteria compare against the cells as written, so a reference is read
+/// as the text of its cells; any other expression gives its values as text.
+fn criterion_and_cells(
+    name: &str,
+    args: &[Expr],
+    rows: &Vec<Vec<String>>,
+    table_map: &std::collections::HashMap<String, Vec<Vec<String>>>,
+    variable_map: &std::collections::HashMap<String, Value>,
+) -> Result<(Criterion, Vec<String>), FormulaError> {
+    let Expr::String(criterion, _) = &args[1] else {
+        return Err(FormulaError::RuntimeError(format!(
+            "{}() second argument must be a quoted criterion such as \">100\"",
             name
-        ))),
+        ))
+        .at(args[1].span()));
+    };
+
+    let cells = match &args[0] {
+        Expr::CellRef(cell_ref, ref_span) => reference::resolve_reference_text(cell_ref, rows)
+            .map_err(|error| error.at(*ref_span))?,
+        expr => match eval_ast_with_tables(expr, rows, table_map, variable_map)? {
+            Value::Scalar(s) => vec![s.to_string()],
+            Value::Matrix { data, .. } => data.iter().map(Decimal::to_string).collect(),
+        },
+    };
+    Ok((Criterion::parse(criterion), cells))
+}
+
+/// The condition a cell must meet to be included by `sumif()` or `countif()`
+///
+/// Written as an optional operator (`>`, `>=`, `<`, `<=`, `=`, `<>` or `!=`)
+/// followed by an operand, so `">100"`, `"=x"` and `"x"` are all criteria. A
+/// number operand compares cell values numerically, and cells that are not
+/// numbers only match `<>`. Any other operand compares the cell text, ignoring
+/// case and surrounding whitespace.
+struct Criterion {
+    comparison: Comparison,
+    operand: String,
+}
+
+impl Criterion {
+    /// Operators in the order they are matched, so `>=` is found before `>`
+    const OPERATORS: [(&'static str, Comparison); 7] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        ("<>", Comparison::NotEqual),
+        ("!=", Comparison::NotEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ];
+
+    fn parse(text: &str) -> Criterion {
+        let text = text.trim();
+        let (comparison, operand) = Self::OPERATORS
+            .iter()
+            .find_map(|&(operator, comparison)| {
+                text.strip_prefix(operator)
+                    .map(|operand| (comparison, operand))
+            })
+            .unwrap_or((Comparison::Equal, text));
+        Criterion {
+            comparison,
+            operand: operand.trim().to_string(),
+        }
+    }
+
+    fn matches(&self, cell: &str) -> bool {
+        match reference::parse_cell_number(&self.operand) {
+            Some(operand) => match reference::parse_cell_number(cell) {
+                Some(value) => self.comparison.holds(value.cmp(&operand)),
+                None => self.comparison == Comparison::NotEqual,
+            },
+            None => self
+                .comparison
+                .holds(cell.trim().to_lowercase().cmp(&self.operand.to_lowercase())),
+        }
+    }
+}
+
+/// Combines two values element by element with `combine`
+///
+/// Two matrices must have the same shape; a scalar is paired with every
+/// element of the other argument. `name` is the function reported in errors.
+fn elementwise(
+    name: &str,
+    left: Value,
+    right: Value,
+    combine: fn(Decimal, Decimal) -> Decimal,
+) -> Result<Value, FormulaError> {
+    match (left, right) {
+        (Value::Scalar(l), Value::Scalar(r)) => Ok(Value::Scalar(combine(l, r))),
+        (Value::Matrix { rows, cols, data }, Value::Scalar(r)) => Ok(Value::Matrix {
+            rows,
+            cols,
+            data: data.into_iter().map(|l| combine(l, r)).collect(),
+        }),
+        (Value::Scalar(l), Value::Matrix { rows, cols, data }) => Ok(Value::Matrix {
+            rows,
+            cols,
+            data: data.into_iter().map(|r| combine(l, r)).collect(),
+        }),
+        (
+            Value::Matrix {
+                rows,
+                cols,
+                data: left_data,
+            },
+            Value::Matrix {
+                rows: right_rows,
+                cols: right_cols,
+                data: right_data,
+            },
+        ) => {
+            if (rows, cols) != (right_rows, right_cols) {
+                return Err(FormulaError::RuntimeError(format!(
+                    "{}() requires arguments of the same shape, got ({}×{}) and ({}×{})",
+                    name.to_lowercase(),
+                    rows,
+                    cols,
+                    right_rows,
+                    right_cols
+                )));
+            }
+            Ok(Value::Matrix {
+                rows,
+                cols,
+                data: left_data
+                    .into_iter()
+                    .zip(right_data)
+                    .map(|(l, r)| combine(l, r))
+                    .collect(),
+            })
+        }
+    }
+}
+
+/// Builds an `n`×`n` identity matrix
+fn identity_matrix(n: usize) -> Value {
+    let data = (0..n * n)
+        .map(|idx| {
+            if idx / n == idx % n {
+                Decimal::ONE
+            } else {
+                Decimal::ZERO
+            }
+        })
+        .collect();
+    Value::Matrix {
+        rows: n,
+        cols: n,
+        data,
     }
 }
 
+/// Computes the determinant of an `n`×`n` matrix stored in row-major order
+///
+/// Uses Bareiss fraction-free elimination: every division in it is exact, so
+/// cells with finitely many decimal places give an exact determinant instead
+/// of one carrying rounding noise from Gaussian elimination. A zero pivot is
+/// swapped for a later row with a non-zero entry in that column (flipping the
+/// sign); if there is none, the matrix is singular and the determinant is zero.
+fn determinant(n: usize, mut data: Vec<Decimal>) -> Result<Decimal, FormulaError> {
+    if n == 0 {
+        return Ok(Decimal::ONE);
+    }
+    let overflow = || FormulaError::RuntimeError("overflow in det()".to_string());
+    let mut negate = false;
+    let mut previous_pivot = Decimal::ONE;
+
+    for k in 0..n - 1 {
+        if data[k * n + k].is_zero() {
+            let Some(swap_row) = (k + 1..n).find(|&row| !data[row * n + k].is_zero()) else {
+                return Ok(Decimal::ZERO);
+            };
+            for col in 0..n {
+                data.swap(k * n + col, swap_row * n + col);
+            }
+            negate = !negate;
+        }
+
+        let pivot = data[k * n + k];
+        for row in k + 1..n {
+            for col in k + 1..n {
+                let scaled = data[row * n + col]
+                    .checked_mul(pivot)
+                    .ok_or_else(overflow)?;
+                let eliminated = data[row * n + k]
+                    .checked_mul(data[k * n + col])
+                    .ok_or_else(overflow)?;
+                data[row * n + col] = scaled
+                    .checked_sub(eliminated)
+                    .and_then(|difference| difference.checked_div(previous_pivot))
+                    .ok_or_else(overflow)?;
+            }
+        }
+        previous_pivot = pivot;
+    }
+
+    let det = data[n * n - 1].normalize();
+    Ok(if negate { -det } else { det })
+}
+
 /// Evaluate a function with a Value argument (for single-arg functions)
 pub(crate) fn eval_function(name: &str, arg: Value) -> Result<Value, FormulaError> {
     match name.to_lowercase().as_str() {
@@ -245,6 +851,241 @@ pub(crate) fn eval_function(name: &str, arg: Value) -> Result<Value, FormulaErro
                 Ok(Value::Scalar(product))
             }
         },
+        "cumsum" => match arg {
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix { rows, cols, data } => {
+                // Running totals in row-major order, keeping the input shape
+                let running = data
+                    .iter()
+                    .scan(Decimal::ZERO, |acc, &x| {
+                        *acc += x;
+                        Some(*acc)
+                    })
+                    .collect();
+                Ok(Value::Matrix {
+                    rows,
+                    cols,
+                    data: running,
+                })
+            }
+        },
+        "cumprod" => match arg {
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix { rows, cols, data } => {
+                // Running products in row-major order, keeping the input shape;
+                // they grow quickly, so overflow is an error rather than a panic
+                let mut product = Decimal::ONE;
+                let running = data
+                    .iter()
+                    .map(|&x| {
+                        product = product.checked_mul(x).ok_or_else(|| {
+                            FormulaError::RuntimeError("overflow in cumprod()".to_string())
+                        })?;
+                        Ok(product)
+                    })
+                    .collect::<Result<_, FormulaError>>()?;
+                Ok(Value::Matrix {
+                    rows,
+                    cols,
+                    data: running,
+                })
+            }
+        },
+        "diff" => match arg {
+            Value::Scalar(_) => Ok(Value::Scalar(Decimal::ZERO)),
+            Value::Matrix { rows, cols, data } => {
+                // Differences from the previous element in row-major order; the
+                // first element has no predecessor and becomes 0, keeping the shape
+                let differences = data
+                    .iter()
+                    .scan(None, |previous: &mut Option<Decimal>, &x| {
+                        let difference = previous.map_or(Decimal::ZERO, |p| x - p);
+                        *previous = Some(x);
+                        Some(difference)
+                    })
+                    .collect();
+                Ok(Value::Matrix {
+                    rows,
+                    cols,
+                    data: differences,
+                })
+            }
+        },
+        "sort" => match arg {
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix {
+                rows,
+                cols,
+                mut data,
+            } => {
+                // Ascending, refilled in row-major order so the shape is unchanged
+                data.sort();
+                Ok(Value::Matrix { rows, cols, data })
+            }
+        },
+        "reverse" => match arg {
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix {
+                rows,
+                cols,
+                mut data,
+            } => {
+                // Reverses the flat row-major order, so a matrix is rotated 180°
+                data.reverse();
+                Ok(Value::Matrix { rows, cols, data })
+            }
+        },
+        "argmax" | "argmin" => match arg {
+            Value::Scalar(_) => Ok(Value::Scalar(Decimal::ONE)),
+            Value::Matrix { data, .. } => {
+                // 1-based position in row-major order; the first occurrence wins ties
+                let find_max = name.eq_ignore_ascii_case("argmax");
+                let mut best: Option<(usize, Decimal)> = None;
+                for (idx, &x) in data.iter().enumerate() {
+                    let better = match best {
+                        None => true,
+                        Some((_, current)) if find_max => x > current,
+                        Some((_, current)) => x < current,
+                    };
+                    if better {
+                        best = Some((idx, x));
+                    }
+                }
+                let position = best.map_or(0, |(idx, _)| idx + 1);
+                Ok(Value::Scalar(Decimal::from(position)))
+            }
+        },
+        "rank" => match arg {
+            Value::Scalar(_) => Ok(Value::Scalar(Decimal::ONE)),
+            Value::Matrix { rows, cols, data } => {
+                // Competition ranking: 1 + the number of larger values, so ties
+                // share the lower rank and the next rank is skipped ([1, 2, 2, 4])
+                let ranks = data
+                    .iter()
+                    .map(|x| Decimal::from(1 + data.iter().filter(|y| *y > x).count()))
+                    .collect();
+                Ok(Value::Matrix {
+                    rows,
+                    cols,
+                    data: ranks,
+                })
+            }
+        },
+        "normalize" => {
+            // Each element divided by the total, so the result sums to 1
+            let total = match &arg {
+                Value::Scalar(s) => *s,
+                Value::Matrix { data, .. } => data.iter().sum(),
+            };
+            if total.is_zero() {
+                return Err(FormulaError::RuntimeError(
+                    "division by zero in normalize(): the values sum to 0".to_string(),
+                ));
+            }
+            Ok(match arg {
+                Value::Scalar(_) => Value::Scalar(Decimal::ONE),
+                Value::Matrix { rows, cols, data } => Value::Matrix {
+                    rows,
+                    cols,
+                    data: data.into_iter().map(|x| x / total).collect(),
+                },
+            })
+        }
+        "zscore" => match arg {
+            Value::Scalar(s) => zscores(&[s]).map(|_| Value::Scalar(Decimal::ZERO)),
+            Value::Matrix { rows, cols, data } => Ok(Value::Matrix {
+                rows,
+                cols,
+                data: zscores(&data)?,
+            }),
+        },
+        "first" | "last" => match arg {
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix { data, .. } => {
+                // Elements are in row-major order, so a matrix gives its
+                // top-left or bottom-right element
+                let element = if name.eq_ignore_ascii_case("first") {
+                    data.first()
+                } else {
+                    data.last()
+                };
+                Ok(Value::Scalar(element.copied().unwrap_or(Decimal::ZERO)))
+            }
+        },
+        "sign" => {
+            let sign = |x: Decimal| {
+                if x.is_zero() {
+                    Decimal::ZERO
+                } else if x.is_sign_negative() {
+                    Decimal::NEGATIVE_ONE
+                } else {
+                    Decimal::ONE
+                }
+            };
+            match arg {
+                Value::Scalar(s) => Ok(Value::Scalar(sign(s))),
+                Value::Matrix { rows, cols, data } => Ok(Value::Matrix {
+                    rows,
+                    cols,
+                    data: data.into_iter().map(sign).collect(),
+                }),
+            }
+        }
+        "trunc" => {
+            // Decimal::trunc drops the fraction, rounding toward zero, but keeps
+            // the sign of a zero result, which would be written as -0
+            let trunc = |x: Decimal| {
+                let whole = x.trunc();
+                if whole.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    whole
+                }
+            };
+            match arg {
+                Value::Scalar(s) => Ok(Value::Scalar(trunc(s))),
+                Value::Matrix { rows, cols, data } => Ok(Value::Matrix {
+                    rows,
+                    cols,
+                    data: data.into_iter().map(trunc).collect(),
+                }),
+            }
+        }
+        "range" => match arg {
+            Value::Scalar(_) => Ok(Value::Scalar(Decimal::ZERO)),
+            matrix => {
+                // max and min already return zero for an empty matrix
+                let max = eval_function("max", matrix.clone())?;
+                let min = eval_function("min", matrix)?;
+                evaluate_operation('-', max, min)
+            }
+        },
+        "diag" => match arg {
+            // A scalar is a 1×1 matrix, so it is its own diagonal
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix { rows, cols, data } => {
+                if rows != cols {
+                    return Err(FormulaError::RuntimeError(format!(
+                        "diag() requires a square matrix, got shape ({}×{})",
+                        rows, cols
+                    )));
+                }
+                let diagonal = (0..rows).map(|idx| data[idx * cols + idx]).collect();
+                Ok(Value::column_vector(diagonal))
+            }
+        },
+        "det" => match arg {
+            Value::Scalar(s) => Ok(Value::Scalar(s)),
+            Value::Matrix { rows, cols, data } => {
+                if rows != cols {
+                    return Err(FormulaError::RuntimeError(format!(
+                        "det() requires a square matrix, got shape ({}×{})",
+                        rows, cols
+                    )));
+                }
+                Ok(Value::Scalar(determinant(rows, data)?))
+            }
+        },
         _ => Err(FormulaError::RuntimeError(format!(
             "unknown function: '{}'",
             name
@@ -340,14 +1181,16 @@ pub(crate) fn evaluate_operation(
         };
     }
 
-    // Handle other operators (+, -, *, /, ^)
+    // Handle other operators (+, -, *, /, //, ^)
     match (left, right) {
         // Scalar op Scalar
         (Value::Scalar(l), Value::Scalar(r)) => {
             let result = apply_scalar_op(op, l, r).ok_or_else(|| {
                 FormulaError::RuntimeError(format!(
                     "division by zero in scalar operation: {} {} {}",
-                    l, op, r
+                    l,
+                    operator_text(op),
+                    r
                 ))
             })?;
             Ok(Value::Scalar(result))
@@ -369,7 +1212,7 @@ pub(crate) fn evaluate_operation(
             // For element-wise operations, dimensions must match
             if m1 != m2 || n1 != n2 {
                 return Err(FormulaError::RuntimeError(
-                    format!("element-wise operation '{}' requires matching dimensions: got ({}×{}) and ({}×{})", op, m1, n1, m2, n2)
+                    format!("element-wise operation '{}' requires matching dimensions: got ({}×{}) and ({}×{})", operator_text(op), m1, n1, m2, n2)
                 ));
             }
 
@@ -433,6 +1276,18 @@ pub(crate) fn evaluate_operation(
     }
 }
 
+/// Operator character used for floor division (`//` in formulas)
+pub(crate) const FLOOR_DIV: char = '\\';
+
+/// Formats an operator character as it is written in formulas
+fn operator_text(op: char) -> String {
+    if op == FLOOR_DIV {
+        "//".to_string()
+    } else {
+        op.to_string()
+    }
+}
+
 /// Helper function to apply a scalar operation to two Decimal values
 pub(crate) fn apply_scalar_op(op: char, left: Decimal, right: Decimal) -> Option<Decimal> {
     match op {
@@ -446,6 +1301,13 @@ pub(crate) fn apply_scalar_op(op: char, left: Decimal, right: Decimal) -> Option
                 Some(left / right)
             }
         }
+        FLOOR_DIV => {
+            if right == Decimal::ZERO {
+                None
+            } else {
+                Some((left / right).floor())
+            }
+        }
         '^' => decimal_pow(left, right),
         _ => None,
     }
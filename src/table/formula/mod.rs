@@ -11,7 +11,7 @@
 //! - **Matrix multiplication**: `D1 = A_.T @ B_` (dot product using transpose operator)
 //! - **Transpose operator**: `.T` to transpose vectors (e.g., `A_.T` converts column to row)
 //! - **Functions**: `sum(A_)` to aggregate vector values
-//! - **Operators**: `+`, `-`, `*`, `/`, `^`, `@` with proper precedence
+//! - **Operators**: `+`, `-`, `*`, `/`, `//`, `^`, `@` with proper precedence
 //! - **Parentheses**: `(A_ + B_) * 2` for grouping
 //!
 //! # Cell References
@@ -37,8 +37,9 @@
 //!
 //! 1. Parentheses `()` and transpose `.T` (highest)
 //! 2. Exponentiation `^`
-//! 3. Matrix multiplication `@`, scalar multiplication `*`, and division `/`
-//! 4. Addition `+` and subtraction `-` (lowest)
+//! 3. Negation `-x`
+//! 4. Matrix multiplication `@`, scalar multiplication `*`, division `/`, and floor division `//`
+//! 5. Addition `+` and subtraction `-` (lowest)
 //!
 //! # Examples
 //!
@@ -51,10 +52,12 @@
 //! <!-- md-table: G1 = A_.T @ B_ -->               // Dot product (transpose then multiply)
 //! <!-- md-table: H1 = _1 @ A_ -->                 // Row times column
 //! <!-- md-table: I1 = (A_.T @ B_) + 10 -->        // Matrix mult in expression
+//! <!-- md-table: to("summary", B1) = sum(A_) -->  // Write into another table
 //! ```
 
 // Internal modules
 mod ast;
+mod dependency;
 mod evaluator;
 mod reference;
 mod tokenizer;
@@ -65,11 +68,31 @@ pub use types::Span;
 
 // Internal imports
 use crate::table::error::FormulaError;
+use crate::table::parser::{RowFilter, TotalSpec};
 use ast::Parser;
+use rust_decimal::Decimal;
 use std::collections::HashMap;
+use std::rc::Rc;
 use tokenizer::tokenize_expression;
 use types::{formula_row_to_table_index, FIRST_DATA_ROW_INDEX};
-use types::{Assignment, Statement, Value};
+use types::{Assignment, Statement};
+
+// Re-export Value so constants can be passed to apply_formulas_with_tables
+pub(crate) use reference::parse_cell_number;
+pub(crate) use types::col_index_to_letter;
+pub use types::Value;
+
+/// Writes a formula result into a cell, ignoring cells outside the table
+///
+/// This is the only place formulas change cell text: every assignment below
+/// goes through it, as does the `error-cell` text written into the targets
+/// of a failing formula, so cells that are not the target of an assignment
+/// keep the text they were parsed with (`007` stays `007`, `1.50` stays `1.50`).
+fn write_cell(rows: &mut [Vec<String>], row: usize, col: usize, value: impl ToString) {
+    if let Some(cell) = rows.get_mut(row).and_then(|cells| cells.get_mut(col)) {
+        *cell = value.to_string();
+    }
+}
 
 /// Applies a column vector of values to a table column
 /// Starts at first data row (after header and separator)
@@ -81,10 +104,7 @@ fn apply_column_vector_assignment(rows: &mut [Vec<String>], col: usize, value: &
     } = value
     {
         for (i, &val) in data.iter().enumerate() {
-            let row_idx = FIRST_DATA_ROW_INDEX + i;
-            if row_idx < rows.len() && col < rows[row_idx].len() {
-                rows[row_idx][col] = val.to_string();
-            }
+            write_cell(rows, FIRST_DATA_ROW_INDEX + i, col, val);
         }
     }
 }
@@ -98,9 +118,7 @@ fn apply_row_vector_assignment(rows: &mut [Vec<String>], row: usize, value: &Val
     } = value
     {
         for (i, &val) in data.iter().enumerate() {
-            if row < rows.len() && i < rows[row].len() {
-                rows[row][i] = val.to_string();
-            }
+            write_cell(rows, row, i, val);
         }
     }
 }
@@ -131,12 +149,8 @@ fn apply_range_assignment(
         // Apply values to range
         for r in 0..expected_rows {
             for c in 0..expected_cols {
-                let table_row = start_row + r;
-                let table_col = start_col + c;
-                if table_row < rows.len() && table_col < rows[table_row].len() {
-                    let data_idx = r * expected_cols + c;
-                    rows[table_row][table_col] = data[data_idx].to_string();
-                }
+                let data_idx = r * expected_cols + c;
+                write_cell(rows, start_row + r, start_col + c, data[data_idx]);
             }
         }
     }
@@ -165,12 +179,13 @@ fn apply_column_range_assignment(
         // Apply values starting from first data row
         for r in 0..*num_rows {
             for c in 0..expected_cols {
-                let table_row = FIRST_DATA_ROW_INDEX + r;
-                let table_col = start_col + c;
-                if table_row < rows.len() && table_col < rows[table_row].len() {
-                    let data_idx = r * expected_cols + c;
-                    rows[table_row][table_col] = data[data_idx].to_string();
-                }
+                let data_idx = r * expected_cols + c;
+                write_cell(
+                    rows,
+                    FIRST_DATA_ROW_INDEX + r,
+                    start_col + c,
+                    data[data_idx],
+                );
             }
         }
     }
@@ -200,10 +215,7 @@ fn apply_row_range_assignment(
         for r in 0..expected_rows {
             let table_row = formula_row_to_table_index(start_row + r);
             for c in 0..*num_cols {
-                if table_row < rows.len() && c < rows[table_row].len() {
-                    let data_idx = r * num_cols + c;
-                    rows[table_row][c] = data[data_idx].to_string();
-                }
+                write_cell(rows, table_row, c, data[r * num_cols + c]);
             }
         }
     }
@@ -211,56 +223,133 @@ fn apply_row_range_assignment(
 
 /// Applies spreadsheet-style formulas to table cells with access to other tables.
 ///
-/// This version supports cross-table references via the from() function.
+/// This version supports cross-table references via the from() function, and
+/// writes into other tables via `to("table_id", TARGET) = EXPRESSION`. Such writes
+/// are applied directly to the target's rows in `table_map`.
+///
+/// Formulas are applied in dependency order, so a formula runs after the ones
+/// that assign the cells it reads; formulas whose results feed each other, or
+/// that name the cell they assign, are reported as circular references and
+/// skipped.
 ///
 /// # Arguments
 ///
 /// * `rows` - Mutable reference to the table rows (header, separator, then data rows)
 /// * `formulas` - Slice of formula strings to evaluate
-/// * `table_map` - Map of table IDs to their data for cross-table references
+/// * `table_map` - Map of table IDs to their data for cross-table references and writes
+/// * `constants` - Document constants visible as variables (shadowed by `let` statements)
 ///
 /// # Returns
 ///
-/// A vector of Option<String> where each element corresponds to a formula.
-/// None indicates the formula succeeded, Some(error) indicates it failed with the given error message.
+/// A [`FormulaResults`] with one error and one warning entry per formula, in the
+/// order the formulas were given.
+/// An error of None indicates the formula succeeded, Some(error) indicates it failed with the
+/// given error message. Warnings are non-fatal: the assignment has already been applied.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::{apply_formulas_with_tables, collect_tables};
+/// use std::collections::HashMap;
+///
+/// let mut tables = collect_tables("| Qty |\n|---|\n| 3 |\n| 4 |\n<!-- md-table: id=\"stock\" -->\n");
+/// let mut rows = vec![
+///     vec!["Total".to_string()],
+///     vec!["---".to_string()],
+///     vec!["".to_string()],
+/// ];
+///
+/// let formulas = vec!["A1 = sum(from(\"stock\", A_))".to_string()];
+/// let results = apply_formulas_with_tables(&mut rows, &formulas, &mut tables, &HashMap::new());
+/// assert_eq!(results.errors, vec![None]);
+/// assert_eq!(rows[2][0], "7");
+/// ```
 pub fn apply_formulas_with_tables(
     rows: &mut Vec<Vec<String>>,
     formulas: &[String],
-    table_map: &std::collections::HashMap<String, Vec<Vec<String>>>,
-) -> Vec<Option<String>> {
-    let mut errors = Vec::new();
-    let mut variable_map: HashMap<String, Value> = HashMap::new();
+    table_map: &mut HashMap<String, Vec<Vec<String>>>,
+    constants: &HashMap<String, Value>,
+) -> FormulaResults {
+    apply_table_formulas(
+        rows,
+        formulas,
+        table_map,
+        constants,
+        false,
+        None,
+        &mut FormulaCache::default(),
+    )
+}
 
-    for formula in formulas {
-        let formula_trimmed = formula.trim();
+/// Applies formulas like [`apply_formulas_with_tables`], optionally reading
+/// percentages such as `8%` in this table's cells as fractions (`0.08`)
+///
+/// The cells themselves keep their `%` text; only the values formulas see change.
+/// With `error_cell` set, a formula that fails writes that text into every cell
+/// it targets, so stale values are not mistaken for results.
+/// Formulas are parsed through `cache`, so tables sharing a formula parse it once.
+pub(crate) fn apply_table_formulas(
+    rows: &mut Vec<Vec<String>>,
+    formulas: &[String],
+    table_map: &mut HashMap<String, Vec<Vec<String>>>,
+    constants: &HashMap<String, Value>,
+    percent_cells: bool,
+    error_cell: Option<&str>,
+    cache: &mut FormulaCache,
+) -> FormulaResults {
+    // Each formula is parsed once, for both ordering and evaluation.
+    // Formulas run in dependency order, but results are reported in written order.
+    // Formulas caught in a cycle are reported instead of applied
+    let parsed: Vec<Option<Rc<ParsedFormula>>> = formulas.iter().map(|f| cache.parse(f)).collect();
+    let dependency::FormulaOrder {
+        order,
+        circular: mut errors,
+    } = dependency::order_formulas(formulas, &parsed);
+    let mut warnings = vec![None; formulas.len()];
+    let mut written_tables = Vec::new();
+    let mut variable_map: HashMap<String, Value> = constants.clone();
+
+    for index in order {
+        let formula_trimmed = formulas[index].trim();
 
         // Try to parse the statement (let or assignment)
-        let (statement, expr) = match parse_statement(formula_trimmed) {
+        let ParsedFormula {
+            statements,
+            expr,
+            ast,
+        } = match parsed[index].as_deref() {
             Some(parsed) => parsed,
             None => {
-                errors.push(Some(format!("Failed to parse statement '{}': invalid syntax (expected format: 'let VAR = EXPRESSION' or 'TARGET = EXPRESSION')", formula_trimmed)));
+                errors[index] = Some(format!("Failed to parse statement '{}': invalid syntax (expected format: 'let VAR = EXPRESSION' or 'TARGET = EXPRESSION')", formula_trimmed));
                 continue;
             }
         };
 
+        // Formulas read from a copy with percentages converted, rebuilt for each
+        // formula so earlier assignments are visible
+        let percent_rows;
+        let eval_rows = if percent_cells {
+            percent_rows = percent_cells_as_fractions(rows);
+            &percent_rows
+        } else {
+            &*rows
+        };
+
         // Handle let statements - evaluate and store in variable map
-        if let Statement::Let { name, span: _ } = &statement {
+        if let [Statement::Let { name, span: _ }] = statements.as_slice() {
             // Try to evaluate the expression
-            let value = match evaluate_expression_value_with_tables(
-                &expr,
-                rows,
-                table_map,
-                &variable_map,
-            ) {
+            let value = match ast.as_ref().map_err(Clone::clone).and_then(|ast| {
+                evaluator::eval_ast_with_tables(ast, eval_rows, table_map, &variable_map)
+            }) {
                 Ok(v) => v,
                 Err(error) => {
-                    // Try to extract span information for better error messages
-                    let error_msg = match extract_error_span(&expr, &error) {
+                    // Show a position indicator when the failing node is known
+                    let error_msg = match error.span() {
                         Some(span) => {
                             format!(
                                 "Failed to evaluate expression for variable '{}': \n{}",
                                 name,
-                                error.with_context(&expr, span)
+                                error.with_context(expr, span)
                             )
                         }
                         None => {
@@ -270,194 +359,796 @@ pub fn apply_formulas_with_tables(
                             )
                         }
                     };
-                    errors.push(Some(error_msg));
+                    errors[index] = Some(error_msg);
                     continue;
                 }
             };
 
             // Store the variable
             variable_map.insert(name.clone(), value);
-            errors.push(None); // Success
+            errors[index] = None; // Success
             continue;
         }
 
-        // Handle assignment statements - evaluate and apply to cells
-        let assignment = match statement {
-            Statement::Assignment(a) => a,
-            _ => unreachable!("Already handled Let statements above"),
+        // Handle assignment statements - evaluate once and apply to each target
+        // Try to evaluate the expression (with table_map and variable_map).
+        // Columns that read previous rows are computed one row at a time, in
+        // the first column this table's targets assign
+        let running_col = statements.iter().find_map(|statement| match statement {
+            Statement::Assignment(Assignment::ColumnVector { col }) => Some(*col),
+            _ => None,
+        });
+        let fills = matches!(statements.as_slice(), [Statement::Fill { .. }]);
+        let result = ast
+            .as_ref()
+            .map_err(Clone::clone)
+            .and_then(|ast| match running_col {
+                _ if fills => evaluate_fill(ast, eval_rows, table_map, &variable_map),
+                Some(col) if reads_previous_row(ast) => evaluate_column_row_by_row(
+                    ast,
+                    rows,
+                    col,
+                    table_map,
+                    &variable_map,
+                    percent_cells,
+                ),
+                _ => evaluator::eval_ast_with_tables(ast, eval_rows, table_map, &variable_map),
+            });
+        let value = match result {
+            Ok(v) => v,
+            Err(error) => {
+                // Show a position indicator when the failing node is known
+                let error_msg = match error.span() {
+                    Some(span) => {
+                        // Use with_context to show visual position indicator
+                        format!(
+                            "Failed to evaluate expression:\n{}",
+                            error.with_context(expr, span)
+                        )
+                    }
+                    None => {
+                        // Fallback to simple error message
+                        format!("Failed to evaluate expression '{}': {}", expr, error)
+                    }
+                };
+                errors[index] = Some(error_msg);
+                if let Some(text) = error_cell {
+                    mark_failed_targets(statements, rows, table_map, &mut written_tables, text);
+                }
+                continue;
+            }
         };
 
-        // Try to evaluate the expression (with table_map and variable_map)
-        let value =
-            match evaluate_expression_value_with_tables(&expr, rows, table_map, &variable_map) {
-                Ok(v) => v,
-                Err(error) => {
-                    // Try to extract span information by re-parsing for better error messages
-                    let error_msg = match extract_error_span(&expr, &error) {
-                        Some(span) => {
-                            // Use with_context to show visual position indicator
-                            format!(
-                                "Failed to evaluate expression:\n{}",
-                                error.with_context(&expr, span)
-                            )
-                        }
-                        None => {
-                            // Fallback to simple error message
-                            format!("Failed to evaluate expression '{}': {}", expr, error)
-                        }
-                    };
-                    errors.push(Some(error_msg));
-                    continue;
+        // Every target gets the value; failures and overwrite warnings are
+        // collected across targets
+        let mut target_errors = Vec::new();
+        let mut target_warnings = Vec::new();
+        for statement in statements {
+            // Assignments normally target this table; to("id", ...) targets another one
+            let fill_target;
+            let (target_rows, assignment) = match statement {
+                Statement::Assignment(assignment) => (&mut *rows, assignment),
+                Statement::Fill { col } => {
+                    fill_target = Assignment::ColumnVector { col: *col };
+                    (&mut *rows, &fill_target)
                 }
-            };
-
-        // Try to apply the assignment
-        let error = match assignment {
-            Assignment::Scalar { row, col } => {
-                // Scalar assignment: single cell update
-                match value.as_scalar() {
-                    Some(decimal) => {
-                        if row >= rows.len() || col >= rows[row].len() {
-                            Some(format!("Assignment failed for '{}': cell index out of bounds", formula_trimmed))
-                        } else {
-                            rows[row][col] = decimal.to_string();
-                            None  // Success
+                Statement::TableAssignment {
+                    table_id,
+                    assignment,
+                } => match table_map.get_mut(table_id) {
+                    Some(target_rows) => {
+                        if !written_tables.contains(table_id) {
+                            written_tables.push(table_id.clone());
                         }
+                        (target_rows, assignment)
                     }
                     None => {
-                        Some(format!("Assignment failed for '{}': cannot assign matrix to scalar cell (use a cell vector assignment like C_ instead)", formula_trimmed))
+                        target_errors.push(format!(
+                            "Assignment failed for '{}': table '{}' not found (tables must have an id attribute)",
+                            formula_trimmed, table_id
+                        ));
+                        continue;
+                    }
+                },
+                Statement::Let { .. } => unreachable!("A let statement has no other targets"),
+            };
+
+            // Keep the text cells the assignment may replace so overwrites can be reported;
+            // error markers left by an earlier failed run are meant to be replaced
+            let target = dependency::Region::from_assignment(assignment);
+            let mut previous_text = text_cells(target_rows, &target);
+            previous_text.retain(|(_, _, cell)| Some(cell.as_str()) != error_cell);
+
+            match apply_assignment(
+                target_rows,
+                assignment.clone(),
+                value.clone(),
+                formula_trimmed,
+            ) {
+                Some(error) => {
+                    if let Some(text) = error_cell {
+                        fill_region(target_rows, &target, text);
                     }
+                    target_errors.push(error)
+                }
+                None => {
+                    target_warnings.extend(overwritten_text_warning(&previous_text, target_rows))
                 }
             }
-            Assignment::ColumnVector { col } => {
-                // Column vector assignment: update entire column
-                if !value.is_column_vector() {
-                    Some(format!(
-                        "Assignment failed for '{}': expected column vector but got {} result",
-                        formula_trimmed,
-                        match value {
-                            Value::Scalar(_) => "scalar",
-                            Value::Matrix {
-                                rows: num_rows,
-                                cols: _,
-                                ..
-                            } => {
-                                if num_rows == 1 {
-                                    "row vector"
-                                } else {
-                                    "matrix"
-                                }
-                            }
-                        }
-                    ))
-                } else if col >= rows.first().map(|r| r.len()).unwrap_or(0) {
-                    Some(format!(
-                        "Assignment failed for '{}': column index out of bounds",
-                        formula_trimmed
-                    ))
-                } else {
-                    apply_column_vector_assignment(rows, col, &value);
-                    None // Success
+        }
+        errors[index] = (!target_errors.is_empty()).then(|| target_errors.join("; "));
+        warnings[index] = (!target_warnings.is_empty()).then(|| target_warnings.join("; "));
+    }
+
+    FormulaResults {
+        errors,
+        warnings,
+        written_tables,
+    }
+}
+
+/// Writes `text` into every target of a formula whose expression failed
+///
+/// Targets in tables that do not exist are skipped; the failure is already reported.
+fn mark_failed_targets(
+    statements: &[Statement],
+    rows: &mut [Vec<String>],
+    table_map: &mut HashMap<String, Vec<Vec<String>>>,
+    written_tables: &mut Vec<String>,
+    text: &str,
+) {
+    for statement in statements {
+        match statement {
+            Statement::Assignment(assignment) => {
+                fill_region(rows, &dependency::Region::from_assignment(assignment), text)
+            }
+            Statement::Fill { col } => fill_region(
+                rows,
+                &dependency::Region::from_assignment(&Assignment::ColumnVector { col: *col }),
+                text,
+            ),
+            Statement::TableAssignment {
+                table_id,
+                assignment,
+            } => {
+                if let Some(target_rows) = table_map.get_mut(table_id) {
+                    fill_region(
+                        target_rows,
+                        &dependency::Region::from_assignment(assignment),
+                        text,
+                    );
+                    if !written_tables.contains(table_id) {
+                        written_tables.push(table_id.clone());
+                    }
                 }
             }
-            Assignment::RowVector { row } => {
-                // Row vector assignment: update entire row
-                let table_row = formula_row_to_table_index(row);
-                match value {
-                    Value::Matrix { rows: 1, cols: _, .. } => {
-                        if table_row >= rows.len() {
-                            Some(format!("Assignment failed for '{}': row index out of bounds", formula_trimmed))
-                        } else {
-                            apply_row_vector_assignment(rows, table_row, &value);
-                            None  // Success
+            Statement::Let { .. } => {}
+        }
+    }
+}
+
+/// Sets every existing data cell inside `region` to `text`
+fn fill_region(rows: &mut [Vec<String>], region: &dependency::Region, text: &str) {
+    for row in FIRST_DATA_ROW_INDEX..rows.len() {
+        for col in 0..rows[row].len() {
+            if region.contains(row, col) {
+                write_cell(rows, row, col, text);
+            }
+        }
+    }
+}
+
+/// Evaluates the expression of a `fill` statement once per data row
+///
+/// Bare column letters such as `B` stand for that column's cell in the row
+/// being computed (see [`bind_column_letters`]); everything else, such as
+/// `B_` or `sum(B_)`, reads the whole table as usual. Each row must give a
+/// single value, and the values make up the column vector to assign.
+fn evaluate_fill(
+    expr: &ast::Expr,
+    rows: &[Vec<String>],
+    table_map: &HashMap<String, Vec<Vec<String>>>,
+    variable_map: &HashMap<String, Value>,
+) -> Result<Value, FormulaError> {
+    let table = rows.to_vec();
+    let mut data = Vec::new();
+    for row_idx in FIRST_DATA_ROW_INDEX..rows.len() {
+        let bound = bind_column_letters(expr, row_idx, variable_map);
+        match evaluator::eval_ast_with_tables(&bound, &table, table_map, variable_map)? {
+            Value::Scalar(cell) => data.push(cell),
+            Value::Matrix {
+                rows: 1,
+                cols: 1,
+                data: cells,
+            } => data.extend(cells),
+            Value::Matrix { rows, cols, .. } => {
+                return Err(FormulaError::RuntimeError(format!(
+                    "fill expected a single value for row {}, got a {}×{} matrix",
+                    row_idx - FIRST_DATA_ROW_INDEX + 1,
+                    rows,
+                    cols
+                )))
+            }
+        }
+    }
+    Ok(Value::column_vector(data))
+}
+
+/// Checks whether an expression uses a previous-row reference such as `C^`
+fn reads_previous_row(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::CellRef(types::CellReference::PreviousRow { .. }, _) => true,
+        ast::Expr::BinaryOp { left, right, .. } => {
+            reads_previous_row(left) || reads_previous_row(right)
+        }
+        ast::Expr::Transpose(inner, _) => reads_previous_row(inner),
+        ast::Expr::FunctionCall { args, .. } => args.iter().any(reads_previous_row),
+        _ => false,
+    }
+}
+
+/// Evaluates a column assignment that reads previous rows (`C_ = C^ + B_`)
+///
+/// The expression is evaluated once per data row against a working copy of the
+/// table in which the rows above already hold their new values, so each row
+/// sees the result computed for the row before it. A result that is not a
+/// column vector is returned as-is for the assignment to report.
+///
+/// Expressions whose rows do not depend on each other apart from the
+/// previous-row reference (see [`bind_row`]) read only the row being computed
+/// and the one above it. If that fails, the row is evaluated against the whole
+/// table again so the error is reported as usual.
+fn evaluate_column_row_by_row(
+    expr: &ast::Expr,
+    rows: &[Vec<String>],
+    col: usize,
+    table_map: &HashMap<String, Vec<Vec<String>>>,
+    variable_map: &HashMap<String, Value>,
+    percent_cells: bool,
+) -> Result<Value, FormulaError> {
+    let mut working = rows.to_vec();
+    let mut data = Vec::new();
+
+    for row_idx in FIRST_DATA_ROW_INDEX..rows.len() {
+        let bound = bind_row(expr, row_idx, variable_map).filter(|_| !percent_cells);
+        if let Some(Ok(Value::Scalar(cell))) = bound
+            .map(|bound| evaluator::eval_ast_with_tables(&bound, &working, table_map, variable_map))
+        {
+            if col < working[row_idx].len() {
+                working[row_idx][col] = cell.to_string();
+            }
+            data.push(cell);
+            continue;
+        }
+
+        let percent_rows;
+        let eval_rows = if percent_cells {
+            percent_rows = percent_cells_as_fractions(&working);
+            &percent_rows
+        } else {
+            &working
+        };
+
+        let value = evaluator::eval_ast_with_tables(expr, eval_rows, table_map, variable_map)?;
+        let cell = match &value {
+            Value::Matrix { cols: 1, data, .. } => {
+                data.get(row_idx - FIRST_DATA_ROW_INDEX).copied()
+            }
+            _ => None,
+        };
+        let Some(cell) = cell else {
+            return Ok(value);
+        };
+
+        if col < working[row_idx].len() {
+            working[row_idx][col] = cell.to_string();
+        }
+        data.push(cell);
+    }
+
+    Ok(Value::column_vector(data))
+}
+
+/// Rewrites a column expression to compute only the element for table row `row_idx`
+///
+/// Column references become that row's cell and previous-row references the
+/// cell above it (0 for the first data row). Returns None unless each element
+/// of the expression depends only on its own row, that is unless it combines
+/// columns, single cells and scalars with element-wise operators. Functions,
+/// ranges, row references, transposes, matrix products and matrix variables
+/// all need the whole column.
+fn bind_row(
+    expr: &ast::Expr,
+    row_idx: usize,
+    variable_map: &HashMap<String, Value>,
+) -> Option<ast::Expr> {
+    use ast::{BinaryOperator, Expr};
+    use types::CellReference;
+
+    match expr {
+        Expr::Literal(..) | Expr::CellRef(CellReference::Scalar { .. }, _) => Some(expr.clone()),
+        Expr::CellRef(CellReference::ColumnVector { col }, span) => Some(Expr::CellRef(
+            CellReference::Scalar {
+                row: row_idx,
+                col: *col,
+            },
+            *span,
+        )),
+        Expr::CellRef(CellReference::PreviousRow { col }, span) => {
+            Some(if row_idx == FIRST_DATA_ROW_INDEX {
+                Expr::Literal(Decimal::ZERO, *span)
+            } else {
+                Expr::CellRef(
+                    CellReference::Scalar {
+                        row: row_idx - 1,
+                        col: *col,
+                    },
+                    *span,
+                )
+            })
+        }
+        Expr::Variable(name, _) => match variable_map.get(name) {
+            Some(Value::Matrix { .. }) => None,
+            _ => Some(expr.clone()),
+        },
+        Expr::BinaryOp { op, .. } if *op == BinaryOperator::MatMul => None,
+        Expr::BinaryOp {
+            left,
+            op,
+            right,
+            span,
+        } => Some(Expr::BinaryOp {
+            left: Box::new(bind_row(left, row_idx, variable_map)?),
+            op: *op,
+            right: Box::new(bind_row(right, row_idx, variable_map)?),
+            span: *span,
+        }),
+        _ => None,
+    }
+}
+
+/// Removes the data rows for which `filter` does not hold
+///
+/// Both sides of the filter are evaluated once per data row against the whole
+/// table. A bare column letter such as `C` stands for that column's cell in the
+/// row being tested, so `C>0` checks `C1` for the first row, `C2` for the second
+/// and so on. Empty and non-numeric cells read as 0, as they do in formulas.
+///
+/// Each side must evaluate to a scalar. On any error no row is removed and the
+/// error is returned.
+pub(crate) fn filter_rows(
+    rows: &mut Vec<Vec<String>>,
+    filter: &RowFilter,
+    constants: &HashMap<String, Value>,
+    percent_cells: bool,
+) -> Result<(), String> {
+    let parse_side = |side: &str| Parser::new(tokenize_expression(side)).parse();
+    let left =
+        parse_side(&filter.left).map_err(|e| format!("Invalid filter '{}': {}", filter.left, e))?;
+    let right = parse_side(&filter.right)
+        .map_err(|e| format!("Invalid filter '{}': {}", filter.right, e))?;
+
+    let percent_rows;
+    let eval_rows = if percent_cells {
+        percent_rows = percent_cells_as_fractions(rows);
+        &percent_rows
+    } else {
+        &*rows
+    };
+
+    let table_map = HashMap::new();
+    let evaluate_side = |ast: &ast::Expr, side: &str, row_idx: usize| -> Result<Decimal, String> {
+        let row = row_idx - FIRST_DATA_ROW_INDEX + 1;
+        let value = evaluator::eval_ast_with_tables(
+            &bind_column_letters(ast, row_idx, constants),
+            eval_rows,
+            &table_map,
+            constants,
+        )
+        .map_err(|e| format!("Filter '{}' failed on row {}: {}", side, row, e))?;
+        match value {
+            Value::Scalar(number) => Ok(number),
+            Value::Matrix { rows, cols, .. } => Err(format!(
+                "Filter '{}' failed on row {}: expected a single value, got a {}x{} matrix",
+                side, row, rows, cols
+            )),
+        }
+    };
+
+    let mut keep = Vec::with_capacity(rows.len());
+    for row_idx in FIRST_DATA_ROW_INDEX..rows.len() {
+        let left_value = evaluate_side(&left, &filter.left, row_idx)?;
+        let right_value = evaluate_side(&right, &filter.right, row_idx)?;
+        keep.push(filter.comparison.holds(left_value.cmp(&right_value)));
+    }
+
+    let mut keep = keep.into_iter();
+    let mut row_idx = 0;
+    rows.retain(|_| {
+        row_idx += 1;
+        row_idx <= FIRST_DATA_ROW_INDEX || keep.next().unwrap_or(true)
+    });
+    Ok(())
+}
+
+/// Builds the totals row requested by a `totals=` attribute
+///
+/// Each aggregate applies its function to the whole column (`sum(B_)`) and must
+/// give a single value. The first cell holds `label`; columns without an
+/// aggregate are left empty.
+pub(crate) fn totals_row(
+    rows: &[Vec<String>],
+    totals: &[TotalSpec],
+    label: &str,
+    constants: &HashMap<String, Value>,
+    percent_cells: bool,
+) -> Result<Vec<String>, String> {
+    let eval_rows = if percent_cells {
+        percent_cells_as_fractions(rows)
+    } else {
+        rows.to_vec()
+    };
+    let width = rows.first().map_or(0, Vec::len);
+
+    let mut row = vec![String::new(); width];
+    if let Some(first) = row.first_mut() {
+        *first = label.to_string();
+    }
+    for total in totals {
+        let expr = format!(
+            "{}({}_)",
+            total.function,
+            types::col_index_to_letter(total.column)
+        );
+        if total.column >= width {
+            return Err(format!(
+                "Totals '{}' failed: column {} is outside the table",
+                expr,
+                types::col_index_to_letter(total.column)
+            ));
+        }
+        let value =
+            evaluate_expression_value_with_tables(&expr, &eval_rows, &HashMap::new(), constants)
+                .map_err(|e| format!("Totals '{}' failed: {}", expr, e))?;
+        match value {
+            Value::Scalar(number) => row[total.column] = number.to_string(),
+            Value::Matrix { rows, cols, .. } => {
+                return Err(format!(
+                    "Totals '{}' failed: expected a single value, got a {}x{} matrix",
+                    expr, rows, cols
+                ))
+            }
+        }
+    }
+
+    Ok(row)
+}
+
+/// Rewrites bare column letters (`C`) into references to that column's cell in `row_idx`
+///
+/// Letters that name a constant or variable keep referring to it.
+fn bind_column_letters(
+    expr: &ast::Expr,
+    row_idx: usize,
+    constants: &HashMap<String, Value>,
+) -> ast::Expr {
+    use ast::Expr;
+    match expr {
+        Expr::Variable(name, span) if !constants.contains_key(name) => match name.as_bytes() {
+            [letter @ b'A'..=b'Z'] => Expr::CellRef(
+                types::CellReference::Scalar {
+                    row: row_idx,
+                    col: (letter - b'A') as usize,
+                },
+                *span,
+            ),
+            _ => expr.clone(),
+        },
+        Expr::BinaryOp {
+            left,
+            op,
+            right,
+            span,
+        } => Expr::BinaryOp {
+            left: Box::new(bind_column_letters(left, row_idx, constants)),
+            op: *op,
+            right: Box::new(bind_column_letters(right, row_idx, constants)),
+            span: *span,
+        },
+        Expr::Transpose(inner, span) => Expr::Transpose(
+            Box::new(bind_column_letters(inner, row_idx, constants)),
+            *span,
+        ),
+        Expr::FunctionCall { name, args, span } => Expr::FunctionCall {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| bind_column_letters(arg, row_idx, constants))
+                .collect(),
+            span: *span,
+        },
+        _ => expr.clone(),
+    }
+}
+
+/// Returns a copy of `rows` with data cells such as `8%` rewritten as fractions (`0.08`)
+fn percent_cells_as_fractions(rows: &[Vec<String>]) -> Vec<Vec<String>> {
+    rows.iter()
+        .enumerate()
+        .map(|(row_idx, row)| {
+            row.iter()
+                .map(
+                    |cell| match cell.strip_suffix('%').and_then(parse_cell_number) {
+                        Some(percent) if row_idx >= FIRST_DATA_ROW_INDEX => {
+                            (percent / Decimal::ONE_HUNDRED).normalize().to_string()
                         }
+                        _ => cell.clone(),
+                    },
+                )
+                .collect()
+        })
+        .collect()
+}
+
+/// Applies an evaluated value to an assignment target in `rows`
+///
+/// Returns None on success, or Some(error) if the value's shape does not fit the
+/// target or the target lies outside the table.
+fn apply_assignment(
+    rows: &mut [Vec<String>],
+    assignment: Assignment,
+    value: Value,
+    formula_trimmed: &str,
+) -> Option<String> {
+    match assignment {
+        Assignment::Scalar { row, col } => {
+            // Scalar assignment: single cell update
+            match value.as_scalar() {
+                Some(decimal) => {
+                    if row >= rows.len() || col >= rows[row].len() {
+                        Some(format!("Assignment failed for '{}': cell index out of bounds", formula_trimmed))
+                    } else {
+                        write_cell(rows, row, col, decimal);
+                        None  // Success
                     }
-                    Value::Scalar(_) => {
-                        Some(format!("Assignment failed for '{}': cannot assign scalar to row vector (expected row vector)", formula_trimmed))
-                    }
-                    Value::Matrix { rows: num_rows, .. } => {
-                        Some(format!("Assignment failed for '{}': expected row vector but got matrix with {} rows", formula_trimmed, num_rows))
-                    }
+                }
+                None => {
+                    Some(format!("Assignment failed for '{}': cannot assign matrix to scalar cell (use a cell vector assignment like C_ instead)", formula_trimmed))
                 }
             }
-            Assignment::Range {
-                start_row,
-                start_col,
-                end_row,
-                end_col,
-            } => {
-                // Range assignment: update rectangular region
-                match value {
-                    Value::Matrix { rows: num_rows, cols: num_cols, .. } => {
-                        let expected_rows = end_row - start_row + 1;
-                        let expected_cols = end_col - start_col + 1;
-
-                        if num_rows != expected_rows || num_cols != expected_cols {
-                            Some(format!("Assignment failed for '{}': dimension mismatch (expected {}×{} but got {}×{})",
-                                formula_trimmed, expected_rows, expected_cols, num_rows, num_cols))
-                        } else if end_row >= rows.len() {
-                            Some(format!("Assignment failed for '{}': range extends beyond table bounds", formula_trimmed))
-                        } else {
-                            apply_range_assignment(rows, start_row, start_col, end_row, end_col, &value);
-                            None  // Success
+        }
+        Assignment::ColumnVector { col } => {
+            // Column vector assignment: update entire column
+            if !value.is_column_vector() {
+                Some(format!(
+                    "Assignment failed for '{}': expected column vector but got {} result",
+                    formula_trimmed,
+                    match value {
+                        Value::Scalar(_) => "scalar",
+                        Value::Matrix {
+                            rows: num_rows,
+                            cols: _,
+                            ..
+                        } => {
+                            if num_rows == 1 {
+                                "row vector"
+                            } else {
+                                "matrix"
+                            }
                         }
                     }
-                    Value::Scalar(_) => {
-                        Some(format!("Assignment failed for '{}': cannot assign scalar to range (expected matrix)", formula_trimmed))
+                ))
+            } else if col >= rows.first().map(|r| r.len()).unwrap_or(0) {
+                Some(format!(
+                    "Assignment failed for '{}': column index out of bounds",
+                    formula_trimmed
+                ))
+            } else {
+                apply_column_vector_assignment(rows, col, &value);
+                None // Success
+            }
+        }
+        Assignment::RowVector { row } => {
+            // Row vector assignment: update entire row
+            let table_row = formula_row_to_table_index(row);
+            match value {
+                Value::Matrix { rows: 1, cols: _, .. } => {
+                    if table_row >= rows.len() {
+                        Some(format!("Assignment failed for '{}': row index out of bounds", formula_trimmed))
+                    } else {
+                        apply_row_vector_assignment(rows, table_row, &value);
+                        None  // Success
                     }
                 }
+                Value::Scalar(_) => {
+                    Some(format!("Assignment failed for '{}': cannot assign scalar to row vector (expected row vector)", formula_trimmed))
+                }
+                Value::Matrix { rows: num_rows, .. } => {
+                    Some(format!("Assignment failed for '{}': expected row vector but got matrix with {} rows", formula_trimmed, num_rows))
+                }
             }
-            Assignment::ColumnRange { start_col, end_col } => {
-                // Column range assignment: update multiple columns
-                match value {
-                    Value::Matrix { rows: _, cols: num_cols, .. } => {
-                        let expected_cols = end_col - start_col + 1;
-
-                        if num_cols != expected_cols {
-                            Some(format!("Assignment failed for '{}': column dimension mismatch (expected {} columns but got {})",
-                                formula_trimmed, expected_cols, num_cols))
-                        } else if end_col >= rows.first().map(|r| r.len()).unwrap_or(0) {
-                            Some(format!("Assignment failed for '{}': column range extends beyond table bounds", formula_trimmed))
-                        } else {
-                            apply_column_range_assignment(rows, start_col, end_col, &value);
-                            None  // Success
-                        }
+        }
+        Assignment::Range {
+            start_row,
+            start_col,
+            end_row,
+            end_col,
+        } => {
+            // Range assignment: update rectangular region
+            match value {
+                Value::Matrix {
+                    rows: num_rows,
+                    cols: num_cols,
+                    ..
+                } => {
+                    let expected_rows = end_row - start_row + 1;
+                    let expected_cols = end_col - start_col + 1;
+
+                    if num_rows != expected_rows || num_cols != expected_cols {
+                        Some(format!("Assignment failed for '{}': dimension mismatch (expected {}×{} but got {}×{})",
+                            formula_trimmed, expected_rows, expected_cols, num_rows, num_cols))
+                    } else if end_row >= rows.len() {
+                        Some(format!(
+                            "Assignment failed for '{}': range extends beyond table bounds",
+                            formula_trimmed
+                        ))
+                    } else {
+                        apply_range_assignment(
+                            rows, start_row, start_col, end_row, end_col, &value,
+                        );
+                        None // Success
                     }
-                    Value::Scalar(_) => {
-                        Some(format!("Assignment failed for '{}': cannot assign scalar to column range (expected matrix)", formula_trimmed))
+                }
+                Value::Scalar(_) => Some(format!(
+                    "Assignment failed for '{}': cannot assign scalar to range (expected matrix)",
+                    formula_trimmed
+                )),
+            }
+        }
+        Assignment::ColumnRange { start_col, end_col } => {
+            // Column range assignment: update multiple columns
+            match value {
+                Value::Matrix { rows: _, cols: num_cols, .. } => {
+                    let expected_cols = end_col - start_col + 1;
+
+                    if num_cols != expected_cols {
+                        Some(format!("Assignment failed for '{}': column dimension mismatch (expected {} columns but got {})",
+                            formula_trimmed, expected_cols, num_cols))
+                    } else if end_col >= rows.first().map(|r| r.len()).unwrap_or(0) {
+                        Some(format!("Assignment failed for '{}': column range extends beyond table bounds", formula_trimmed))
+                    } else {
+                        apply_column_range_assignment(rows, start_col, end_col, &value);
+                        None  // Success
                     }
                 }
+                Value::Scalar(_) => {
+                    Some(format!("Assignment failed for '{}': cannot assign scalar to column range (expected matrix)", formula_trimmed))
+                }
             }
-            Assignment::RowRange { start_row, end_row } => {
-                // Row range assignment: update multiple rows
-                match value {
-                    Value::Matrix { rows: num_rows, cols: _, .. } => {
-                        let expected_rows = end_row - start_row + 1;
-
-                        if num_rows != expected_rows {
-                            Some(format!("Assignment failed for '{}': row dimension mismatch (expected {} rows but got {})",
-                                formula_trimmed, expected_rows, num_rows))
+        }
+        Assignment::RowRange { start_row, end_row } => {
+            // Row range assignment: update multiple rows
+            match value {
+                Value::Matrix { rows: num_rows, cols: _, .. } => {
+                    let expected_rows = end_row - start_row + 1;
+
+                    if num_rows != expected_rows {
+                        Some(format!("Assignment failed for '{}': row dimension mismatch (expected {} rows but got {})",
+                            formula_trimmed, expected_rows, num_rows))
+                    } else {
+                        let table_end_row = formula_row_to_table_index(end_row);
+                        if table_end_row >= rows.len() {
+                            Some(format!("Assignment failed for '{}': row range extends beyond table bounds", formula_trimmed))
                         } else {
-                            let table_end_row = formula_row_to_table_index(end_row);
-                            if table_end_row >= rows.len() {
-                                Some(format!("Assignment failed for '{}': row range extends beyond table bounds", formula_trimmed))
-                            } else {
-                                apply_row_range_assignment(rows, start_row, end_row, &value);
-                                None  // Success
-                            }
+                            apply_row_range_assignment(rows, start_row, end_row, &value);
+                            None  // Success
                         }
                     }
-                    Value::Scalar(_) => {
-                        Some(format!("Assignment failed for '{}': cannot assign scalar to row range (expected matrix)", formula_trimmed))
-                    }
+                }
+                Value::Scalar(_) => {
+                    Some(format!("Assignment failed for '{}': cannot assign scalar to row range (expected matrix)", formula_trimmed))
                 }
             }
-        };
+        }
+    }
+}
 
-        errors.push(error);
+/// Per-formula outcome of applying a table's formulas
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormulaResults {
+    /// `errors[i]` is Some(message) if formula `i` failed
+    pub errors: Vec<Option<String>>,
+    /// `warnings[i]` is Some(message) if formula `i` succeeded but replaced non-numeric text
+    pub warnings: Vec<Option<String>>,
+    /// IDs of other tables modified by `to("id", ...)` assignments, in first-write order
+    pub written_tables: Vec<String>,
+}
+
+/// The non-empty, non-numeric data cells inside `region`, as `(row, col, text)`
+fn text_cells(rows: &[Vec<String>], region: &dependency::Region) -> Vec<(usize, usize, String)> {
+    let mut cells = Vec::new();
+    for (row_idx, row) in rows.iter().enumerate().skip(FIRST_DATA_ROW_INDEX) {
+        for (col_idx, cell) in row.iter().enumerate() {
+            if region.contains(row_idx, col_idx)
+                && !cell.is_empty()
+                && parse_cell_number(cell).is_none()
+            {
+                cells.push((row_idx, col_idx, cell.clone()));
+            }
+        }
+    }
+    cells
+}
+
+/// Reports cells whose non-empty, non-numeric content was replaced by an assignment
+///
+/// `before` holds the text cells of the target (see [`text_cells`]) as they were
+/// before the assignment. Such cells usually hold labels (e.g. "TOTAL") that a
+/// mistyped target clobbered.
+fn overwritten_text_warning(
+    before: &[(usize, usize, String)],
+    after: &[Vec<String>],
+) -> Option<String> {
+    use types::col_index_to_letter;
+
+    let mut overwritten = Vec::new();
+    for (row_idx, col_idx, old) in before {
+        if after.get(*row_idx).and_then(|row| row.get(*col_idx)) != Some(old) {
+            overwritten.push(format!(
+                "{}{} (\"{}\")",
+                col_index_to_letter(*col_idx),
+                row_idx - FIRST_DATA_ROW_INDEX + 1,
+                old
+            ));
+        }
+    }
+
+    if overwritten.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "overwrote non-numeric {} {}",
+            if overwritten.len() == 1 {
+                "cell"
+            } else {
+                "cells"
+            },
+            overwritten.join(", ")
+        ))
     }
+}
+
+/// Evaluates a document constant definition of the form `NAME = EXPRESSION`
+///
+/// The expression may use numbers, operators, functions, and previously defined
+/// constants, but not cell references since constants do not belong to a table.
+///
+/// # Returns
+///
+/// * `Ok((name, value))` if the definition is valid
+/// * `Err(String)` with an error message if it cannot be parsed or evaluated
+pub fn evaluate_constant(
+    definition: &str,
+    constants: &HashMap<String, Value>,
+) -> Result<(String, Value), String> {
+    let definition = definition.trim();
+
+    // Constants share the naming rules of let statements
+    let (name, expr) = match parse_statement(&format!("let {}", definition)) {
+        Some((Statement::Let { name, .. }, expr)) => (name, expr),
+        _ => {
+            return Err(format!(
+            "Failed to parse constant '{}': invalid syntax (expected format: 'NAME = EXPRESSION')",
+            definition
+        ))
+        }
+    };
 
-    errors
+    let value =
+        evaluate_expression_value_with_tables(&expr, &Vec::new(), &HashMap::new(), constants)
+            .map_err(|error| format!("Failed to evaluate constant '{}': {}", name, error))?;
+
+    Ok((name, value))
 }
 
 /// Parses an assignment target (left side of formula)
@@ -519,6 +1210,21 @@ fn parse_assignment(target: &str) -> Option<Assignment> {
     }
 }
 
+/// Parses a cross-table assignment target like `to("summary", B1)`
+/// into the target table ID and the assignment within that table
+fn parse_table_target(target: &str) -> Option<(String, Assignment)> {
+    let inner = target.strip_prefix("to(")?.strip_suffix(')')?;
+    let (table_id, cell_target) = inner.split_once(',')?;
+
+    let table_id = table_id.trim();
+    let table_id = table_id.strip_prefix('"')?.strip_suffix('"')?;
+    if table_id.is_empty() {
+        return None;
+    }
+
+    Some((table_id.to_string(), parse_assignment(cell_target)?))
+}
+
 /// Parses a formula like "A1 = B1 + C1" into (assignment, expression) - test helper
 #[cfg(test)]
 fn parse_formula(formula: &str) -> Option<(Assignment, String)> {
@@ -539,8 +1245,67 @@ fn is_cell_reference_like(name: &str) -> bool {
     parse_cell_reference(name).is_some()
 }
 
-/// Parse a statement (either `let variable = expression` or `target = expression`)
+/// A parsed formula, shared by dependency ordering and evaluation
+pub(crate) struct ParsedFormula {
+    /// One statement per target (`C_, D_ = ...`); a `let` is always alone
+    pub(crate) statements: Vec<Statement>,
+    /// The expression as written, for error messages
+    pub(crate) expr: String,
+    /// The parsed expression, or the error that parsing it gave
+    pub(crate) ast: Result<ast::Expr, FormulaError>,
+}
+
+impl ParsedFormula {
+    /// Parses a formula; None if it is not a `let` or assignment statement
+    pub(crate) fn parse(formula: &str) -> Option<Self> {
+        let (statements, expr) = parse_statements(formula)?;
+        let ast = Parser::new(tokenize_expression(&expr)).parse();
+        Some(ParsedFormula {
+            statements,
+            expr,
+            ast,
+        })
+    }
+}
+
+/// Parsed formulas by their text, kept for one formatting run
+///
+/// Generated documents often repeat the same table and directive many times;
+/// parsing depends only on the formula text, so each distinct formula is
+/// parsed once and every table using it shares the result.
+#[derive(Default)]
+pub(crate) struct FormulaCache {
+    parsed: HashMap<String, Option<Rc<ParsedFormula>>>,
+}
+
+impl FormulaCache {
+    /// Returns the parsed formula, parsing it on first use
+    pub(crate) fn parse(&mut self, formula: &str) -> Option<Rc<ParsedFormula>> {
+        let formula = formula.trim();
+        if let Some(parsed) = self.parsed.get(formula) {
+            return parsed.clone();
+        }
+        let parsed = ParsedFormula::parse(formula).map(Rc::new);
+        self.parsed.insert(formula.to_string(), parsed.clone());
+        parsed
+    }
+}
+
+/// Parse a statement (`let variable = expression`, `target = expression`,
+/// or `to("table_id", target) = expression`)
 fn parse_statement(formula: &str) -> Option<(Statement, String)> {
+    let (mut statements, expr) = parse_statements(formula)?;
+    if statements.len() != 1 {
+        return None;
+    }
+    Some((statements.pop()?, expr))
+}
+
+/// Parse a statement that may assign one expression to several targets,
+/// either listed (`C_, D_ = expression`) or chained (`C_ = D_ = expression`)
+///
+/// Returns one statement per target, in written order.
+fn parse_statements(formula: &str) -> Option<(Vec<Statement>, String)> {
     let formula = formula.trim();
 
     // Check if this is a let statement
@@ -567,87 +1332,70 @@ fn parse_statement(formula: &str) -> Option<(Statement, String)> {
 
         // Create a Let statement
         let span = Span::new(0, formula.len()); // Full formula span
-        Some((Statement::let_statement(var_name, span), expr))
+        Some((vec![Statement::let_statement(var_name, span)], expr))
+    } else if let Some(rest) = formula.strip_prefix("fill ") {
+        // Parse: fill COLUMN = expression, where COLUMN is a bare letter (`D`)
+        let mut parts = split_outside_strings(rest, '=');
+        let expr = parts.pop()?.trim();
+        let [target] = parts.as_slice() else {
+            return None;
+        };
+        let col = match target.trim().as_bytes() {
+            [letter @ b'A'..=b'Z'] => (letter - b'A') as usize,
+            _ => return None,
+        };
+        Some((vec![Statement::Fill { col }], expr.to_string()))
     } else {
-        // Parse as a regular assignment: target = expression
-        let parts: Vec<&str> = formula.split('=').collect();
-        if parts.len() == 2 {
-            let assignment = parse_assignment(parts[0])?;
-            Some((
-                Statement::assignment(assignment),
-                parts[1].trim().to_string(),
-            ))
-        } else {
-            None
+        // Parse as a regular assignment: target = expression, where each
+        // `=`-separated part before the expression lists one or more targets
+        let mut parts = split_outside_strings(formula, '=');
+        let expr = parts.pop()?.trim();
+        if parts.is_empty() {
+            return None;
+        }
+        let statements = parts
+            .into_iter()
+            .flat_map(|targets| split_outside_strings(targets, ','))
+            .map(|target| {
+                let target = target.trim();
+                if target.starts_with("to(") {
+                    let (table_id, assignment) = parse_table_target(target)?;
+                    Some(Statement::table_assignment(table_id, assignment))
+                } else {
+                    Some(Statement::assignment(parse_assignment(target)?))
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some((statements, expr.to_string()))
+    }
+}
+
+/// Splits `text` on `separator` where it is outside string literals and parentheses
+fn split_outside_strings(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut depth = 0usize;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth = depth.saturating_sub(1),
+            c if c == separator && !in_string && depth == 0 => {
+                parts.push(&text[start..idx]);
+                start = idx + c.len_utf8();
+            }
+            _ => {}
         }
     }
+    parts.push(&text[start..]);
+    parts
 }
 
-/// Attempts to extract span information from an error by analyzing the error type.
-/// This allows us to provide visual position indicators even when the error doesn't
-/// carry span information through the entire call chain.
+/// Evaluates a mathematical expression string and returns its computed value.
 ///
-/// # Strategy
-///
-/// For errors that reference specific tokens or positions, we can infer the span.
-/// For other errors, we return None and fall back to simple error messages.
-fn extract_error_span(expr: &str, error: &FormulaError) -> Option<Span> {
-    match error {
-        FormulaError::UnexpectedToken { position, .. } => {
-            // Use the position from the error
-            Some(Span::single(*position))
-        }
-        FormulaError::InvalidToken { token, .. } => {
-            // Try to find the token in the expression
-            expr.find(token.as_str())
-                .map(|pos| Span::new(pos, pos + token.len()))
-        }
-        FormulaError::CellOutOfBounds { cell, .. }
-        | FormulaError::ColumnOutOfBounds { column: cell, .. } => {
-            // Try to find the cell reference in the expression
-            expr.find(cell.as_str())
-                .map(|pos| Span::new(pos, pos + cell.len()))
-        }
-        FormulaError::RowOutOfBounds { row, .. } => {
-            // Try to find the row reference pattern (e.g., "_1", "_2")
-            let row_ref = format!("_{}", row);
-            expr.find(&row_ref)
-                .map(|pos| Span::new(pos, pos + row_ref.len()))
-        }
-        FormulaError::UnknownFunction { name, .. } => {
-            // Try to find the function name in the expression
-            expr.find(name.as_str())
-                .map(|pos| Span::new(pos, pos + name.len()))
-        }
-        FormulaError::RuntimeError(msg) => {
-            // For runtime errors, try to extract span from common patterns
-
-            // Pattern 1: "unknown function: 'name'"
-            if let Some(start_idx) = msg.find("unknown function: '") {
-                let name_start = start_idx + "unknown function: '".len();
-                if let Some(end_idx) = msg[name_start..].find('\'') {
-                    let func_name = &msg[name_start..name_start + end_idx];
-                    if let Some(pos) = expr.find(func_name) {
-                        return Some(Span::new(pos, pos + func_name.len()));
-                    }
-                }
-            }
-
-            // Pattern 2: "cannot transpose a scalar" or other common messages
-            // For now, we don't extract spans for these
-
-            None
-        }
-        // For other error types, we could add more sophisticated span extraction
-        // but for now we'll return None and use simple error messages
-        _ => None,
-    }
-}
-
-/// Evaluates a mathematical expression string and returns its computed value.
-///
-/// Supports cell references, numbers, operators, functions, and parentheses.
-/// Handles both scalar and vector expressions with automatic type inference.
+/// Supports cell references, numbers, operators, functions, and parentheses.
+/// Handles both scalar and vector expressions with automatic type inference.
 ///
 /// # Expression Components
 ///
@@ -702,7 +1450,7 @@ fn evaluate_expression_value(expr: &str, rows: &Vec<Vec<String>>) -> Result<Valu
 #[allow(dead_code)]
 pub fn apply_formulas(rows: &mut Vec<Vec<String>>, formulas: &[String]) -> Vec<Option<String>> {
     use std::collections::HashMap;
-    apply_formulas_with_tables(rows, formulas, &HashMap::new())
+    apply_formulas_with_tables(rows, formulas, &mut HashMap::new(), &HashMap::new()).errors
 }
 
 #[cfg(test)]
@@ -751,6 +1499,21 @@ mod tests {
         ); // lowercase
     }
 
+    #[test]
+    fn test_parse_previous_row() {
+        use reference::parse_cell_reference;
+        assert_eq!(
+            parse_cell_reference("C^"),
+            Some(CellReference::PreviousRow { col: 2 })
+        );
+        assert_eq!(
+            parse_cell_reference("a^"),
+            Some(CellReference::PreviousRow { col: 0 })
+        ); // lowercase
+        assert_eq!(parse_cell_reference("AB^"), None);
+        assert_eq!(parse_cell_reference("A1^"), None);
+    }
+
     #[test]
     fn test_parse_row_vector() {
         use reference::parse_cell_reference;
@@ -854,6 +1617,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_resolve_scientific_notation() {
+        use reference::resolve_reference;
+        let rows = vec![
+            vec!["Col".to_string()],
+            vec!["---".to_string()],
+            vec!["1.2e5".to_string()],
+            vec!["2.5E-3".to_string()],
+            vec!["(4e2)".to_string()],
+            vec!["inf".to_string()], // f64 text with no Decimal value
+            vec!["1e".to_string()],  // not a number
+        ];
+
+        let col_ref = CellReference::ColumnVector { col: 0 };
+        let result = resolve_reference(&col_ref, &rows);
+
+        assert_eq!(
+            result,
+            Ok(Value::column_vector(vec![
+                Decimal::from(120000),
+                Decimal::new(25, 4),
+                Decimal::from(-400),
+                Decimal::ZERO,
+                Decimal::ZERO,
+            ]))
+        );
+
+        let scalar = resolve_reference(&CellReference::Scalar { row: 2, col: 0 }, &rows);
+        assert_eq!(scalar.unwrap().as_scalar().unwrap().to_string(), "120000");
+    }
+
     #[test]
     fn test_vector_addition() {
         use evaluator::evaluate_operation;
@@ -952,6 +1746,309 @@ mod tests {
         assert_eq!(result, Ok(Value::Scalar(Decimal::ZERO)));
     }
 
+    #[test]
+    fn test_cumsum_vector() {
+        use evaluator::eval_function;
+        let vec = Value::row_vector(vec![Decimal::from(1), Decimal::from(2), Decimal::from(3)]);
+        let result = eval_function("cumsum", vec);
+
+        assert_eq!(
+            result,
+            Ok(Value::row_vector(vec![
+                Decimal::from(1),
+                Decimal::from(3),
+                Decimal::from(6)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_cumsum_scalar() {
+        use evaluator::eval_function;
+        let scalar = Value::Scalar(Decimal::from(42));
+        let result = eval_function("cumsum", scalar);
+
+        assert_eq!(result, Ok(Value::Scalar(Decimal::from(42))));
+    }
+
+    #[test]
+    fn test_diff_vector() {
+        use evaluator::eval_function;
+        let vec = Value::column_vector(vec![Decimal::from(1), Decimal::from(3), Decimal::from(6)]);
+        let result = eval_function("diff", vec);
+
+        // The first element has no predecessor, so the shape is kept
+        assert_eq!(
+            result,
+            Ok(Value::column_vector(vec![
+                Decimal::ZERO,
+                Decimal::from(2),
+                Decimal::from(3)
+            ]))
+        );
+
+        // A cumulative column becomes per-row changes
+        let mut rows = vec![
+            vec!["Total".to_string(), "Delta".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["10".to_string(), "".to_string()],
+            vec!["25".to_string(), "".to_string()],
+            vec!["27.5".to_string(), "".to_string()],
+        ];
+        let errors = apply_formulas(&mut rows, &["B_ = diff(A_)".to_string()]);
+        assert_eq!(errors, vec![None]);
+        assert_eq!(rows[2][1], "0");
+        assert_eq!(rows[3][1], "15");
+        assert_eq!(rows[4][1], "2.5");
+    }
+
+    #[test]
+    fn test_diff_scalar() {
+        use evaluator::eval_function;
+        let result = eval_function("diff", Value::Scalar(Decimal::from(42)));
+
+        assert_eq!(result, Ok(Value::Scalar(Decimal::ZERO)));
+    }
+
+    #[test]
+    fn test_cumprod_vector() {
+        use evaluator::eval_function;
+        let vec = Value::row_vector(vec![Decimal::from(2), Decimal::from(3), Decimal::from(4)]);
+        let result = eval_function("cumprod", vec);
+
+        assert_eq!(
+            result,
+            Ok(Value::row_vector(vec![
+                Decimal::from(2),
+                Decimal::from(6),
+                Decimal::from(24)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_cumprod_overflow() {
+        use evaluator::eval_function;
+        let large: Decimal = "100000000000000000000".parse().unwrap();
+        let result = eval_function("cumprod", Value::column_vector(vec![large, large]));
+
+        assert_eq!(
+            result,
+            Err(FormulaError::RuntimeError(
+                "overflow in cumprod()".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_cumprod_scalar() {
+        use evaluator::eval_function;
+        let scalar = Value::Scalar(Decimal::from(42));
+        let result = eval_function("cumprod", scalar);
+
+        assert_eq!(result, Ok(Value::Scalar(Decimal::from(42))));
+    }
+
+    #[test]
+    fn test_sort_vector() {
+        use evaluator::eval_function;
+        let vec = Value::column_vector(vec![Decimal::from(3), Decimal::from(1), Decimal::from(2)]);
+        let result = eval_function("sort", vec);
+
+        assert_eq!(
+            result,
+            Ok(Value::column_vector(vec![
+                Decimal::from(1),
+                Decimal::from(2),
+                Decimal::from(3)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_reverse_vector() {
+        use evaluator::eval_function;
+        let vec = Value::column_vector(vec![Decimal::from(1), Decimal::from(2), Decimal::from(3)]);
+        let result = eval_function("reverse", vec);
+
+        assert_eq!(
+            result,
+            Ok(Value::column_vector(vec![
+                Decimal::from(3),
+                Decimal::from(2),
+                Decimal::from(1)
+            ]))
+        );
+
+        // Scalars pass through unchanged
+        let scalar = Value::Scalar(Decimal::from(42));
+        assert_eq!(eval_function("reverse", scalar.clone()), Ok(scalar));
+    }
+
+    #[test]
+    fn test_reverse_matrix() {
+        use evaluator::eval_function;
+        // [[1, 2, 3], [4, 5, 6]] becomes [[6, 5, 4], [3, 2, 1]]
+        let matrix = Value::Matrix {
+            rows: 2,
+            cols: 3,
+            data: (1..=6).map(Decimal::from).collect(),
+        };
+        let result = eval_function("reverse", matrix);
+
+        assert_eq!(
+            result,
+            Ok(Value::Matrix {
+                rows: 2,
+                cols: 3,
+                data: (1..=6).rev().map(Decimal::from).collect(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_argmax_argmin() {
+        use evaluator::eval_function;
+        let vec = || {
+            Value::column_vector(vec![
+                Decimal::from(10),
+                Decimal::from(30),
+                Decimal::from(20),
+            ])
+        };
+
+        assert_eq!(
+            eval_function("argmax", vec()),
+            Ok(Value::Scalar(Decimal::from(2)))
+        );
+        assert_eq!(
+            eval_function("argmin", vec()),
+            Ok(Value::Scalar(Decimal::from(1)))
+        );
+
+        // Ties resolve to the first occurrence
+        let ties = Value::row_vector(vec![Decimal::from(5), Decimal::from(9), Decimal::from(9)]);
+        assert_eq!(
+            eval_function("argmax", ties),
+            Ok(Value::Scalar(Decimal::from(2)))
+        );
+
+        // Empty vectors have no position
+        assert_eq!(
+            eval_function("argmax", Value::column_vector(vec![])),
+            Ok(Value::Scalar(Decimal::ZERO))
+        );
+        assert_eq!(
+            eval_function("argmin", Value::Scalar(Decimal::from(7))),
+            Ok(Value::Scalar(Decimal::ONE))
+        );
+    }
+
+    #[test]
+    fn test_range_function() {
+        use evaluator::eval_function;
+        let vec = Value::column_vector(vec![Decimal::from(3), Decimal::from(10), Decimal::from(5)]);
+
+        assert_eq!(
+            eval_function("range", vec),
+            Ok(Value::Scalar(Decimal::from(7)))
+        );
+        assert_eq!(
+            eval_function("range", Value::column_vector(vec![])),
+            Ok(Value::Scalar(Decimal::ZERO))
+        );
+        assert_eq!(
+            eval_function("range", Value::Scalar(Decimal::from(42))),
+            Ok(Value::Scalar(Decimal::ZERO))
+        );
+    }
+
+    #[test]
+    fn test_percentile_function() {
+        let rows = vec![
+            vec!["A".to_string(), "B".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["120".to_string(), "4".to_string()],
+            vec!["80".to_string(), "1".to_string()],
+            vec!["200".to_string(), "3".to_string()],
+            vec!["95".to_string(), "2".to_string()],
+            vec!["150".to_string(), "".to_string()],
+        ];
+        let scalar = |expr: &str| evaluate_expression_value(expr, &rows).unwrap().as_scalar();
+
+        // p = 50 is the median: the middle value, or the mean of the middle two
+        assert_eq!(scalar("percentile(A_, 50)"), Some(Decimal::from(120)));
+        assert_eq!(scalar("percentile(B1:B4, 50)"), Some(Decimal::new(25, 1)));
+
+        // Rank 0.95 * 4 = 3.8 lies 80% of the way from 150 to 200
+        assert_eq!(scalar("percentile(A_, 95)"), Some(Decimal::from(190)));
+
+        // p is clamped into 0-100
+        assert_eq!(scalar("percentile(A_, 0)"), Some(Decimal::from(80)));
+        assert_eq!(scalar("percentile(A_, 150)"), Some(Decimal::from(200)));
+        assert_eq!(scalar("percentile(A_, -5)"), Some(Decimal::from(80)));
+        assert_eq!(scalar("percentile(7, 90)"), Some(Decimal::from(7)));
+
+        assert!(evaluate_expression_value("percentile(A_)", &rows).is_err());
+        assert!(evaluate_expression_value("percentile(A_, B_)", &rows).is_err());
+    }
+
+    #[test]
+    fn test_sort_function_order() {
+        let rows = vec![
+            vec!["A".to_string()],
+            vec!["---".to_string()],
+            vec!["3".to_string()],
+            vec!["1".to_string()],
+            vec!["2".to_string()],
+        ];
+        let column = |values: [i64; 3]| {
+            Ok(Value::column_vector(
+                values.iter().map(|&v| Decimal::from(v)).collect(),
+            ))
+        };
+
+        assert_eq!(
+            evaluate_expression_value("sort(A_)", &rows),
+            column([1, 2, 3])
+        );
+        assert_eq!(
+            evaluate_expression_value("sort(A_, \"asc\")", &rows),
+            column([1, 2, 3])
+        );
+        assert_eq!(
+            evaluate_expression_value("sort(A_, \"desc\")", &rows),
+            column([3, 2, 1])
+        );
+        assert_eq!(
+            evaluate_expression_value("sort(A1)", &rows),
+            Ok(Value::Scalar(Decimal::from(3)))
+        );
+
+        let error = evaluate_expression_value("sort(A_, \"down\")", &rows).unwrap_err();
+        assert!(error.to_string().contains("must be \"asc\" or \"desc\""));
+        assert!(evaluate_expression_value("sort(A_, 1)", &rows).is_err());
+        assert!(evaluate_expression_value("sort()", &rows).is_err());
+    }
+
+    #[test]
+    fn test_cumsum_column_assignment() {
+        let mut rows = vec![
+            vec!["Amount".to_string(), "Total".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["10".to_string(), "".to_string()],
+            vec!["5".to_string(), "".to_string()],
+            vec!["2.5".to_string(), "".to_string()],
+        ];
+
+        let errors = apply_formulas(&mut rows, &["B_ = cumsum(A_)".to_string()]);
+
+        assert_eq!(errors, vec![None]);
+        assert_eq!(rows[2][1], "10");
+        assert_eq!(rows[3][1], "15");
+        assert_eq!(rows[4][1], "17.5");
+    }
+
     #[test]
     fn test_power_operator() {
         use evaluator::evaluate_operation;
@@ -2475,4 +3572,1283 @@ mod tests {
         assert_eq!(errors, vec![None, None]);
         assert_eq!(rows[2][2], "20"); // (5 * 2) + 10 = 20
     }
+
+    #[test]
+    fn test_error_span_points_at_failing_occurrence() {
+        // The first A3 resolves against the "other" table, the second against this one
+        let mut rows = vec![
+            vec!["A".to_string(), "B".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["1".to_string(), "0".to_string()],
+        ];
+        let mut table_map = HashMap::new();
+        table_map.insert(
+            "other".to_string(),
+            vec![
+                vec!["X".to_string()],
+                vec!["---".to_string()],
+                vec!["1".to_string()],
+                vec!["2".to_string()],
+                vec!["3".to_string()],
+            ],
+        );
+
+        let formulas = vec!["B1 = from(\"other\", A3) + A3".to_string()];
+        let errors =
+            apply_formulas_with_tables(&mut rows, &formulas, &mut table_map, &HashMap::new())
+                .errors;
+        let error = errors[0]
+            .as_ref()
+            .expect("second A3 should be out of bounds");
+
+        assert!(error.contains("is out of bounds"));
+        assert!(error.ends_with("\nfrom(\"other\", A3) + A3\n                    ^^"));
+    }
+
+    #[test]
+    fn test_error_span_for_nested_operation() {
+        let mut rows = vec![
+            vec!["A".to_string(), "B".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["0".to_string(), "0".to_string()],
+        ];
+
+        // Only the inner division fails, so the caret covers just that sub-expression
+        let formulas = vec!["B1 = A1 + 1 / A1".to_string()];
+        let errors = apply_formulas(&mut rows, &formulas);
+        let error = errors[0].as_ref().unwrap();
+
+        assert!(error.contains("division by zero"));
+        assert!(error.ends_with("\nA1 + 1 / A1\n     ^^^^^^"));
+    }
+
+    #[test]
+    fn test_overwrite_text_cell_warning() {
+        let mut rows = vec![
+            vec!["Item".to_string(), "Amount".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["Rent".to_string(), "1200".to_string()],
+            vec!["TOTAL".to_string(), "".to_string()],
+        ];
+
+        let formulas = vec![
+            "B2 = B1".to_string(),
+            "A_ = B_".to_string(),
+            "A1 = 5".to_string(),
+        ];
+        let results =
+            apply_formulas_with_tables(&mut rows, &formulas, &mut HashMap::new(), &HashMap::new());
+
+        assert_eq!(results.errors, vec![None, None, None]);
+        // Filling an empty cell is not an overwrite
+        assert_eq!(results.warnings[0], None);
+        assert_eq!(
+            results.warnings[1],
+            Some("overwrote non-numeric cells A1 (\"Rent\"), A2 (\"TOTAL\")".to_string())
+        );
+        // Replacing a number is not reported
+        assert_eq!(results.warnings[2], None);
+        assert_eq!(rows[3][0], "1200");
+    }
+
+    #[test]
+    fn test_evaluate_constant() {
+        let mut constants = HashMap::new();
+        let (name, value) = evaluate_constant("tax = 0.08", &constants).unwrap();
+        assert_eq!(name, "tax");
+        assert_eq!(value, Value::Scalar(Decimal::new(8, 2)));
+        constants.insert(name, value);
+
+        // Later constants may use earlier ones
+        let (_, value) = evaluate_constant("double_tax = tax * 2", &constants).unwrap();
+        assert_eq!(value, Value::Scalar(Decimal::new(16, 2)));
+
+        // Cell references have no table to resolve against
+        assert!(evaluate_constant("x = A1", &constants).is_err());
+        assert!(evaluate_constant("A1 = 5", &constants).is_err());
+        assert!(evaluate_constant("missing equals", &constants).is_err());
+    }
+
+    #[test]
+    fn test_let_shadows_constant() {
+        let mut rows = vec![
+            vec!["A".to_string()],
+            vec!["---".to_string()],
+            vec!["0".to_string()],
+        ];
+        let mut constants = HashMap::new();
+        constants.insert("rate".to_string(), Value::Scalar(Decimal::from(2)));
+
+        let formulas = vec!["let rate = 3".to_string(), "A1 = rate".to_string()];
+        let results =
+            apply_formulas_with_tables(&mut rows, &formulas, &mut HashMap::new(), &constants);
+        assert_eq!(results.errors, vec![None, None]);
+        assert_eq!(rows[2][0], "3");
+    }
+
+    #[test]
+    fn test_floor_division() {
+        let rows = vec![
+            vec!["A".to_string()],
+            vec!["---".to_string()],
+            vec!["7".to_string()],
+            vec!["25".to_string()],
+            vec!["-7".to_string()],
+            vec!["9.5".to_string()],
+        ];
+        let scalar = |expr: &str| evaluate_expression_value(expr, &rows).unwrap().as_scalar();
+
+        assert_eq!(scalar("7 // 2"), Some(Decimal::from(3)));
+        assert_eq!(scalar("A3 // 2"), Some(Decimal::from(-4))); // floors toward negative infinity
+        assert_eq!(scalar("A4 // 0.5"), Some(Decimal::from(19)));
+        // Same precedence as / and *, evaluated left to right
+        assert_eq!(scalar("20 // 3 * 2"), Some(Decimal::from(12)));
+        assert_eq!(scalar("1 + 7 // 2"), Some(Decimal::from(4)));
+
+        // Element-wise over a vector, with the scalar broadcast
+        assert_eq!(
+            evaluate_expression_value("A_ // 10", &rows),
+            Ok(Value::column_vector(vec![
+                Decimal::from(0),
+                Decimal::from(2),
+                Decimal::from(-1),
+                Decimal::from(0),
+            ]))
+        );
+
+        let error = evaluate_expression_value("A1 // 0", &rows).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("division by zero in scalar operation: 7 // 0"));
+    }
+
+    #[test]
+    fn test_unary_negation() {
+        let rows = vec![
+            vec!["A".to_string(), "B".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["4".to_string(), "1".to_string()],
+            vec!["-2".to_string(), "5".to_string()],
+        ];
+        let scalar = |expr: &str| evaluate_expression_value(expr, &rows).unwrap().as_scalar();
+
+        assert_eq!(scalar("-A1"), Some(Decimal::from(-4)));
+        assert_eq!(scalar("-A2"), Some(Decimal::from(2)));
+        assert_eq!(scalar("2 * -3"), Some(Decimal::from(-6)));
+        assert_eq!(scalar("A1 - -B1"), Some(Decimal::from(5)));
+        assert_eq!(scalar("--A1"), Some(Decimal::from(4)));
+
+        // Negation binds more loosely than ^ but more tightly than * and +
+        assert_eq!(scalar("-2 ^ 2"), Some(Decimal::from(-4)));
+        assert_eq!(scalar("(-2) ^ 2"), Some(Decimal::from(4)));
+        assert_eq!(scalar("2 ^ -1"), Some(Decimal::new(5, 1)));
+        assert_eq!(scalar("-A1 + B1"), Some(Decimal::from(-3)));
+
+        // Vectors are negated element-wise
+        assert_eq!(
+            evaluate_expression_value("-(A_ )", &rows),
+            Ok(Value::column_vector(vec![
+                Decimal::from(-4),
+                Decimal::from(2)
+            ]))
+        );
+        assert_eq!(
+            evaluate_expression_value("-(A_ + B_)", &rows),
+            Ok(Value::column_vector(vec![
+                Decimal::from(-5),
+                Decimal::from(-3)
+            ]))
+        );
+
+        assert!(evaluate_expression_value("2 * -", &rows).is_err());
+    }
+
+    #[test]
+    fn test_builtin_constants() {
+        let rows = vec![
+            vec![
+                "A".to_string(),
+                "B".to_string(),
+                "C".to_string(),
+                "D".to_string(),
+                "E".to_string(),
+            ],
+            vec!["---".to_string(); 5],
+            vec![
+                "2".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "7".to_string(),
+            ],
+        ];
+        let scalar = |expr: &str| evaluate_expression_value(expr, &rows).unwrap().as_scalar();
+
+        let pi = Decimal::from_i128_with_scale(31_415_926_535_897_932_384_626_433_833, 28);
+        assert_eq!(scalar("pi"), Some(pi));
+        assert_eq!(scalar("PI"), Some(pi));
+        assert_eq!(scalar("A1 * pi"), Some(pi * Decimal::from(2)));
+        assert_eq!(
+            scalar("e").map(|e| e.round_dp(10)),
+            Some(Decimal::new(27_182_818_285, 10))
+        );
+        assert_eq!(scalar("E"), scalar("e"));
+
+        // Column E is still a cell reference
+        assert_eq!(scalar("E1"), Some(Decimal::from(7)));
+        assert_eq!(scalar("sum(E_)"), Some(Decimal::from(7)));
+
+        // Variables shadow the built-in constants
+        let mut rows = rows.clone();
+        let formulas = vec!["let e = 10".to_string(), "B1 = e + E1".to_string()];
+        assert_eq!(apply_formulas(&mut rows, &formulas), vec![None, None]);
+        assert_eq!(rows[2][1], "17");
+    }
+
+    #[test]
+    fn test_parse_table_assignment() {
+        assert_eq!(
+            parse_statement("to(\"summary\", B1) = sum(D_)"),
+            Some((
+                Statement::TableAssignment {
+                    table_id: "summary".to_string(),
+                    assignment: Assignment::Scalar { row: 2, col: 1 },
+                },
+                "sum(D_)".to_string()
+            ))
+        );
+
+        // The table ID must be quoted and the target must be a valid assignment
+        assert_eq!(parse_statement("to(summary, B1) = 1"), None);
+        assert_eq!(parse_statement("to(\"\", B1) = 1"), None);
+        assert_eq!(parse_statement("to(\"summary\") = 1"), None);
+        assert_eq!(parse_statement("to(\"summary\", 5) = 1"), None);
+    }
+
+    #[test]
+    fn test_parse_multiple_targets() {
+        let columns = |cols: &[usize]| -> Vec<Statement> {
+            cols.iter()
+                .map(|&col| Statement::assignment(Assignment::ColumnVector { col }))
+                .collect()
+        };
+        assert_eq!(
+            parse_statements("C_, D_ = A_ * 2"),
+            Some((columns(&[2, 3]), "A_ * 2".to_string()))
+        );
+        assert_eq!(
+            parse_statements("C_ = D_ = A_ * 2"),
+            Some((columns(&[2, 3]), "A_ * 2".to_string()))
+        );
+        assert_eq!(
+            parse_statements("to(\"summary\", B1), C_ = 1"),
+            Some((
+                vec![
+                    Statement::table_assignment(
+                        "summary".to_string(),
+                        Assignment::Scalar { row: 2, col: 1 }
+                    ),
+                    Statement::assignment(Assignment::ColumnVector { col: 2 }),
+                ],
+                "1".to_string()
+            ))
+        );
+
+        // `=` and `,` inside strings belong to the expression
+        assert_eq!(
+            parse_statements("D1 = sumif(B_, \">=1,5\")"),
+            Some((
+                vec![Statement::assignment(Assignment::Scalar { row: 2, col: 3 })],
+                "sumif(B_, \">=1,5\")".to_string()
+            ))
+        );
+
+        // A single-statement parse rejects several targets; every target must be valid
+        assert_eq!(parse_statement("C_, D_ = 1"), None);
+        assert_eq!(parse_statements("C_, 5 = 1"), None);
+        assert_eq!(parse_statements("C_, = 1"), None);
+    }
+
+    #[test]
+    fn test_assign_one_result_to_two_columns() {
+        let mut rows = vec![
+            vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            vec!["---".to_string(), "---".to_string(), "---".to_string()],
+            vec!["1".to_string(), "".to_string(), "".to_string()],
+            vec!["2".to_string(), "".to_string(), "".to_string()],
+        ];
+        let formulas = vec!["B_, C_ = A_ * 2".to_string(), "A3 = B_ = 1".to_string()];
+        let errors = apply_formulas(&mut rows, &formulas);
+        assert_eq!(errors[0], None);
+        assert_eq!(rows[2], vec!["1", "2", "2"]);
+        assert_eq!(rows[3], vec!["2", "4", "4"]);
+
+        // Each target that fails is reported
+        let error = errors[1].as_deref().unwrap();
+        assert!(error.contains("out of bounds"), "{}", error);
+        assert!(error.contains("expected column vector"), "{}", error);
+    }
+
+    #[test]
+    fn test_percent_cells_read_as_fractions() {
+        let mut rows = vec![
+            vec!["Rate".to_string(), "Value".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["8%".to_string(), "".to_string()],
+        ];
+        let formulas = vec!["B1 = A1".to_string()];
+
+        let results = apply_table_formulas(
+            &mut rows,
+            &formulas,
+            &mut HashMap::new(),
+            &HashMap::new(),
+            true,
+            None,
+            &mut FormulaCache::default(),
+        );
+        assert_eq!(results.errors, vec![None]);
+        assert_eq!(rows[2], vec!["8%", "0.08"]);
+
+        // Off by default: 8% is non-numeric and reads as 0
+        let errors = apply_formulas(&mut rows, &formulas);
+        assert_eq!(errors, vec![None]);
+        assert_eq!(rows[2], vec!["8%", "0"]);
+    }
+
+    #[test]
+    fn test_error_cell_marks_failing_targets() {
+        let mut rows = vec![
+            vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            vec!["---".to_string(), "---".to_string(), "---".to_string()],
+            vec!["1".to_string(), "old".to_string(), "5".to_string()],
+            vec!["2".to_string(), "old".to_string(), "6".to_string()],
+        ];
+        let formulas = vec!["B1 = A1 / 0".to_string(), "C_ = A_ + missing".to_string()];
+
+        let results = apply_table_formulas(
+            &mut rows,
+            &formulas,
+            &mut HashMap::new(),
+            &HashMap::new(),
+            false,
+            Some("#ERR"),
+            &mut FormulaCache::default(),
+        );
+        assert!(results.errors.iter().all(Option::is_some));
+        assert_eq!(rows[2], vec!["1", "#ERR", "#ERR"]);
+        assert_eq!(rows[3], vec!["2", "old", "#ERR"]);
+
+        // Once fixed, the formula replaces the marker without an overwrite warning
+        let fixed = vec!["B1 = A1 * 10".to_string()];
+        let results = apply_table_formulas(
+            &mut rows,
+            &fixed,
+            &mut HashMap::new(),
+            &HashMap::new(),
+            false,
+            Some("#ERR"),
+            &mut FormulaCache::default(),
+        );
+        assert_eq!(results.errors, vec![None]);
+        assert_eq!(results.warnings, vec![None]);
+        assert_eq!(rows[2][1], "10");
+
+        // Without the option the targets keep their old values
+        rows[2][1] = "old".to_string();
+        let errors = apply_formulas(&mut rows, &formulas[..1]);
+        assert!(errors[0].is_some());
+        assert_eq!(rows[2][1], "old");
+    }
+
+    #[test]
+    fn test_formula_cache_shares_parses_between_tables() {
+        let mut cache = FormulaCache::default();
+        let first = cache.parse("C_ = A_ + B_").unwrap();
+        let second = cache.parse("  C_ = A_ + B_ ").unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+        assert!(cache.parse("not a formula").is_none());
+
+        // Each table still reports errors against its own cells and the formula text
+        let formulas = vec!["C_ = A_ + missing".to_string(), "C_ = A_ + B_".to_string()];
+        let mut errors = Vec::new();
+        for value in ["1", "2"] {
+            let mut rows = vec![
+                vec!["A".to_string(), "B".to_string(), "C".to_string()],
+                vec!["---".to_string(), "---".to_string(), "---".to_string()],
+                vec![value.to_string(), "10".to_string(), "".to_string()],
+            ];
+            let results = apply_table_formulas(
+                &mut rows,
+                &formulas,
+                &mut HashMap::new(),
+                &HashMap::new(),
+                false,
+                None,
+                &mut cache,
+            );
+            assert_eq!(rows[2][2], (value.parse::<i32>().unwrap() + 10).to_string());
+            errors.push(results.errors);
+        }
+        assert_eq!(errors[0], errors[1]);
+        let error = errors[0][0].as_deref().unwrap();
+        assert!(error.contains("A_ + missing\n     ^^^^^^^"), "{}", error);
+    }
+
+    #[test]
+    fn test_running_total_with_previous_row() {
+        let mut rows = vec![
+            vec!["Amount".to_string(), "Balance".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["100".to_string(), "".to_string()],
+            vec!["-30".to_string(), "".to_string()],
+            vec!["45.5".to_string(), "".to_string()],
+        ];
+
+        // Each row adds its amount to the balance computed for the row above;
+        // the first row has no row above and reads 0
+        let formulas = vec!["B_ = B^ + A_".to_string()];
+        let errors = apply_formulas(&mut rows, &formulas);
+        assert_eq!(errors, vec![None]);
+        assert_eq!(rows[2][1], "100");
+        assert_eq!(rows[3][1], "70");
+        assert_eq!(rows[4][1], "115.5");
+
+        // Reapplying recomputes the same balances
+        let errors = apply_formulas(&mut rows, &formulas);
+        assert_eq!(errors, vec![None]);
+        assert_eq!(rows[4][1], "115.5");
+    }
+
+    #[test]
+    fn test_previous_row_results_match_whole_column_evaluation() {
+        let table = || -> Vec<Vec<String>> {
+            [
+                ["A", "B"],
+                ["---", "---"],
+                ["2", "9"],
+                ["-3", ""],
+                ["1.5", "x"],
+            ]
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+        };
+        let apply = |formula: &str| {
+            let mut rows = table();
+            let errors = apply_formulas(&mut rows, &[formula.to_string()]);
+            (errors, rows)
+        };
+
+        // Only this row and the one above are read, unless a function needs
+        // the whole column; both give the same results
+        let (errors, rows) = apply("B_ = B^ * 2 + A_ // 1 - pi * 0");
+        assert_eq!(errors, vec![None]);
+        assert_eq!(
+            apply("B_ = B^ * 2 + A_ // 1 - pi * 0 + sum(A_) * 0").1,
+            rows
+        );
+        let balances: Vec<&str> = rows[2..].iter().map(|row| row[1].as_str()).collect();
+        assert_eq!(balances, ["2", "1", "3"]);
+
+        // Errors read the same as when evaluating the whole column
+        let (errors, _) = apply("B_ = A_ / B^");
+        assert!(errors[0]
+            .as_deref()
+            .unwrap()
+            .contains("division by zero in element-wise operation at position 0"));
+    }
+
+    #[test]
+    fn test_previous_row_of_another_column() {
+        let rows = vec![
+            vec!["A".to_string()],
+            vec!["---".to_string()],
+            vec!["1".to_string()],
+            vec!["4".to_string()],
+            vec!["9".to_string()],
+        ];
+
+        let result = evaluate_expression_value("A_ - A^", &rows).unwrap();
+        assert_eq!(
+            result,
+            Value::column_vector(vec![Decimal::from(1), Decimal::from(3), Decimal::from(5)])
+        );
+    }
+
+    #[test]
+    fn test_table_assignment_writes_into_table_map() {
+        let mut rows = vec![
+            vec!["Cost".to_string()],
+            vec!["---".to_string()],
+            vec!["6".to_string()],
+            vec!["7.5".to_string()],
+        ];
+        let mut table_map = HashMap::new();
+        table_map.insert(
+            "summary".to_string(),
+            vec![
+                vec!["Metric".to_string(), "Value".to_string()],
+                vec!["---".to_string(), "---".to_string()],
+                vec!["Total".to_string(), "".to_string()],
+            ],
+        );
+
+        let formulas = vec![
+            "to(\"summary\", B1) = sum(A_)".to_string(),
+            "to(\"summary\", _1) = A_".to_string(),
+            "to(\"summary\", C1) = 1".to_string(),
+            "to(\"missing\", A1) = 1".to_string(),
+        ];
+        let results =
+            apply_formulas_with_tables(&mut rows, &formulas, &mut table_map, &HashMap::new());
+
+        assert_eq!(results.errors[0], None);
+        assert_eq!(table_map["summary"][2][1], "13.5");
+        // This table's own rows are untouched
+        assert_eq!(rows[2][0], "6");
+        assert_eq!(results.written_tables, vec!["summary".to_string()]);
+
+        // Dimension and bounds checks mirror local assignments
+        assert!(results.errors[1]
+            .as_ref()
+            .unwrap()
+            .contains("expected row vector but got matrix with 2 rows"));
+        assert!(results.errors[2]
+            .as_ref()
+            .unwrap()
+            .contains("cell index out of bounds"));
+        assert!(results.errors[3]
+            .as_ref()
+            .unwrap()
+            .contains("table 'missing' not found"));
+    }
+
+    #[test]
+    fn test_from_variable_slices_3x3_to_2x2() {
+        let mut rows = vec![
+            vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            vec!["---".to_string(), "---".to_string(), "---".to_string()],
+            vec!["1".to_string(), "2".to_string(), "3".to_string()],
+            vec!["4".to_string(), "5".to_string(), "6".to_string()],
+            vec!["7".to_string(), "8".to_string(), "9".to_string()],
+        ];
+        let formulas = vec![
+            "let m = A_:C_".to_string(),
+            "A1:B2 = from(m, B2:C3)".to_string(),
+            "C1 = sum(from(m, B2:D4))".to_string(),
+        ];
+
+        let errors = apply_formulas(&mut rows, &formulas);
+        assert_eq!(errors[..2], [None, None]);
+        assert_eq!(rows[2][..2], ["5", "6"]);
+        assert_eq!(rows[3][..2], ["8", "9"]);
+
+        // The slice extends past the 3×3 matrix
+        assert!(errors[2].as_ref().unwrap().contains("out of bounds"));
+    }
+
+    #[test]
+    fn test_slice_matrix() {
+        use reference::{parse_cell_reference, slice_matrix};
+
+        // 3×2 matrix: [[1, 2], [3, 4], [5, 6]]
+        let matrix = Value::Matrix {
+            rows: 3,
+            cols: 2,
+            data: (1..=6).map(Decimal::from).collect(),
+        };
+        let slice = |reference: &str| {
+            let cell_ref = match parse_cell_reference(reference) {
+                Some(cell_ref) => cell_ref,
+                None => {
+                    let mut parser = Parser::new(tokenize_expression(reference));
+                    match parser.parse().unwrap() {
+                        ast::Expr::CellRef(cell_ref, _) => cell_ref,
+                        _ => unreachable!(),
+                    }
+                }
+            };
+            slice_matrix(&matrix, &cell_ref)
+        };
+
+        assert_eq!(slice("A1"), Some(Value::Scalar(Decimal::from(1))));
+        assert_eq!(slice("B3"), Some(Value::Scalar(Decimal::from(6))));
+        assert_eq!(
+            slice("B_"),
+            Some(Value::column_vector(vec![
+                Decimal::from(2),
+                Decimal::from(4),
+                Decimal::from(6)
+            ]))
+        );
+        assert_eq!(
+            slice("_2"),
+            Some(Value::row_vector(vec![Decimal::from(3), Decimal::from(4)]))
+        );
+        assert_eq!(
+            slice("A2:B3"),
+            Some(Value::Matrix {
+                rows: 2,
+                cols: 2,
+                data: vec![
+                    Decimal::from(3),
+                    Decimal::from(4),
+                    Decimal::from(5),
+                    Decimal::from(6)
+                ],
+            })
+        );
+        assert_eq!(
+            slice("_1:_2"),
+            Some(Value::Matrix {
+                rows: 2,
+                cols: 2,
+                data: (1..=4).map(Decimal::from).collect(),
+            })
+        );
+
+        // Out of bounds in either direction
+        assert_eq!(slice("C1"), None);
+        assert_eq!(slice("A4"), None);
+        assert_eq!(slice("_4"), None);
+        assert_eq!(slice("A_:C_"), None);
+        assert_eq!(
+            slice_matrix(
+                &Value::Scalar(Decimal::ONE),
+                &parse_cell_reference("A1").unwrap()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_identity_function() {
+        let mut rows: Vec<Vec<String>> = [
+            ["A", "B", "C"],
+            ["---", "---", "---"],
+            ["", "", "2"],
+            ["", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+
+        assert_eq!(
+            evaluate_expression_value("identity(2)", &rows),
+            Ok(Value::Matrix {
+                rows: 2,
+                cols: 2,
+                data: vec![Decimal::ONE, Decimal::ZERO, Decimal::ZERO, Decimal::ONE],
+            })
+        );
+        assert!(evaluate_expression_value("identity(0)", &rows).is_err());
+        assert!(evaluate_expression_value("identity(1.5)", &rows).is_err());
+        assert!(evaluate_expression_value("identity(A_)", &rows).is_err());
+
+        // The size can come from a cell, and the result fills a range
+        let errors = apply_formulas(&mut rows, &["A1:B2 = identity(C1)".to_string()]);
+        assert_eq!(errors, [None]);
+        assert_eq!(rows[2][..2], ["1", "0"]);
+        assert_eq!(rows[3][..2], ["0", "1"]);
+    }
+
+    #[test]
+    fn test_diag_function() {
+        let rows: Vec<Vec<String>> = [
+            ["A", "B", "C", "D"],
+            ["---", "---", "---", "---"],
+            ["1", "2", "3", "10"],
+            ["4", "5", "6", "11"],
+            ["7", "8", "9", "12"],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+
+        assert_eq!(
+            evaluate_expression_value("diag(A1:C3)", &rows),
+            Ok(Value::column_vector(vec![
+                Decimal::from(1),
+                Decimal::from(5),
+                Decimal::from(9)
+            ]))
+        );
+        assert_eq!(
+            evaluate_expression_value("sum(diag(A1:C3))", &rows)
+                .unwrap()
+                .as_scalar(),
+            Some(Decimal::from(15))
+        );
+        assert_eq!(
+            evaluate_expression_value("diag(identity(3) * 4)", &rows),
+            Ok(Value::column_vector(vec![Decimal::from(4); 3]))
+        );
+
+        let error = evaluate_expression_value("diag(A1:D3)", &rows).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("diag() requires a square matrix, got shape (3×4)"));
+    }
+
+    #[test]
+    fn test_rowsum_and_colsum_functions() {
+        let mut rows: Vec<Vec<String>> = [
+            ["A", "B", "C", "D"],
+            ["---", "---", "---", "---"],
+            ["1", "2", "3", ""],
+            ["4", "5", "6", ""],
+            ["", "", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let decimals = |values: &[i64]| values.iter().map(|&v| Decimal::from(v)).collect();
+
+        assert_eq!(
+            evaluate_expression_value("rowsum(A1:C2)", &rows),
+            Ok(Value::column_vector(decimals(&[6, 15])))
+        );
+        assert_eq!(
+            evaluate_expression_value("colsum(A1:C2)", &rows),
+            Ok(Value::row_vector(decimals(&[5, 7, 9])))
+        );
+        assert_eq!(
+            evaluate_expression_value("rowsum(7)", &rows),
+            Ok(Value::Scalar(Decimal::from(7)))
+        );
+        assert!(evaluate_expression_value("rowsum(A1:C2, 1)", &rows).is_err());
+
+        // The totals fill a column and a row
+        let errors = apply_formulas(
+            &mut rows,
+            &[
+                "D1:D2 = rowsum(A1:C2)".to_string(),
+                "A3:C3 = colsum(A1:C2)".to_string(),
+            ],
+        );
+        assert_eq!(errors, [None, None]);
+        assert_eq!(rows[2][3], "6");
+        assert_eq!(rows[3][3], "15");
+        assert_eq!(rows[4][..3], ["5", "7", "9"]);
+    }
+
+    #[test]
+    fn test_rank_function() {
+        use evaluator::eval_function;
+        let decimals = |values: &[i64]| values.iter().map(|&v| Decimal::from(v)).collect();
+
+        assert_eq!(
+            eval_function("rank", Value::row_vector(decimals(&[30, 10, 20]))),
+            Ok(Value::row_vector(decimals(&[1, 3, 2])))
+        );
+        // Ties share the lower rank, and the rank after them is skipped
+        assert_eq!(
+            eval_function("rank", Value::column_vector(decimals(&[10, 20, 20, 5]))),
+            Ok(Value::column_vector(decimals(&[3, 1, 1, 4])))
+        );
+        assert_eq!(
+            eval_function("rank", Value::Scalar(Decimal::from(7))),
+            Ok(Value::Scalar(Decimal::ONE))
+        );
+
+        let mut rows: Vec<Vec<String>> = [
+            ["Player", "Score", "Place", "Low"],
+            ["---", "---", "---", "---"],
+            ["a", "30", "", ""],
+            ["b", "10", "", ""],
+            ["c", "20", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let errors = apply_formulas(
+            &mut rows,
+            &[
+                "C_ = rank(B_)".to_string(),
+                "D_ = rank(B_, \"asc\")".to_string(),
+            ],
+        );
+        assert_eq!(errors, [None, None]);
+        let column = |col: usize| {
+            rows[2..]
+                .iter()
+                .map(|row| row[col].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column(2), ["1", "3", "2"]);
+        assert_eq!(column(3), ["3", "1", "2"]);
+        assert!(evaluate_expression_value("rank(B_, \"up\")", &rows).is_err());
+    }
+
+    #[test]
+    fn test_first_and_last_functions() {
+        use evaluator::eval_function;
+        let values = Value::row_vector([7, 8, 9].into_iter().map(Decimal::from).collect());
+
+        assert_eq!(
+            eval_function("first", values.clone()),
+            Ok(Value::Scalar(Decimal::from(7)))
+        );
+        assert_eq!(
+            eval_function("last", values),
+            Ok(Value::Scalar(Decimal::from(9)))
+        );
+        assert_eq!(
+            eval_function("last", Value::Scalar(Decimal::from(4))),
+            Ok(Value::Scalar(Decimal::from(4)))
+        );
+        assert_eq!(
+            eval_function("first", Value::row_vector(Vec::new())),
+            Ok(Value::Scalar(Decimal::ZERO))
+        );
+
+        let mut rows: Vec<Vec<String>> = [
+            ["Day", "Open", "Close"],
+            ["---", "---", "---"],
+            ["12", "", ""],
+            ["15", "", ""],
+            ["11", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let formulas = ["B1 = first(A_)".to_string(), "C1 = LAST(A_)".to_string()];
+        assert_eq!(apply_formulas(&mut rows, &formulas), [None, None]);
+        assert_eq!(rows[2], ["12", "12", "11"]);
+    }
+
+    #[test]
+    fn test_head_and_tail_functions() {
+        let mut rows: Vec<Vec<String>> = [
+            ["Value", "First", "Last"],
+            ["---", "---", "---"],
+            ["10", "", ""],
+            ["20", "", ""],
+            ["30", "", ""],
+            ["40", "", ""],
+            ["50", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let formulas = [
+            "B_ = head(A_, 3)".to_string(),
+            "C_ = tail(A_, 2)".to_string(),
+        ];
+        assert_eq!(apply_formulas(&mut rows, &formulas), [None, None]);
+        let column =
+            |col: usize| -> Vec<&str> { rows[2..].iter().map(|r| r[col].as_str()).collect() };
+        assert_eq!(column(1), ["10", "20", "30", "", ""]);
+        assert_eq!(column(2), ["40", "50", "", "", ""]);
+
+        // n is clamped to the length, and a row vector stays a row vector
+        let table = rows.clone();
+        let eval = |formula: &str| {
+            let ast = Parser::new(tokenize_expression(formula)).parse().unwrap();
+            evaluator::eval_ast_with_tables(&ast, &table, &HashMap::new(), &HashMap::new())
+        };
+        let decimals = |values: &[i64]| values.iter().map(|&v| Decimal::from(v)).collect();
+        assert_eq!(
+            eval("head(A_, 9)"),
+            Ok(Value::column_vector(decimals(&[10, 20, 30, 40, 50])))
+        );
+        assert_eq!(eval("tail(A_, 0)"), Ok(Value::column_vector(Vec::new())));
+        assert_eq!(
+            eval("tail(A1:C1, 2)"),
+            Ok(Value::row_vector(decimals(&[10, 40])))
+        );
+        assert!(eval("head(A_, -1)").is_err());
+        assert!(eval("head(A_, 1.5)").is_err());
+        assert!(eval("head(A_)").is_err());
+    }
+
+    #[test]
+    fn test_sign_function() {
+        use evaluator::eval_function;
+        let decimals = |values: &[i64]| values.iter().map(|&v| Decimal::from(v)).collect();
+
+        assert_eq!(
+            eval_function("sign", Value::row_vector(decimals(&[-3, 0, 5]))),
+            Ok(Value::row_vector(decimals(&[-1, 0, 1])))
+        );
+        assert_eq!(
+            eval_function("sign", Value::Scalar("-0.5".parse().unwrap())),
+            Ok(Value::Scalar(Decimal::NEGATIVE_ONE))
+        );
+
+        // The result keeps the shape of its argument, so it fills a column
+        let mut rows: Vec<Vec<String>> = [
+            ["Change", "Up"],
+            ["---", "---"],
+            ["-3", ""],
+            ["0", ""],
+            ["5", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let errors = apply_formulas(&mut rows, &["B_ = sign(A_)".to_string()]);
+        assert_eq!(errors, [None]);
+        let signs: Vec<&str> = rows[2..].iter().map(|row| row[1].as_str()).collect();
+        assert_eq!(signs, ["-1", "0", "1"]);
+    }
+
+    #[test]
+    fn test_trunc_function() {
+        use evaluator::eval_function;
+        let decimals = |values: &[&str]| values.iter().map(|v| v.parse().unwrap()).collect();
+
+        // Truncation rounds toward zero, unlike floor division
+        assert_eq!(
+            eval_function("trunc", Value::column_vector(decimals(&["-1.7", "1.7"]))),
+            Ok(Value::column_vector(decimals(&["-1", "1"])))
+        );
+
+        // A negative value that truncates to zero is written as 0, not -0
+        let mut rows: Vec<Vec<String>> = [
+            ["Value", "Whole", "Quarter"],
+            ["---", "---", "---"],
+            ["-1.7", "", ""],
+            ["1.7", "", ""],
+            ["-0.5", "", ""],
+            ["-2", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let errors = apply_formulas(
+            &mut rows,
+            &[
+                "B_ = trunc(A_)".to_string(),
+                "C_ = trunc(A_ / 4)".to_string(),
+            ],
+        );
+        assert_eq!(errors, [None, None]);
+        let whole: Vec<&str> = rows[2..].iter().map(|row| row[1].as_str()).collect();
+        assert_eq!(whole, ["-1", "1", "0", "-2"]);
+        let quarter: Vec<&str> = rows[2..].iter().map(|row| row[2].as_str()).collect();
+        assert_eq!(quarter, ["0", "0", "0", "0"]);
+    }
+
+    #[test]
+    fn test_normalize_function() {
+        use evaluator::eval_function;
+        let decimals = |values: &[&str]| values.iter().map(|v| v.parse().unwrap()).collect();
+
+        assert_eq!(
+            eval_function("normalize", Value::row_vector(decimals(&["1", "1", "2"]))),
+            Ok(Value::row_vector(decimals(&["0.25", "0.25", "0.5"])))
+        );
+        assert_eq!(
+            eval_function("normalize", Value::Scalar(Decimal::from(4))),
+            Ok(Value::Scalar(Decimal::ONE))
+        );
+
+        // A zero total cannot be divided by
+        for zero in [
+            Value::column_vector(decimals(&["1", "-1"])),
+            Value::Scalar(Decimal::ZERO),
+        ] {
+            let error = eval_function("normalize", zero).unwrap_err();
+            assert!(error.to_string().contains("division by zero"));
+        }
+
+        // The result keeps the shape of its argument, so it fills a column
+        let mut rows: Vec<Vec<String>> = [["Count", "Share"], ["---", "---"], ["1", ""], ["3", ""]]
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        let errors = apply_formulas(&mut rows, &["B_ = normalize(A_)".to_string()]);
+        assert_eq!(errors, [None]);
+        assert_eq!(rows[2][1], "0.25");
+        assert_eq!(rows[3][1], "0.75");
+    }
+
+    #[test]
+    fn test_zscore_function() {
+        use evaluator::eval_function;
+        let decimals = |values: &[i64]| values.iter().map(|&v| Decimal::from(v)).collect();
+
+        // mean 5, sample standard deviation 2
+        let Ok(Value::Matrix { rows, cols, data }) =
+            eval_function("zscore", Value::column_vector(decimals(&[3, 5, 7])))
+        else {
+            panic!("zscore() should return a matrix");
+        };
+        assert_eq!((rows, cols), (3, 1));
+        assert_eq!(data[0], Decimal::from(-1));
+        assert_eq!(data[1], Decimal::ZERO);
+        assert_eq!(data[2], Decimal::ONE);
+        let mean = data.iter().sum::<Decimal>() / Decimal::from(data.len());
+        assert!(mean.abs() < Decimal::new(1, 20), "mean {}", mean);
+
+        // An irrational standard deviation still centers the values on 0
+        let Ok(Value::Matrix { data, .. }) =
+            eval_function("zscore", Value::row_vector(decimals(&[1, 2, 4])))
+        else {
+            panic!("zscore() should return a matrix");
+        };
+        assert!(data.iter().sum::<Decimal>().abs() < Decimal::new(1, 20));
+        assert_eq!(data[0].round_dp(6), Decimal::new(-872_872, 6));
+
+        // No spread to divide by
+        for flat in [
+            Value::column_vector(decimals(&[4, 4, 4])),
+            Value::column_vector(decimals(&[4])),
+            Value::Scalar(Decimal::from(4)),
+        ] {
+            assert!(eval_function("zscore", flat).is_err());
+        }
+    }
+
+    #[test]
+    fn test_fill_computes_every_data_row() {
+        let mut rows: Vec<Vec<String>> = [
+            ["Item", "Qty", "Price", "Total", "Share"],
+            ["---", "---", "---", "---", "---"],
+            ["a", "2", "3", "", ""],
+            ["b", "4", "5", "", ""],
+            ["c", "1", "10", "", ""],
+            ["d", "7", "2", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+
+        // Bare letters are the current row; B_ and friends still see the whole column
+        let errors = apply_formulas(
+            &mut rows,
+            &[
+                "fill D = B * C".to_string(),
+                "fill E = D / sum(D_)".to_string(),
+            ],
+        );
+        assert_eq!(errors, [None, None]);
+        let column = |col: usize| {
+            rows[2..]
+                .iter()
+                .map(|row| row[col].as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column(3), ["6", "20", "10", "14"]);
+        assert_eq!(column(4), ["0.12", "0.40", "0.20", "0.28"]);
+
+        // A row must give a single value
+        let errors = apply_formulas(&mut rows, &["fill D = B_".to_string()]);
+        assert!(errors[0].as_ref().unwrap().contains("row 1"));
+        assert_eq!(rows[2][3], "6");
+
+        // The target is a single column letter
+        for formula in ["fill D1 = 1", "fill d = 1", "fill D = "] {
+            let errors = apply_formulas(&mut rows, &[formula.to_string()]);
+            assert!(errors[0].is_some(), "{formula}");
+        }
+    }
+
+    #[test]
+    fn test_det_function() {
+        let rows: Vec<Vec<String>> = [
+            ["A", "B", "C", "D"],
+            ["---", "---", "---", "---"],
+            ["3", "8", "2", "0"],
+            ["4", "6", "-3", "1"],
+            ["1", "5", "7", "2"],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let scalar = |expr: &str| evaluate_expression_value(expr, &rows).unwrap().as_scalar();
+
+        // 2×2: ad - bc = 3×6 - 8×4
+        assert_eq!(scalar("det(A1:B2)"), Some(Decimal::from(-14)));
+        // 3×3 by cofactor expansion:
+        // 3(6×7 - (-3)×5) - 8(4×7 - (-3)×1) + 2(4×5 - 6×1) = 171 - 248 + 28
+        assert_eq!(scalar("det(A1:C3)"), Some(Decimal::from(-49)));
+        assert_eq!(scalar("det(identity(4) * 2)"), Some(Decimal::from(16)));
+        assert_eq!(scalar("det(5)"), Some(Decimal::from(5)));
+
+        let small = vec![
+            vec!["A".to_string(), "B".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec!["0".to_string(), "2".to_string()],
+            vec!["3".to_string(), "1".to_string()],
+            vec!["6".to_string(), "2".to_string()],
+        ];
+        let det = |expr: &str| evaluate_expression_value(expr, &small).unwrap().as_scalar();
+        // A zero first pivot is handled by swapping rows
+        assert_eq!(det("det(A1:B2)"), Some(Decimal::from(-6)));
+        // Singular: the third row is twice the second
+        assert_eq!(det("det(A2:B3)"), Some(Decimal::ZERO));
+
+        // Elimination divides exactly, leaving no rounding noise
+        let exact = vec![
+            vec!["A".to_string(), "B".to_string(), "C".to_string()],
+            vec!["---".to_string(), "---".to_string(), "---".to_string()],
+            vec!["3".to_string(), "2".to_string(), "7".to_string()],
+            vec!["1".to_string(), "5".to_string(), "4".to_string()],
+            vec!["2".to_string(), "9".to_string(), "11".to_string()],
+            vec!["0.5".to_string(), "1.25".to_string(), "3".to_string()],
+        ];
+        let det = |expr: &str| evaluate_expression_value(expr, &exact).unwrap().as_scalar();
+        assert_eq!(det("det(A1:C3)"), Some(Decimal::from(44)));
+        assert_eq!(det("det(A2:C4)"), Some(Decimal::new(275, 2)));
+
+        let error = evaluate_expression_value("det(A1:C2)", &rows).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("det() requires a square matrix, got shape (2×3)"));
+    }
+
+    #[test]
+    fn test_det_overflow() {
+        let large = "100000000000000000000".to_string();
+        let rows = vec![
+            vec!["A".to_string(), "B".to_string()],
+            vec!["---".to_string(), "---".to_string()],
+            vec![large.clone(), large.clone()],
+            vec![large.clone(), large],
+        ];
+
+        let error = evaluate_expression_value("det(A1:B2)", &rows).unwrap_err();
+        assert!(error.to_string().contains("overflow in det()"));
+    }
+
+    #[test]
+    fn test_vmin_vmax_functions() {
+        let mut rows: Vec<Vec<String>> = [
+            ["A", "B", "C", "D"],
+            ["---", "---", "---", "---"],
+            ["1", "4", "", ""],
+            ["5", "2", "", ""],
+            ["3", "6", "", ""],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let column = |values: &[i64]| {
+            Ok(Value::column_vector(
+                values.iter().map(|&v| Decimal::from(v)).collect(),
+            ))
+        };
+
+        assert_eq!(
+            evaluate_expression_value("vmax(A_, B_)", &rows),
+            column(&[4, 5, 6])
+        );
+        assert_eq!(
+            evaluate_expression_value("vmin(A_, B_)", &rows),
+            column(&[1, 2, 3])
+        );
+        // A scalar is compared with every element
+        assert_eq!(
+            evaluate_expression_value("vmax(A_, 3)", &rows),
+            column(&[3, 5, 3])
+        );
+        assert_eq!(
+            evaluate_expression_value("VMIN(2, B_)", &rows),
+            column(&[2, 2, 2])
+        );
+        assert_eq!(
+            evaluate_expression_value("vmax(2, 7)", &rows),
+            Ok(Value::Scalar(Decimal::from(7)))
+        );
+        // Unlike max(), vmax() does not reduce
+        assert_eq!(
+            evaluate_expression_value("max(vmax(A_, B_))", &rows),
+            Ok(Value::Scalar(Decimal::from(6)))
+        );
+
+        let error = evaluate_expression_value("vmax(A_, B1:B2)", &rows).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("vmax() requires arguments of the same shape, got (3×1) and (2×1)"));
+        assert!(evaluate_expression_value("vmax(A_.T, B_)", &rows).is_err());
+        assert!(evaluate_expression_value("vmin(A_)", &rows).is_err());
+
+        let errors = apply_formulas(
+            &mut rows,
+            &[
+                "C_ = vmax(A_, B_)".to_string(),
+                "D_ = vmin(A_, B_)".to_string(),
+            ],
+        );
+        assert_eq!(errors, [None, None]);
+        assert_eq!(rows[2][2..], ["4", "1"]);
+        assert_eq!(rows[3][2..], ["5", "2"]);
+        assert_eq!(rows[4][2..], ["6", "3"]);
+    }
+
+    #[test]
+    fn test_sumif_function() {
+        let rows: Vec<Vec<String>> = [
+            ["Item", "Amount", "Cost"],
+            ["---", "---", "---"],
+            ["x", "150", "3"],
+            ["Y", "80", "5"],
+            ["x", "120", "7"],
+            ["", "-5", "11"],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+        .collect();
+        let sumif = |expression: &str| evaluate_expression_value(expression, &rows);
+        let scalar = |value: i64| Ok(Value::Scalar(Decimal::from(value)));
+
+        // Numeric comparisons, summing the criteria range itself by default
+        assert_eq!(sumif("sumif(B_, \">100\", C_)"), scalar(10));
+        assert_eq!(sumif("sumif(B_, \">100\")"), scalar(270));
+        assert_eq!(sumif("sumif(B_, \">=120\", C_)"), scalar(10));
+        assert_eq!(sumif("sumif(B_, \"<0\", C_)"), scalar(11));
+        assert_eq!(sumif("sumif(B_, \"=80\", C_)"), scalar(5));
+        assert_eq!(sumif("sumif(B_, \"80\", C_)"), scalar(5));
+
+        // Text comparisons ignore case, and non-numbers never equal a number
+        assert_eq!(sumif("sumif(A_, \"=x\", B_)"), scalar(270));
+        assert_eq!(sumif("sumif(A_, \"y\", C_)"), scalar(5));
+        assert_eq!(sumif("sumif(A_, \"<>x\", C_)"), scalar(16));
+        assert_eq!(sumif("sumif(A_, \"=0\", C_)"), scalar(0));
+        assert_eq!(sumif("sumif(A_, \"<>0\", C_)"), scalar(26));
+
+        // The criteria can be any expression, compared by value
+        assert_eq!(sumif("sumif(B_ * 2, \">200\", C_)"), scalar(10));
+        assert_eq!(sumif("sumif(B1:B2, \">100\", C1:C2)"), scalar(3));
+
+        let error = sumif("sumif(B_, \">100\", C1:C2)").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("sumif() criteria range has 4 elements but sum range has 2"));
+        assert!(sumif("sumif(B_, 100, C_)").is_err());
+        assert!(sumif("sumif(B_)").is_err());
+    }
+
+    #[test]
+    fn test_countif_function() {
+        let rows: Vec<Vec<String>> = [
+            "Score", "---", "50", "72.5", "", "n/a", "49.99", "(60)", "1e2", "50.0",
+        ]
+        .iter()
+        .map(|cell| vec![cell.to_string()])
+        .collect();
+        let countif = |expression: &str| evaluate_expression_value(expression, &rows);
+        let count = |value: i64| Ok(Value::Scalar(Decimal::from(value)));
+
+        // Empty and text cells never meet a numeric comparison
+        assert_eq!(countif("countif(A_, \">=50\")"), count(4));
+        assert_eq!(countif("countif(A_, \">50\")"), count(2));
+        assert_eq!(countif("countif(A_, \"<50\")"), count(2));
+        assert_eq!(countif("countif(A_, \"<=50\")"), count(4));
+        assert_eq!(countif("countif(A_, \"=50\")"), count(2));
+        assert_eq!(countif("countif(A_, \"!=50\")"), count(6));
+        assert_eq!(countif("countif(A_, \"n/a\")"), count(1));
+
+        // Any expression can be counted, not just references
+        assert_eq!(countif("countif(A_ * 2, \">=100\")"), count(4));
+        assert_eq!(countif("countif(A1:A2, \">=50\")"), count(2));
+        assert_eq!(countif("countif(7, \">5\")"), count(1));
+
+        assert!(countif("countif(A_, 50)").is_err());
+        assert!(countif("countif(A_)").is_err());
+        assert!(countif("countif(A_, \">1\", A_)").is_err());
+    }
 }
@@ -7,9 +7,10 @@ use std::str::FromStr;
 
 /// Parses a cell reference string into a structured `CellReference`.
 ///
-/// Recognizes three types of references:
+/// Recognizes four types of references:
 /// - **Scalar**: `A1`, `B2`, etc. (column letter + row number)
 /// - **Column Vector**: `A_`, `B_`, etc. (column letter + underscore)
+/// - **Previous Row**: `A^`, `B^`, etc. (column letter + caret)
 /// - **Row Vector**: `_1`, `_2`, etc. (underscore + row number)
 ///
 /// # Arguments
@@ -33,6 +34,11 @@ use std::str::FromStr;
 /// - Column letter is converted to zero-based index
 /// - Used for operations on entire columns
 ///
+/// **Previous row references** (e.g., "A^", "C^"):
+/// - Represents the cell one row above in the specified column, for every data row
+/// - Resolves to the column vector shifted down by one, starting with 0
+/// - Used for running totals such as `C_ = C^ + B_`
+///
 /// **Row vector references** (e.g., "_1", "_2", "_99"):
 /// - Represents all columns in the specified row
 /// - Row number is 1-based where 1 refers to the first data row
@@ -69,6 +75,18 @@ pub(crate) fn parse_cell_reference(token: &str) -> Option<CellReference> {
         return None;
     }
 
+    // Check for previous row pattern: A^ (letter followed by caret)
+    if let Some(col_str) = token.strip_suffix('^') {
+        if col_str.len() == 1 {
+            let col_char = col_str.chars().next()?;
+            if col_char.is_ascii_alphabetic() {
+                let col_idx = (col_char as u32 - 'A' as u32) as usize;
+                return Some(CellReference::PreviousRow { col: col_idx });
+            }
+        }
+        return None;
+    }
+
     // Check for scalar pattern: A1 (letter followed by number)
     let mut chars = token.chars();
     let first = chars.next()?;
@@ -105,6 +123,7 @@ pub(crate) fn parse_cell_reference(token: &str) -> Option<CellReference> {
 /// Extracts data from the table based on the reference type:
 /// - **Scalar**: Returns a single cell value as `Value::Scalar`
 /// - **Column Vector**: Returns all values in a column (from first data row onwards) as `Value::Vector`
+/// - **Previous Row**: Returns the column shifted down one row, with 0 for the first data row
 /// - **Row Vector**: Returns all values in a row as `Value::Vector`
 ///
 /// # Empty Cell Handling
@@ -161,7 +180,7 @@ pub(crate) fn resolve_reference(
             }
 
             let cell_value = &rows[*row][*col];
-            if let Ok(decimal) = Decimal::from_str(cell_value) {
+            if let Some(decimal) = parse_cell_number(cell_value) {
                 Ok(Value::Scalar(decimal))
             } else {
                 // Empty or non-numeric cells are treated as 0
@@ -196,7 +215,7 @@ pub(crate) fn resolve_reference(
             for row in rows.iter().skip(FIRST_DATA_ROW_INDEX) {
                 if *col < row.len() {
                     let cell_value = &row[*col];
-                    if let Ok(decimal) = Decimal::from_str(cell_value) {
+                    if let Some(decimal) = parse_cell_number(cell_value) {
                         data.push(decimal);
                     } else {
                         // Empty or non-numeric cells are treated as 0
@@ -206,6 +225,16 @@ pub(crate) fn resolve_reference(
             }
             Ok(Value::column_vector(data))
         }
+        CellReference::PreviousRow { col } => {
+            // The first data row has no row above it, so it reads 0
+            let column = resolve_reference(&CellReference::ColumnVector { col: *col }, rows)?;
+            let Value::Matrix { mut data, .. } = column else {
+                unreachable!("column references resolve to a column vector")
+            };
+            data.pop();
+            data.insert(0, Decimal::ZERO);
+            Ok(Value::column_vector(data))
+        }
         CellReference::RowVector { row } => {
             // Get all values in the row
             // Row 1 means first data row
@@ -220,7 +249,7 @@ pub(crate) fn resolve_reference(
 
             let mut data = Vec::new();
             for cell_value in &rows[row_idx] {
-                if let Ok(decimal) = Decimal::from_str(cell_value) {
+                if let Some(decimal) = parse_cell_number(cell_value) {
                     data.push(decimal);
                 } else {
                     // Empty or non-numeric cells are treated as 0
@@ -291,7 +320,7 @@ pub(crate) fn resolve_reference(
 
             for row in &rows[*start_row..=*end_row] {
                 for cell_value in &row[*start_col..=*end_col] {
-                    if let Ok(decimal) = Decimal::from_str(cell_value) {
+                    if let Some(decimal) = parse_cell_number(cell_value) {
                         data.push(decimal);
                     } else {
                         // Empty or non-numeric cells are treated as 0
@@ -348,7 +377,7 @@ pub(crate) fn resolve_reference(
                 for col_idx in *start_col..=*end_col {
                     if col_idx < row.len() {
                         let cell_value = &row[col_idx];
-                        if let Ok(decimal) = Decimal::from_str(cell_value) {
+                        if let Some(decimal) = parse_cell_number(cell_value) {
                             data.push(decimal);
                         } else {
                             data.push(Decimal::ZERO);
@@ -398,7 +427,7 @@ pub(crate) fn resolve_reference(
                 for col_idx in 0..num_cols {
                     if col_idx < row.len() {
                         let cell_value = &row[col_idx];
-                        if let Ok(decimal) = Decimal::from_str(cell_value) {
+                        if let Some(decimal) = parse_cell_number(cell_value) {
                             data.push(decimal);
                         } else {
                             data.push(Decimal::ZERO);
@@ -423,6 +452,154 @@ pub(crate) fn resolve_reference(
     }
 }
 
+/// Resolves a cell reference to the raw text of its cells
+///
+/// The cells come in the same order as the elements of the value returned by
+/// [`resolve_reference`], which also does the bounds checking. Cells missing
+/// from short rows are empty, and a 1x1 range gives a single cell.
+pub(crate) fn resolve_reference_text(
+    cell_ref: &CellReference,
+    rows: &[Vec<String>],
+) -> Result<Vec<String>, FormulaError> {
+    resolve_reference(cell_ref, rows)?;
+
+    let cell = |row: &Vec<String>, col: usize| row.get(col).cloned().unwrap_or_default();
+    let data_rows = rows.iter().skip(FIRST_DATA_ROW_INDEX);
+    let cells = match cell_ref {
+        CellReference::Scalar { row, col } => vec![rows[*row][*col].clone()],
+        CellReference::ColumnVector { col } => data_rows
+            .filter(|row| *col < row.len())
+            .map(|row| row[*col].clone())
+            .collect(),
+        CellReference::PreviousRow { col } => {
+            let mut cells =
+                resolve_reference_text(&CellReference::ColumnVector { col: *col }, rows)?;
+            cells.pop();
+            cells.insert(0, String::new());
+            cells
+        }
+        CellReference::RowVector { row } => rows[formula_row_to_table_index(*row)].clone(),
+        CellReference::Range {
+            start_row,
+            start_col,
+            end_row,
+            end_col,
+        } => rows[*start_row..=*end_row]
+            .iter()
+            .flat_map(|row| row[*start_col..=*end_col].iter().cloned())
+            .collect(),
+        CellReference::ColumnRange { start_col, end_col } => data_rows
+            .flat_map(|row| (*start_col..=*end_col).map(|col| cell(row, col)))
+            .collect(),
+        CellReference::RowRange { start_row, end_row } => {
+            let start_row_idx = formula_row_to_table_index(*start_row);
+            let end_row_idx = formula_row_to_table_index(*end_row);
+            let num_cols = rows[start_row_idx].len();
+            rows[start_row_idx..=end_row_idx]
+                .iter()
+                .flat_map(|row| (0..num_cols).map(|col| cell(row, col)))
+                .collect()
+        }
+    };
+    Ok(cells)
+}
+
+/// Selects part of a matrix using a cell reference in the matrix's own coordinates
+///
+/// Row 1 is the first matrix row and column A the first matrix column, so `A1` is the
+/// top-left element, `B_` the second column, and `_2:_3` the second and third rows.
+/// Scalar references return `Value::Scalar`; everything else returns a sub-matrix.
+///
+/// # Returns
+///
+/// * `Some(Value)` if the reference lies within the matrix
+/// * `None` if the value is a scalar or the reference is out of bounds
+pub(crate) fn slice_matrix(value: &Value, cell_ref: &CellReference) -> Option<Value> {
+    let Value::Matrix { rows, cols, data } = value else {
+        return None;
+    };
+
+    // Half-open (start, end) bounds in 0-based matrix coordinates
+    let (row_bounds, col_bounds) = match *cell_ref {
+        CellReference::Scalar { row, col } => {
+            let row = row - FIRST_DATA_ROW_INDEX;
+            ((row, row + 1), (col, col + 1))
+        }
+        CellReference::ColumnVector { col } => ((0, *rows), (col, col + 1)),
+        // Relative references only make sense against a table
+        CellReference::PreviousRow { .. } => return None,
+        CellReference::RowVector { row } => ((row - 1, row), (0, *cols)),
+        CellReference::Range {
+            start_row,
+            start_col,
+            end_row,
+            end_col,
+        } => (
+            (
+                start_row - FIRST_DATA_ROW_INDEX,
+                end_row - FIRST_DATA_ROW_INDEX + 1,
+            ),
+            (start_col, end_col + 1),
+        ),
+        CellReference::ColumnRange { start_col, end_col } => ((0, *rows), (start_col, end_col + 1)),
+        CellReference::RowRange { start_row, end_row } => ((start_row - 1, end_row), (0, *cols)),
+    };
+
+    if row_bounds.1 > *rows
+        || col_bounds.1 > *cols
+        || row_bounds.0 >= row_bounds.1
+        || col_bounds.0 >= col_bounds.1
+    {
+        return None;
+    }
+
+    if let CellReference::Scalar { .. } = cell_ref {
+        return Some(Value::Scalar(data[row_bounds.0 * cols + col_bounds.0]));
+    }
+
+    let mut sliced =
+        Vec::with_capacity((row_bounds.1 - row_bounds.0) * (col_bounds.1 - col_bounds.0));
+    for row in row_bounds.0..row_bounds.1 {
+        sliced.extend_from_slice(&data[row * cols + col_bounds.0..row * cols + col_bounds.1]);
+    }
+
+    Some(Value::Matrix {
+        rows: row_bounds.1 - row_bounds.0,
+        cols: col_bounds.1 - col_bounds.0,
+        data: sliced,
+    })
+}
+
+/// Parses a cell's contents as a number
+///
+/// Accounting-style negatives such as `(500)` are read as `-500`, so tables
+/// rendered with `negatives=paren` keep their values on the next run.
+/// Scientific notation such as `1.2e5` is accepted as well.
+pub(crate) fn parse_cell_number(cell: &str) -> Option<Decimal> {
+    if let Some(inner) = cell.strip_prefix('(').and_then(|c| c.strip_suffix(')')) {
+        if inner.starts_with(['-', '+']) {
+            return None;
+        }
+        return parse_decimal(inner).map(|decimal| Decimal::ZERO - decimal);
+    }
+    parse_decimal(cell)
+}
+
+/// Parses plain decimal text, falling back to an f64 parse for scientific notation
+///
+/// `Decimal::from_str` rejects exponents, so `1.2e5` goes through f64. Text such
+/// as `inf` or `NaN` that f64 accepts has no Decimal value and stays unparsed.
+fn parse_decimal(text: &str) -> Option<Decimal> {
+    use rust_decimal::prelude::FromPrimitive;
+
+    Decimal::from_str(text).ok().or_else(|| {
+        text.parse::<f64>()
+            .ok()
+            .and_then(Decimal::from_f64)
+            .map(|decimal| decimal.normalize())
+    })
+}
+
 /// Converts an entire table to a matrix (all data rows, all columns)
 pub(crate) fn table_to_matrix(rows: &[Vec<String>]) -> Result<Value, FormulaError> {
     if rows.len() < FIRST_DATA_ROW_INDEX {
@@ -447,7 +624,7 @@ pub(crate) fn table_to_matrix(rows: &[Vec<String>]) -> Result<Value, FormulaErro
         for col_idx in 0..num_cols {
             if col_idx < row.len() {
                 let cell = &row[col_idx];
-                if let Ok(decimal) = Decimal::from_str(cell) {
+                if let Some(decimal) = parse_cell_number(cell) {
                     data.push(decimal);
                 } else {
                     data.push(Decimal::ZERO);
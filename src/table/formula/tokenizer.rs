@@ -1,15 +1,21 @@
 use crate::table::formula::types::Span;
+use std::borrow::Cow;
 
 /// Represents a token with its value and position in the source expression
+///
+/// The value borrows from the expression, so tokenizing allocates nothing per token.
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Token {
-    pub(crate) value: String,
+pub(crate) struct Token<'a> {
+    pub(crate) value: Cow<'a, str>,
     pub(crate) span: Span,
 }
 
-impl Token {
-    pub(crate) fn new(value: String, span: Span) -> Self {
-        Token { value, span }
+impl<'a> Token<'a> {
+    pub(crate) fn new(value: impl Into<Cow<'a, str>>, span: Span) -> Self {
+        Token {
+            value: value.into(),
+            span,
+        }
     }
 }
 
@@ -32,11 +38,11 @@ impl Token {
 /// # Tokenization Rules
 ///
 /// **Operators** are split into individual tokens:
-/// - Arithmetic: `+`, `-`, `*`, `/`, `^` (exponentiation)
+/// - Arithmetic: `+`, `-`, `*`, `/`, `//` (floor division), `^` (exponentiation)
 /// - Matrix operations: `@` (matrix multiplication)
 /// - Range: `:` (cell range operator, e.g., A1:C5)
 /// - Comma: `,` (function argument separator)
-/// - Each operator becomes a single-character token
+/// - Each operator becomes a single token (one character, except `//`)
 ///
 /// **Parentheses** are split into individual tokens:
 /// - Opening `(` and closing `)` parentheses
@@ -58,6 +64,7 @@ impl Token {
 ///
 /// **Identifiers** (cell references and function names) are kept together:
 /// - Cell references: "A1", "B2", "A_", "_1"
+/// - Previous-row references keep their caret: "C^" (see `is_previous_row_caret`)
 /// - Function names: "sum", "avg"
 /// - Alphanumeric characters and underscores
 ///
@@ -66,122 +73,144 @@ impl Token {
 ///
 /// For example, "A1 + B2 * 3" becomes tokens with values ["A1", "+", "B2", "*", "3"], and
 /// "sum(A_)" becomes tokens with values ["sum", "(", "A_", ")"], each with their position spans.
-pub(crate) fn tokenize_expression(expr: &str) -> Vec<Token> {
+pub(crate) fn tokenize_expression(expr: &str) -> Vec<Token<'_>> {
     let mut tokens = Vec::new();
-    let mut current_token = String::new();
-    let mut token_start = 0;
     let chars: Vec<char> = expr.chars().collect();
+    // Byte offset of each char (and of the end), so tokens can borrow from `expr`
+    let offsets: Vec<usize> = expr
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([expr.len()])
+        .collect();
+    let text = |start: usize, end: usize| &expr[offsets[start]..offsets[end]];
+
+    // While `in_token` is set, chars[token_start..i] is the identifier or number being read
+    let mut in_token = false;
+    let mut token_start = 0;
 
     let mut i = 0;
     while i < chars.len() {
         let ch = chars[i];
+        let current_token = if in_token { text(token_start, i) } else { "" };
 
         match ch {
             '"' => {
                 // Handle string literals
-                if !current_token.is_empty() {
-                    let trimmed = current_token.trim();
-                    if !trimmed.is_empty() {
-                        tokens.push(Token::new(trimmed.to_string(), Span::new(token_start, i)));
-                    }
-                    current_token.clear();
+                if in_token {
+                    push_trimmed(&mut tokens, current_token, Span::new(token_start, i));
+                    in_token = false;
                 }
 
                 // Find the closing quote
                 let string_start = i;
                 i += 1; // Skip opening quote
-                let mut string_value = String::new();
-
                 while i < chars.len() && chars[i] != '"' {
-                    string_value.push(chars[i]);
                     i += 1;
                 }
 
-                if i < chars.len() {
+                // The token keeps its quotes; an unterminated string is closed here
+                let value = if i < chars.len() {
                     i += 1; // Skip closing quote
-                }
-
-                // Store the string without quotes, but mark it as a string token with special prefix
-                tokens.push(Token::new(
-                    format!("\"{}\"", string_value),
-                    Span::new(string_start, i),
-                ));
-                token_start = i;
+                    Cow::Borrowed(text(string_start, i))
+                } else {
+                    Cow::Owned(format!("{}\"", text(string_start, i)))
+                };
+                tokens.push(Token::new(value, Span::new(string_start, i)));
+            }
+            '^' if is_previous_row_caret(current_token, chars.get(i + 1)) => {
+                // Previous-row reference such as C^ - the caret belongs to the column letter
+                i += 1;
             }
             '+' | '-' | '*' | '/' | '^' | '@' | '(' | ')' | ':' | ',' => {
-                if !current_token.is_empty() {
-                    let trimmed = current_token.trim();
-                    if !trimmed.is_empty() {
-                        tokens.push(Token::new(trimmed.to_string(), Span::new(token_start, i)));
-                    }
-                    current_token.clear();
+                if in_token {
+                    push_trimmed(&mut tokens, current_token, Span::new(token_start, i));
+                    in_token = false;
                 }
-                tokens.push(Token::new(ch.to_string(), Span::new(i, i + 1)));
-                token_start = i + 1;
-                i += 1;
+                // Floor division `//` is a single token, not two divisions
+                let len = if ch == '/' && chars.get(i + 1) == Some(&'/') {
+                    2
+                } else {
+                    1
+                };
+                tokens.push(Token::new(text(i, i + len), Span::new(i, i + len)));
+                i += len;
             }
             '.' => {
                 // Check if this is a decimal point in a number
                 // It's a decimal point if:
                 // 1. We're building a numeric token AND
                 // 2. The next character is a digit
-                let is_decimal_point = !current_token.is_empty()
+                let is_decimal_point = in_token
                     && current_token.chars().all(|c| c.is_ascii_digit())
                     && i + 1 < chars.len()
                     && chars[i + 1].is_ascii_digit();
 
                 if is_decimal_point {
                     // Include the decimal point in the current number token
-                    current_token.push(ch);
                     i += 1;
                 } else {
                     // It's the transpose operator - treat as separate token
-                    if !current_token.is_empty() {
-                        let trimmed = current_token.trim();
-                        if !trimmed.is_empty() {
-                            tokens.push(Token::new(trimmed.to_string(), Span::new(token_start, i)));
-                        }
-                        current_token.clear();
+                    if in_token {
+                        push_trimmed(&mut tokens, current_token, Span::new(token_start, i));
+                        in_token = false;
                     }
-                    tokens.push(Token::new(ch.to_string(), Span::new(i, i + 1)));
-                    token_start = i + 1;
+                    tokens.push(Token::new(text(i, i + 1), Span::new(i, i + 1)));
                     i += 1;
                 }
             }
             ' ' => {
-                if !current_token.is_empty() {
-                    let trimmed = current_token.trim();
-                    if !trimmed.is_empty() {
-                        tokens.push(Token::new(trimmed.to_string(), Span::new(token_start, i)));
-                    }
-                    current_token.clear();
+                if in_token {
+                    push_trimmed(&mut tokens, current_token, Span::new(token_start, i));
+                    in_token = false;
                 }
-                token_start = i + 1;
                 i += 1;
             }
             _ => {
-                if current_token.is_empty() {
+                if !in_token {
                     token_start = i;
+                    in_token = true;
                 }
-                current_token.push(ch);
                 i += 1;
             }
         }
     }
 
-    if !current_token.is_empty() {
-        let trimmed = current_token.trim();
-        if !trimmed.is_empty() {
-            tokens.push(Token::new(
-                trimmed.to_string(),
-                Span::new(token_start, chars.len()),
-            ));
-        }
+    if in_token {
+        push_trimmed(
+            &mut tokens,
+            text(token_start, chars.len()),
+            Span::new(token_start, chars.len()),
+        );
     }
 
     tokens
 }
 
+/// Adds an identifier or number token, unless it is only whitespace
+fn push_trimmed<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str, span: Span) {
+    let trimmed = text.trim();
+    if !trimmed.is_empty() {
+        tokens.push(Token::new(trimmed, span));
+    }
+}
+
+/// Checks whether a `^` ends a previous-row reference like `C^` rather than
+/// starting an exponent
+///
+/// The caret must follow a single column letter directly and be followed by
+/// whitespace, an operator, a closing parenthesis, a comma or the end of the
+/// expression, so `x^2` and `x^(n)` stay exponents.
+fn is_previous_row_caret(current_token: &str, next: Option<&char>) -> bool {
+    let mut letters = current_token.chars();
+    let is_column_letter =
+        matches!((letters.next(), letters.next()), (Some(c), None) if c.is_ascii_alphabetic());
+    is_column_letter
+        && matches!(
+            next,
+            None | Some(' ' | '+' | '-' | '*' | '/' | '@' | ')' | ',')
+        )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,4 +300,51 @@ mod tests {
         assert_eq!(tokens[4].value, "A_");
         assert_eq!(tokens[5].value, ")");
     }
+
+    #[test]
+    fn test_tokenize_previous_row_reference() {
+        let values = |expr: &str| -> Vec<String> {
+            tokenize_expression(expr)
+                .into_iter()
+                .map(|token| token.value.into_owned())
+                .collect()
+        };
+
+        assert_eq!(values("C^ + B_"), vec!["C^", "+", "B_"]);
+        assert_eq!(values("sum(c^)"), vec!["sum", "(", "c^", ")"]);
+        // Exponents are unchanged
+        assert_eq!(values("A1^2"), vec!["A1", "^", "2"]);
+        assert_eq!(values("x^2"), vec!["x", "^", "2"]);
+        assert_eq!(values("x ^ 2"), vec!["x", "^", "2"]);
+    }
+
+    #[test]
+    fn test_tokenize_floor_division() {
+        let tokens = tokenize_expression("A_//10 / 2");
+        let values: Vec<&str> = tokens.iter().map(|token| token.value.as_ref()).collect();
+        assert_eq!(values, vec!["A_", "//", "10", "/", "2"]);
+        assert_eq!(tokens[1].span, Span::new(2, 4));
+        assert_eq!(tokens[2].span, Span::new(4, 6));
+    }
+
+    #[test]
+    fn test_tokenize_borrows_from_expression() {
+        // Spans count characters while the values are slices of the expression
+        let tokens = tokenize_expression("from(\"日本\", A1) + x");
+        let values: Vec<&str> = tokens.iter().map(|token| token.value.as_ref()).collect();
+        assert_eq!(
+            values,
+            vec!["from", "(", "\"日本\"", ",", "A1", ")", "+", "x"]
+        );
+        assert!(tokens
+            .iter()
+            .all(|token| matches!(token.value, Cow::Borrowed(_))));
+        assert_eq!(tokens[2].span, Span::new(5, 9));
+        assert_eq!(tokens[4].span, Span::new(11, 13));
+
+        // An unterminated string runs to the end of the expression
+        let tokens = tokenize_expression("from(\"sales");
+        assert_eq!(tokens[2].value, "\"sales\"");
+        assert_eq!(tokens[2].span, Span::new(5, 11));
+    }
 }
@@ -12,13 +12,6 @@ impl Span {
         Span { start, end }
     }
 
-    pub fn single(pos: usize) -> Self {
-        Span {
-            start: pos,
-            end: pos + 1,
-        }
-    }
-
     pub(crate) fn merge(&self, other: &Span) -> Span {
         Span {
             start: self.start.min(other.start),
@@ -39,15 +32,25 @@ pub(crate) fn formula_row_to_table_index(row_num: usize) -> usize {
 }
 
 /// Converts a column index to its letter representation (0 -> A, 1 -> B, etc.)
+///
+/// Columns past Z continue as in spreadsheets: 26 -> AA, 27 -> AB, ...
 pub(crate) fn col_index_to_letter(col: usize) -> String {
-    let col_char = (b'A' + col as u8) as char;
-    col_char.to_string()
+    let mut letters = Vec::new();
+    let mut remaining = col + 1;
+    while remaining > 0 {
+        remaining -= 1;
+        letters.push((b'A' + (remaining % 26) as u8) as char);
+        remaining /= 26;
+    }
+    letters.iter().rev().collect()
 }
 
 /// Represents a value in a formula - either a scalar or a matrix
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum Value {
+pub enum Value {
+    /// A single number
     Scalar(Decimal),
+    /// A `rows`×`cols` matrix (vectors are 1×n or n×1 matrices)
     Matrix {
         rows: usize,
         cols: usize,
@@ -126,6 +129,9 @@ pub(crate) enum CellReference {
     ColumnVector {
         col: usize,
     }, // A_, B_, etc.
+    PreviousRow {
+        col: usize,
+    }, // A^, B^, etc. (column vector shifted down one row)
     RowVector {
         row: usize,
     }, // _1, _2, etc.
@@ -181,6 +187,14 @@ pub(crate) enum Statement {
     Let { name: String, span: Span },
     /// Cell assignment: A1 = expression
     Assignment(Assignment),
+    /// Assignment into another table: to("table_id", A1) = expression
+    TableAssignment {
+        table_id: String,
+        assignment: Assignment,
+    },
+    /// Column filled one row at a time: fill D = B * C, where the bare
+    /// letters stand for the cells of the row being computed
+    Fill { col: usize },
 }
 
 impl Statement {
@@ -193,4 +207,12 @@ impl Statement {
     pub(crate) fn assignment(assignment: Assignment) -> Self {
         Statement::Assignment(assignment)
     }
+
+    /// Creates a TableAssignment statement
+    pub(crate) fn table_assignment(table_id: String, assignment: Assignment) -> Self {
+        Statement::TableAssignment {
+            table_id,
+            assignment,
+        }
+    }
 }
@@ -0,0 +1,577 @@
+//! Conversion between markdown tables and JSON
+
+use super::csv::{export_tables, records_to_table, TableSelection};
+use super::parser::is_separator_row;
+use crate::common::{ProcessingError, ProcessingResult};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Exports every markdown table in the document as JSON
+///
+/// Each table becomes an array with one object per data row, keyed by the
+/// header cells. Formulas are evaluated first (as by [`super::format_tables`]),
+/// so computed cells export their values. Cells that parse as decimal numbers
+/// are written as JSON numbers and every other cell as a string. Repeated
+/// header names get a `_2`, `_3`, ... suffix. Escaped pipes (`\|`) become
+/// plain `|`. When the document contains several tables, their arrays are
+/// separated by a blank line. Tables inside code fences are ignored.
+///
+/// # Returns
+///
+/// A [`ProcessingResult`] containing the JSON text, along with any formula
+/// errors. If the document has no tables, the output is empty and an error is
+/// reported.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::export_json;
+///
+/// let result = export_json("| Name | Qty |\n|---|---|\n| Bob | 3 |\n");
+/// assert_eq!(result.output, "[\n  {\"Name\": \"Bob\", \"Qty\": 3}\n]\n");
+/// ```
+#[allow(dead_code)]
+pub fn export_json(text: &str) -> ProcessingResult {
+    export_json_selected(text, &TableSelection::All)
+}
+
+/// Exports the tables picked by `selection` as JSON
+///
+/// Works like [`export_json`]. Formula errors in tables that are not selected
+/// are not reported. If no table matches the selection, the output is empty
+/// and an error is reported.
+pub fn export_json_selected(text: &str, selection: &TableSelection) -> ProcessingResult {
+    export_tables(text, selection, table_to_json)
+}
+
+/// Converts a JSON array of objects into an aligned markdown table
+///
+/// The columns are the union of the objects' keys, in the order each key is
+/// first seen, and every object becomes one row. Missing keys and `null`
+/// give empty cells, numbers keep their JSON spelling, booleans become `true`
+/// or `false`, and nested arrays or objects are written as compact JSON.
+/// Line breaks in strings are folded into spaces and pipes are escaped, as by
+/// [`super::import_csv`].
+///
+/// # Returns
+///
+/// A [`ProcessingResult`] containing the formatted table. If the input is not
+/// valid JSON, is not an array of objects, or has no keys, the output is
+/// empty and an error is reported.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::import_json;
+///
+/// let result = import_json(r#"[{"Name": "Bob", "Qty": 3}, {"Name": "Ann"}]"#);
+/// assert_eq!(
+///     result.output,
+///     "| Name | Qty |\n| ---- | --- |\n| Bob  | 3   |\n| Ann  |     |\n"
+/// );
+/// ```
+pub fn import_json(text: &str) -> ProcessingResult {
+    match json_to_records(text) {
+        Ok(records) => ProcessingResult::success(records_to_table(records)),
+        Err(e) => ProcessingResult::with_errors(String::new(), vec![ProcessingError::table(0, e)]),
+    }
+}
+
+/// Parses a JSON array of objects into a header record followed by one record per object
+fn json_to_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let JsonValue::Array(items) = JsonParser::new(text).parse_document()? else {
+        return Err("expected a JSON array of objects".to_string());
+    };
+
+    let mut header: Vec<String> = Vec::new();
+    let mut objects = Vec::with_capacity(items.len());
+    for (idx, item) in items.into_iter().enumerate() {
+        let JsonValue::Object(members) = item else {
+            return Err(format!(
+                "expected a JSON array of objects, but item {} is not an object",
+                idx + 1
+            ));
+        };
+        for (key, _) in &members {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+        objects.push(members);
+    }
+    if header.is_empty() {
+        return Err("no keys found in JSON input".to_string());
+    }
+
+    let mut records = Vec::with_capacity(objects.len() + 1);
+    for members in &objects {
+        let record = header
+            .iter()
+            .map(|key| {
+                // The last of several members with the same key wins
+                members
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| name == key)
+                    .map_or_else(String::new, |(_, value)| value.to_cell())
+            })
+            .collect();
+        records.push(record);
+    }
+    records.insert(0, header);
+    Ok(records)
+}
+
+/// A parsed JSON value; numbers keep their source text
+#[derive(Debug, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// The text of the value as a table cell
+    fn to_cell(&self) -> String {
+        match self {
+            JsonValue::Null => String::new(),
+            JsonValue::Bool(value) => value.to_string(),
+            JsonValue::Number(number) => number.clone(),
+            JsonValue::String(text) => text.clone(),
+            JsonValue::Array(_) | JsonValue::Object(_) => {
+                let mut json = String::new();
+                self.write_compact(&mut json);
+                json
+            }
+        }
+    }
+
+    /// Writes the value as JSON without any whitespace
+    fn write_compact(&self, json: &mut String) {
+        match self {
+            JsonValue::Null => json.push_str("null"),
+            JsonValue::Bool(value) => json.push_str(&value.to_string()),
+            JsonValue::Number(number) => json.push_str(number),
+            JsonValue::String(text) => push_json_string(json, text),
+            JsonValue::Array(items) => {
+                json.push('[');
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        json.push(',');
+                    }
+                    item.write_compact(json);
+                }
+                json.push(']');
+            }
+            JsonValue::Object(members) => {
+                json.push('{');
+                for (idx, (key, value)) in members.iter().enumerate() {
+                    if idx > 0 {
+                        json.push(',');
+                    }
+                    push_json_string(json, key);
+                    json.push(':');
+                    value.write_compact(json);
+                }
+                json.push('}');
+            }
+        }
+    }
+}
+
+/// Recursive descent parser for JSON text (RFC 8259)
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(text: &'a str) -> Self {
+        JsonParser { text, pos: 0 }
+    }
+
+    /// Parses the whole text as a single value
+    fn parse_document(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        if self.peek().is_none() {
+            return Err("no JSON value found in input".to_string());
+        }
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(self.error("unexpected text after the JSON value")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => self.parse_string().map(JsonValue::String),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some('t') => self.parse_literal("true", JsonValue::Bool(true)),
+            Some('f') => self.parse_literal("false", JsonValue::Bool(false)),
+            Some('n') => self.parse_literal("null", JsonValue::Null),
+            Some(_) => Err(self.error("expected a JSON value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return Err(self.error("expected ':' after an object key"));
+            }
+            self.skip_whitespace();
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(JsonValue::Object(members));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or '}' in object"));
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(JsonValue::Array(items));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or ']' in array"));
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.next() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape in string")),
+                    };
+                    text.push(escaped);
+                }
+                c if c < ' ' => return Err(self.error("control character in string")),
+                c => text.push(c),
+            }
+        }
+    }
+
+    /// Parses the hex digits after `\u`, combining a surrogate pair if present
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) && self.text[self.pos..].starts_with("\\u") {
+            self.pos += 2;
+            let low = self.parse_hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("invalid surrogate pair in string"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape in string"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).unwrap_or("");
+        if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(self.error("expected four hex digits after \\u"));
+        }
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).map_err(|e| e.to_string())
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        self.eat('-');
+        if !self.eat('0') && self.eat_digits() == 0 {
+            return Err(self.error("expected a digit"));
+        }
+        if self.eat('.') && self.eat_digits() == 0 {
+            return Err(self.error("expected a digit after '.'"));
+        }
+        if self.eat('e') || self.eat('E') {
+            if !self.eat('+') {
+                self.eat('-');
+            }
+            if self.eat_digits() == 0 {
+                return Err(self.error("expected a digit in exponent"));
+            }
+        }
+        Ok(JsonValue::Number(self.text[start..self.pos].to_string()))
+    }
+
+    fn parse_literal(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("expected a JSON value"))
+        }
+    }
+
+    fn eat_digits(&mut self) -> usize {
+        let count = self.text[self.pos..]
+            .bytes()
+            .take_while(u8::is_ascii_digit)
+            .count();
+        self.pos += count;
+        count
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// An error message pointing at the current line and column
+    fn error(&self, message: &str) -> String {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        format!(
+            "invalid JSON at line {}, column {}: {}",
+            line, column, message
+        )
+    }
+}
+
+/// Converts parsed table rows to a JSON array of row objects, one per line
+fn table_to_json(rows: &[Vec<String>]) -> String {
+    let Some((header, rest)) = rows.split_first() else {
+        return "[]\n".to_string();
+    };
+    let data = match rest.first() {
+        Some(separator) if is_separator_row(separator) => &rest[1..],
+        _ => rest,
+    };
+    let keys = unique_keys(header);
+
+    if data.is_empty() {
+        return "[]\n".to_string();
+    }
+    let mut json = String::from("[\n");
+    for (idx, row) in data.iter().enumerate() {
+        json.push_str("  {");
+        for (col, key) in keys.iter().enumerate() {
+            if col > 0 {
+                json.push_str(", ");
+            }
+            push_json_string(&mut json, key);
+            json.push_str(": ");
+            push_json_value(&mut json, row.get(col).map_or("", String::as_str));
+        }
+        json.push('}');
+        if idx + 1 < data.len() {
+            json.push(',');
+        }
+        json.push('\n');
+    }
+    json.push_str("]\n");
+    json
+}
+
+/// Object keys for the header cells, with `_2`, `_3`, ... added to repeated names
+fn unique_keys(header: &[String]) -> Vec<String> {
+    let names: Vec<String> = header.iter().map(|cell| cell_text(cell)).collect();
+    let mut used: HashSet<String> = HashSet::new();
+    let mut keys = Vec::with_capacity(names.len());
+    for name in &names {
+        let mut key = name.clone();
+        let mut suffix = 2;
+        while used.contains(&key) || (key != *name && names.contains(&key)) {
+            key = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+        used.insert(key.clone());
+        keys.push(key);
+    }
+    keys
+}
+
+/// The text of a cell as exported, with escaped pipes unescaped
+fn cell_text(cell: &str) -> String {
+    cell.trim().replace("\\|", "|")
+}
+
+/// Writes a cell as a JSON number if it is a decimal number, else as a string
+fn push_json_value(json: &mut String, cell: &str) {
+    let text = cell_text(cell);
+    match Decimal::from_str(&text) {
+        Ok(number) => json.push_str(&number.to_string()),
+        Err(_) => push_json_string(json, &text),
+    }
+}
+
+/// Writes `text` as a quoted JSON string, escaping as RFC 8259 requires
+fn push_json_string(json: &mut String, text: &str) {
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(table: &[&[&str]]) -> Vec<Vec<String>> {
+        table
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_table_to_json() {
+        let table = rows(&[
+            &["Name", "Qty", "Note"],
+            &["---", "---", "---"],
+            &["Bob", "1.50", "say \"hi\""],
+            &["Ann", "-2", "a \\| b\\c"],
+            &["", "007", "1e5"],
+        ]);
+        assert_eq!(
+            table_to_json(&table),
+            "[\n  {\"Name\": \"Bob\", \"Qty\": 1.50, \"Note\": \"say \\\"hi\\\"\"},\n  \
+             {\"Name\": \"Ann\", \"Qty\": -2, \"Note\": \"a | b\\\\c\"},\n  \
+             {\"Name\": \"\", \"Qty\": 7, \"Note\": \"1e5\"}\n]\n"
+        );
+
+        assert_eq!(table_to_json(&rows(&[&["A"], &["---"]])), "[]\n");
+    }
+
+    #[test]
+    fn test_unique_keys() {
+        let header = rows(&[&["Qty", "Name", "Qty", "Qty_2", "Qty"]]);
+        assert_eq!(
+            unique_keys(&header[0]),
+            vec!["Qty", "Name", "Qty_3", "Qty_2", "Qty_4"]
+        );
+    }
+
+    #[test]
+    fn test_json_to_records() {
+        let records = json_to_records(
+            r#"[
+              {"Name": "Bob", "Qty": 1.50, "Tags": ["a", "b"]},
+              {"Active": true, "Name": "Ann \u00e9\ud83d\ude00", "Qty": null},
+              {}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            records,
+            rows(&[
+                &["Name", "Qty", "Tags", "Active"],
+                &["Bob", "1.50", "[\"a\",\"b\"]", ""],
+                &["Ann é😀", "", "", "true"],
+                &["", "", "", ""],
+            ])
+        );
+    }
+
+    #[test]
+    fn test_json_to_records_errors() {
+        for (input, message) in [
+            ("", "no JSON value found in input"),
+            ("{\"a\": 1}", "expected a JSON array of objects"),
+            ("[{\"a\": 1}, 2]", "item 2 is not an object"),
+            ("[]", "no keys found in JSON input"),
+            (
+                "[{\"a\": 01}]",
+                "line 1, column 9: expected ',' or '}' in object",
+            ),
+            (
+                "[\n  {\"a\": tru}]",
+                "line 2, column 9: expected a JSON value",
+            ),
+            ("[{\"a\": \"x}]", "unterminated string"),
+            ("[{\"a\": 1}] x", "unexpected text after the JSON value"),
+        ] {
+            let error = json_to_records(input).unwrap_err();
+            assert!(error.contains(message), "{:?}: {}", input, error);
+        }
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        let mut json = String::new();
+        push_json_string(&mut json, "tab\there\u{1}");
+        assert_eq!(json, "\"tab\\there\\u0001\"");
+    }
+}
@@ -1,60 +1,71 @@
+mod check;
+mod columns;
+mod csv;
 mod error;
 mod formatter;
 mod formula;
+mod json;
+mod model;
 mod parser;
-
-use crate::common::{CodeFenceTracker, ProcessingError, ProcessingResult};
-use formatter::format_table_row;
-use formula::apply_formulas_with_tables;
+mod transpose;
+
+// The binary goes through the _with_delimiter/_selected variants; these are library API
+pub use check::count_changed_tables;
+pub(crate) use check::lint_tables;
+#[allow(unused_imports)]
+pub use csv::{export_csv, import_csv};
+pub use csv::{export_csv_selected, import_csv_with_delimiter, TableSelection};
+#[allow(unused_imports)]
+pub use json::export_json;
+pub use json::{export_json_selected, import_json};
+// The binary formats through apply_table_formulas; this is library API
+#[allow(unused_imports)]
+pub use formula::apply_formulas_with_tables;
+pub use formula::{FormulaResults, Value};
+pub use model::{FormatOptions, Table};
+pub use transpose::transpose_tables;
+
+use crate::common::{with_line_endings, CodeFenceTracker, ProcessingError, ProcessingResult};
+use formula::{apply_table_formulas, evaluate_constant, FormulaCache};
 use parser::{
-    extract_formulas_from_comment, is_formula_comment, is_md_table_comment, is_table_row,
-    parse_table_row,
+    extract_constants_from_comment, extract_formulas_from_comment, find_full_cells, find_table_id,
+    is_formula_comment, is_md_table_comment, is_md_table_const_comment, is_pipeless_row, pad_rows,
+    parse_table_row, restore_full_cells, skip_generated_comments, strip_column_edits, table_end,
+    FullCell, RowFilter, SortColumn, SortSpec, TableDirective, TotalSpec,
 };
 
 /// Creates a new empty markdown table with the specified dimensions
+///
+/// Library API; `md new` goes through [`create_table_with_headers`].
+#[allow(dead_code)]
 pub fn create_table(rows: usize, cols: usize) -> String {
-    if rows == 0 || cols == 0 {
-        return String::new();
-    }
-
-    let mut table_rows = Vec::new();
-
-    // Create header row (empty)
-    let header: Vec<String> = vec![String::new(); cols];
-    table_rows.push(header);
-
-    // Create separator row (not counted in the row count)
-    let separator: Vec<String> = vec!["---".to_string(); cols];
-    table_rows.push(separator);
-
-    // Create data rows
-    for _ in 0..rows {
-        let row: Vec<String> = vec![String::new(); cols];
-        table_rows.push(row);
-    }
+    create_table_with_headers(rows, &vec![String::new(); cols])
+}
 
-    // Calculate column widths
-    let mut col_widths = vec![0; cols];
-    for row in &table_rows {
-        for (col_idx, cell) in row.iter().enumerate() {
-            col_widths[col_idx] = col_widths[col_idx].max(cell.len());
-        }
+/// Creates a new markdown table with the given header cells and `rows` empty data rows
+///
+/// Columns are as wide as their header text (at least 3 characters).
+pub fn create_table_with_headers(rows: usize, headers: &[String]) -> String {
+    if rows == 0 || headers.is_empty() {
+        return String::new();
     }
 
-    // Format each row
-    let formatted_rows: Vec<String> = table_rows
-        .iter()
-        .map(|row| format_table_row(row, &col_widths))
-        .collect();
-
-    formatted_rows.join("\n")
+    // The separator row is not counted in `rows`
+    let table = Table::new(
+        headers.to_vec(),
+        vec![vec![String::new(); headers.len()]; rows],
+    );
+    table.to_markdown(&FormatOptions::default())
 }
 
-/// Parses a table specification string into row and column counts.
+/// Parses a table specification string into a row count and header cells.
 ///
-/// The specification must follow the format `"table:R:C"` where:
-/// - `R` is the number of data rows (must be > 0)
-/// - `C` is the number of columns (must be > 0)
+/// The specification must follow one of two formats:
+/// - `"table:R:C"`, where `R` is the number of data rows and `C` the number
+///   of columns (both > 0); every header cell is empty
+/// - `"table:R:H1,H2,..."`, where the columns are named by a comma-separated
+///   header list. A header containing a comma can be double-quoted
+///   (`"Name, Inc"`), with embedded quotes doubled as in CSV
 ///
 /// # Arguments
 ///
@@ -62,31 +73,35 @@ pub fn create_table(rows: usize, cols: usize) -> String {
 ///
 /// # Returns
 ///
-/// * `Ok((rows, cols))` if the spec is valid
+/// * `Ok((rows, headers))` if the spec is valid; the column count is `headers.len()`
 /// * `Err(String)` with an error message if the spec is invalid
 ///
 /// # Examples
 ///
 /// ```
 /// use mdutils::table::parse_table_spec;
-/// let (rows, cols) = parse_table_spec("table:2:3").unwrap();
+/// let (rows, headers) = parse_table_spec("table:2:3").unwrap();
 /// assert_eq!(rows, 2);
-/// assert_eq!(cols, 3);
+/// assert_eq!(headers, vec!["", "", ""]);
+///
+/// let (rows, headers) = parse_table_spec("table:3:\"Name, Inc\",Price").unwrap();
+/// assert_eq!(rows, 3);
+/// assert_eq!(headers, vec!["Name, Inc", "Price"]);
 /// ```
 ///
 /// # Errors
 ///
 /// Returns an error if:
-/// - Format is not `"table:R:C"`
+/// - Format is not `"table:R:C"` or `"table:R:H1,H2,..."`
 /// - Row or column count is not a valid number
 /// - Row or column count is zero
-pub fn parse_table_spec(spec: &str) -> Result<(usize, usize), String> {
-    // Expected format: "table:R:C" where R is rows and C is columns
-    let parts: Vec<&str> = spec.split(':').collect();
+pub fn parse_table_spec(spec: &str) -> Result<(usize, Vec<String>), String> {
+    // Expected format: "table:R:C" or "table:R:H1,H2,..." (headers may contain ':')
+    let parts: Vec<&str> = spec.splitn(3, ':').collect();
 
-    if parts.len() != 3 || parts[0] != "table" {
+    if parts.len() != 3 || parts[0] != "table" || parts[2].is_empty() {
         return Err(format!(
-            "Invalid spec format '{}'. Expected format: table:R:C (e.g., table:2:3)",
+            "Invalid spec format '{}'. Expected format: table:R:C (e.g., table:2:3) or table:R:HEADERS (e.g., table:3:Name,Price,Qty)",
             spec
         ));
     }
@@ -94,15 +109,111 @@ pub fn parse_table_spec(spec: &str) -> Result<(usize, usize), String> {
     let rows = parts[1]
         .parse::<usize>()
         .map_err(|_| format!("Invalid row count '{}'", parts[1]))?;
-    let cols = parts[2]
-        .parse::<usize>()
-        .map_err(|_| format!("Invalid column count '{}'", parts[2]))?;
 
-    if rows == 0 || cols == 0 {
+    let headers = if parts[2].bytes().all(|b| b.is_ascii_digit()) {
+        let cols = parts[2]
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid column count '{}'", parts[2]))?;
+        vec![String::new(); cols]
+    } else {
+        parse_header_list(parts[2])?
+    };
+
+    if rows == 0 || headers.is_empty() {
         return Err("Row and column counts must be greater than 0".to_string());
     }
 
-    Ok((rows, cols))
+    Ok((rows, headers))
+}
+
+/// Splits a spec's comma-separated header list, honoring CSV-style quotes
+fn parse_header_list(list: &str) -> Result<Vec<String>, String> {
+    let mut records = csv::parse_csv(list, ',')
+        .map_err(|_| format!("Invalid header list '{}': unterminated quoted header", list))?;
+    if records.len() != 1 {
+        return Err(format!(
+            "Invalid header list '{}': headers must be on a single line",
+            list
+        ));
+    }
+
+    Ok(records
+        .remove(0)
+        .iter()
+        .map(|header| csv::field_to_cell(header.trim()))
+        .collect())
+}
+
+/// Collects every table that declares an `id` attribute, keyed by that ID
+///
+/// Each table is returned as parsed rows (header, separator, then data rows),
+/// the same shape `from()` reads and [`apply_formulas_with_tables`] expects
+/// for its table map. Tables inside code fences are ignored. If an ID is
+/// declared more than once, the first table wins; [`format_tables`] reports
+/// the duplicates as errors.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::collect_tables;
+///
+/// let tables = collect_tables("| Item | Qty |\n|---|---|\n| Apple | 3 |\n<!-- md-table: id=\"stock\" -->\n");
+/// assert_eq!(tables["stock"][2], vec!["Apple", "3"]);
+/// ```
+#[allow(dead_code)]
+pub fn collect_tables(text: &str) -> std::collections::HashMap<String, Vec<Vec<String>>> {
+    let lines: Vec<&str> = text.lines().collect();
+    collect_tables_by_id(&lines).0
+}
+
+/// Counts the tables in the document, ignoring those inside code fences
+pub(crate) fn count_tables(text: &str) -> usize {
+    let lines: Vec<&str> = text.lines().collect();
+    parser::collect_tables(&lines).len()
+}
+
+/// Collects tables with IDs along with an error for each duplicate ID
+///
+/// Errors are keyed by the 0-indexed first line of the offending table.
+#[allow(clippy::type_complexity)]
+fn collect_tables_by_id(
+    lines: &[&str],
+) -> (
+    std::collections::HashMap<String, Vec<Vec<String>>>,
+    std::collections::HashMap<usize, String>,
+) {
+    use std::collections::HashMap;
+
+    let mut table_map = HashMap::new();
+    let mut first_lines: HashMap<String, usize> = HashMap::new();
+    let mut duplicate_errors = HashMap::new();
+
+    for (start_line, mut rows) in parser::collect_tables(lines) {
+        let end = start_line + rows.len();
+        let Some(id) = find_table_id(lines, end) else {
+            continue;
+        };
+        // Other tables read this one as it will be rendered, with cells in full
+        pad_rows(&mut rows);
+        restore_full_cells(&mut rows, &find_full_cells(lines, end));
+
+        if let Some(previous_line) = first_lines.get(&id) {
+            duplicate_errors.insert(
+                start_line,
+                format!(
+                    "Duplicate table ID '{}' found at line {} (previously defined at line {})",
+                    id,
+                    start_line + 1,
+                    previous_line + 1
+                ),
+            );
+        } else {
+            first_lines.insert(id.clone(), start_line);
+            table_map.insert(id, rows);
+        }
+    }
+
+    (table_map, duplicate_errors)
 }
 
 /// Formats markdown tables in the input text and returns the full text with aligned tables
@@ -113,71 +224,137 @@ pub fn parse_table_spec(spec: &str) -> Result<(usize, usize), String> {
 /// - The formatted document (with tables aligned and formulas evaluated)
 /// - Any errors that occurred during formula evaluation (also embedded inline as `<!-- md-error: ... -->`)
 pub fn format_tables(text: &str) -> ProcessingResult {
-    use std::collections::HashMap;
+    format_tables_with_options(text, &FormatOptions::default())
+}
+
+/// Formats markdown tables like [`format_tables`], starting every table from `defaults`
+///
+/// Attributes in a table's `md-table` directive take precedence over `defaults`,
+/// so `md table --compact` still aligns a table marked `style=aligned`.
+pub fn format_tables_with_options(text: &str, defaults: &FormatOptions) -> ProcessingResult {
+    with_line_endings(text, |text| format_tables_lf(text, defaults))
+}
+
+/// Formats the tables of a document with `\n` line endings
+fn format_tables_lf(text: &str, defaults: &FormatOptions) -> ProcessingResult {
+    use std::collections::{HashMap, HashSet};
 
     let lines: Vec<&str> = text.lines().collect();
-    let mut collected_errors: Vec<ProcessingError> = Vec::new();
 
-    // First pass: collect all tables with IDs (skip tables inside code fences)
-    let mut table_map: HashMap<String, Vec<Vec<String>>> = HashMap::new();
-    let mut i = 0;
+    // Collect document constants from md-table-const comments (skip code fences)
+    // Errors are keyed by line so they can be emitted after the offending comment
+    // (or, for tables, after the table's directive comments)
+    let mut constants: HashMap<String, Value> = HashMap::new();
+    let mut document_errors: HashMap<usize, Vec<String>> = HashMap::new();
     let mut fence_tracker = CodeFenceTracker::new();
 
-    while i < lines.len() {
-        // Process line through fence tracker
-        fence_tracker.process_line(lines[i]);
+    for (line_idx, line) in lines.iter().enumerate() {
+        fence_tracker.process_line(line);
 
-        // Skip lines inside code fences
-        if fence_tracker.is_inside_code_block() || crate::common::is_code_fence(lines[i]) {
-            i += 1;
+        if fence_tracker.is_inside_code_block()
+            || crate::common::is_code_fence(line)
+            || !is_md_table_const_comment(line)
+        {
             continue;
         }
 
-        if is_table_row(lines[i]) {
-            let mut table_lines = Vec::new();
-            table_lines.push(lines[i]);
-            i += 1;
-
-            while i < lines.len() && is_table_row(lines[i]) {
-                table_lines.push(lines[i]);
-                i += 1;
-            }
-
-            // Check for table ID in following comment
-            let mut table_id: Option<String> = None;
-            if i < lines.len() && is_md_table_comment(lines[i]) {
-                if let Ok((id, _formulas)) = extract_formulas_from_comment(lines[i]) {
-                    table_id = id;
+        for definition in extract_constants_from_comment(line) {
+            let error = match evaluate_constant(&definition, &constants) {
+                Ok((name, _)) if constants.contains_key(&name) => {
+                    Some(format!("constant '{}' is already defined", name))
                 }
-
-                // Also check continuation lines
-                let mut j = i + 1;
-                while j < lines.len() && is_formula_comment(lines[j]) {
-                    if let Ok((id, _formulas)) = extract_formulas_from_comment(lines[j]) {
-                        if id.is_some() && table_id.is_none() {
-                            table_id = id;
-                            break;
-                        }
-                    }
-                    j += 1;
+                Ok((name, value)) => {
+                    constants.insert(name, value);
+                    None
                 }
-            }
+                Err(e) => Some(e),
+            };
 
-            // If this table has an ID, parse and store it
-            if let Some(id) = table_id {
-                let rows: Vec<Vec<String>> = table_lines
-                    .iter()
-                    .map(|line| parse_table_row(line))
-                    .collect();
-                table_map.insert(id, rows);
+            if let Some(error) = error {
+                document_errors.entry(line_idx).or_default().push(error);
             }
-        } else {
-            i += 1;
         }
     }
 
-    // Second pass: format tables with formulas (with access to table_map)
+    // First pass: collect all tables with IDs (skip tables inside code fences).
+    // Duplicate IDs are reported on the first line of the later table
+    let (mut table_map, duplicate_errors) = collect_tables_by_id(&lines);
+    for (line_idx, error) in duplicate_errors {
+        document_errors.entry(line_idx).or_default().push(error);
+    }
+
+    // Second pass: format tables with formulas (with access to table_map).
+    // Formulas can write into other tables with to(), so if a table receives
+    // writes after it has been rendered, render the document once more.
+    // Tables repeating the same formulas share one parse of each
+    let mut written_tables = HashSet::new();
+    let mut cache = FormulaCache::default();
+    let mut rendered = render_tables(
+        &lines,
+        &mut table_map,
+        &mut written_tables,
+        &constants,
+        &document_errors,
+        defaults,
+        &mut cache,
+    );
+    if rendered.stale {
+        rendered = render_tables(
+            &lines,
+            &mut table_map,
+            &mut written_tables,
+            &constants,
+            &document_errors,
+            defaults,
+            &mut cache,
+        );
+    }
+    let RenderedTables {
+        output,
+        errors: collected_errors,
+        ..
+    } = rendered;
+
+    let mut result = output.join("\n");
+
+    // Preserve trailing newline if the original input had one
+    if text.ends_with('\n') {
+        result.push('\n');
+    }
+
+    ProcessingResult::with_errors(result, collected_errors)
+}
+
+/// Output of one rendering pass over a document's tables
+struct RenderedTables {
+    output: Vec<String>,
+    errors: Vec<ProcessingError>,
+    /// True if a formula wrote into a table that had already been rendered
+    stale: bool,
+}
+
+/// Formats every table in the document, evaluating its formulas
+///
+/// Tables listed in `written_tables` are rendered from their (modified) rows in
+/// `table_map` rather than from the document text; the IDs of tables written by
+/// `to()` during this pass are added to it. Every table starts from the
+/// `defaults` options before its directive is applied. Formulas are parsed
+/// through `cache`.
+fn render_tables(
+    lines: &[&str],
+    table_map: &mut std::collections::HashMap<String, Vec<Vec<String>>>,
+    written_tables: &mut std::collections::HashSet<String>,
+    constants: &std::collections::HashMap<String, Value>,
+    document_errors: &std::collections::HashMap<usize, Vec<String>>,
+    defaults: &FormatOptions,
+    cache: &mut FormulaCache,
+) -> RenderedTables {
+    use std::collections::HashSet;
+
     let mut output = Vec::new();
+    let mut collected_errors = Vec::new();
+    let mut rendered_tables: HashSet<String> = HashSet::new();
+    let mut stale = false;
     let mut current_table_lines = Vec::new();
     let mut i = 0;
     let mut fence_tracker = CodeFenceTracker::new();
@@ -193,75 +370,151 @@ pub fn format_tables(text: &str) -> ProcessingResult {
             continue;
         }
 
-        if is_table_row(lines[i]) {
-            // Start collecting table lines
-            let current_table_start_line = i + 1; // 1-indexed for user display
-            current_table_lines.push(lines[i]);
-            i += 1;
-
+        if let Some(end) = table_end(lines, i) {
             // Collect all consecutive table lines
-            while i < lines.len() && is_table_row(lines[i]) {
-                current_table_lines.push(lines[i]);
-                i += 1;
-            }
+            let current_table_start_line = i + 1; // 1-indexed for user display
+            current_table_lines.extend_from_slice(&lines[i..end]);
+            // Cells cut short by wrap=ellipsis on the last run get their full text back
+            let full_cells = find_full_cells(lines, end);
+            i = end;
 
-            // Collect all formula comments and extract table ID from first comment
+            // Collect all formula comments and merge their settings into one directive
             let mut formula_comments = Vec::new();
-            let mut table_id: Option<String> = None;
+            let mut directive = TableDirective::default();
 
             // Look for <!-- md-table: --> comments
             if i < lines.len() && is_md_table_comment(lines[i]) {
-                let comment_line = lines[i];
-                match extract_formulas_from_comment(comment_line) {
-                    Ok((id, formulas)) => {
-                        // Extract ID from first comment if present
-                        if id.is_some() {
-                            table_id = id;
-                        }
-                        formula_comments.push((comment_line, formulas, None));
-                    }
-                    Err(e) => {
-                        // Parse error - store it to output later
-                        formula_comments.push((comment_line, Vec::new(), Some(e)));
-                    }
-                }
-                i += 1;
-
-                // Collect additional formula comments on following lines
-                while i < lines.len() && is_formula_comment(lines[i]) {
+                loop {
                     let comment_line = lines[i];
                     match extract_formulas_from_comment(comment_line) {
-                        Ok((id, formulas)) => {
-                            // Extract ID if present (though typically only in first comment)
-                            if id.is_some() && table_id.is_none() {
-                                table_id = id;
-                            }
-                            formula_comments.push((comment_line, formulas, None));
+                        Ok(parsed) => {
+                            formula_comments.push((comment_line, parsed.formulas.clone(), None));
+                            directive.merge(parsed);
                         }
                         Err(e) => {
+                            // Parse error - store it to output later
                             formula_comments.push((comment_line, Vec::new(), Some(e)));
                         }
                     }
-                    i += 1;
+                    // Drop error/warning comments from a previous run; fresh ones are emitted below
+                    i = skip_generated_comments(lines, i + 1);
+
+                    // Collect additional formula comments on following lines
+                    if i >= lines.len() || !is_formula_comment(lines[i]) {
+                        break;
+                    }
                 }
+            } else {
+                // md-cell comments from default options; fresh ones are emitted below
+                i = skip_generated_comments(lines, i);
             }
 
+            let TableDirective {
+                id: table_id,
+                allow_overwrite,
+                preserve_pipes,
+                transpose,
+                column_edits,
+                ..
+            } = directive;
+            let mut format_options = defaults.clone();
+            format_options.auto_align |= directive.auto_align;
+            format_options.paren_negatives |= directive.paren_negatives;
+            format_options.compact = directive.compact.unwrap_or(format_options.compact);
+            // A capped column either cuts its long cells short on display or lets them overflow
+            format_options.max_col_width = directive.max_col_width.or(format_options.max_col_width);
+            format_options.ellipsis |= directive.wrap_ellipsis;
+            let row_options = RowOptions {
+                sort: directive.sort,
+                filter: directive.filter,
+                percent_cells: directive.percent_cells,
+                totals: directive.totals,
+                totals_label: directive.totals_label,
+                autosum: directive.autosum,
+                error_cell: directive.error_cell,
+            };
+
+            // Tables written without outer pipes are normalized unless asked to keep
+            // their style (or the caller leaves out outer pipes everywhere)
+            format_options.pipeless |= preserve_pipes && is_pipeless_row(current_table_lines[0]);
+
+            // Only the first table with an ID owns it (later ones are duplicates).
+            // Start from the owner's copy in table_map if other tables have written into it
+            let owns_id = table_id
+                .as_ref()
+                .is_some_and(|id| rendered_tables.insert(id.clone()));
+            let mut rows: Vec<Vec<String>> = match table_id.as_ref() {
+                Some(id) if owns_id && written_tables.contains(id) => table_map[id].clone(),
+                _ => {
+                    let mut rows: Vec<Vec<String>> = current_table_lines
+                        .iter()
+                        .map(|line| parse_table_row(line))
+                        .collect();
+                    restore_full_cells(&mut rows, &full_cells);
+                    rows
+                }
+            };
+
+            // Column edits reshape the table as written, before anything else runs.
+            // Once applied they are removed from the directive so they run only once
+            let column_edit_error = columns::apply_column_edits(&mut rows, &column_edits).err();
+            let consume_column_edits = !column_edits.is_empty() && column_edit_error.is_none();
+
+            // A transposed table is formatted (and sorted, filtered, ...) in its new shape
+            let rows = if transpose {
+                transpose::transpose_table_rows(&rows)
+            } else {
+                rows
+            };
+
+            // Sort keys that name a header cell nobody has are reported and skipped
+            let unknown_sort_columns: Vec<String> = row_options
+                .sort
+                .iter()
+                .filter(|key| key.column_index(&rows[0]).is_none())
+                .map(|key| match &key.column {
+                    SortColumn::Header(name) => format!(
+                        "Unknown sort column '[{}]': no header cell has that text",
+                        name
+                    ),
+                    SortColumn::Index(_) => unreachable!("column letters always resolve"),
+                })
+                .collect();
+
             // Format the table with all formulas applied (now with table_map)
             let all_formulas: Vec<String> = formula_comments
                 .iter()
                 .flat_map(|(_, formulas, _)| formulas.clone())
                 .collect();
-            let (formatted, all_errors) = format_table_with_formulas_and_tables(
-                &current_table_lines,
+            let (table, results, row_errors) = format_table_with_formulas_and_tables(
+                rows,
                 &all_formulas,
-                &table_map,
+                table_map,
+                constants,
+                &row_options,
+                cache,
             );
-            output.push(formatted);
+            output.push(table.to_markdown(&format_options));
 
-            // Add the formula comments back with their respective errors
+            // Writes into a table that is already rendered need another pass
+            for id in &results.written_tables {
+                stale |= rendered_tables.contains(id);
+                written_tables.insert(id.clone());
+            }
+
+            // Add the formula comments back with their respective errors and warnings
             let mut error_idx = 0;
-            for (comment_line, formulas, parse_error) in &formula_comments {
-                output.push(comment_line.to_string());
+            for (comment_idx, (comment_line, formulas, parse_error)) in
+                formula_comments.iter().enumerate()
+            {
+                if !consume_column_edits || parse_error.is_some() {
+                    output.push(comment_line.to_string());
+                } else if let Some(line) = strip_column_edits(comment_line) {
+                    output.push(line);
+                } else if comment_idx == 0 && formula_comments.len() > 1 {
+                    // Continuation comments are only recognized after an md-table comment
+                    output.push("<!-- md-table: -->".to_string());
+                }
 
                 // Add parse error if present
                 if let Some(ref error) = parse_error {
@@ -269,20 +522,63 @@ pub fn format_tables(text: &str) -> ProcessingResult {
                     collected_errors.push(ProcessingError::table(current_table_start_line, error));
                 }
 
-                // Add error comments for formulas from this comment line
-                for _ in 0..formulas.len() {
-                    if error_idx < all_errors.len() {
-                        if let Some(ref error) = all_errors[error_idx] {
-                            output.push(format!("<!-- md-error: {} -->", error));
+                // Add error comments for formulas from this comment line, identifying
+                // each failing formula by its 1-based position within the table
+                for formula in formulas {
+                    if error_idx < results.errors.len() {
+                        if let Some(ref error) = results.errors[error_idx] {
+                            let message =
+                                format!("formula {} \"{}\": {}", error_idx + 1, formula, error);
+                            output.push(format!("<!-- md-error: {} -->", message));
                             collected_errors
-                                .push(ProcessingError::table(current_table_start_line, error));
+                                .push(ProcessingError::table(current_table_start_line, message));
+                        }
+                        // Warnings are informational only and never fail the run
+                        if let Some(Some(ref warning)) = results.warnings.get(error_idx) {
+                            if !allow_overwrite {
+                                output.push(format!(
+                                    "<!-- md-warning: formula {} \"{}\": {} -->",
+                                    error_idx + 1,
+                                    formula,
+                                    warning
+                                ));
+                            }
                         }
                         error_idx += 1;
                     }
                 }
             }
 
+            // Problems with the table itself, such as a duplicate ID
+            for error in document_errors
+                .get(&(current_table_start_line - 1))
+                .into_iter()
+                .flatten()
+                .chain(&unknown_sort_columns)
+                .chain(&row_errors)
+                .chain(&column_edit_error)
+            {
+                output.push(format!("<!-- md-error: {} -->", error));
+                collected_errors.push(ProcessingError::table(current_table_start_line, error));
+            }
+
+            // Keep the full text of cells the markdown shows cut short
+            output.extend(
+                table
+                    .truncated_cells(&format_options)
+                    .iter()
+                    .map(FullCell::to_comment),
+            );
+
             current_table_lines.clear();
+        } else if is_md_table_const_comment(lines[i]) {
+            // Keep the constants comment and report any problems with its definitions
+            output.push(lines[i].to_string());
+            for error in document_errors.get(&i).into_iter().flatten() {
+                output.push(format!("<!-- md-error: {} -->", error));
+                collected_errors.push(ProcessingError::table(i + 1, error));
+            }
+            i = skip_generated_comments(lines, i + 1);
         } else {
             // Regular line, pass through as-is
             output.push(lines[i].to_string());
@@ -290,59 +586,226 @@ pub fn format_tables(text: &str) -> ProcessingResult {
         }
     }
 
-    let mut result = output.join("\n");
-
-    // Preserve trailing newline if the original input had one
-    if text.ends_with('\n') {
-        result.push('\n');
+    RenderedTables {
+        output,
+        errors: collected_errors,
+        stale,
     }
+}
 
-    ProcessingResult::with_errors(result, collected_errors)
+/// Directive settings that change which data rows a table has and how formulas read them
+#[derive(Debug, Default)]
+struct RowOptions {
+    /// Keys from `sort=`, applied in order
+    sort: Vec<SortSpec>,
+    /// Condition from `filter=` that data rows must meet to be kept
+    filter: Option<RowFilter>,
+    /// Whether formulas read cells such as `8%` as `0.08`
+    percent_cells: bool,
+    /// Aggregates from `totals=` for a generated last row
+    totals: Vec<TotalSpec>,
+    /// First-column text of the totals row, "Total" unless set
+    totals_label: Option<String>,
+    /// Whether `autosum` adds a sum to the totals row for every numeric column
+    autosum: bool,
+    /// Text from `error-cell=` written into the target of a failing formula
+    error_cell: Option<String>,
 }
 
 /// Formats a table with formula evaluation and access to other tables
-/// Returns a tuple of (formatted_table, per_formula_errors)
-/// where per_formula_errors[i] is None if formula i succeeded, or Some(error) if it failed
+/// Returns a tuple of (table, per_formula_results, row_errors)
+/// where per_formula_results.errors[i] is None if formula i succeeded, or Some(error) if it failed,
+/// and row_errors lists problems with the filter or totals
+///
+/// Ragged rows are first padded with empty cells (see [`pad_rows`]), so formulas
+/// and the output see a rectangular table. If totals are requested, a totals
+/// row from an earlier run (the last row, recognized by its label) is removed
+/// so nothing else sees it.
+/// The data rows are ordered by the sort keys (if any) before formulas run, so
+/// `A1` is the first row after sorting. They are sorted again afterwards, which
+/// only changes anything if the formulas wrote to a sort key column.
+/// Rows failing the filter are then removed for good; if the filter cannot be
+/// evaluated every row is kept and the error is returned.
+/// A fresh totals row is appended last. If it cannot be computed the earlier
+/// one (if any) is put back and the error is returned.
+/// The caller renders the returned table (see [`Table::to_markdown`]), so
+/// truncation and automatic alignment see the values the formulas produced.
 fn format_table_with_formulas_and_tables(
-    lines: &[&str],
+    mut rows: Vec<Vec<String>>,
     formulas: &[String],
-    table_map: &std::collections::HashMap<String, Vec<Vec<String>>>,
-) -> (String, Vec<Option<String>>) {
-    if lines.is_empty() {
-        return (String::new(), Vec::new());
-    }
-
-    // Parse all rows into cells
-    let mut rows: Vec<Vec<String>> = lines.iter().map(|line| parse_table_row(line)).collect();
-
+    table_map: &mut std::collections::HashMap<String, Vec<Vec<String>>>,
+    constants: &std::collections::HashMap<String, Value>,
+    row_options: &RowOptions,
+    cache: &mut FormulaCache,
+) -> (Table, FormulaResults, Vec<String>) {
     if rows.is_empty() {
-        return (lines.join("\n"), Vec::new());
+        return (
+            Table::from_rows(rows),
+            FormulaResults::default(),
+            Vec::new(),
+        );
     }
+    pad_rows(&mut rows);
+
+    let totals_label = row_options.totals_label.as_deref().unwrap_or("Total");
+    let wants_totals = !row_options.totals.is_empty() || row_options.autosum;
+    let previous_totals = if wants_totals
+        && rows.len() > 2
+        && rows
+            .last()
+            .and_then(|row| row.first())
+            .map(|cell| cell.trim())
+            == Some(totals_label)
+    {
+        rows.pop()
+    } else {
+        None
+    };
 
-    // Apply formulas if any and collect errors per formula
-    let errors = if !formulas.is_empty() {
-        apply_formulas_with_tables(&mut rows, formulas, table_map)
+    sort_data_rows(&mut rows, &row_options.sort);
+
+    // Apply formulas if any and collect errors and warnings per formula
+    let results = if !formulas.is_empty() {
+        apply_table_formulas(
+            &mut rows,
+            formulas,
+            table_map,
+            constants,
+            row_options.percent_cells,
+            row_options.error_cell.as_deref(),
+            cache,
+        )
     } else {
-        Vec::new()
+        FormulaResults::default()
     };
 
-    // Find the maximum width for each column
-    let num_cols = rows.iter().map(|r| r.len()).max().unwrap_or(0);
-    let mut col_widths = vec![0; num_cols];
+    sort_data_rows(&mut rows, &row_options.sort);
 
-    for row in &rows {
-        for (col_idx, cell) in row.iter().enumerate() {
-            col_widths[col_idx] = col_widths[col_idx].max(cell.len());
+    let mut row_errors = Vec::new();
+    if let Some(filter) = &row_options.filter {
+        if let Err(error) =
+            formula::filter_rows(&mut rows, filter, constants, row_options.percent_cells)
+        {
+            row_errors.push(error);
         }
     }
 
-    // Format each row
-    let formatted_rows: Vec<String> = rows
+    if wants_totals {
+        let mut totals = row_options.totals.clone();
+        if row_options.autosum {
+            totals.extend(autosum_columns(&rows, &totals));
+        }
+        match formula::totals_row(
+            &rows,
+            &totals,
+            totals_label,
+            constants,
+            row_options.percent_cells,
+        ) {
+            Ok(totals) => rows.push(totals),
+            Err(error) => {
+                rows.extend(previous_totals);
+                row_errors.push(error);
+            }
+        }
+    }
+
+    (Table::from_rows(rows), results, row_errors)
+}
+
+/// Sums for every numeric column not already in `totals`, for `autosum`
+///
+/// A column is numeric when it has at least one data cell and every non-empty
+/// data cell holds a number. Column A holds the totals label and is skipped.
+fn autosum_columns(rows: &[Vec<String>], totals: &[TotalSpec]) -> Vec<TotalSpec> {
+    let width = rows.first().map_or(0, Vec::len);
+    (1..width)
+        .filter(|&column| totals.iter().all(|total| total.column != column))
+        .filter(|&column| {
+            let mut cells = rows
+                .iter()
+                .skip(2)
+                .map(|row| row.get(column).map_or("", |cell| cell.trim()))
+                .filter(|cell| !cell.is_empty())
+                .peekable();
+            cells.peek().is_some() && cells.all(|cell| formula::parse_cell_number(cell).is_some())
+        })
+        .map(|column| TotalSpec {
+            column,
+            function: "sum".to_string(),
+        })
+        .collect()
+}
+
+/// Stably reorders the data rows (never the header or separator) by the sort keys
+///
+/// Later keys only break ties left by earlier ones. Within each key, values are
+/// compared numerically when every value of that column parses as a number and
+/// lexically otherwise. Missing cells sort as empty strings. Rows that tie on
+/// every key keep their original relative order. Keys naming an unknown header
+/// are skipped.
+fn sort_data_rows(rows: &mut [Vec<String>], sort: &[SortSpec]) {
+    use formula::parse_cell_number;
+    use rust_decimal::Decimal;
+    use std::cmp::Ordering;
+
+    /// The values of one sort key column, in data row order
+    struct KeyColumn {
+        text: Vec<String>,
+        numbers: Option<Vec<Decimal>>,
+        descending: bool,
+    }
+
+    if rows.len() <= 2 || sort.is_empty() {
+        return;
+    }
+    let (header_rows, data_rows) = rows.split_at_mut(2);
+
+    let key_columns: Vec<KeyColumn> = sort
         .iter()
-        .map(|row| format_table_row(row, &col_widths))
+        .filter_map(|key| {
+            let col = key.column_index(&header_rows[0])?;
+            let text: Vec<String> = data_rows
+                .iter()
+                .map(|row| {
+                    row.get(col)
+                        .map(|cell| cell.trim())
+                        .unwrap_or("")
+                        .to_string()
+                })
+                .collect();
+            let numbers = text.iter().map(|value| parse_cell_number(value)).collect();
+            Some(KeyColumn {
+                text,
+                numbers,
+                descending: key.descending,
+            })
+        })
         .collect();
 
-    (formatted_rows.join("\n"), errors)
+    let compare = |a: usize, b: usize| -> Ordering {
+        for key in &key_columns {
+            let ordering = match &key.numbers {
+                Some(numbers) => numbers[a].cmp(&numbers[b]),
+                None => key.text[a].cmp(&key.text[b]),
+            };
+            let ordering = if key.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    };
+
+    let mut order: Vec<usize> = (0..data_rows.len()).collect();
+    order.sort_by(|&a, &b| compare(a, b));
+
+    let sorted: Vec<Vec<String>> = order.iter().map(|&idx| data_rows[idx].clone()).collect();
+    data_rows.clone_from_slice(&sorted);
 }
 
 #[cfg(test)]
@@ -511,7 +974,7 @@ With some text but no tables.
 | B | 20 | 3 | 0 |
 | C | 15 | 2 | 0 |
 | TOTAL | 0 | 0 | 0 |
-<!-- md-table: D_ = B_ * C_; A4 = sum(B_); B4 = sum(C_); D4 = sum(D_) -->"#;
+<!-- md-table: D_ = B_ * C_; A4 = sum(B1:B3); B4 = sum(C1:C3); D4 = sum(D1:D3) -->"#;
 
         let result = format_tables(input);
 
@@ -650,4 +1113,437 @@ With some text but no tables.
         // No errors
         assert!(!result.output.contains("md-error"));
     }
+
+    #[test]
+    fn test_table_with_max_width_truncates_long_cells() {
+        let long_cell = "x".repeat(100);
+        let input = format!(
+            "| Name | Description |\n|---|---|\n| A | {} |\n<!-- md-table: maxwidth=40 -->",
+            long_cell
+        );
+
+        let result = format_tables(&input);
+        assert!(!result.has_errors());
+
+        let lines: Vec<&str> = result.output.lines().collect();
+        let cells: Vec<&str> = lines[2].split('|').map(|c| c.trim()).collect();
+        // Cell is capped at 40 characters including the ellipsis
+        assert_eq!(cells[2].chars().count(), 40);
+        assert!(cells[2].ends_with('…'));
+        // Column width uses the capped width, so the separator matches it
+        assert_eq!(lines[1], format!("| ---- | {} |", "-".repeat(40)));
+
+        // The full text is kept, so the next run cuts the same cell the same way
+        assert_eq!(lines[4], format!("<!-- md-cell: B1 = {} -->", long_cell));
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+    }
+
+    #[test]
+    fn test_max_width_keeps_full_text_past_column_z() {
+        // 200 columns, with long cells in columns AB and GR
+        let mut cells = vec!["x".to_string(); 200];
+        cells[27] = "abcdefgh".to_string();
+        cells[199] = "stuvwxyz".to_string();
+        let header = vec!["H"; 200].join(" | ");
+        let separator = vec!["---"; 200].join(" | ");
+        let input = format!(
+            "| {} |\n| {} |\n| {} |\n<!-- md-table: maxwidth=5 -->\n",
+            header,
+            separator,
+            cells.join(" | ")
+        );
+
+        let result = format_tables(&input);
+        assert!(!result.has_errors());
+        assert!(result.output.contains("| abcd… |"));
+        assert!(result
+            .output
+            .contains("<!-- md-cell: AB1 = abcdefgh -->\n<!-- md-cell: GR1 = stuvwxyz -->\n"));
+
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+        let result3 = format_tables(&result.output.replace("maxwidth=5", ""));
+        assert!(result3.output.contains("| abcdefgh |"));
+        assert!(result3.output.contains("| stuvwxyz |"));
+    }
+
+    #[test]
+    fn test_sort_numeric_descending() {
+        // 9 < 10 numerically but not lexically; ties keep their original order
+        let input = "| Player | Score |\n|---|---|\n| Ann | 9 |\n| Bob | 10 |\n| Cid | 9.5 |\n| Dee | 10 |\n<!-- md-table: sort=B desc -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+
+        let lines: Vec<&str> = result.output.lines().collect();
+        assert_eq!(lines[0], "| Player | Score |");
+        assert_eq!(lines[1], "| ------ | ----- |");
+        assert_eq!(lines[2], "| Bob    | 10    |");
+        assert_eq!(lines[3], "| Dee    | 10    |");
+        assert_eq!(lines[4], "| Cid    | 9.5   |");
+        assert_eq!(lines[5], "| Ann    | 9     |");
+    }
+
+    #[test]
+    fn test_sort_string_ascending() {
+        // "n/a" is not numeric, so the whole column sorts lexically
+        let input = "| Name | Team |\n|---|---|\n| Zed | 2 |\n| Amy | n/a |\n| Max | 10 |\n<!-- md-table: sort=A asc -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+
+        let names: Vec<&str> = result
+            .output
+            .lines()
+            .skip(2)
+            .take(3)
+            .map(|line| line.split('|').nth(1).unwrap().trim())
+            .collect();
+        assert_eq!(names, vec!["Amy", "Max", "Zed"]);
+
+        let mut rows = vec![
+            vec!["Team".to_string()],
+            vec!["---".to_string()],
+            vec!["2".to_string()],
+            vec!["n/a".to_string()],
+            vec!["10".to_string()],
+        ];
+        sort_data_rows(
+            &mut rows,
+            &[SortSpec {
+                column: SortColumn::Index(0),
+                descending: false,
+            }],
+        );
+        assert_eq!(rows[2..], [vec!["10"], vec!["2"], vec!["n/a"]]);
+    }
+
+    #[test]
+    fn test_sort_by_formula_column() {
+        // Formulas write the sort column, so the rows are sorted again afterwards
+        let input = "| A | B |\n|---|---|\n| 1 | 0 |\n| 3 | 0 |\n| 2 | 0 |\n<!-- md-table: sort=B desc; B_ = A_ * 10 -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result.output.starts_with(
+            "| A   | B   |\n| --- | --- |\n| 3   | 30  |\n| 2   | 20  |\n| 1   | 10  |"
+        ));
+
+        // Sorting an already sorted table is a no-op
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+    }
+
+    #[test]
+    fn test_sort_runs_before_formulas() {
+        // B1 is the first row after sorting, on the first run and every later one
+        let input = "| A | B |\n|---|---|\n| 2 | |\n| 9 | |\n| 5 | |\n<!-- md-table: sort=A desc; B1 = A1 * 10 -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result.output.starts_with(
+            "| A   | B   |\n| --- | --- |\n| 9   | 90  |\n| 5   |     |\n| 2   |     |"
+        ));
+
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+    }
+
+    #[test]
+    fn test_sort_multiple_keys() {
+        let input = "| Team | Name | Score |\n|---|---|---|\n| red | Cid | 7 |\n| blue | Ann | 7 |\n| red | Bob | 9 |\n| blue | Dee | 3 |\n<!-- md-table: sort=[Team] asc, C desc -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        let names: Vec<&str> = result
+            .output
+            .lines()
+            .skip(2)
+            .take(4)
+            .map(|line| line.split('|').nth(2).unwrap().trim())
+            .collect();
+        assert_eq!(names, vec!["Ann", "Dee", "Bob", "Cid"]);
+    }
+
+    #[test]
+    fn test_sort_unknown_header() {
+        let input = "| Name | Score |\n|---|---|\n| Bob | 2 |\n| Ann | 1 |\n<!-- md-table: sort=[Points] desc, A -->";
+
+        let result = format_tables(input);
+        assert!(result.has_errors());
+        assert!(result.output.contains(
+            "<!-- md-error: Unknown sort column '[Points]': no header cell has that text -->"
+        ));
+        // The remaining keys still apply
+        assert!(result.output.contains("| Ann  | 1     |\n| Bob  | 2     |"));
+    }
+
+    #[test]
+    fn test_filter_numeric_column() {
+        // C is filled by the formula first, then rows with C <= 0 are dropped
+        let input = "| Item | Qty | Left |\n|---|---|---|\n| a | 5 | |\n| b | 2 | |\n| c | 7 | |\n<!-- md-table: filter=C>0; C_ = B_ - 4 -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result.output.starts_with(
+            "| Item | Qty | Left |\n| ---- | --- | ---- |\n| a    | 5   | 1    |\n| c    | 7   | 3    |\n<!--"
+        ));
+
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+    }
+
+    #[test]
+    fn test_filter_empty_cells() {
+        // Empty and text cells read as 0
+        let input =
+            "| Name | Score |\n|---|---|\n| Ann | 3 |\n| Bob | |\n| Cid | n/a |\n<!-- md-table: filter=B!=0 -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result
+            .output
+            .starts_with("| Name | Score |\n| ---- | ----- |\n| Ann  | 3     |\n<!--"));
+    }
+
+    #[test]
+    fn test_filter_error_keeps_rows() {
+        let input = "| A |\n|---|\n| 1 |\n| 2 |\n<!-- md-table: filter=A_>0 -->";
+
+        let result = format_tables(input);
+        assert!(result.has_errors());
+        assert!(result.output.contains("| 1   |\n| 2   |"));
+        assert!(result.output.contains(
+            "<!-- md-error: Filter 'A_' failed on row 1: expected a single value, got a 2x1 matrix -->"
+        ));
+    }
+
+    #[test]
+    fn test_totals_row() {
+        let input = "| Item | Qty | Price |\n|---|---|---|\n| a | 2 | 1 |\n| b | 3 | 2 |\n| c | 1 | 6 |\n<!-- md-table: totals=B:sum,avg(C_) -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result
+            .output
+            .contains("| c     | 1   | 6     |\n| Total | 6   | 3     |\n<!--"));
+
+        // The totals row is replaced on every run, not appended again
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+
+        // Edited data updates the existing totals row
+        let edited = result.output.replace("| a     | 2 ", "| a     | 7 ");
+        let result3 = format_tables(&edited);
+        assert!(result3.output.contains("| Total | 11  | 3     |"));
+        assert_eq!(result3.output.matches("Total").count(), 1);
+    }
+
+    #[test]
+    fn test_autosum_row() {
+        let input = "| Item | Qty | Note | Price | Empty |\n|---|---|---|---|---|\n| a | 2 | x | 1.50 | |\n| b | | 5 | 2 | |\n| c | 1 | | (0.50) | |\n<!-- md-table: autosum -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result.output.contains(
+            "| Item  | Qty | Note | Price  | Empty |\n\
+             | ----- | --- | ---- | ------ | ----- |\n\
+             | a     | 2   | x    | 1.50   |       |\n\
+             | b     |     | 5    | 2      |       |\n\
+             | c     | 1   |      | (0.50) |       |\n\
+             | Total | 3   |      | 3.00   |       |\n<!--"
+        ));
+
+        // The autosum row is replaced on every run, and explicit totals take precedence
+        assert_eq!(format_tables(&result.output).output, result.output);
+        let input = input.replace("autosum", "autosum; totals=D:max");
+        let result = format_tables(&input);
+        assert!(result
+            .output
+            .contains("| Total | 3   |      | 2      |       |"));
+    }
+
+    #[test]
+    fn test_totals_row_with_label_sort_and_formulas() {
+        // Formulas and sorting never see the totals row, which always stays last
+        let input = "| Name | Hours | Pay |\n|---|---|---|\n| Ann | 2 | |\n| Bob | 5 | |\n| Sum | 7 | 70 |\n<!-- md-table: sort=B desc; totals=B:sum,C:sum; totals-label=Sum; C_ = B_ * 10 -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result.output.starts_with(
+            "| Name | Hours | Pay |\n| ---- | ----- | --- |\n| Bob  | 5     | 50  |\n| Ann  | 2     | 20  |\n| Sum  | 7     | 70  |\n<!--"
+        ));
+
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+    }
+
+    #[test]
+    fn test_totals_row_error_keeps_previous_row() {
+        let input =
+            "| Item | Qty |\n|---|---|\n| a | 2 |\n| Total | 2 |\n<!-- md-table: totals=B:nope -->";
+
+        let result = format_tables(input);
+        assert!(result.has_errors());
+        assert!(result.output.contains("| a     | 2   |\n| Total | 2   |"));
+        assert!(result
+            .output
+            .contains("<!-- md-error: Totals 'nope(B_)' failed: unknown function: 'nope'"));
+    }
+
+    #[test]
+    fn test_transpose_directive_pads_ragged_rows() {
+        let input = "| Name | Q1 | Q2 |\n|---|---|---|\n| Ann | 1 | 2 |\n| Bob | 3 |\n<!-- md-table: transpose -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert!(result.output.starts_with(
+            "| Name | Ann | Bob |\n| ---- | --- | --- |\n| Q1   | 1   | 3   |\n| Q2   | 2   |     |\n"
+        ));
+
+        let result2 = format_tables(&result.output);
+        assert!(result2.output.starts_with(
+            "| Name | Q1  | Q2  |\n| ---- | --- | --- |\n| Ann  | 1   | 2   |\n| Bob  | 3   |     |\n"
+        ));
+    }
+
+    #[test]
+    fn test_transpose_directive_rejects_formulas() {
+        let input = "| A | B |\n|---|---|\n| 1 | 2 |\n<!-- md-table: transpose; B1 = A1 -->";
+
+        let result = format_tables(input);
+        assert!(result.has_errors());
+        // The directive is rejected as a whole, so the table is neither transposed nor computed
+        assert!(result
+            .output
+            .starts_with("| A   | B   |\n| --- | --- |\n| 1   | 2   |\n"));
+        assert!(result.output.contains(
+            "<!-- md-error: transpose cannot be combined with formulas in the same comment"
+        ));
+    }
+
+    #[test]
+    fn test_column_edits_with_formulas() {
+        // The new column exists before formulas run, and the edit is consumed
+        let input = "| Item | Qty | Price |\n|---|---|---|\n| a | 2 | 3 |\n<!-- md-table: addcol=Total; D_ = B_ * C_ -->";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert_eq!(
+            result.output,
+            "| Item | Qty | Price | Total |\n| ---- | --- | ----- | ----- |\n| a    | 2   | 3     | 6     |\n<!-- md-table: D_ = B_ * C_ -->"
+        );
+
+        let result2 = format_tables(&result.output);
+        assert_eq!(result2.output, result.output);
+    }
+
+    #[test]
+    fn test_column_edits_only_comment_is_removed() {
+        let input = "| A | B | C |\n|---|---|---|\n| 1 | 2 | 3 |\n<!-- md-table: dropcol=B; movecol=B before A -->\n";
+
+        let result = format_tables(input);
+        assert!(!result.has_errors());
+        assert_eq!(
+            result.output,
+            "| C   | A   |\n| --- | --- |\n| 3   | 1   |\n"
+        );
+    }
+
+    #[test]
+    fn test_column_edit_error_keeps_directive() {
+        let input =
+            "| A | B |\n|---|---|\n| 1 | 2 |\n<!-- md-table: dropcol=A; movecol=C before A -->";
+
+        let result = format_tables(input);
+        assert!(result.has_errors());
+        assert_eq!(
+            result.output,
+            "| A   | B   |\n| --- | --- |\n| 1   | 2   |\n<!-- md-table: dropcol=A; movecol=C before A -->\n<!-- md-error: Column C does not exist (table has 1 columns) -->"
+        );
+    }
+
+    #[test]
+    fn test_parse_table_spec() {
+        assert_eq!(
+            parse_table_spec("table:2:3"),
+            Ok((2, vec![String::new(); 3]))
+        );
+        assert_eq!(
+            parse_table_spec("table:3:Name, Price ,Qty"),
+            Ok((
+                3,
+                vec!["Name".to_string(), "Price".to_string(), "Qty".to_string()]
+            ))
+        );
+        assert_eq!(
+            parse_table_spec("table:1:\"Name, Inc\",\"Say \"\"hi\"\"\",a|b,Time: UTC"),
+            Ok((
+                1,
+                vec![
+                    "Name, Inc".to_string(),
+                    "Say \"hi\"".to_string(),
+                    "a\\|b".to_string(),
+                    "Time: UTC".to_string(),
+                ]
+            ))
+        );
+
+        assert!(parse_table_spec("table:2").is_err());
+        assert!(parse_table_spec("table:2:").is_err());
+        assert!(parse_table_spec("table:0:Name").is_err());
+        assert!(parse_table_spec("table:2:0").is_err());
+        assert!(parse_table_spec("grid:2:3").is_err());
+        assert!(parse_table_spec("table:2:\"Name").is_err());
+    }
+
+    #[test]
+    fn test_create_table_with_headers() {
+        let headers = vec!["Name".to_string(), "Qty".to_string()];
+        assert_eq!(
+            create_table_with_headers(2, &headers),
+            "| Name | Qty |\n| ---- | --- |\n|      |     |\n|      |     |"
+        );
+        assert_eq!(create_table_with_headers(2, &[]), "");
+        assert_eq!(
+            create_table(1, 2),
+            "|     |     |\n| --- | --- |\n|     |     |"
+        );
+    }
+
+    #[test]
+    fn test_percent_cells() {
+        let table = "| Price | Tax | Total |\n|---|---|---|\n| 200 | 8% | 0 |\n";
+
+        // Opted in: 8% is read as 0.08, and the cell keeps its percent sign
+        let result = format_tables(&format!(
+            "{}<!-- md-table: percent-cells=true; C1 = A1 * B1 -->",
+            table
+        ));
+        assert!(!result.has_errors());
+        assert!(result.output.contains("| 200   | 8%  | 16.00 |"));
+
+        // Without the directive 8% is not a number and resolves to 0
+        let result = format_tables(&format!("{}<!-- md-table: C1 = A1 * B1 -->", table));
+        assert!(!result.has_errors());
+        assert!(result.output.contains("| 200   | 8%  | 0     |"));
+    }
+
+    #[test]
+    fn test_collect_tables_by_id() {
+        let input = "| A |\n|---|\n| 1 |\n<!-- md-table: id=\"first\" -->\n\n| B |\n|---|\n| 2 |\n\n```\n| C |\n<!-- md-table: id=\"fenced\" -->\n```\n\n| D |\n|---|\n| 4 |\n<!-- md-table: id=\"first\" -->";
+
+        let tables = collect_tables(input);
+        assert_eq!(tables.len(), 1);
+        // The first table with a repeated ID keeps it
+        assert_eq!(tables["first"][2], vec!["1"]);
+
+        let result = format_tables(input);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].line, 15);
+        assert!(result.output.ends_with(
+            "<!-- md-error: Duplicate table ID 'first' found at line 15 (previously defined at line 1) -->"
+        ));
+    }
 }
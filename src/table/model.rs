@@ -0,0 +1,525 @@
+//! Structured access to a single markdown table
+
+use super::formatter::{
+    column_widths, display_width, format_compact_separator_row, push_table_row, truncate_cell,
+};
+use super::formula::{apply_formulas_with_tables, parse_cell_number, FormulaResults};
+use super::parser::{
+    collect_tables, column_alignments, is_separator_cell, is_separator_row, FullCell,
+};
+
+/// Options controlling how a [`Table`] is rendered back to markdown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormatOptions {
+    /// Cap column widths at this many characters without changing any cell
+    ///
+    /// Cells wider than the cap are written in full without padding, so they
+    /// overflow their column while the other rows stay compact.
+    pub max_col_width: Option<usize>,
+    /// Cut data cells wider than `max_col_width` short with an ellipsis instead
+    ///
+    /// Only the rendered markdown is cut: the table keeps the full text, which
+    /// [`format_tables`](crate::table::format_tables) writes into md-cell
+    /// comments after the table and its directive. Header cells and cells
+    /// containing `-->` still overflow.
+    pub ellipsis: bool,
+    /// Right-align columns whose data cells are all numbers (blank cells are ignored)
+    ///
+    /// Only columns whose separator has no alignment colons are affected; their
+    /// separator is rewritten to `---:` so rendered markdown is right-aligned too.
+    pub auto_align: bool,
+    /// Show negative numbers accounting-style, e.g. `-500` as `(500)`
+    ///
+    /// Formulas read `(500)` back as `-500`, so re-formatting keeps the values.
+    pub paren_negatives: bool,
+    /// Leave out the leading and trailing pipes of each row (`a | b`)
+    ///
+    /// Rows whose first or last cell is empty keep that pipe, since without it
+    /// the empty cell would be lost when the table is read back.
+    pub pipeless: bool,
+    /// Separate cells with single spaces instead of padding them into columns
+    ///
+    /// Data rows become `| a | b |` and the separator row `|---|---|`, whatever
+    /// the width of the cells.
+    pub compact: bool,
+}
+
+/// A markdown table: a header row followed by data rows
+///
+/// Rows and columns are 0-indexed, and row 0 is the first data row (the header
+/// and separator are not counted). Note that formulas use 1-based rows, so
+/// formula cell `B1` is `table.cell(0, 1)`.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::{FormatOptions, Table};
+///
+/// let mut table = Table::parse("| Item | Qty |\n|---|---|\n| Apple | 3 |\n").unwrap();
+/// assert_eq!(table.headers(), ["Item", "Qty"]);
+/// assert_eq!(table.cell(0, 1), Some("3"));
+///
+/// table.set_cell(0, 1, "4").unwrap();
+/// assert_eq!(
+///     table.to_markdown(&FormatOptions::default()),
+///     "| Item  | Qty |\n| ----- | --- |\n| Apple | 4   |"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    // Header, separator, then data rows; the layout the formula engine works on
+    rows: Vec<Vec<String>>,
+}
+
+#[allow(dead_code)]
+impl Table {
+    /// Creates a table from header cells and data rows, with a plain `---` separator
+    pub fn new(headers: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+        let separator = vec!["---".to_string(); headers.len()];
+        let mut all_rows = Vec::with_capacity(rows.len() + 2);
+        all_rows.push(headers);
+        all_rows.push(separator);
+        all_rows.extend(rows);
+        Table { rows: all_rows }
+    }
+
+    /// Parses the first markdown table in `text`
+    ///
+    /// Tables inside code fences are ignored. Column alignment markers in the
+    /// separator row (e.g. `:---:`) are kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `text` contains no table or the table has no
+    /// separator row after its header.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = text.lines().collect();
+        let (_, rows) = collect_tables(&lines)
+            .into_iter()
+            .next()
+            .ok_or_else(|| "no markdown table found".to_string())?;
+
+        match rows.get(1) {
+            Some(separator) if is_separator_row(separator) => Ok(Table { rows }),
+            _ => Err("table is missing the separator row after its header".to_string()),
+        }
+    }
+
+    /// Wraps already parsed rows (header, separator, then data rows)
+    pub(crate) fn from_rows(rows: Vec<Vec<String>>) -> Self {
+        Table { rows }
+    }
+
+    /// The header cells
+    pub fn headers(&self) -> &[String] {
+        self.rows.first().map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The data rows, excluding the header and separator
+    pub fn rows(&self) -> &[Vec<String>] {
+        self.rows.get(2..).unwrap_or(&[])
+    }
+
+    /// The contents of a data cell, or None if it is out of bounds
+    pub fn cell(&self, row: usize, col: usize) -> Option<&str> {
+        self.rows()
+            .get(row)
+            .and_then(|cells| cells.get(col))
+            .map(String::as_str)
+    }
+
+    /// Replaces the contents of a data cell
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cell is out of bounds.
+    pub fn set_cell(
+        &mut self,
+        row: usize,
+        col: usize,
+        value: impl Into<String>,
+    ) -> Result<(), String> {
+        let num_rows = self.rows().len();
+        match self
+            .rows
+            .get_mut(row + 2)
+            .and_then(|cells| cells.get_mut(col))
+        {
+            Some(cell) => {
+                *cell = value.into();
+                Ok(())
+            }
+            None => Err(format!(
+                "cell ({}, {}) is out of bounds for a table with {} data rows",
+                row, col, num_rows
+            )),
+        }
+    }
+
+    /// Evaluates formulas such as `C_ = A_ * B_` against this table
+    ///
+    /// Cross-table references and document constants are not available; use
+    /// [`apply_formulas_with_tables`] for those.
+    pub fn apply_formulas(&mut self, formulas: &[String]) -> FormulaResults {
+        use std::collections::HashMap;
+        apply_formulas_with_tables(
+            &mut self.rows,
+            formulas,
+            &mut HashMap::new(),
+            &HashMap::new(),
+        )
+    }
+
+    /// Indices of columns with at least one data cell where every non-blank data cell is a number
+    fn numeric_columns(&self) -> Vec<usize> {
+        let num_cols = self.headers().len();
+        (0..num_cols)
+            .filter(|&col| {
+                let mut values = self
+                    .rows()
+                    .iter()
+                    .filter_map(|row| row.get(col))
+                    .filter(|cell| !cell.is_empty())
+                    .peekable();
+                values.peek().is_some() && values.all(|cell| parse_cell_number(cell).is_some())
+            })
+            .collect()
+    }
+
+    /// Data cells that [`Table::to_markdown`] cuts short with these options, with their full text
+    ///
+    /// The text is the cell as rendered before the cut, e.g. with negatives in
+    /// parentheses, so reading it back gives the same value.
+    pub(crate) fn truncated_cells(&self, options: &FormatOptions) -> Vec<FullCell> {
+        let Some(cap) = options.max_col_width.filter(|_| options.ellipsis) else {
+            return Vec::new();
+        };
+        let rows = self.display_rows(options);
+        let mut cells = Vec::new();
+        for (row, cells_in_row) in rows.iter().enumerate().skip(2) {
+            for (col, cell) in cells_in_row.iter().enumerate() {
+                if is_cut_short(cell, cap) {
+                    cells.push(FullCell {
+                        row: row - 1,
+                        col,
+                        text: cell.clone(),
+                    });
+                }
+            }
+        }
+        cells
+    }
+
+    /// The rows with the cell rewrites of `options` that come before truncation
+    fn display_rows(&self, options: &FormatOptions) -> std::borrow::Cow<'_, [Vec<String>]> {
+        // Rows are only copied if an option rewrites cells
+        let mut rows = std::borrow::Cow::Borrowed(self.rows.as_slice());
+
+        if options.paren_negatives {
+            for cell in rows.to_mut().iter_mut().skip(2).flatten() {
+                if let Some(paren) = paren_negative(cell) {
+                    *cell = paren;
+                }
+            }
+        }
+
+        // Detect numeric columns before truncation can cut numbers short
+        if options.auto_align && rows.len() > 1 {
+            for col in self.numeric_columns() {
+                if let Some(separator) = rows.to_mut()[1].get_mut(col) {
+                    if is_separator_cell(separator) && !separator.contains(':') {
+                        *separator = "---:".to_string();
+                    }
+                }
+            }
+        }
+
+        rows
+    }
+
+    /// Renders the table as aligned markdown (without a trailing newline)
+    pub fn to_markdown(&self, options: &FormatOptions) -> String {
+        let mut rows = self.display_rows(options);
+
+        // Cut capped data cells short for display; self.rows keeps their full text
+        if let Some(cap) = options.max_col_width.filter(|_| options.ellipsis) {
+            for cell in rows.to_mut().iter_mut().skip(2).flatten() {
+                if is_cut_short(cell, cap) {
+                    *cell = truncate_cell(cell, cap);
+                }
+            }
+        }
+
+        // Find the maximum width for each column (none in compact mode, so no padding)
+        let mut col_widths = if options.compact {
+            Vec::new()
+        } else {
+            column_widths(&rows)
+        };
+        if let Some(cap) = options.max_col_width {
+            for width in col_widths.iter_mut() {
+                *width = (*width).min(cap);
+            }
+        }
+
+        let alignments = column_alignments(&rows);
+        let mut output = String::new();
+        let mut line = String::new();
+        for (idx, row) in rows.iter().enumerate() {
+            if idx > 0 {
+                output.push('\n');
+            }
+            line.clear();
+            if options.compact && idx == 1 && is_separator_row(row) {
+                line.push_str(&format_compact_separator_row(row));
+            } else {
+                push_table_row(&mut line, row, &col_widths, &alignments);
+            }
+            if options.pipeless {
+                output.push_str(strip_outer_pipes(&line, row, self.headers()));
+            } else {
+                output.push_str(&line);
+            }
+        }
+        output
+    }
+}
+
+/// Whether `wrap=ellipsis` cuts a data cell short under a column cap of `cap`
+///
+/// Cells containing `-->` overflow instead, since an md-cell comment could not
+/// hold their full text.
+fn is_cut_short(cell: &str, cap: usize) -> bool {
+    display_width(cell) > cap && !cell.contains("-->")
+}
+
+/// Removes the outer pipes from a formatted row, keeping those next to an empty cell
+///
+/// If the header starts with an empty cell every row keeps its leading pipe, as
+/// a header line starting with a pipe would be read as a fully piped table.
+/// Single-cell rows keep both pipes so they still read as part of the table.
+fn strip_outer_pipes<'a>(line: &'a str, cells: &[String], headers: &[String]) -> &'a str {
+    if cells.len() < 2 {
+        return line;
+    }
+    let mut line = line;
+    let keeps_leading = |cells: &[String]| cells.first().is_some_and(String::is_empty);
+    // Compact separator rows have no space next to their pipes
+    if !keeps_leading(headers) && !keeps_leading(cells) {
+        line = line
+            .strip_prefix("| ")
+            .or_else(|| line.strip_prefix('|'))
+            .unwrap_or(line);
+    }
+    if cells.last().is_some_and(|cell| !cell.is_empty()) {
+        line = line
+            .strip_suffix(" |")
+            .or_else(|| line.strip_suffix('|'))
+            .unwrap_or(line)
+            .trim_end();
+    }
+    line
+}
+
+/// Rewrites a negative number such as `-1.5` as `(1.5)`; None for anything else
+fn paren_negative(cell: &str) -> Option<String> {
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    let magnitude = cell.strip_prefix('-')?;
+    match Decimal::from_str(cell) {
+        Ok(decimal) if decimal.is_sign_negative() && !decimal.is_zero() => {
+            Some(format!("({})", magnitude))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_access() {
+        let text =
+            "Intro\n\n| Name | Score |\n|:---|---:|\n| Ann | 9 |\n| Bob |\n\n| Other |\n|---|\n";
+        let table = Table::parse(text).unwrap();
+
+        assert_eq!(table.headers(), ["Name", "Score"]);
+        assert_eq!(table.rows().len(), 2);
+        assert_eq!(table.cell(0, 0), Some("Ann"));
+        assert_eq!(table.cell(1, 1), None);
+        assert_eq!(table.cell(2, 0), None);
+
+        // Alignment markers survive a round trip and are applied to the cells
+        assert_eq!(
+            table.to_markdown(&FormatOptions::default()),
+            "| Name | Score |\n| :--- | ----: |\n| Ann  |     9 |\n| Bob  |"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Table::parse("no tables here").is_err());
+        assert!(Table::parse("```\n| A |\n|---|\n```").is_err());
+        assert!(Table::parse("| A |\n| 1 |").is_err());
+    }
+
+    #[test]
+    fn test_set_cell_and_formulas() {
+        let mut table = Table::new(
+            vec!["A".to_string(), "B".to_string()],
+            vec![
+                vec!["1".to_string(), "".to_string()],
+                vec!["2".to_string(), "".to_string()],
+            ],
+        );
+
+        table.set_cell(1, 0, "5").unwrap();
+        assert!(table.set_cell(2, 0, "x").is_err());
+        assert!(table.set_cell(0, 2, "x").is_err());
+
+        let results = table.apply_formulas(&["B_ = A_ * 2".to_string()]);
+        assert_eq!(results.errors, vec![None]);
+        assert_eq!(table.cell(0, 1), Some("2"));
+        assert_eq!(table.cell(1, 1), Some("10"));
+    }
+
+    #[test]
+    fn test_to_markdown_auto_align() {
+        let table = Table::parse(
+            "| Item | Price | Qty | Note |\n|---|---|:---|---|\n| Pen | 1.5 | 3 | n/a |\n| Desk | | 1 | 2 |",
+        )
+        .unwrap();
+        let options = FormatOptions {
+            auto_align: true,
+            ..FormatOptions::default()
+        };
+
+        // Price is numeric (the blank is ignored); Qty is numeric but explicitly
+        // left-aligned; Item and Note contain text
+        assert_eq!(
+            table.to_markdown(&options),
+            "| Item | Price | Qty  | Note |\n| ---- | ----: | :--- | ---- |\n| Pen  |   1.5 | 3    | n/a  |\n| Desk |       | 1    | 2    |"
+        );
+
+        // Off by default
+        assert!(!table
+            .to_markdown(&FormatOptions::default())
+            .contains("---:"));
+    }
+
+    #[test]
+    fn test_to_markdown_paren_negatives() {
+        let table = Table::new(
+            vec!["Amount".to_string()],
+            vec![
+                vec!["-500".to_string()],
+                vec!["500".to_string()],
+                vec!["-x".to_string()],
+            ],
+        );
+        let options = FormatOptions {
+            paren_negatives: true,
+            ..FormatOptions::default()
+        };
+
+        assert_eq!(
+            table.to_markdown(&options),
+            "| Amount |\n| ------ |\n| (500)  |\n| 500    |\n| -x     |"
+        );
+
+        // The parenthesized form still counts as a number
+        let rendered = Table::parse(&table.to_markdown(&options)).unwrap();
+        assert_eq!(
+            parse_cell_number(rendered.cell(0, 0).unwrap()),
+            Some((-500).into())
+        );
+    }
+
+    #[test]
+    fn test_to_markdown_max_col_width() {
+        let table = Table::new(
+            vec!["Id".to_string(), "Notes".to_string()],
+            vec![
+                vec!["1".to_string(), "a very long note".to_string()],
+                vec!["2".to_string(), "short".to_string()],
+            ],
+        );
+        let options = FormatOptions {
+            max_col_width: Some(6),
+            ..FormatOptions::default()
+        };
+
+        // The long cell overflows unpadded; the other rows use the capped width
+        assert_eq!(
+            table.to_markdown(&options),
+            "| Id  | Notes  |\n| --- | ------ |\n| 1   | a very long note |\n| 2   | short  |"
+        );
+        assert!(table.truncated_cells(&options).is_empty());
+
+        // With an ellipsis only the display is cut; the table keeps the full text
+        let options = FormatOptions {
+            ellipsis: true,
+            ..options
+        };
+        assert_eq!(
+            table.to_markdown(&options),
+            "| Id  | Notes  |\n| --- | ------ |\n| 1   | a ver… |\n| 2   | short  |"
+        );
+        assert_eq!(table.cell(0, 1), Some("a very long note"));
+        assert_eq!(
+            table.truncated_cells(&options),
+            [FullCell {
+                row: 1,
+                col: 1,
+                text: "a very long note".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_markdown_compact() {
+        let table =
+            Table::parse("| Item | Qty |\n|:---|--:|\n| Watermelon | 10 |\n| Fig |  |").unwrap();
+        let options = FormatOptions {
+            compact: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            table.to_markdown(&options),
+            "| Item | Qty |\n|:---|---:|\n| Watermelon | 10 |\n| Fig |  |"
+        );
+
+        let options = FormatOptions {
+            compact: true,
+            pipeless: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            table.to_markdown(&options),
+            "Item | Qty\n:---|---:\nWatermelon | 10\nFig |  |"
+        );
+    }
+
+    #[test]
+    fn test_to_markdown_pipeless() {
+        let cells = |row: &[&str]| row.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let table = Table::new(
+            cells(&["A", "B"]),
+            vec![cells(&["long", "1"]), cells(&["", "2"]), cells(&["x", ""])],
+        );
+        let options = FormatOptions {
+            pipeless: true,
+            ..FormatOptions::default()
+        };
+
+        // Rows with an empty first or last cell keep that pipe
+        assert_eq!(
+            table.to_markdown(&options),
+            "A    | B\n---- | ---\nlong | 1\n|      | 2\nx    |     |"
+        );
+        let reparsed = Table::parse(&table.to_markdown(&options)).unwrap();
+        assert_eq!(reparsed.rows(), table.rows());
+    }
+}
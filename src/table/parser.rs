@@ -1,16 +1,73 @@
 /// Parses a table row into individual cells
+///
+/// Pipes inside inline code spans (`` `a|b` ``) and escaped pipes (`\|`) are
+/// part of the cell content rather than column separators.
 pub fn parse_table_row(line: &str) -> Vec<String> {
     let trimmed = line.trim();
+    let mut cells = split_on_column_pipes(trimmed);
 
-    // Remove leading and trailing pipes
-    let content = trimmed.strip_prefix('|').unwrap_or(trimmed);
-    let content = content.strip_suffix('|').unwrap_or(content);
+    // Remove the empty pieces outside the leading and trailing pipes
+    if cells.len() > 1 && cells.first() == Some(&"") {
+        cells.remove(0);
+    }
+    if cells.len() > 1 && cells.last() == Some(&"") {
+        cells.pop();
+    }
 
-    // Split by pipe and trim each cell
-    content
-        .split('|')
-        .map(|cell| cell.trim().to_string())
-        .collect()
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// Splits a line on the pipes that separate columns
+///
+/// A run of backticks opens a code span that ends at the next run of the same
+/// length; an unmatched run is literal text. A backslash escapes the next
+/// character.
+fn split_on_column_pipes(line: &str) -> Vec<&str> {
+    let bytes = line.as_bytes();
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => {
+                let run = backtick_run(bytes, i);
+                i = closing_backticks(bytes, i + run, run).map_or(i + run, |end| end + run);
+            }
+            b'|' => {
+                pieces.push(&line[start..i]);
+                start = i + 1;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    pieces.push(&line[start..]);
+
+    pieces
+}
+
+/// Length of the run of backticks starting at `start`
+fn backtick_run(bytes: &[u8], start: usize) -> usize {
+    bytes[start..].iter().take_while(|&&b| b == b'`').count()
+}
+
+/// Finds the start of the next run of exactly `len` backticks at or after `from`
+fn closing_backticks(bytes: &[u8], from: usize, len: usize) -> Option<usize> {
+    let mut i = from;
+    while i < bytes.len() {
+        if bytes[i] == b'`' {
+            let run = backtick_run(bytes, i);
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
 }
 
 /// Checks if a line looks like a markdown table row
@@ -26,27 +83,568 @@ pub fn is_table_row(line: &str) -> bool {
     trimmed.matches('|').count() >= 2
 }
 
+/// Checks if a line contains at least one pipe that separates columns
+///
+/// Pipes inside code spans and escaped pipes do not count.
+pub fn has_column_pipe(line: &str) -> bool {
+    split_on_column_pipes(line.trim()).len() > 1
+}
+
+/// Checks if a table row is written without its leading pipe (`a | b`)
+pub fn is_pipeless_row(line: &str) -> bool {
+    !line.trim_start().starts_with('|')
+}
+
+/// Finds the end (exclusive) of the table whose first row is `lines[start]`
+///
+/// Returns None if no table starts there. Fully piped tables (`| a | b |`) run
+/// for as long as rows start with a pipe. Tables without the outer pipes
+/// (`a | b`) must have a separator row (`--- | ---`) with as many cells as the
+/// header right after it, and then run until the first line without a column
+/// pipe or the first HTML comment.
+pub fn table_end(lines: &[&str], start: usize) -> Option<usize> {
+    let first = *lines.get(start)?;
+
+    if is_table_row(first) {
+        let mut end = start + 1;
+        while end < lines.len() && is_table_row(lines[end]) {
+            end += 1;
+        }
+        return Some(end);
+    }
+
+    let separator = lines.get(start + 1)?;
+    if first.trim_start().starts_with("<!--")
+        || !has_column_pipe(first)
+        || !has_column_pipe(separator)
+    {
+        return None;
+    }
+    let separator_cells = parse_table_row(separator);
+    if !is_separator_row(&separator_cells) || separator_cells.len() != parse_table_row(first).len()
+    {
+        return None;
+    }
+
+    let mut end = start + 2;
+    while end < lines.len()
+        && has_column_pipe(lines[end])
+        && !lines[end].trim_start().starts_with("<!--")
+    {
+        end += 1;
+    }
+    Some(end)
+}
+
+/// Collects every markdown table in the document, skipping tables inside code fences
+///
+/// Returns `(start_line, rows)` pairs where `start_line` is the 0-indexed line of the
+/// table's first row and `rows` holds the parsed cells (including the separator row).
+pub fn collect_tables(lines: &[&str]) -> Vec<(usize, Vec<Vec<String>>)> {
+    use crate::common::{is_code_fence, CodeFenceTracker};
+
+    let mut tables = Vec::new();
+    let mut fence_tracker = CodeFenceTracker::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        fence_tracker.process_line(lines[i]);
+
+        if fence_tracker.is_inside_code_block() || is_code_fence(lines[i]) {
+            i += 1;
+            continue;
+        }
+
+        if let Some(end) = table_end(lines, i) {
+            let rows = lines[i..end]
+                .iter()
+                .map(|line| parse_table_row(line))
+                .collect();
+            tables.push((i, rows));
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    tables
+}
+
+/// Checks if every cell in a row is a separator cell (e.g. `|---|:---:|`)
+pub fn is_separator_row(cells: &[String]) -> bool {
+    !cells.is_empty() && cells.iter().all(|cell| is_separator_cell(cell))
+}
+
+/// Pads every row with empty cells up to the widest row's cell count
+///
+/// The separator row (the second row) is padded with `---` instead, so a row
+/// with extra cells widens the whole table, header included.
+pub fn pad_rows(rows: &mut [Vec<String>]) {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    for (idx, row) in rows.iter_mut().enumerate() {
+        let filler = if idx == 1 && is_separator_row(row) {
+            "---"
+        } else {
+            ""
+        };
+        row.resize(width, filler.to_string());
+    }
+}
+
 /// Checks if a cell is part of a separator row
 pub fn is_separator_cell(cell: &str) -> bool {
     !cell.is_empty() && cell.chars().all(|c| c == '-' || c == ':' || c == ' ')
 }
 
+/// Horizontal alignment of a column, set by colons in its separator cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alignment {
+    /// `---` or `:---`
+    #[default]
+    Left,
+    /// `:---:`
+    Center,
+    /// `---:`
+    Right,
+}
+
+/// Reads a column's alignment from its separator cell
+pub fn parse_alignment(cell: &str) -> Alignment {
+    match (cell.starts_with(':'), cell.ends_with(':')) {
+        (true, true) => Alignment::Center,
+        (false, true) => Alignment::Right,
+        _ => Alignment::Left,
+    }
+}
+
+/// Reads every column's alignment from the separator row (row 1) of parsed table rows
+///
+/// Returns an empty list if the table has no separator row, which leaves every
+/// column left-aligned.
+pub fn column_alignments(rows: &[Vec<String>]) -> Vec<Alignment> {
+    match rows.get(1) {
+        Some(separator) if is_separator_row(separator) => {
+            separator.iter().map(|cell| parse_alignment(cell)).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
 /// Checks if a line is an HTML comment with md-table marker
 pub fn is_md_table_comment(line: &str) -> bool {
     let trimmed = line.trim();
     trimmed.starts_with("<!--") && trimmed.contains("md-table:")
 }
 
+/// Checks if a line is an HTML comment with md-table-const marker
+pub fn is_md_table_const_comment(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with("<!--") && trimmed.contains("md-table-const:")
+}
+
 /// Checks if a line is an HTML comment (for continuation formulas)
+///
+/// Generated `md-error`, `md-warning` and `md-cell` comments and `md-table-const`
+/// comments are excluded so they are never treated as formulas.
 pub fn is_formula_comment(line: &str) -> bool {
     let trimmed = line.trim();
-    trimmed.starts_with("<!--") && trimmed.ends_with("-->")
+    trimmed.starts_with("<!--")
+        && trimmed.ends_with("-->")
+        && !is_generated_comment(line)
+        && !is_md_table_const_comment(line)
+}
+
+/// Checks if a line starts an md-error comment generated by a previous run
+pub fn is_md_error_comment(line: &str) -> bool {
+    line.trim().starts_with("<!-- md-error:")
+}
+
+/// Checks if a line starts an md-warning comment generated by a previous run
+pub fn is_md_warning_comment(line: &str) -> bool {
+    line.trim().starts_with("<!-- md-warning:")
+}
+
+/// Checks if a line starts an md-cell comment holding the full text of a truncated cell
+pub fn is_md_cell_comment(line: &str) -> bool {
+    line.trim().starts_with("<!-- md-cell:")
+}
+
+/// Checks if a line starts a comment generated by a previous run (error, warning or cell text)
+fn is_generated_comment(line: &str) -> bool {
+    is_md_error_comment(line) || is_md_warning_comment(line) || is_md_cell_comment(line)
+}
+
+/// Skips over any md-error, md-warning and md-cell comments starting at `start`
+///
+/// Error comments may span multiple lines (e.g. when they include a position
+/// indicator), so each one runs until the first line ending with `-->`.
+/// Returns the index of the first line after the skipped comments.
+pub fn skip_generated_comments(lines: &[&str], start: usize) -> usize {
+    let mut i = start;
+    while i < lines.len() && is_generated_comment(lines[i]) {
+        match generated_comment_end(lines, i) {
+            Some(end) => i = end + 1,
+            None => break,
+        }
+    }
+    i
+}
+
+/// Finds the line closing the generated comment that starts at `start`
+///
+/// Generated comments never contain blank lines, table rows or other
+/// comments, so a comment still open when one of those comes was not closed
+/// at all. Returns `None` for it, leaving it and the rest of the document in
+/// place.
+fn generated_comment_end(lines: &[&str], start: usize) -> Option<usize> {
+    let mut i = start;
+    while !lines[i].trim_end().ends_with("-->") {
+        i += 1;
+        let line = lines.get(i)?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || is_table_row(line) || trimmed.starts_with("<!--") {
+            return None;
+        }
+    }
+    Some(i)
+}
+
+/// The full text of a data cell that `wrap=ellipsis` shows cut short
+///
+/// It is kept in an md-cell comment after the table's directive, e.g.
+/// `<!-- md-cell: C1 = Roll out the new release -->`. Like formula addresses,
+/// `row` is 1-based and `col` 0-indexed, in the layout the table is written in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullCell {
+    pub row: usize,
+    pub col: usize,
+    pub text: String,
+}
+
+impl FullCell {
+    /// Formats the md-cell comment holding this cell's text
+    pub fn to_comment(&self) -> String {
+        use crate::table::formula::col_index_to_letter;
+
+        format!(
+            "<!-- md-cell: {}{} = {} -->",
+            col_index_to_letter(self.col),
+            self.row,
+            self.text
+        )
+    }
+
+    /// Parses an md-cell comment written by [`FullCell::to_comment`]
+    fn from_comment(line: &str) -> Option<FullCell> {
+        let content = line
+            .trim()
+            .strip_prefix("<!-- md-cell:")?
+            .strip_suffix("-->")?;
+        let (address, text) = content.split_once('=')?;
+        let address = address.trim();
+        let split = address.find(|c: char| c.is_ascii_digit())?;
+        let col = parse_column_letters(&address[..split])?;
+        let row = address[split..]
+            .parse::<usize>()
+            .ok()
+            .filter(|&row| row > 0)?;
+        Some(FullCell {
+            row,
+            col,
+            text: text.trim().to_string(),
+        })
+    }
+}
+
+/// Collects the md-cell comments of the table whose last row is just before `start`
+///
+/// Looks through the generated comments among the table's md-table directive
+/// comments, the same stretch of lines [`find_table_id`] searches. A table
+/// without a directive (formatted with default options) can only be followed
+/// by generated comments.
+pub fn find_full_cells(lines: &[&str], start: usize) -> Vec<FullCell> {
+    let has_directive = start < lines.len() && is_md_table_comment(lines[start]);
+    let mut cells = Vec::new();
+    let mut i = if has_directive { start + 1 } else { start };
+    loop {
+        let end = skip_generated_comments(lines, i);
+        cells.extend(
+            lines[i..end]
+                .iter()
+                .filter_map(|line| FullCell::from_comment(line)),
+        );
+        if !has_directive || end >= lines.len() || !is_formula_comment(lines[end]) {
+            return cells;
+        }
+        i = end + 1;
+    }
+}
+
+/// Puts the full text of cells cut short by `wrap=ellipsis` back into `rows`
+///
+/// A cell only gets its text back while it still shows the cut: the start of
+/// the text followed by `…`. A cell edited since it was written keeps the edit.
+pub fn restore_full_cells(rows: &mut [Vec<String>], cells: &[FullCell]) {
+    for full in cells {
+        let Some(cell) = rows
+            .get_mut(full.row + 1)
+            .and_then(|row| row.get_mut(full.col))
+        else {
+            continue;
+        };
+        if cell
+            .strip_suffix('…')
+            .is_some_and(|shown| full.text.starts_with(shown))
+        {
+            *cell = full.text.clone();
+        }
+    }
+}
+
+/// Finds the `id` attribute of the table whose last row is just before `start`
+///
+/// Looks through the md-table directive comments following the table (stale
+/// generated comments may be interleaved) and returns the first ID declared.
+pub fn find_table_id(lines: &[&str], start: usize) -> Option<String> {
+    if start >= lines.len() || !is_md_table_comment(lines[start]) {
+        return None;
+    }
+
+    let mut i = start;
+    while i < lines.len() && (i == start || is_formula_comment(lines[i])) {
+        if let Ok(TableDirective { id: Some(id), .. }) = extract_formulas_from_comment(lines[i]) {
+            return Some(id);
+        }
+        i = skip_generated_comments(lines, i + 1);
+    }
+
+    None
+}
+
+/// Options and formulas parsed from an md-table directive comment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableDirective {
+    /// Optional table ID for cross-table references
+    pub id: Option<String>,
+    /// Formula statements in the order they appear
+    pub formulas: Vec<String>,
+    /// Optional cap on column widths; longer cells overflow unpadded (`max_col_width=40`)
+    pub max_col_width: Option<usize>,
+    /// Whether cells wider than `max_col_width` are truncated instead (`wrap=ellipsis`)
+    pub wrap_ellipsis: bool,
+    /// Whether formulas may overwrite text cells without a warning (`overwrite=allow`)
+    pub allow_overwrite: bool,
+    /// Sort keys for the data rows, most significant first (`sort=B desc,[Name] asc`)
+    pub sort: Vec<SortSpec>,
+    /// Whether all-numeric columns are right-aligned automatically (`align=auto`)
+    pub auto_align: bool,
+    /// Whether negative numbers are shown in parentheses (`negatives=paren`)
+    pub paren_negatives: bool,
+    /// Whether formulas read cells such as `8%` as fractions (`percent-cells=true`)
+    pub percent_cells: bool,
+    /// Whether a table written without outer pipes keeps that style (`pipes=preserve`)
+    pub preserve_pipes: bool,
+    /// Requested layout: Some(true) for `style=compact`, Some(false) for `style=aligned`
+    pub compact: Option<bool>,
+    /// Optional condition a data row must meet to be kept (`filter=C>0`)
+    pub filter: Option<RowFilter>,
+    /// Aggregates for a generated totals row (`totals=B:sum,C:avg`)
+    pub totals: Vec<TotalSpec>,
+    /// First-column text of the totals row (`totals-label=Grand total`)
+    pub totals_label: Option<String>,
+    /// Whether the totals row sums every numeric column (`autosum`)
+    pub autosum: bool,
+    /// Whether the table's rows and columns are swapped before formatting (`transpose`)
+    pub transpose: bool,
+    /// Structural column edits, applied in order before formulas run (`addcol=Total after D`)
+    pub column_edits: Vec<ColumnEdit>,
+    /// Text written into the target of a formula that fails (`error-cell="#ERR"`)
+    pub error_cell: Option<String>,
+}
+
+impl TableDirective {
+    /// Adds the settings of a continuation comment to this directive
+    ///
+    /// The first `id` is kept; for other values the later comment wins when it
+    /// sets them. Flags are on if either comment turns them on, and formulas
+    /// and column edits run in the order the comments list them.
+    pub fn merge(&mut self, other: TableDirective) {
+        self.id = self.id.take().or(other.id);
+        self.formulas.extend(other.formulas);
+        self.max_col_width = other.max_col_width.or(self.max_col_width);
+        self.wrap_ellipsis |= other.wrap_ellipsis;
+        self.allow_overwrite |= other.allow_overwrite;
+        if !other.sort.is_empty() {
+            self.sort = other.sort;
+        }
+        self.auto_align |= other.auto_align;
+        self.paren_negatives |= other.paren_negatives;
+        self.percent_cells |= other.percent_cells;
+        self.preserve_pipes |= other.preserve_pipes;
+        self.compact = other.compact.or(self.compact);
+        self.filter = other.filter.or(self.filter.take());
+        if !other.totals.is_empty() {
+            self.totals = other.totals;
+        }
+        self.totals_label = other.totals_label.or(self.totals_label.take());
+        self.autosum |= other.autosum;
+        self.transpose |= other.transpose;
+        self.column_edits.extend(other.column_edits);
+        self.error_cell = other.error_cell.or(self.error_cell.take());
+    }
+}
+
+/// A structural edit requested by `addcol=`, `dropcol=` or `movecol=`
+///
+/// Column letters refer to the table as left by the previous edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnEdit {
+    /// Insert an empty column with this header (`addcol=Total after D`)
+    Add {
+        header: String,
+        position: ColumnPosition,
+    },
+    /// Remove a 0-indexed column (`dropcol=C`)
+    Drop { column: usize },
+    /// Move a 0-indexed column next to another one (`movecol=E before B`)
+    Move {
+        column: usize,
+        position: ColumnPosition,
+    },
+}
+
+/// Where a column edit places its column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnPosition {
+    /// Just before the 0-indexed column
+    Before(usize),
+    /// Just after the 0-indexed column
+    After(usize),
+    /// After the last column
+    End,
+}
+
+/// Attribute prefixes of column edits, which are removed from the comment once applied
+const COLUMN_EDIT_PREFIXES: [&str; 3] = ["addcol=", "dropcol=", "movecol="];
+
+/// One aggregate of a `totals=` attribute, such as `B:sum` or `sum(B_)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TotalSpec {
+    /// 0-indexed column the aggregate is computed over and written to
+    pub column: usize,
+    /// Name of the formula function applied to the column (`sum`, `avg`, ...)
+    pub function: String,
+}
+
+/// One key of a `sort=` attribute
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec {
+    /// Column whose values are the sort keys
+    pub column: SortColumn,
+    /// Sort from largest to smallest instead of smallest to largest
+    pub descending: bool,
+}
+
+/// How a sort key names its column
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortColumn {
+    /// A column letter (`B`), as a 0-indexed column
+    Index(usize),
+    /// A header cell's text in brackets (`[Price]`)
+    Header(String),
+}
+
+impl SortSpec {
+    /// The 0-indexed column this key sorts by, or None if no header matches
+    pub fn column_index(&self, headers: &[String]) -> Option<usize> {
+        match &self.column {
+            SortColumn::Index(col) => Some(*col),
+            SortColumn::Header(name) => headers.iter().position(|header| header == name),
+        }
+    }
 }
 
-/// Parses md-table directive and extracts optional ID and formulas
-/// Format: <!-- md-table: id="table_name"; A1 = B1 + C1; D1 = sum(C_) -->
-/// Returns (optional_id, formulas)
-pub fn extract_formulas_from_comment(line: &str) -> Result<(Option<String>, Vec<String>), String> {
+/// Condition requested by a `filter=` attribute, e.g. `C>0` or `B_ * 2 != A`
+///
+/// Both sides are formula expressions, evaluated once per data row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowFilter {
+    pub left: String,
+    pub comparison: Comparison,
+    pub right: String,
+}
+
+/// Comparison operator of a [`RowFilter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    /// Operators in the order they are matched, so `>=` is found before `>`
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        ("!=", Comparison::NotEqual),
+        ("==", Comparison::Equal),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+    ];
+
+    /// Whether the comparison holds for `left` compared to `right`
+    pub fn holds(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering;
+        match self {
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+        }
+    }
+}
+
+/// Parses the value of a `filter=` attribute into its two sides and operator
+fn parse_row_filter(value: &str) -> Result<RowFilter, String> {
+    let found = value.char_indices().find_map(|(idx, _)| {
+        Comparison::OPERATORS
+            .iter()
+            .find(|(operator, _)| value[idx..].starts_with(operator))
+            .map(|&(operator, comparison)| (idx, operator, comparison))
+    });
+    let Some((idx, operator, comparison)) = found else {
+        return Err(format!(
+            "Invalid filter: '{}' (expected a comparison such as C>0 using <, <=, >, >=, == or !=)",
+            value
+        ));
+    };
+
+    let left = value[..idx].trim();
+    let right = value[idx + operator.len()..].trim();
+    if left.is_empty() || right.is_empty() || right.contains(['<', '>', '=', '!']) {
+        return Err(format!(
+            "Invalid filter: '{}' (expected one comparison with an expression on each side)",
+            value
+        ));
+    }
+
+    Ok(RowFilter {
+        left: left.to_string(),
+        comparison,
+        right: right.to_string(),
+    })
+}
+
+/// Parses md-table directive and extracts optional attributes and formulas
+/// Format: <!-- md-table: id="table_name"; maxwidth=40; max_col_width=30; wrap=ellipsis; align=auto; negatives=paren; percent-cells=true; pipes=preserve; style=compact; sort=B desc,[Name] asc; filter=C>0; totals=B:sum,C:avg; totals-label=Total; autosum; error-cell="#ERR"; addcol=Total after D; dropcol=C; movecol=E before B; A1 = B1 + C1 -->
+/// or, without formulas: <!-- md-table: transpose -->
+pub fn extract_formulas_from_comment(line: &str) -> Result<TableDirective, String> {
     use crate::common::validate_id;
 
     let trimmed = line.trim();
@@ -62,8 +660,7 @@ pub fn extract_formulas_from_comment(line: &str) -> Result<(Option<String>, Vec<
     // Remove md-table: prefix if present
     let content = content.strip_prefix("md-table:").unwrap_or(content).trim();
 
-    let mut id = None;
-    let mut formulas = Vec::new();
+    let mut directive = TableDirective::default();
 
     // Split by semicolon
     for part in content.split(';') {
@@ -84,17 +681,427 @@ pub fn extract_formulas_from_comment(line: &str) -> Result<(Option<String>, Vec<
                 // Validate ID format
                 validate_id(&extracted_id).map_err(|e| format!("Invalid table ID: {}", e))?;
 
-                id = Some(extracted_id);
+                directive.id = Some(extracted_id);
             } else {
                 return Err("Table ID must be enclosed in double quotes".to_string());
             }
+        } else if part.starts_with("max_col_width=") {
+            // Extract column width cap (no quotes)
+            let value = part.strip_prefix("max_col_width=").unwrap().trim();
+            let width = value
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid max_col_width value: {}", value))?;
+            if width == 0 {
+                return Err("max_col_width must be greater than 0".to_string());
+            }
+            directive.max_col_width = Some(width);
+        } else if part.starts_with("wrap=") {
+            let value = part.strip_prefix("wrap=").unwrap().trim();
+            directive.wrap_ellipsis = match value {
+                "ellipsis" => true,
+                "overflow" => false,
+                _ => {
+                    return Err(format!(
+                        "Invalid wrap value: {} (expected wrap=overflow or wrap=ellipsis)",
+                        value
+                    ))
+                }
+            };
+        } else if part.starts_with("maxwidth=") {
+            // Shorthand for max_col_width=N; wrap=ellipsis
+            let value = part.strip_prefix("maxwidth=").unwrap().trim();
+            let width = value
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid maxwidth value: {}", value))?;
+            if width == 0 {
+                return Err("maxwidth must be greater than 0".to_string());
+            }
+            directive.max_col_width = Some(width);
+            directive.wrap_ellipsis = true;
+        } else if part.starts_with("overwrite=") {
+            // Only "allow" is meaningful; warnings are the default
+            let value = part.strip_prefix("overwrite=").unwrap().trim();
+            if value != "allow" {
+                return Err(format!(
+                    "Invalid overwrite value: {} (expected overwrite=allow)",
+                    value
+                ));
+            }
+            directive.allow_overwrite = true;
+        } else if part.starts_with("align=") {
+            // Only "auto" is meaningful; explicit alignment uses the separator row
+            let value = part.strip_prefix("align=").unwrap().trim();
+            if value != "auto" {
+                return Err(format!(
+                    "Invalid align value: {} (expected align=auto)",
+                    value
+                ));
+            }
+            directive.auto_align = true;
+        } else if part.starts_with("negatives=") {
+            // Only "paren" is meaningful; a leading minus sign is the default
+            let value = part.strip_prefix("negatives=").unwrap().trim();
+            if value != "paren" {
+                return Err(format!(
+                    "Invalid negatives value: {} (expected negatives=paren)",
+                    value
+                ));
+            }
+            directive.paren_negatives = true;
+        } else if part.starts_with("pipes=") {
+            // Only "preserve" is meaningful; tables are normalized to outer pipes by default
+            let value = part.strip_prefix("pipes=").unwrap().trim();
+            if value != "preserve" {
+                return Err(format!(
+                    "Invalid pipes value: {} (expected pipes=preserve)",
+                    value
+                ));
+            }
+            directive.preserve_pipes = true;
+        } else if part.starts_with("style=") {
+            let value = part.strip_prefix("style=").unwrap().trim();
+            directive.compact = match value {
+                "compact" => Some(true),
+                "aligned" => Some(false),
+                _ => {
+                    return Err(format!(
+                        "Invalid style value: {} (expected style=aligned or style=compact)",
+                        value
+                    ))
+                }
+            };
+        } else if part.starts_with("percent-cells=") {
+            let value = part.strip_prefix("percent-cells=").unwrap().trim();
+            directive.percent_cells = value.parse::<bool>().map_err(|_| {
+                format!(
+                    "Invalid percent-cells value: {} (expected true or false)",
+                    value
+                )
+            })?;
+        } else if part.starts_with("filter=") {
+            let value = part.strip_prefix("filter=").unwrap().trim();
+            directive.filter = Some(parse_row_filter(value)?);
+        } else if part.starts_with("addcol=") {
+            let value = part.strip_prefix("addcol=").unwrap().trim();
+            let (header, position) = split_column_position(value)?;
+            // The header may be quoted like other text attributes (addcol="Total")
+            let header = header
+                .strip_prefix('"')
+                .and_then(|header| header.strip_suffix('"'))
+                .unwrap_or(header);
+            if header.is_empty() || header.contains('|') {
+                return Err(format!(
+                    "Invalid addcol value: '{}' (expected a header without '|', optionally followed by before or after a column letter)",
+                    value
+                ));
+            }
+            directive.column_edits.push(ColumnEdit::Add {
+                header: header.to_string(),
+                position: position.unwrap_or(ColumnPosition::End),
+            });
+        } else if part.starts_with("dropcol=") {
+            let value = part.strip_prefix("dropcol=").unwrap().trim();
+            let column = parse_column_letter(value).ok_or_else(|| {
+                format!(
+                    "Invalid dropcol value: '{}' (expected a column letter)",
+                    value
+                )
+            })?;
+            directive.column_edits.push(ColumnEdit::Drop { column });
+        } else if part.starts_with("movecol=") {
+            let value = part.strip_prefix("movecol=").unwrap().trim();
+            let invalid = || {
+                format!(
+                    "Invalid movecol value: '{}' (expected COLUMN before|after COLUMN, e.g. E before B)",
+                    value
+                )
+            };
+            let (column, position) = split_column_position(value)?;
+            let column = parse_column_letter(column).ok_or_else(invalid)?;
+            let position = position.ok_or_else(invalid)?;
+            directive
+                .column_edits
+                .push(ColumnEdit::Move { column, position });
+        } else if part == "transpose" {
+            directive.transpose = true;
+        } else if part == "autosum" {
+            directive.autosum = true;
+        } else if part.starts_with("totals=") {
+            // Comma-separated aggregates, as `B:sum` or `sum(B_)`
+            let value = part.strip_prefix("totals=").unwrap().trim();
+            directive.totals = value
+                .split(',')
+                .map(parse_total_spec)
+                .collect::<Result<_, _>>()?;
+        } else if part.starts_with("totals-label=") {
+            let value = part.strip_prefix("totals-label=").unwrap().trim();
+            if value.is_empty() || value.contains('|') {
+                return Err(format!(
+                    "Invalid totals-label value: '{}' (expected non-empty text without '|')",
+                    value
+                ));
+            }
+            directive.totals_label = Some(value.to_string());
+        } else if part.starts_with("error-cell=") {
+            let value = part.strip_prefix("error-cell=").unwrap().trim();
+            match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(text) if !text.contains('|') => directive.error_cell = Some(text.to_string()),
+                _ => {
+                    return Err(format!(
+                        "Invalid error-cell value: {} (expected quoted text without '|', e.g. error-cell=\"#ERR\")",
+                        value
+                    ))
+                }
+            }
+        } else if part.starts_with("sort=") {
+            // Comma-separated keys, each a column and an optional direction (no quotes)
+            let value = part.strip_prefix("sort=").unwrap().trim();
+            directive.sort = value
+                .split(',')
+                .map(parse_sort_spec)
+                .collect::<Result<_, _>>()?;
         } else {
-            // This is a formula
-            formulas.push(part.to_string());
+            // This is a formula, possibly followed by a `# comment`
+            let formula = strip_formula_comment(part);
+            if !formula.is_empty() {
+                directive.formulas.push(formula.to_string());
+            }
+        }
+    }
+
+    if directive.transpose && !directive.formulas.is_empty() {
+        return Err(
+            "transpose cannot be combined with formulas in the same comment (cell coordinates would be ambiguous)"
+                .to_string(),
+        );
+    }
+
+    Ok(directive)
+}
+
+/// Removes a trailing `# comment` from a formula
+///
+/// A `#` inside a string literal (`from("a#b")`) is part of the formula. The
+/// comment ends at the next `;`, which starts the next formula.
+fn strip_formula_comment(formula: &str) -> &str {
+    let mut in_string = false;
+    for (idx, c) in formula.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return formula[..idx].trim_end(),
+            _ => {}
+        }
+    }
+    formula
+}
+
+/// Parses one key of a `sort=` attribute, e.g. `B`, `B asc`, `B desc`, or `[Price] desc`
+fn parse_sort_spec(value: &str) -> Result<SortSpec, String> {
+    let value = value.trim();
+
+    // A bracketed header name may contain spaces, so split it off first
+    let (column, rest) = if let Some(bracketed) = value.strip_prefix('[') {
+        let (name, rest) = bracketed
+            .split_once(']')
+            .ok_or_else(|| format!("Invalid sort column: '{}' (missing ']')", value))?;
+        if name.trim().is_empty() {
+            return Err(format!(
+                "Invalid sort column: '{}' (expected a header name)",
+                value
+            ));
+        }
+        (SortColumn::Header(name.trim().to_string()), rest)
+    } else {
+        let (column, rest) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+        let col = parse_column_letter(column).ok_or_else(|| {
+            format!(
+                "Invalid sort column: '{}' (expected a column letter or [Header])",
+                column
+            )
+        })?;
+        (SortColumn::Index(col), rest)
+    };
+
+    let mut parts = rest.split_whitespace();
+    let descending = match parts.next() {
+        None | Some("asc") => false,
+        Some("desc") => true,
+        Some(other) => {
+            return Err(format!(
+                "Invalid sort direction: '{}' (expected asc or desc)",
+                other
+            ))
+        }
+    };
+
+    if parts.next().is_some() {
+        return Err(format!("Invalid sort value: '{}'", value));
+    }
+
+    Ok(SortSpec { column, descending })
+}
+
+/// Parses one aggregate of a `totals=` attribute, e.g. `B:sum` or `sum(B_)`
+///
+/// Column A is rejected because it holds the totals row's label.
+fn parse_total_spec(value: &str) -> Result<TotalSpec, String> {
+    let value = value.trim();
+    let invalid = || {
+        format!(
+            "Invalid totals value: '{}' (expected COLUMN:FUNCTION such as B:sum, or FUNCTION(COLUMN_) such as sum(B_))",
+            value
+        )
+    };
+
+    let (column, function) = if let Some((column, function)) = value.split_once(':') {
+        (column.trim(), function.trim())
+    } else {
+        let (function, column) = value
+            .strip_suffix(')')
+            .and_then(|call| call.split_once('('))
+            .ok_or_else(invalid)?;
+        let column = column.trim().strip_suffix('_').ok_or_else(invalid)?;
+        (column, function.trim())
+    };
+
+    let column = parse_column_letter(column).ok_or_else(invalid)?;
+    if function.is_empty()
+        || !function
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(invalid());
+    }
+    if column == 0 {
+        return Err(format!(
+            "Invalid totals value: '{}' (column A holds the totals label)",
+            value
+        ));
+    }
+
+    Ok(TotalSpec {
+        column,
+        function: function.to_string(),
+    })
+}
+
+/// Splits a trailing `before X` or `after X` off a column edit value
+///
+/// Returns the text before it (trimmed) and the position, if there is one.
+fn split_column_position(value: &str) -> Result<(&str, Option<ColumnPosition>), String> {
+    let mut words = value.rsplitn(3, char::is_whitespace);
+    let (Some(column), Some(keyword @ ("before" | "after")), Some(subject)) =
+        (words.next(), words.next(), words.next())
+    else {
+        return Ok((value, None));
+    };
+
+    let column = parse_column_letter(column).ok_or_else(|| {
+        format!(
+            "Invalid column position: '{}' (expected {} followed by a column letter)",
+            value, keyword
+        )
+    })?;
+    let position = if keyword == "before" {
+        ColumnPosition::Before(column)
+    } else {
+        ColumnPosition::After(column)
+    };
+    Ok((subject.trim(), Some(position)))
+}
+
+/// Removes column edit attributes (`addcol=`, `dropcol=`, `movecol=`) from a directive comment
+///
+/// Returns the rewritten comment, or None if nothing else was in it. Comments
+/// without column edits are returned unchanged.
+pub fn strip_column_edits(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    let content = trimmed
+        .strip_prefix("<!--")
+        .unwrap_or(trimmed)
+        .strip_suffix("-->")
+        .unwrap_or(trimmed)
+        .trim();
+    let (prefix, content) = match content.strip_prefix("md-table:") {
+        Some(rest) => ("md-table: ", rest.trim()),
+        None => ("", content),
+    };
+
+    let is_edit = |part: &str| {
+        COLUMN_EDIT_PREFIXES
+            .iter()
+            .any(|edit| part.trim().starts_with(edit))
+    };
+    if !content.split(';').any(is_edit) {
+        return Some(line.to_string());
+    }
+
+    let kept: Vec<&str> = content
+        .split(';')
+        .map(str::trim)
+        .filter(|part| !part.is_empty() && !is_edit(part))
+        .collect();
+    if kept.is_empty() {
+        return None;
+    }
+    let indent = &line[..line.len() - line.trim_start().len()];
+    Some(format!("{}<!-- {}{} -->", indent, prefix, kept.join("; ")))
+}
+
+/// Parses a single column letter (`B` or `b`) into a 0-indexed column
+fn parse_column_letter(value: &str) -> Option<usize> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            Some((c.to_ascii_uppercase() as u32 - 'A' as u32) as usize)
         }
+        _ => None,
     }
+}
+
+/// Parses a column address of one or more letters (`B`, `AB`) into a 0-indexed column
+///
+/// The inverse of `col_index_to_letter`: columns past Z continue AA, AB, ...
+fn parse_column_letters(value: &str) -> Option<usize> {
+    if value.is_empty() {
+        return None;
+    }
+    let mut number: usize = 0;
+    for c in value.chars() {
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        let digit = (c.to_ascii_uppercase() as u8 - b'A') as usize + 1;
+        number = number.checked_mul(26)?.checked_add(digit)?;
+    }
+    Some(number - 1)
+}
+
+/// Extracts constant definitions from an md-table-const comment
+/// Format: <!-- md-table-const: tax = 0.0825; shipping = 4.99 -->
+pub fn extract_constants_from_comment(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+
+    // Remove <!-- and -->
+    let content = trimmed
+        .strip_prefix("<!--")
+        .unwrap_or(trimmed)
+        .strip_suffix("-->")
+        .unwrap_or(trimmed)
+        .trim();
+
+    // Remove md-table-const: prefix
+    let content = content
+        .strip_prefix("md-table-const:")
+        .unwrap_or(content)
+        .trim();
 
-    Ok((id, formulas))
+    content
+        .split(';')
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_string())
+        .collect()
 }
 
 #[cfg(test)]
@@ -106,6 +1113,10 @@ mod tests {
         assert_eq!(parse_table_row("| A | B | C |"), vec!["A", "B", "C"]);
         assert_eq!(parse_table_row("|A|B|C|"), vec!["A", "B", "C"]);
         assert_eq!(parse_table_row("  | A | B |  "), vec!["A", "B"]);
+        // Rows without the outer pipes have no empty cells at the edges
+        assert_eq!(parse_table_row("A | B"), vec!["A", "B"]);
+        assert_eq!(parse_table_row("---|---"), vec!["---", "---"]);
+        assert_eq!(parse_table_row("A | B |"), vec!["A", "B"]);
     }
 
     #[test]
@@ -121,6 +1132,34 @@ mod tests {
         assert!(!is_table_row("# Header"));
     }
 
+    #[test]
+    fn test_table_end_pipeless() {
+        let lines = vec![
+            "A | B",
+            "--- | ---",
+            "1 | 2",
+            "3 | 4",
+            "<!-- md-table: A1 = 1 -->",
+            "Prose with a | pipe",
+            "and more | prose",
+            "A | B",
+            "--- | --- | ---",
+            "Heading | text",
+            "---",
+        ];
+
+        assert_eq!(table_end(&lines, 0), Some(4));
+        assert_eq!(table_end(&lines, 2), None);
+        assert_eq!(table_end(&lines, 4), None);
+        assert_eq!(table_end(&lines, 5), None);
+        // The separator must have a cell per header cell
+        assert_eq!(table_end(&lines, 7), None);
+        // A setext underline without pipes is not a separator
+        assert_eq!(table_end(&lines, 9), None);
+
+        assert_eq!(table_end(&["| A |", "|---|", "| 1 |", "2 | 3"], 0), Some(3));
+    }
+
     #[test]
     fn test_is_separator_cell() {
         assert!(is_separator_cell("---"));
@@ -131,44 +1170,579 @@ mod tests {
         assert!(!is_separator_cell(""));
     }
 
+    #[test]
+    fn test_is_separator_row() {
+        let row = |cells: &[&str]| cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert!(is_separator_row(&row(&["---", ":---:", "---:"])));
+        assert!(!is_separator_row(&row(&["---", "data"])));
+        assert!(!is_separator_row(&[]));
+    }
+
+    #[test]
+    fn test_collect_tables_skips_code_fences() {
+        let lines = vec![
+            "| A | B |",
+            "|---|---|",
+            "| 1 | 2 |",
+            "",
+            "```",
+            "| X | Y |",
+            "```",
+            "| C |",
+            "|---|",
+        ];
+        let tables = collect_tables(&lines);
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].0, 0);
+        assert_eq!(tables[0].1.len(), 3);
+        assert_eq!(tables[1].0, 7);
+        assert_eq!(tables[1].1, vec![vec!["C"], vec!["---"]]);
+    }
+
+    #[test]
+    fn test_is_md_error_comment() {
+        assert!(is_md_error_comment("<!-- md-error: division by zero -->"));
+        assert!(is_md_error_comment("  <!-- md-error: Failed to evaluate"));
+        assert!(!is_md_error_comment("<!-- md-table: A1 = 1 -->"));
+        assert!(!is_formula_comment("<!-- md-error: division by zero -->"));
+        assert!(is_formula_comment("<!-- B1 = 2 -->"));
+    }
+
+    #[test]
+    fn test_is_md_warning_comment() {
+        assert!(is_md_warning_comment("<!-- md-warning: formula 1 -->"));
+        assert!(!is_md_warning_comment("<!-- md-error: formula 1 -->"));
+        assert!(!is_formula_comment("<!-- md-warning: formula 1 -->"));
+    }
+
+    #[test]
+    fn test_skip_generated_comments() {
+        let lines = vec![
+            "<!-- md-error: single line -->",
+            "<!-- md-error: Failed to evaluate expression:",
+            "unknown function: 'foo'",
+            "foo(A_)",
+            "^^^ -->",
+            "<!-- B1 = 2 -->",
+        ];
+        assert_eq!(skip_generated_comments(&lines, 0), 5);
+        assert_eq!(skip_generated_comments(&lines, 5), 5);
+
+        // Warnings are skipped alongside errors
+        let lines = vec![
+            "<!-- md-warning: overwrote -->",
+            "<!-- md-error: failed -->",
+            "text",
+        ];
+        assert_eq!(skip_generated_comments(&lines, 0), 2);
+
+        // An unclosed comment is left in place rather than swallowing what follows
+        let lines = vec!["<!-- md-error: never closed", "text"];
+        assert_eq!(skip_generated_comments(&lines, 0), 0);
+        let lines = vec![
+            "<!-- md-error: failed -->",
+            "<!-- md-error: never closed",
+            "",
+            "More prose",
+        ];
+        assert_eq!(skip_generated_comments(&lines, 0), 1);
+        let lines = vec!["<!-- md-error: never closed", "| A |", "| - |"];
+        assert_eq!(skip_generated_comments(&lines, 0), 0);
+        let lines = vec!["<!-- md-error: never closed", "<!-- md-table: A1 = 1 -->"];
+        assert_eq!(skip_generated_comments(&lines, 0), 0);
+    }
+
+    #[test]
+    fn test_md_table_const_comment() {
+        let line = "<!-- md-table-const: tax = 0.0825; shipping = 4.99 -->";
+        assert!(is_md_table_const_comment(line));
+        assert!(!is_md_table_comment(line));
+        assert!(!is_formula_comment(line));
+        assert_eq!(
+            extract_constants_from_comment(line),
+            vec!["tax = 0.0825", "shipping = 4.99"]
+        );
+        assert!(extract_constants_from_comment("<!-- md-table-const: -->").is_empty());
+    }
+
     #[test]
     fn test_extract_formulas_from_comment() {
         // Test with formulas only (no ID)
-        let (id, formulas) =
-            extract_formulas_from_comment("<!-- md-table: A1 = B1 + C1 -->").unwrap();
-        assert_eq!(id, None);
-        assert_eq!(formulas, vec!["A1 = B1 + C1"]);
+        let directive = extract_formulas_from_comment("<!-- md-table: A1 = B1 + C1 -->").unwrap();
+        assert_eq!(directive.id, None);
+        assert_eq!(directive.formulas, vec!["A1 = B1 + C1"]);
 
-        let (id, formulas) =
+        let directive =
             extract_formulas_from_comment("<!-- md-table: A1 = 5; B1 = 10 -->").unwrap();
-        assert_eq!(id, None);
-        assert_eq!(formulas, vec!["A1 = 5", "B1 = 10"]);
+        assert_eq!(directive.id, None);
+        assert_eq!(directive.formulas, vec!["A1 = 5", "B1 = 10"]);
 
-        let (id, formulas) = extract_formulas_from_comment("<!-- A1 = B1 + C1 -->").unwrap();
-        assert_eq!(id, None);
-        assert_eq!(formulas, vec!["A1 = B1 + C1"]);
+        let directive = extract_formulas_from_comment("<!-- A1 = B1 + C1 -->").unwrap();
+        assert_eq!(directive.id, None);
+        assert_eq!(directive.formulas, vec!["A1 = B1 + C1"]);
     }
 
     #[test]
     fn test_extract_formulas_with_id() {
         // Test with ID and formulas
-        let (id, formulas) =
+        let directive =
             extract_formulas_from_comment("<!-- md-table: id=\"sales_data\"; A1 = B1 + C1 -->")
                 .unwrap();
-        assert_eq!(id, Some("sales_data".to_string()));
-        assert_eq!(formulas, vec!["A1 = B1 + C1"]);
+        assert_eq!(directive.id, Some("sales_data".to_string()));
+        assert_eq!(directive.formulas, vec!["A1 = B1 + C1"]);
 
-        let (id, formulas) =
+        let directive =
             extract_formulas_from_comment("<!-- md-table: id=\"my_table\"; A1 = 5; B1 = 10 -->")
                 .unwrap();
-        assert_eq!(id, Some("my_table".to_string()));
-        assert_eq!(formulas, vec!["A1 = 5", "B1 = 10"]);
+        assert_eq!(directive.id, Some("my_table".to_string()));
+        assert_eq!(directive.formulas, vec!["A1 = 5", "B1 = 10"]);
 
         // Test with ID only (no formulas)
-        let (id, formulas) =
-            extract_formulas_from_comment("<!-- md-table: id=\"table1\" -->").unwrap();
-        assert_eq!(id, Some("table1".to_string()));
-        assert_eq!(formulas.len(), 0);
+        let directive = extract_formulas_from_comment("<!-- md-table: id=\"table1\" -->").unwrap();
+        assert_eq!(directive.id, Some("table1".to_string()));
+        assert_eq!(directive.formulas.len(), 0);
+    }
+
+    #[test]
+    fn test_extract_max_width() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: maxwidth=40; A1 = 5 -->").unwrap();
+        assert_eq!(directive.max_col_width, Some(40));
+        assert!(directive.wrap_ellipsis);
+        assert_eq!(directive.formulas, vec!["A1 = 5"]);
+
+        assert!(extract_formulas_from_comment("<!-- md-table: maxwidth=abc -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: maxwidth=0 -->").is_err());
+    }
+
+    #[test]
+    fn test_parse_row_with_pipes_in_code_spans() {
+        assert_eq!(parse_table_row("| `a|b` | 3 |"), vec!["`a|b`", "3"]);
+        assert_eq!(
+            parse_table_row("| ``ls | grep `x` `` | `cat` | 1 |"),
+            vec!["``ls | grep `x` ``", "`cat`", "1"]
+        );
+        // Escaped pipes are content too
+        assert_eq!(parse_table_row("| a \\| b | c |"), vec!["a \\| b", "c"]);
+        // An unmatched backtick is literal, so the pipe after it still splits
+        assert_eq!(parse_table_row("| it`s | ok |"), vec!["it`s", "ok"]);
+        // Empty cells and rows without a trailing pipe are unchanged
+        assert_eq!(parse_table_row("| a | |"), vec!["a", ""]);
+        assert_eq!(parse_table_row("| a | b"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_column_alignments() {
+        assert_eq!(parse_alignment("---"), Alignment::Left);
+        assert_eq!(parse_alignment(":---"), Alignment::Left);
+        assert_eq!(parse_alignment(":---:"), Alignment::Center);
+        assert_eq!(parse_alignment("---:"), Alignment::Right);
+
+        let rows = vec![
+            parse_table_row("| A | B | C |"),
+            parse_table_row("|:---|:---:|---:|"),
+        ];
+        assert_eq!(
+            column_alignments(&rows),
+            vec![Alignment::Left, Alignment::Center, Alignment::Right]
+        );
+
+        // Without a separator row nothing is aligned explicitly
+        let rows = vec![parse_table_row("| A |"), parse_table_row("| 1 |")];
+        assert!(column_alignments(&rows).is_empty());
+    }
+
+    #[test]
+    fn test_find_table_id() {
+        let lines = vec![
+            "| A |",
+            "|---|",
+            "<!-- md-table: A1 = 1 -->",
+            "<!-- md-error: stale -->",
+            "<!-- md-table: id=\"totals\" -->",
+            "",
+            "<!-- md-table: id=\"other\" -->",
+        ];
+
+        // The ID may be on a continuation comment after stale generated comments
+        assert_eq!(find_table_id(&lines, 2), Some("totals".to_string()));
+        // Comments separated by a blank line belong to nothing
+        assert_eq!(find_table_id(&lines, 5), None);
+        assert_eq!(find_table_id(&lines, 7), None);
+    }
+
+    #[test]
+    fn test_extract_auto_align() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: align=auto; A1 = 1 -->").unwrap();
+        assert!(directive.auto_align);
+        assert_eq!(directive.formulas, vec!["A1 = 1"]);
+
+        assert!(
+            !extract_formulas_from_comment("<!-- md-table: A1 = 1 -->")
+                .unwrap()
+                .auto_align
+        );
+        assert!(extract_formulas_from_comment("<!-- md-table: align=right -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_percent_cells() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: percent-cells=true -->").unwrap();
+        assert!(directive.percent_cells);
+        assert!(
+            !extract_formulas_from_comment("<!-- md-table: percent-cells=false -->")
+                .unwrap()
+                .percent_cells
+        );
+        assert!(extract_formulas_from_comment("<!-- md-table: percent-cells=yes -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_max_col_width() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: max_col_width=30; wrap=ellipsis -->")
+                .unwrap();
+        assert_eq!(directive.max_col_width, Some(30));
+        assert!(directive.wrap_ellipsis);
+
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: max_col_width=30 -->").unwrap();
+        assert!(!directive.wrap_ellipsis);
+
+        assert!(extract_formulas_from_comment("<!-- md-table: max_col_width=0 -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: wrap=word -->").is_err());
+    }
+
+    #[test]
+    fn test_merge_directives() {
+        let mut directive =
+            extract_formulas_from_comment("<!-- md-table: id=\"a\"; sort=B; A1 = 1 -->").unwrap();
+        directive.merge(
+            extract_formulas_from_comment(
+                "<!-- id=\"b\"; maxwidth=20; max_col_width=30; dropcol=C; A2 = 2 -->",
+            )
+            .unwrap(),
+        );
+
+        // The first ID is kept, later values replace earlier ones, lists are appended
+        assert_eq!(directive.id, Some("a".to_string()));
+        assert_eq!(directive.sort.len(), 1);
+        assert_eq!(directive.max_col_width, Some(30));
+        assert!(directive.wrap_ellipsis);
+        assert_eq!(directive.formulas, vec!["A1 = 1", "A2 = 2"]);
+        assert_eq!(directive.column_edits, vec![ColumnEdit::Drop { column: 2 }]);
+    }
+
+    #[test]
+    fn test_full_cells_restored_unless_edited() {
+        let lines = [
+            "| Id | Notes |",
+            "|---|---|",
+            "| 1 | a ver… |",
+            "| 2 | some… |",
+            "<!-- md-table: max_col_width=6; wrap=ellipsis -->",
+            "<!-- md-cell: B1 = a very long note = yes -->",
+            "<!-- md-cell: B2 = another long note -->",
+        ];
+        let cells = find_full_cells(&lines, 4);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].to_comment(), lines[5]);
+        assert_eq!(cells[0].text, "a very long note = yes");
+        assert!(!is_formula_comment(lines[5]));
+
+        // B2 no longer shows the cut of its full text, so the edit is kept
+        let mut rows = collect_tables(&lines).remove(0).1;
+        restore_full_cells(&mut rows, &cells);
+        assert_eq!(rows[2][1], "a very long note = yes");
+        assert_eq!(rows[3][1], "some…");
+    }
+
+    #[test]
+    fn test_extract_style() {
+        let directive = extract_formulas_from_comment("<!-- md-table: style=compact -->").unwrap();
+        assert_eq!(directive.compact, Some(true));
+        let directive = extract_formulas_from_comment("<!-- md-table: style=aligned -->").unwrap();
+        assert_eq!(directive.compact, Some(false));
+        let directive = extract_formulas_from_comment("<!-- md-table: A1 = 1 -->").unwrap();
+        assert_eq!(directive.compact, None);
+        assert!(extract_formulas_from_comment("<!-- md-table: style=dense -->").is_err());
+    }
+
+    #[test]
+    fn test_pad_rows() {
+        let mut rows: Vec<Vec<String>> = ["| A | B |", "|---|---|", "| 1 |", "| 2 | 3 | 4 |"]
+            .iter()
+            .map(|line| parse_table_row(line))
+            .collect();
+        pad_rows(&mut rows);
+        assert_eq!(
+            rows,
+            vec![
+                vec!["A", "B", ""],
+                vec!["---", "---", "---"],
+                vec!["1", "", ""],
+                vec!["2", "3", "4"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_preserve_pipes() {
+        let directive = extract_formulas_from_comment("<!-- md-table: pipes=preserve -->").unwrap();
+        assert!(directive.preserve_pipes);
+        assert!(extract_formulas_from_comment("<!-- md-table: pipes=strip -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_paren_negatives() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: negatives=paren -->").unwrap();
+        assert!(directive.paren_negatives);
+        assert!(extract_formulas_from_comment("<!-- md-table: negatives=red -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_sort() {
+        let key = |col: usize, descending: bool| SortSpec {
+            column: SortColumn::Index(col),
+            descending,
+        };
+
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: sort=B desc; A1 = 5 -->").unwrap();
+        assert_eq!(directive.sort, vec![key(1, true)]);
+        assert_eq!(directive.formulas, vec!["A1 = 5"]);
+
+        let directive = extract_formulas_from_comment("<!-- md-table: sort=c -->").unwrap();
+        assert_eq!(directive.sort, vec![key(2, false)]);
+
+        let directive = extract_formulas_from_comment("<!-- md-table: A1 = 5 -->").unwrap();
+        assert!(directive.sort.is_empty());
+
+        assert!(extract_formulas_from_comment("<!-- md-table: sort=B down -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: sort=AB -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: sort= -->").is_err());
+    }
+
+    #[test]
+    fn test_error_cell_attribute() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: error-cell=\"#ERR\"; A1 = 1 -->")
+                .unwrap();
+        assert_eq!(directive.error_cell.as_deref(), Some("#ERR"));
+        assert!(extract_formulas_from_comment("<!-- md-table: error-cell=#ERR -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_sort_multiple_keys_and_headers() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: sort=B desc, [Unit Price] asc,A -->")
+                .unwrap();
+        assert_eq!(
+            directive.sort,
+            vec![
+                SortSpec {
+                    column: SortColumn::Index(1),
+                    descending: true
+                },
+                SortSpec {
+                    column: SortColumn::Header("Unit Price".to_string()),
+                    descending: false
+                },
+                SortSpec {
+                    column: SortColumn::Index(0),
+                    descending: false
+                },
+            ]
+        );
+
+        let headers = vec!["Item".to_string(), "Unit Price".to_string()];
+        assert_eq!(directive.sort[1].column_index(&headers), Some(1));
+        let unknown = SortSpec {
+            column: SortColumn::Header("Cost".to_string()),
+            descending: false,
+        };
+        assert_eq!(unknown.column_index(&headers), None);
+
+        assert!(extract_formulas_from_comment("<!-- md-table: sort=[Price desc -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: sort=[] -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: sort=B desc, -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_filter() {
+        let directive = extract_formulas_from_comment("<!-- md-table: filter=C>0 -->").unwrap();
+        assert_eq!(
+            directive.filter,
+            Some(RowFilter {
+                left: "C".to_string(),
+                comparison: Comparison::Greater,
+                right: "0".to_string(),
+            })
+        );
+
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: filter=B_ * 2 >= sum(A_); A1 = 1 -->")
+                .unwrap();
+        let filter = directive.filter.unwrap();
+        assert_eq!(filter.left, "B_ * 2");
+        assert_eq!(filter.comparison, Comparison::GreaterOrEqual);
+        assert_eq!(filter.right, "sum(A_)");
+        assert_eq!(directive.formulas, vec!["A1 = 1"]);
+
+        assert!(extract_formulas_from_comment("<!-- md-table: filter=C -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: filter=>0 -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: filter=0<C<5 -->").is_err());
+    }
+
+    #[test]
+    fn test_extract_totals() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: totals=B:sum, c:avg -->").unwrap();
+        assert_eq!(
+            directive.totals,
+            vec![
+                TotalSpec {
+                    column: 1,
+                    function: "sum".to_string(),
+                },
+                TotalSpec {
+                    column: 2,
+                    function: "avg".to_string(),
+                },
+            ]
+        );
+        assert_eq!(directive.totals_label, None);
+
+        let directive = extract_formulas_from_comment(
+            "<!-- md-table: totals=sum(B_),max(D_); totals-label=Grand total -->",
+        )
+        .unwrap();
+        assert_eq!(directive.totals.len(), 2);
+        assert_eq!(directive.totals[1].column, 3);
+        assert_eq!(directive.totals[1].function, "max");
+        assert_eq!(directive.totals_label, Some("Grand total".to_string()));
+        assert!(directive.formulas.is_empty());
+
+        assert!(extract_formulas_from_comment("<!-- md-table: totals=A:sum -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: totals=sum(B) -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: totals=B -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: totals-label= -->").is_err());
+
+        let directive = extract_formulas_from_comment("<!-- md-table: autosum -->").unwrap();
+        assert!(directive.autosum);
+        assert!(directive.formulas.is_empty());
+    }
+
+    #[test]
+    fn test_extract_transpose() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: transpose; style=compact -->").unwrap();
+        assert!(directive.transpose);
+        assert_eq!(directive.compact, Some(true));
+        assert!(directive.formulas.is_empty());
+
+        let error =
+            extract_formulas_from_comment("<!-- md-table: transpose; A1 = 1 -->").unwrap_err();
+        assert!(error.contains("transpose cannot be combined with formulas"));
+    }
+
+    #[test]
+    fn test_extract_formulas_with_comments() {
+        let directive = extract_formulas_from_comment(
+            "<!-- md-table: C_ = A_ + B_ # total; # a note; D1 = from(\"a#b\", A1) # copy -->",
+        )
+        .unwrap();
+        assert_eq!(
+            directive.formulas,
+            vec!["C_ = A_ + B_", "D1 = from(\"a#b\", A1)"]
+        );
+    }
+
+    #[test]
+    fn test_extract_column_edits() {
+        let directive = extract_formulas_from_comment(
+            "<!-- md-table: addcol=Unit Price after b; dropcol=C; movecol=E before B; addcol=Notes; D_ = B_ * 2 -->",
+        )
+        .unwrap();
+        assert_eq!(
+            directive.column_edits,
+            vec![
+                ColumnEdit::Add {
+                    header: "Unit Price".to_string(),
+                    position: ColumnPosition::After(1),
+                },
+                ColumnEdit::Drop { column: 2 },
+                ColumnEdit::Move {
+                    column: 4,
+                    position: ColumnPosition::Before(1),
+                },
+                ColumnEdit::Add {
+                    header: "Notes".to_string(),
+                    position: ColumnPosition::End,
+                },
+            ]
+        );
+        assert_eq!(directive.formulas, vec!["D_ = B_ * 2"]);
+
+        assert!(extract_formulas_from_comment("<!-- md-table: dropcol=Total -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: movecol=E -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: movecol=E before -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: addcol=Total after DD -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: addcol= -->").is_err());
+        assert!(extract_formulas_from_comment("<!-- md-table: addcol=\"\" -->").is_err());
+
+        // Quoted headers lose one pair of quotes, like id= and error-cell=
+        let directive = extract_formulas_from_comment(
+            "<!-- md-table: addcol=\"Total\"; addcol=\"Grand total\" after A -->",
+        )
+        .unwrap();
+        assert_eq!(
+            directive.column_edits,
+            vec![
+                ColumnEdit::Add {
+                    header: "Total".to_string(),
+                    position: ColumnPosition::End,
+                },
+                ColumnEdit::Add {
+                    header: "Grand total".to_string(),
+                    position: ColumnPosition::After(0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_column_edits() {
+        assert_eq!(
+            strip_column_edits("<!-- md-table: id=\"t\"; addcol=Total after D; E_ = D_ * 2 -->"),
+            Some("<!-- md-table: id=\"t\"; E_ = D_ * 2 -->".to_string())
+        );
+        assert_eq!(strip_column_edits("<!-- md-table: dropcol=C -->"), None);
+        assert_eq!(
+            strip_column_edits("<!-- movecol=C before A;B1 = 2 -->"),
+            Some("<!-- B1 = 2 -->".to_string())
+        );
+        // Comments without column edits keep their exact text
+        assert_eq!(
+            strip_column_edits("<!-- md-table:  A1 = 1;B1 = 2 -->"),
+            Some("<!-- md-table:  A1 = 1;B1 = 2 -->".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_overwrite_allow() {
+        let directive =
+            extract_formulas_from_comment("<!-- md-table: overwrite=allow; A1 = 5 -->").unwrap();
+        assert!(directive.allow_overwrite);
+        assert_eq!(directive.formulas, vec!["A1 = 5"]);
+
+        let directive = extract_formulas_from_comment("<!-- md-table: A1 = 5 -->").unwrap();
+        assert!(!directive.allow_overwrite);
+
+        assert!(extract_formulas_from_comment("<!-- md-table: overwrite=yes -->").is_err());
     }
 
     #[test]
@@ -0,0 +1,148 @@
+//! Swapping the rows and columns of markdown tables
+
+use super::formatter::{column_widths, push_table_row};
+use super::parser::{collect_tables, is_separator_row};
+use crate::common::{with_line_endings, ProcessingResult};
+
+/// Transposes every markdown table in the document
+///
+/// Row 0 is treated as the header, so the old first column becomes the new
+/// header row and each old row becomes a column. The separator row is dropped
+/// and regenerated, ragged rows are padded with empty cells, and the result is
+/// re-aligned. Everything outside tables (including `md-table` comments and
+/// tables inside code fences) is passed through unchanged; formulas are not
+/// rewritten, so references in them will point at the old positions.
+///
+/// # Returns
+///
+/// A [`ProcessingResult`] containing the document with all tables transposed.
+/// This operation is infallible, so the result will never contain errors.
+///
+/// # Examples
+///
+/// ```
+/// use mdutils::table::transpose_tables;
+///
+/// let result = transpose_tables("| Name | Age |\n|---|---|\n| Bob | 30 |\n");
+/// assert_eq!(
+///     result.output,
+///     "| Name | Bob |\n| ---- | --- |\n| Age  | 30  |\n"
+/// );
+/// ```
+pub fn transpose_tables(text: &str) -> ProcessingResult {
+    with_line_endings(text, transpose_tables_lf)
+}
+
+/// Transposes the tables of a document with `\n` line endings
+fn transpose_tables_lf(text: &str) -> ProcessingResult {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut i = 0;
+
+    for (start_line, rows) in collect_tables(&lines) {
+        output.extend(lines[i..start_line].iter().map(|line| line.to_string()));
+        output.push(format_transposed(&rows));
+        i = start_line + rows.len();
+    }
+    output.extend(lines[i..].iter().map(|line| line.to_string()));
+
+    let mut result = output.join("\n");
+
+    // Preserve trailing newline if the original input had one
+    if text.ends_with('\n') {
+        result.push('\n');
+    }
+
+    ProcessingResult::success(result)
+}
+
+/// Transposes parsed table rows (including the separator) and formats the result
+fn format_transposed(rows: &[Vec<String>]) -> String {
+    let table_rows = transpose_table_rows(rows);
+    let col_widths = column_widths(&table_rows);
+
+    let mut output = String::new();
+    for (idx, row) in table_rows.iter().enumerate() {
+        if idx > 0 {
+            output.push('\n');
+        }
+        push_table_row(&mut output, row, &col_widths, &[]);
+    }
+    output
+}
+
+/// Transposes parsed table rows, dropping the separator row and generating a fresh one
+///
+/// The old first column becomes the new header row. Short rows are padded with
+/// empty cells first.
+pub(crate) fn transpose_table_rows(rows: &[Vec<String>]) -> Vec<Vec<String>> {
+    let content: Vec<&Vec<String>> = rows
+        .iter()
+        .enumerate()
+        .filter(|(idx, row)| !(*idx == 1 && is_separator_row(row)))
+        .map(|(_, row)| row)
+        .collect();
+
+    let transposed = transpose(&content);
+    let cols = transposed.first().map(|row| row.len()).unwrap_or(0);
+
+    let mut table_rows = Vec::with_capacity(transposed.len() + 1);
+    for (idx, row) in transposed.into_iter().enumerate() {
+        table_rows.push(row);
+        if idx == 0 {
+            table_rows.push(vec!["---".to_string(); cols]);
+        }
+    }
+    table_rows
+}
+
+/// Swaps rows and columns, padding short rows with empty cells
+fn transpose(rows: &[&Vec<String>]) -> Vec<Vec<String>> {
+    let cols = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+    (0..cols)
+        .map(|col| {
+            rows.iter()
+                .map(|row| row.get(col).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transpose_pads_ragged_rows() {
+        let a = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let b = vec!["d".to_string()];
+        let result = transpose(&[&a, &b]);
+        assert_eq!(result, vec![vec!["a", "d"], vec!["b", ""], vec!["c", ""]]);
+    }
+
+    #[test]
+    fn test_transpose_2x3_table() {
+        let input = "| Name | Q1 | Q2 |\n|---|---|---|\n| Alice | 10 | 20 |\n| Bob | 30 | 40 |\n";
+        let result = transpose_tables(input);
+        assert!(!result.has_errors());
+
+        let lines: Vec<&str> = result.output.lines().collect();
+        // 3 columns become 3 rows (header + separator + 2 data rows), 3 rows become 3 columns
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "| Name | Alice | Bob |");
+        assert_eq!(lines[1], "| ---- | ----- | --- |");
+        assert_eq!(lines[2], "| Q1   | 10    | 30  |");
+        assert_eq!(lines[3], "| Q2   | 20    | 40  |");
+    }
+
+    #[test]
+    fn test_transpose_leaves_other_content() {
+        let input = "Intro\n\n| A | B |\n|---|---|\n| 1 | 2 |\n<!-- md-table: id=\"t\" -->\n\n```\n| X | Y |\n```\n";
+        let result = transpose_tables(input);
+        assert_eq!(
+            result.output,
+            "Intro\n\n| A   | 1   |\n| --- | --- |\n| B   | 2   |\n<!-- md-table: id=\"t\" -->\n\n```\n| X | Y |\n```\n"
+        );
+    }
+}
@@ -44,7 +44,7 @@
 /// ```
 mod parser;
 
-use crate::common::{CodeFenceTracker, ProcessingResult};
+use crate::common::{with_line_endings, CodeFenceTracker, ProcessingError, ProcessingResult};
 use parser::{parse_headers, Header};
 
 const TOC_START_MARKER: &str = "<!-- md-toc: -->";
@@ -61,6 +61,11 @@ const TOC_END_MARKER: &str = "<!-- md-toc: end -->";
 /// A [`ProcessingResult`] containing the updated document with TOC generated or updated.
 /// This operation is infallible, so the result will never contain errors.
 pub fn process_toc(input: &str) -> ProcessingResult {
+    with_line_endings(input, process_toc_lf)
+}
+
+/// Generates or updates the TOC of a document with `\n` line endings
+fn process_toc_lf(input: &str) -> ProcessingResult {
     let lines: Vec<&str> = input.lines().collect();
 
     // Find TOC marker (skip those inside code fences)
@@ -127,6 +132,64 @@ pub fn process_toc(input: &str) -> ProcessingResult {
     ProcessingResult::success(result.join("\n") + "\n")
 }
 
+/// Counts the document's headers by level, `counts[0]` being H1
+///
+/// Headers inside code fences are not counted. Used by `md stats`.
+pub(crate) fn count_headers(input: &str) -> [usize; 6] {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut counts = [0; 6];
+    for header in parse_headers(&lines, 0) {
+        counts[header.level - 1] += 1;
+    }
+    counts
+}
+
+/// Finds problems with the TOC markers without changing the document
+///
+/// Reports a start marker with no end marker after it, and a start marker
+/// directly followed by its end marker although the document has headers to
+/// list. Markers inside code fences are ignored.
+pub(crate) fn lint_toc(input: &str) -> Vec<ProcessingError> {
+    let lines: Vec<&str> = input.lines().collect();
+    let mut fence_tracker = CodeFenceTracker::new();
+    let mut start = None;
+    let mut end = None;
+    for (i, line) in lines.iter().enumerate() {
+        fence_tracker.process_line(line);
+        if fence_tracker.is_inside_code_block() {
+            continue;
+        }
+        match line.trim() {
+            TOC_START_MARKER if start.is_none() => start = Some(i),
+            TOC_END_MARKER if start.is_some() => {
+                end = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let Some(start) = start else {
+        return Vec::new();
+    };
+    match end {
+        None => vec![ProcessingError::toc(
+            start + 1,
+            format!(
+                "{} marker has no {} after it",
+                TOC_START_MARKER, TOC_END_MARKER
+            ),
+        )],
+        Some(end) if end == start + 1 && !parse_headers(&lines, start + 1).is_empty() => {
+            vec![ProcessingError::toc(
+                start + 1,
+                "table of contents is empty; run md toc to fill it in",
+            )]
+        }
+        Some(_) => Vec::new(),
+    }
+}
+
 /// Generate table of contents from headers
 ///
 /// # Arguments
@@ -163,6 +226,26 @@ fn generate_toc(headers: &[Header]) -> Vec<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_lint_toc() {
+        let empty = "# Doc\n<!-- md-toc: -->\n<!-- md-toc: end -->\n\n## Section\n";
+        let findings = lint_toc(empty);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 2);
+        assert!(findings[0].message.contains("empty"));
+
+        let unclosed = "# Doc\n\n<!-- md-toc: -->\n## Section\n";
+        let findings = lint_toc(unclosed);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 3);
+        assert!(findings[0].message.contains("has no <!-- md-toc: end -->"));
+
+        // Filled in, or empty because there is nothing to list
+        assert!(lint_toc(&process_toc(empty).output).is_empty());
+        assert!(lint_toc("<!-- md-toc: -->\n<!-- md-toc: end -->\n").is_empty());
+        assert!(lint_toc("```\n<!-- md-toc: -->\n```\n").is_empty());
+    }
+
     #[test]
     fn test_no_toc_marker() {
         let input = "# Header\n## Subheader\n";
@@ -1,4 +1,7 @@
-use mdutils::process_code_blocks;
+use mdutils::{
+    list_code_blocks, process_code_blocks, process_code_blocks_in,
+    process_code_blocks_with_options, verify_code_blocks, CodeOptions,
+};
 /// Integration tests for code execution
 /// Tests use fixture files in tests/code/fixtures/ directory
 use std::fs;
@@ -18,9 +21,7 @@ fn test_basic_python_execution() {
     );
     assert_eq!(result.output.trim(), expected.trim());
     // Verify output block was created
-    assert!(result
-        .output
-        .contains("<!-- md-code-output: id=\"hello\" -->"));
+    assert!(result.output.contains("<!-- md-code-output: id=\"hello\""));
     assert!(result.output.contains("Hello, World!"));
 
     // Idempotency check: command(expected) should equal expected
@@ -84,15 +85,9 @@ fn test_multiple_code_blocks() {
     );
     assert_eq!(result.output.trim(), expected.trim());
     // Verify all three blocks executed
-    assert!(result
-        .output
-        .contains("<!-- md-code-output: id=\"first\" -->"));
-    assert!(result
-        .output
-        .contains("<!-- md-code-output: id=\"second\" -->"));
-    assert!(result
-        .output
-        .contains("<!-- md-code-output: id=\"third\" -->"));
+    assert!(result.output.contains("<!-- md-code-output: id=\"first\""));
+    assert!(result.output.contains("<!-- md-code-output: id=\"second\""));
+    assert!(result.output.contains("<!-- md-code-output: id=\"third\""));
     assert!(result.output.contains("First"));
     assert!(result.output.contains("Second"));
     assert!(result.output.contains("Third"));
@@ -128,10 +123,10 @@ fn test_no_execute_flag() {
     // Verify both blocks executed (both have md-code directives)
     assert!(result
         .output
-        .contains("<!-- md-code-output: id=\"no_exec\" -->"));
+        .contains("<!-- md-code-output: id=\"no_exec\""));
     assert!(result
         .output
-        .contains("<!-- md-code-output: id=\"yes_exec\" -->"));
+        .contains("<!-- md-code-output: id=\"yes_exec\""));
 
     // Idempotency check: command(expected) should equal expected
     let result2 = process_code_blocks(&expected);
@@ -196,7 +191,7 @@ fn test_empty_output() {
     // Verify no output block was created for empty output
     assert!(!result
         .output
-        .contains("<!-- md-code-output: id=\"no_output\" -->"));
+        .contains("<!-- md-code-output: id=\"no_output\""));
 
     // Idempotency check: command(expected) should equal expected
     let result2 = process_code_blocks(&expected);
@@ -307,7 +302,7 @@ fn test_skip_nested_code_blocks() {
     // Verify there's no output block for the "example" id
     assert!(!result
         .output
-        .contains("<!-- md-code-output: id=\"example\" -->"));
+        .contains("<!-- md-code-output: id=\"example\""));
 
     // Idempotency check: command(expected) should equal expected
     let result2 = process_code_blocks(&expected);
@@ -351,10 +346,10 @@ fn test_meta_programming() {
     // Verify both output blocks were created
     assert!(result
         .output
-        .contains("<!-- md-code-output: id=\"table_demo\" -->"));
+        .contains("<!-- md-code-output: id=\"table_demo\""));
     assert!(result
         .output
-        .contains("<!-- md-code-output: id=\"toc_demo\" -->"));
+        .contains("<!-- md-code-output: id=\"toc_demo\""));
 
     // Idempotency check: command(expected) should equal expected
     let result2 = process_code_blocks(&expected);
@@ -498,3 +493,1075 @@ fn test_custom_syntax() {
         "Not idempotent: running on expected output produced different result"
     );
 }
+
+#[test]
+fn test_program_args() {
+    let input = fs::read_to_string("tests/code/fixtures/program_args_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/code/fixtures/program_args_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = process_code_blocks(&input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert_eq!(result.output.trim(), expected.trim());
+    // Quoted arguments arrive as one argv entry
+    assert!(result
+        .output
+        .contains("['--format', 'svg', '--title', 'My plot']"));
+    assert!(result.output.contains("2 arguments: first / second arg"));
+    // An interpreter told to read the script from stdin gets the args too
+    assert!(result.output.contains("['--dpi', '120']"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = process_code_blocks(&expected);
+    assert!(
+        !result2.has_errors(),
+        "Processing expected failed: {:?}",
+        result2.errors
+    );
+    assert_eq!(
+        result2.output.trim(),
+        expected.trim(),
+        "Not idempotent: running on expected output produced different result"
+    );
+}
+
+#[test]
+fn test_env_vars() {
+    let input = fs::read_to_string("tests/code/fixtures/env_vars_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/code/fixtures/env_vars_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = process_code_blocks(&input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert_eq!(result.output.trim(), expected.trim());
+    assert!(result
+        .output
+        .contains("http://localhost:8080 (debug=1)\nhello, world"));
+
+    // A malformed entry is reported instead of running the block
+    let malformed = input.replace("DEBUG=1", "DEBUG");
+    let result = process_code_blocks(&malformed);
+    assert!(result.has_errors());
+    assert!(result.errors[0]
+        .message
+        .contains("Invalid env entry 'DEBUG'"));
+    assert_eq!(result.output, malformed);
+}
+
+#[test]
+fn test_cwd_resolved_from_document_directory() {
+    let dir = std::env::temp_dir().join(format!("mdutils-code-cwd-{}", std::process::id()));
+    fs::create_dir_all(dir.join("scripts")).unwrap();
+    fs::write(dir.join("scripts/data.csv"), "a,b\n1,2\n").unwrap();
+    let doc = "```bash\nhead -n 1 data.csv\n```\n<!-- md-code: id=\"header\"; bin=\"bash\"; cwd=\"./scripts\" -->\n";
+    fs::write(dir.join("doc.md"), doc).unwrap();
+
+    let result = process_code_blocks_in(doc, &dir);
+    let missing = process_code_blocks_in(&doc.replace("./scripts", "nope"), &dir);
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+        .arg("code")
+        .arg(dir.join("doc.md"))
+        .output()
+        .expect("Failed to run md");
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert!(result.output.contains("Output:\n```\na,b\n"));
+
+    // Given a file, md code resolves cwd= from the file's directory
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), result.output);
+
+    // A missing directory is reported by path and the input is left unchanged
+    assert!(missing.has_errors());
+    let message = &missing.errors[0].message;
+    assert!(
+        message.contains(&dir.join("nope").display().to_string()),
+        "{}",
+        message
+    );
+    assert!(message.contains("does not exist"), "{}", message);
+}
+
+#[test]
+fn test_stdin() {
+    let input = fs::read_to_string("tests/code/fixtures/stdin_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/code/fixtures/stdin_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = process_code_blocks(&input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert_eq!(result.output.trim(), expected.trim());
+    // Literal input, and a consumer that runs after the producer below it
+    assert!(result.output.contains("Output:\n```\napple\nfig\npear\n"));
+    assert!(result.output.contains("Output:\n```\n2 rows, total 13\n"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = process_code_blocks(&expected);
+    assert_eq!(
+        result2.output.trim(),
+        expected.trim(),
+        "Not idempotent: running on expected output produced different result"
+    );
+}
+
+#[test]
+fn test_stdin_from_errors() {
+    let input = fs::read_to_string("tests/code/fixtures/stdin_input.md")
+        .expect("Failed to read input fixture");
+
+    // A missing producer is reported and nothing runs
+    let missing = input.replace("stdin-from=\"data\"", "stdin-from=\"nope\"");
+    let result = process_code_blocks(&missing);
+    assert!(result.has_errors());
+    assert!(result.errors[0]
+        .message
+        .contains("Code block 'summary' at line 16 reads stdin from unknown code block 'nope'"));
+    assert_eq!(result.output, missing);
+
+    // So are blocks that read each other's output
+    let cycle = input.replace(
+        "id=\"data\"; bin=\"bash\"",
+        "id=\"data\"; bin=\"bash\"; stdin-from=\"summary\"",
+    );
+    let result = process_code_blocks(&cycle);
+    assert!(result.has_errors());
+    assert!(result.errors[0]
+        .message
+        .contains("Code blocks 'summary', 'data' read stdin from each other in a cycle"));
+    assert_eq!(result.output, cycle);
+}
+
+#[test]
+fn test_unchanged_blocks_are_not_rerun() {
+    let input = "```python\nimport time\nprint(time.time_ns())\n```\n<!-- md-code: id=\"stamp\"; bin=\"python3\" -->\n";
+
+    let first = process_code_blocks(input);
+    assert!(!first.has_errors(), "Processing failed: {:?}", first.errors);
+    let stamp = |output: &str| output.split("```\n").nth(2).unwrap().to_string();
+    let hash = |output: &str| {
+        let start = output.find("; hash=\"").unwrap();
+        output[start..].lines().next().unwrap().to_string()
+    };
+
+    // The stored hash matches, so the timestamp is kept
+    let second = process_code_blocks(&first.output);
+    assert!(!second.has_errors());
+    assert_eq!(second.output, first.output);
+
+    // Forcing runs the block again without changing its hash
+    let forced = process_code_blocks_with_options(
+        &first.output,
+        &CodeOptions {
+            force: true,
+            ..CodeOptions::default()
+        },
+    );
+    assert!(!forced.has_errors());
+    assert_ne!(stamp(&forced.output), stamp(&first.output));
+    assert_eq!(hash(&forced.output), hash(&first.output));
+
+    // Changing the code runs it again and records the new hash
+    let changed = first.output.replace("print(", "print(\"at\", ");
+    let rerun = process_code_blocks(&changed);
+    assert!(!rerun.has_errors());
+    assert!(stamp(&rerun.output).starts_with("at "));
+    assert_ne!(hash(&rerun.output), hash(&first.output));
+
+    // An output comment without a hash is treated as stale
+    let unhashed = first.output.replace(&hash(&first.output), " -->");
+    let refreshed = process_code_blocks(&unhashed);
+    assert_ne!(stamp(&refreshed.output), stamp(&first.output));
+    assert_eq!(hash(&refreshed.output), hash(&first.output));
+}
+
+#[test]
+fn test_force_flag_reruns_blocks() {
+    let input = "```python\nimport time\nprint(time.time_ns())\n```\n<!-- md-code: id=\"stamp\"; bin=\"python3\" -->\n";
+    let first = process_code_blocks(input);
+    assert!(!first.has_errors(), "Processing failed: {:?}", first.errors);
+
+    let run = |args: &[&str]| {
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("Failed to run md");
+        std::io::Write::write_all(&mut child.stdin.take().unwrap(), first.output.as_bytes())
+            .unwrap();
+        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    };
+
+    assert_eq!(run(&["code"]), first.output);
+    assert_ne!(run(&["code", "--force"]), first.output);
+}
+
+#[test]
+fn test_only_named_blocks_are_run() {
+    let input = "```python\nprint(\"first\")\n```\n<!-- md-code: id=\"a\"; bin=\"python3\" -->\n\n\
+                 ```python\nprint(\"second\")\n```\n<!-- md-code: id=\"b\"; bin=\"python3\" -->\n";
+    let first = process_code_blocks(input);
+    assert!(!first.has_errors(), "Processing failed: {:?}", first.errors);
+
+    // Both blocks change, but only b is refreshed; a keeps its old output verbatim
+    let edited = first
+        .output
+        .replace("print(\"first\")", "print(\"FIRST\")")
+        .replace("print(\"second\")", "print(\"SECOND\")");
+    let only_b = CodeOptions {
+        only: vec!["b".to_string()],
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(&edited, &only_b);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    // Only b's output and hash differ from the edited input
+    assert_eq!(
+        result.output,
+        edited.replace("```\nsecond\n", "```\nSECOND\n").replace(
+            &first.output[first.output.rfind("; hash=").unwrap()..],
+            &result.output[result.output.rfind("; hash=").unwrap()..]
+        )
+    );
+    assert!(result.output.contains("```\nfirst\n"));
+
+    // The same through the CLI
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["code", "--id", "b"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), edited.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), result.output);
+
+    // Unknown ids are an error that lists the ids there are
+    let unknown = process_code_blocks_with_options(
+        &edited,
+        &CodeOptions {
+            only: vec!["b".to_string(), "c".to_string()],
+            ..CodeOptions::default()
+        },
+    );
+    assert_eq!(unknown.output, edited);
+    assert!(unknown.errors[0]
+        .message
+        .contains("Unknown code block ID 'c' (available IDs: a, b)"));
+}
+
+#[test]
+fn test_only_named_block_reads_existing_stdin_source() {
+    let input = "```bash\necho old\n```\n<!-- md-code: id=\"src\"; bin=\"bash\" -->\n\n\
+                 ```bash\ntr a-z A-Z\n```\n<!-- md-code: id=\"upper\"; bin=\"bash\"; stdin-from=\"src\" -->\n";
+    let first = process_code_blocks(input);
+    assert!(!first.has_errors(), "Processing failed: {:?}", first.errors);
+    assert!(first.output.contains("```\nOLD\n"));
+
+    // The producer is not run again, so the consumer still reads its old output
+    let edited = first.output.replace("echo old", "echo new");
+    let result = process_code_blocks_with_options(
+        &edited,
+        &CodeOptions {
+            only: vec!["upper".to_string()],
+            force: true,
+            ..CodeOptions::default()
+        },
+    );
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert!(result.output.contains("```\nold\n"));
+    assert!(result.output.contains("```\nOLD\n"));
+
+    // Without any output yet, the producer runs but its output is not written
+    let result = process_code_blocks_with_options(
+        input,
+        &CodeOptions {
+            only: vec!["upper".to_string()],
+            ..CodeOptions::default()
+        },
+    );
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert!(result.output.contains("```\nOLD\n"));
+    assert!(!result.output.contains("md-code-output: id=\"src\""));
+}
+
+#[test]
+fn test_dry_run_lists_blocks_without_running_them() {
+    let input = fs::read_to_string("tests/code/fixtures/stdin_input.md")
+        .expect("Failed to read input fixture");
+
+    let blocks = list_code_blocks(&input).expect("Failed to list blocks");
+    let ids: Vec<&str> = blocks.iter().map(|block| block.id.as_str()).collect();
+    assert_eq!(ids, ["sorted", "summary", "data"]);
+    assert_eq!(blocks[1].line, 16);
+    assert_eq!(blocks[1].bin.as_deref(), Some("awk -f"));
+    assert_eq!(blocks[1].timeout_seconds, 30);
+    assert_eq!(blocks[1].stdin_from.as_deref(), Some("data"));
+
+    let dir = std::env::temp_dir().join(format!("mdutils-code-dry-run-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let doc = "```bash\ntouch ran\n```\n<!-- md-code: id=\"touch\"; bin=\"bash\"; cwd=\".\"; timeout=5 -->\n";
+    fs::write(dir.join("doc.md"), doc).unwrap();
+    for flag in ["--dry-run", "--list"] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+            .args(["code", flag])
+            .arg(dir.join("doc.md"))
+            .output()
+            .expect("Failed to run md");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "line 1: id=\"touch\"; bin=\"bash\"; timeout=5\n"
+        );
+    }
+    let ran = dir.join("ran").exists();
+    let unchanged = fs::read_to_string(dir.join("doc.md")).unwrap() == doc;
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(!ran, "a block was executed");
+    assert!(unchanged);
+
+    // Duplicate ids are reported as md code would
+    let duplicate = format!("{}\n{}", doc, doc);
+    assert!(list_code_blocks(&duplicate)
+        .unwrap_err()
+        .to_string()
+        .contains("Duplicate code block ID 'touch'"));
+}
+
+#[test]
+fn test_keep_going_runs_blocks_after_a_failure() {
+    let input =
+        "```sh\necho broken\n```\n<!-- md-code: id=\"broken\"; bin=\"md-no-such-program\" -->\n\n\
+                 ```sh\necho fine\n```\n<!-- md-code: id=\"fine\"; bin=\"sh\" -->\n";
+
+    // By default the first failure leaves the document unchanged
+    let result = process_code_blocks(input);
+    assert_eq!(result.output, input);
+    assert_eq!(result.errors.len(), 1);
+
+    // With keep_going the error is recorded and the second block still runs
+    let keep_going = CodeOptions {
+        keep_going: true,
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(input, &keep_going);
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].line, 1);
+    assert!(result.errors[0].message.contains("md-no-such-program"));
+    assert!(!result.output.contains("md-code-output: id=\"broken\""));
+    assert!(result.output.contains("md-code-output: id=\"fine\""));
+    assert!(result.output.contains("```\nfine\n"));
+
+    // The same with on-error="continue" on the failing block alone
+    let per_block = input.replace(
+        "bin=\"md-no-such-program\"",
+        "bin=\"md-no-such-program\"; on-error=\"continue\"",
+    );
+    let result = process_code_blocks(&per_block);
+    assert_eq!(result.errors.len(), 1);
+    assert!(result.output.contains("```\nfine\n"));
+
+    // Through the CLI the document is printed and the exit status reports the failure
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["code", "--keep-going"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("```\nfine\n"));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("md-no-such-program"));
+}
+
+#[test]
+fn test_keep_going_skips_blocks_reading_a_failed_block() {
+    let input = "```sh\necho words\n```\n<!-- md-code: id=\"words\"; bin=\"md-no-such-program\" -->\n\n\
+                 ```sh\ncat\n```\n<!-- md-code: id=\"count\"; bin=\"sh\"; stdin-from=\"words\" -->\n\n\
+                 ```sh\necho other\n```\n<!-- md-code: id=\"other\"; bin=\"sh\" -->\n";
+    let keep_going = CodeOptions {
+        keep_going: true,
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(input, &keep_going);
+    assert_eq!(result.errors.len(), 2);
+    assert!(result.errors[1]
+        .message
+        .contains("Code block 'count' was not run because code block 'words'"));
+    assert!(result.output.contains("```\nother\n"));
+}
+
+#[test]
+fn test_jobs_run_independent_blocks_at_the_same_time() {
+    let input = "```sh\nsleep 1; echo slow\n```\n<!-- md-code: id=\"slow\"; bin=\"sh\" -->\n\n\
+                 ```sh\nsleep 0.5; echo fast\n```\n<!-- md-code: id=\"fast\"; bin=\"sh\" -->\n";
+    let serial = process_code_blocks(input);
+    assert!(
+        !serial.has_errors(),
+        "Processing failed: {:?}",
+        serial.errors
+    );
+
+    let parallel = CodeOptions {
+        jobs: 2,
+        force: true,
+        ..CodeOptions::default()
+    };
+    let start = std::time::Instant::now();
+    let result = process_code_blocks_with_options(input, &parallel);
+    let elapsed = start.elapsed();
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+
+    // The output is the same as running them one at a time, in about the
+    // time of the slower block rather than the sum
+    assert_eq!(result.output, serial.output);
+    assert!(
+        elapsed < std::time::Duration::from_millis(1400),
+        "took {:?}",
+        elapsed
+    );
+}
+
+#[test]
+fn test_jobs_keep_stdin_from_order() {
+    let input = "```sh\nsleep 0.3; printf 'b\\na\\n'\n```\n<!-- md-code: id=\"letters\"; bin=\"sh\" -->\n\n\
+                 ```sh\nsort\n```\n<!-- md-code: id=\"sorted\"; bin=\"sh\"; stdin-from=\"letters\" -->\n\n\
+                 ```sh\necho other\n```\n<!-- md-code: id=\"other\"; bin=\"sh\" -->\n";
+    let parallel = CodeOptions {
+        jobs: 4,
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(input, &parallel);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert_eq!(result.output, process_code_blocks(input).output);
+    assert!(result.output.contains("```\na\nb\n"));
+
+    // Failures are reported in the order the blocks run one at a time
+    let failing = input
+        .replace(
+            "bin=\"sh\" -->\n\n```sh\nsort",
+            "bin=\"md-no-such-program\" -->\n\n```sh\nsort",
+        )
+        .replace(
+            "id=\"other\"; bin=\"sh\"",
+            "id=\"other\"; bin=\"md-no-such-program\"",
+        );
+    let keep_going = CodeOptions {
+        keep_going: true,
+        ..parallel
+    };
+    let result = process_code_blocks_with_options(&failing, &keep_going);
+    let lines: Vec<usize> = result.errors.iter().map(|error| error.line).collect();
+    assert_eq!(lines, vec![1, 6, 11]);
+}
+
+#[test]
+fn test_depends_on_runs_dependencies_first() {
+    let dir = std::env::temp_dir().join(format!("mdutils-code-depends-on-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("state.txt");
+    let _ = fs::remove_file(&path);
+
+    // The reader comes first in the document but needs the file the setup block writes
+    let input = format!(
+        "```sh\ncat {path}\n```\n<!-- md-code: id=\"read\"; bin=\"sh\"; depends-on=\"setup\" -->\n\n\
+         ```sh\necho other\n```\n<!-- md-code: id=\"other\"; bin=\"sh\" -->\n\n\
+         ```sh\necho ready > {path}\n```\n<!-- md-code: id=\"setup\"; bin=\"sh\" -->\n",
+        path = path.display()
+    );
+    let result = process_code_blocks(&input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert!(result.output.contains("```\nready\n"));
+
+    // Dependencies are waited for when blocks run in parallel too
+    fs::remove_file(&path).unwrap();
+    let parallel = CodeOptions {
+        jobs: 3,
+        force: true,
+        ..CodeOptions::default()
+    };
+    let parallel = process_code_blocks_with_options(&input, &parallel);
+    assert!(
+        !parallel.has_errors(),
+        "Processing failed: {:?}",
+        parallel.errors
+    );
+    assert_eq!(parallel.output, result.output);
+
+    // A failed dependency stops the blocks that depend on it
+    let failing = input.replace(
+        "id=\"setup\"; bin=\"sh\"",
+        "id=\"setup\"; bin=\"md-no-such-program\"",
+    );
+    let keep_going = CodeOptions {
+        keep_going: true,
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(&failing, &keep_going);
+    let messages: Vec<&str> = result.errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(messages.len(), 2, "{:?}", messages);
+    assert!(messages[1]
+        .contains("Code block 'read' was not run because code block 'setup' it depends on failed"));
+    assert!(result.output.contains("```\nother\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_depends_on_errors() {
+    let input = "```sh\necho a\n```\n<!-- md-code: id=\"a\"; bin=\"sh\"; depends-on=\"b\" -->\n\n\
+                 ```sh\necho b\n```\n<!-- md-code: id=\"b\"; bin=\"sh\" -->\n";
+
+    // Unknown ids are reported at the block that names them and nothing runs
+    let unknown = input.replace("depends-on=\"b\"", "depends-on=\"b, nope\"");
+    let result = process_code_blocks(&unknown);
+    assert_eq!(result.output, unknown);
+    assert_eq!(result.errors[0].line, 1);
+    assert!(result.errors[0]
+        .message
+        .contains("Code block 'a' at line 1 depends on unknown code block 'nope'"));
+
+    // So are cycles, mixing depends-on and stdin-from
+    let cycle = input.replace(
+        "id=\"b\"; bin=\"sh\"",
+        "id=\"b\"; bin=\"sh\"; stdin-from=\"a\"",
+    );
+    let result = process_code_blocks(&cycle);
+    assert_eq!(result.output, cycle);
+    assert_eq!(result.errors[0].line, 1);
+    assert!(result.errors[0]
+        .message
+        .contains("Code blocks 'a', 'b' depend on each other in a cycle"));
+    let findings = mdutils::lint_document(&cycle);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].line, 1);
+}
+
+#[test]
+fn test_exit_code_show() {
+    let input =
+        "```python\nimport sys\nprint(\"partial result\", file=sys.stderr)\nsys.exit(3)\n```\n\
+                 <!-- md-code: id=\"fails\"; bin=\"python3\"; exit-code=\"show\" -->\n";
+    let result = process_code_blocks(input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert!(result
+        .output
+        .contains("```\npartial result\n[exit status: 3]\n"));
+
+    // Without it, the output is what the program printed, as before
+    let hidden = process_code_blocks(&input.replace("; exit-code=\"show\"", ""));
+    assert!(hidden.output.contains("```\npartial result\n"));
+    assert!(!hidden.output.contains("[exit status"));
+
+    // A zero exit status is never shown
+    let succeeds = process_code_blocks(&input.replace("sys.exit(3)", "sys.exit(0)"));
+    assert!(!succeeds.output.contains("[exit status"));
+}
+
+#[test]
+fn test_require_success() {
+    let input = "```python\nraise ValueError(\"bad input\")\n```\n\
+                 <!-- md-code: id=\"fails\"; bin=\"python3\"; require-success=\"true\" -->\n\n\
+                 ```python\nprint(\"fine\")\n```\n<!-- md-code: id=\"fine\"; bin=\"python3\" -->\n";
+
+    // The non-zero exit is an error, so the document is left as it was
+    let result = process_code_blocks(input);
+    assert_eq!(result.output, input);
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].line, 1);
+    assert!(result.errors[0]
+        .message
+        .contains("Program exited with status 1: ValueError: bad input"));
+
+    // With --keep-going the other block still runs
+    let keep_going = CodeOptions {
+        keep_going: true,
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(input, &keep_going);
+    assert_eq!(result.errors.len(), 1);
+    assert!(!result.output.contains("md-code-output: id=\"fails\""));
+    assert!(result.output.contains("```\nfine\n"));
+}
+
+#[test]
+fn test_separate_stderr_block() {
+    let input = "```python\nimport sys\nprint(\"result\")\nprint(\"warning: careful\", file=sys.stderr)\n```\n\
+                 <!-- md-code: id=\"warns\"; bin=\"python3\"; stderr=\"separate\" -->\n\nEnd.\n";
+    let result = process_code_blocks(input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    let output_at = result.output.find("Output:\n```\nresult\n").unwrap();
+    let stderr_at = result
+        .output
+        .find("Stderr:\n```\nwarning: careful\n")
+        .unwrap();
+    assert!(output_at < stderr_at);
+    assert!(result
+        .output
+        .contains("<!-- md-code-output: id=\"warns\"; stream=\"stderr\"; hash="));
+    assert!(!result.output[output_at..stderr_at].contains("warning"));
+
+    // The stderr block is updated on its own...
+    let changed = process_code_blocks(&result.output.replace("careful", "again"));
+    assert!(changed.output.contains("```\nresult\n"));
+    assert!(changed.output.contains("Stderr:\n```\nwarning: again\n"));
+    assert!(!changed.output.contains("careful"));
+
+    // ...and removed once the program writes nothing to stderr
+    let quiet = process_code_blocks(
+        &changed
+            .output
+            .replace("print(\"warning: again\", file=sys.stderr)\n", ""),
+    );
+    assert!(quiet.output.contains("```\nresult\n"));
+    assert!(!quiet.output.contains("Stderr:"));
+    assert!(!quiet.output.contains("stream=\"stderr\""));
+    assert!(quiet.output.ends_with("-->\n\nEnd.\n"));
+
+    // By default a successful program's stderr is not shown, as before
+    let merged = process_code_blocks(&input.replace("; stderr=\"separate\"", ""));
+    assert!(merged.output.contains("Output:\n```\nresult\n"));
+    assert!(!merged.output.contains("Stderr:"));
+}
+
+#[test]
+fn test_separate_stderr_keeps_stdout_of_a_failing_program() {
+    let input = "```python\nprint(\"partial\")\nraise ValueError(\"bad input\")\n```\n\
+                 <!-- md-code: id=\"fails\"; bin=\"python3\"; stderr=\"separate\" -->\n";
+    let result = process_code_blocks(input);
+    assert!(!result.has_errors());
+    assert!(result.output.contains("Output:\n```\npartial\n"));
+    assert!(result.output.contains("ValueError: bad input"));
+
+    // The last stderr line still explains a required success that failed
+    let required = process_code_blocks(&input.replace(" -->", "; require-success=\"true\" -->"));
+    assert!(required.errors[0]
+        .message
+        .contains("Program exited with status 1: ValueError: bad input"));
+}
+
+#[test]
+fn test_output_none_removes_output_blocks() {
+    let input =
+        "```python\nimport sys\nprint(\"regenerated\")\nprint(\"note\", file=sys.stderr)\n```\n\
+                 <!-- md-code: id=\"gen\"; bin=\"python3\"; stderr=\"separate\" -->\n\nEnd.\n";
+    let shown = process_code_blocks(input);
+    assert!(shown.output.contains("Output:\n```\nregenerated\n"));
+    assert!(shown.output.contains("Stderr:\n```\nnote\n"));
+
+    // The block still runs, but its stale output and stderr blocks are removed
+    let hidden_input = shown.output.replace(
+        "stderr=\"separate\" -->",
+        "stderr=\"separate\"; output=\"none\" -->",
+    );
+    let hidden = process_code_blocks(&hidden_input);
+    assert!(!hidden.has_errors(), "{:?}", hidden.errors);
+    assert!(!hidden.output.contains("Output:"));
+    assert!(!hidden.output.contains("Stderr:"));
+    assert!(!hidden.output.contains("md-code-output"));
+    assert!(hidden.output.ends_with("output=\"none\" -->\n\nEnd.\n"));
+
+    // A fresh block gets no output block either
+    assert_eq!(process_code_blocks(&hidden.output).output, hidden.output);
+
+    // A failing program's output is still shown
+    let failing = hidden_input.replace("print(\"regenerated\")", "print(\"oops\")\nsys.exit(1)");
+    let failed = process_code_blocks(&failing);
+    assert!(failed.output.contains("```\noops\n"));
+}
+
+/// Runs `md code --verify` (plus `args`) on a fixture, returning the exit code and stdout
+fn run_verify(fixture: &str, args: &[&str]) -> (Option<i32>, String) {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["code", "--verify"])
+        .args(args)
+        .arg(format!("tests/code/fixtures/{}", fixture))
+        .output()
+        .expect("Failed to run md");
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_verify_passes_when_output_is_current() {
+    let input = fs::read_to_string("tests/code/fixtures/basic_python_expected.md")
+        .expect("Failed to read fixture");
+
+    let report = verify_code_blocks(&input, &CodeOptions::default(), false);
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert!(report.changed.is_empty(), "{:?}", report.changed);
+    assert!(verify_code_blocks(&input, &CodeOptions::default(), true)
+        .changed
+        .is_empty());
+
+    // Nothing is printed, not even the document
+    assert_eq!(
+        run_verify("basic_python_expected.md", &[]),
+        (Some(0), String::new())
+    );
+
+    // Trailing whitespace only matters with --strict
+    let padded = input.replace("Hello, World!\n", "Hello, World!  \n");
+    assert!(verify_code_blocks(&padded, &CodeOptions::default(), false)
+        .changed
+        .is_empty());
+    assert_eq!(
+        verify_code_blocks(&padded, &CodeOptions::default(), true).changed[0].id,
+        "hello"
+    );
+}
+
+#[test]
+fn test_verify_reports_changed_output() {
+    let input = fs::read_to_string("tests/code/fixtures/update_output_input.md")
+        .expect("Failed to read fixture");
+
+    let report = verify_code_blocks(&input, &CodeOptions::default(), false);
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert_eq!(report.changed.len(), 1);
+    let change = &report.changed[0];
+    assert_eq!((change.id.as_str(), change.line), ("update_me", 3));
+    assert_eq!(change.expected, "Old output");
+    assert_eq!(change.actual, "New output\n");
+
+    let (code, stdout) = run_verify("update_output_input.md", &[]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("output of block 'update_me' (line 3) changed:"));
+    assert!(stdout.contains("-Old output\n+New output\n"));
+    assert!(stdout.ends_with("1 output changed\n"));
+
+    // A block that is always different can opt out
+    let skipped = input.replace("bin=\"python3\"", "bin=\"python3\"; verify=\"skip\"");
+    assert!(verify_code_blocks(&skipped, &CodeOptions::default(), false)
+        .changed
+        .is_empty());
+
+    // A block with no output block yet counts as changed
+    let fresh = input.split("\nOutput:").next().unwrap();
+    let report = verify_code_blocks(fresh, &CodeOptions::default(), false);
+    assert_eq!(report.changed[0].expected, "");
+}
+
+#[test]
+fn test_timing_is_recorded_in_the_output_comment() {
+    let input = "```python\nimport time\ntime.sleep(0.05)\nprint(\"done\")\n```\n\
+                 <!-- md-code: id=\"slow\"; bin=\"python3\"; timing=\"true\" -->\n";
+    let result = process_code_blocks(input);
+    assert!(!result.has_errors(), "{:?}", result.errors);
+
+    // The output block is left clean; the time goes after the hash
+    assert!(result.output.contains("Output:\n```\ndone\n\n```\n"));
+    let comment = result
+        .output
+        .lines()
+        .find(|line| line.starts_with("<!-- md-code-output:"))
+        .unwrap();
+    let time = comment
+        .split("; time=\"")
+        .nth(1)
+        .and_then(|rest| rest.strip_suffix("s\" -->"))
+        .unwrap_or_else(|| panic!("no time in {}", comment));
+    let seconds: f64 = time.parse().unwrap();
+    assert!(seconds >= 0.05, "{}", comment);
+    assert_eq!(time.split('.').nth(1).map(str::len), Some(2));
+
+    // The time does not affect caching, so a second run keeps it as it is...
+    assert_eq!(process_code_blocks(&result.output).output, result.output);
+
+    // ...nor verification
+    let report = verify_code_blocks(&result.output, &CodeOptions::default(), true);
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert!(report.changed.is_empty(), "{:?}", report.changed);
+
+    // A stale time is not a reason to run the block again
+    let stale = result
+        .output
+        .replace(&format!("time=\"{}s\"", time), "time=\"9.99s\"");
+    assert_eq!(process_code_blocks(&stale).output, stale);
+
+    // Without timing="true" there is no time
+    let untimed = process_code_blocks(&input.replace("; timing=\"true\"", ""));
+    assert!(!untimed.output.contains("time="));
+}
+
+#[test]
+fn test_bin_from_fence_language() {
+    // Without bin, a python fence runs with python3
+    let input = "```python\nprint(\"from python\")\n```\n<!-- md-code: id=\"py\" -->\n";
+    let result = process_code_blocks(input);
+    assert!(!result.has_errors(), "{:?}", result.errors);
+    assert!(result.output.contains("Output:\n```\nfrom python\n"));
+    assert_eq!(
+        list_code_blocks(input).unwrap()[0].bin.as_deref(),
+        Some("python3")
+    );
+
+    // An md-code-config comment maps other languages, or overrides the defaults
+    let configured = "<!-- md-code-config: text=\"cat\"; python=\"bash\" -->\n\n\
+                      ```text\nplain text\n```\n<!-- md-code: id=\"txt\" -->\n\n\
+                      ```python\necho from bash\n```\n<!-- md-code: id=\"py\" -->\n";
+    let result = process_code_blocks(configured);
+    assert!(!result.has_errors(), "{:?}", result.errors);
+    assert!(result.output.contains("Output:\n```\nplain text\n```"));
+    assert!(result.output.contains("Output:\n```\nfrom bash\n"));
+    assert!(result
+        .output
+        .starts_with("<!-- md-code-config: text=\"cat\"; python=\"bash\" -->\n"));
+
+    // An explicit bin always wins
+    let explicit = configured.replace("id=\"py\"", "id=\"py\"; bin=\"cat\"");
+    let result = process_code_blocks(&explicit);
+    assert!(result.output.contains("Output:\n```\necho from bash\n"));
+
+    // Lint only reports blocks whose program cannot be found either way
+    assert!(mdutils::lint_document(input).is_empty());
+    let unknown = input.replace("```python", "```cobol");
+    let findings = mdutils::lint_document(&unknown);
+    assert_eq!(findings.len(), 1);
+    assert!(findings[0].message.contains("'cobol'"), "{:?}", findings);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_shebang_script() {
+    let input = "```python\n#!/usr/bin/env python3\nprint(\"from a script\")\n```\n\
+                 <!-- md-code: id=\"script\"; bin=\"shebang\" -->\n";
+    let result = process_code_blocks(input);
+    assert!(!result.has_errors(), "{:?}", result.errors);
+    assert!(result.output.contains("Output:\n```\nfrom a script\n"));
+    assert!(mdutils::lint_document(input).is_empty());
+
+    // A block without a #! line cannot be run as a script
+    let missing = input.replace("#!/usr/bin/env python3\n", "");
+    let result = process_code_blocks(&missing);
+    assert_eq!(result.errors.len(), 1);
+    assert!(
+        result.errors[0].message.contains("#! line"),
+        "{:?}",
+        result.errors
+    );
+    assert!(!result.output.contains("Output:"));
+    let findings = mdutils::lint_document(&missing);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].line, 1);
+}
+
+#[test]
+fn test_max_lines() {
+    let block = |lines: usize, max_lines: usize| {
+        format!(
+            "```python\nfor i in range({}):\n    print(i)\n```\n\
+             <!-- md-code: id=\"count\"; bin=\"python3\"; max-lines={} -->\n",
+            lines, max_lines
+        )
+    };
+
+    // Exactly max-lines lines are kept as they are
+    let result = process_code_blocks(&block(3, 3));
+    assert!(!result.has_errors(), "{:?}", result.errors);
+    assert!(result.output.contains("```\n0\n1\n2\n\n```"));
+    assert!(!result.output.contains("truncated"));
+
+    // One line more is cut, with a marker inside the output block
+    let result = process_code_blocks(&block(4, 3));
+    assert!(result
+        .output
+        .contains("```\n0\n1\n2\n… (1 more line truncated)\n\n```"));
+
+    let result = process_code_blocks(&block(4000, 50));
+    assert!(result
+        .output
+        .contains("\n49\n… (3950 more lines truncated)\n"));
+    assert!(!result.output.contains("\n50\n"));
+
+    // Running again gives the same document, whether the block is skipped as
+    // unchanged or forced to run
+    assert_eq!(process_code_blocks(&result.output).output, result.output);
+    let force = CodeOptions {
+        force: true,
+        ..CodeOptions::default()
+    };
+    let forced = process_code_blocks_with_options(&result.output, &force);
+    assert_eq!(forced.output, result.output);
+}
+
+#[test]
+fn test_max_lines_default_from_options() {
+    let input = "```python\nfor i in range(5):\n    print(i)\n```\n\
+                 <!-- md-code: id=\"count\"; bin=\"python3\" -->\n";
+    let limited = CodeOptions {
+        max_lines: Some(2),
+        ..CodeOptions::default()
+    };
+    let result = process_code_blocks_with_options(input, &limited);
+    assert!(result
+        .output
+        .contains("```\n0\n1\n… (3 more lines truncated)\n"));
+
+    // A block's own max-lines wins over the default
+    let own = input.replace("bin=\"python3\"", "bin=\"python3\"; max-lines=4");
+    let result = process_code_blocks_with_options(&own, &limited);
+    assert!(result
+        .output
+        .contains("```\n0\n1\n2\n3\n… (1 more line truncated)\n"));
+
+    // Changing the limit runs the block again
+    let rerun = process_code_blocks(&result.output);
+    assert_eq!(rerun.output, result.output);
+    let unlimited = process_code_blocks(&result.output.replace("; max-lines=4", ""));
+    assert!(unlimited.output.contains("```\n0\n1\n2\n3\n4\n"));
+    assert!(!unlimited.output.contains("truncated"));
+
+    // Through the CLI
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["code", "--max-lines", "1"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("```\n0\n… (4 more lines truncated)\n"));
+}
+
+#[test]
+fn test_output_file() {
+    let dir = std::env::temp_dir().join(format!("mdutils-code-output-file-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = "```python\nprint('<svg width=\"10\"></svg>')\n```\n\
+                 <!-- md-code: id=\"plot\"; bin=\"python3\"; output-file=\"assets/plot.svg\" -->\n\n\
+                 ![plot](assets/plot.svg)\n";
+
+    // The output goes to the file, in a new directory, and a comment takes its place
+    let result = process_code_blocks_in(input, &dir);
+    let written = fs::read_to_string(dir.join("assets/plot.svg"));
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert_eq!(written.unwrap(), "<svg width=\"10\"></svg>\n");
+    assert!(!result.output.contains("Output:"));
+    let comment = "<!-- md-code-output: id=\"plot\"; file=\"assets/plot.svg\"; hash=\"";
+    assert!(result.output.contains(&format!(
+        "output-file=\"assets/plot.svg\" -->\n\n{}",
+        comment
+    )));
+    assert!(result
+        .output
+        .ends_with(" -->\n\n![plot](assets/plot.svg)\n"));
+
+    // Unchanged code with its file in place is not run again
+    fs::write(dir.join("assets/plot.svg"), "edited by hand\n").unwrap();
+    assert_eq!(
+        process_code_blocks_in(&result.output, &dir).output,
+        result.output
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("assets/plot.svg")).unwrap(),
+        "edited by hand\n"
+    );
+
+    // Changed code rewrites the file and updates the comment in place
+    let changed = result.output.replace("width=\"10\"", "width=\"20\"");
+    let changed = process_code_blocks_in(&changed, &dir);
+    assert_eq!(
+        fs::read_to_string(dir.join("assets/plot.svg")).unwrap(),
+        "<svg width=\"20\"></svg>\n"
+    );
+    assert_eq!(changed.output.matches("md-code-output").count(), 1);
+    assert_ne!(changed.output, result.output);
+
+    // Empty output creates no file
+    let empty = input.replace("print('<svg width=\"10\"></svg>')", "pass");
+    let empty = process_code_blocks_in(
+        &empty.replace("assets/plot.svg\" -->", "empty.txt\" -->"),
+        &dir,
+    );
+    assert!(!empty.has_errors());
+    assert!(!dir.join("empty.txt").exists());
+    assert!(!empty.output.contains("md-code-output"));
+
+    // A file that cannot be written is an error naming it, and the document is unchanged
+    fs::write(dir.join("blocker"), "").unwrap();
+    let blocked = input.replace("assets/plot.svg\" -->", "blocker/plot.svg\" -->");
+    let failed = process_code_blocks_in(&blocked, &dir);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(failed.output, blocked);
+    assert_eq!(failed.errors.len(), 1);
+    let message = &failed.errors[0].message;
+    assert!(
+        message.contains("Failed to write output file"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains(&dir.join("blocker/plot.svg").display().to_string()),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,52 @@
+use mdutils::{export_csv, export_csv_selected, export_json, export_json_selected, TableSelection};
+/// Integration tests for the export command
+/// Tests use fixture files in tests/export/fixtures/ directory
+use std::fs;
+
+#[test]
+fn test_export_csv() {
+    let input = fs::read_to_string("tests/export/fixtures/csv_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/export/fixtures/csv_expected.csv")
+        .expect("Failed to read expected fixture");
+
+    let result = export_csv(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+}
+
+#[test]
+fn test_export_csv_evaluates_formulas() {
+    let input = fs::read_to_string("tests/export/fixtures/formulas_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/export/fixtures/formulas_expected.csv")
+        .expect("Failed to read expected fixture");
+
+    let result = export_csv(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Selecting a table exports just its block
+    let order_csv = expected.split("\n\n").next().unwrap().to_string() + "\n";
+    let by_id = export_csv_selected(&input, &TableSelection::Id("order".to_string()));
+    assert_eq!(by_id.output, order_csv);
+    let by_position = export_csv_selected(&input, &TableSelection::Nth(1));
+    assert_eq!(by_position.output, order_csv);
+}
+
+#[test]
+fn test_export_json() {
+    let input = fs::read_to_string("tests/export/fixtures/json_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/export/fixtures/json_expected.json")
+        .expect("Failed to read expected fixture");
+
+    let result = export_json(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Selecting a table exports just its array
+    let stock_json = expected.split("\n\n").nth(1).unwrap();
+    let by_id = export_json_selected(&input, &TableSelection::Id("stock".to_string()));
+    assert_eq!(by_id.output, stock_json);
+}
@@ -0,0 +1,113 @@
+use mdutils::done::process_done_with_timestamp;
+/// Integration tests for CRLF line endings
+/// Each processor is fed a CRLF copy of one of its fixtures from tests/*/fixtures/
+use mdutils::ProcessingResult;
+use mdutils::{format_tables, process_code_blocks, process_toc, transpose_tables};
+use std::fs;
+
+/// Reads a fixture with every line ending turned into `\r\n`
+fn read_crlf(path: &str) -> String {
+    fs::read_to_string(path)
+        .expect("Failed to read fixture")
+        .replace("\r\n", "\n")
+        .replace('\n', "\r\n")
+}
+
+/// Asserts that processing the CRLF input gives the CRLF expected output,
+/// and returns that output
+fn assert_keeps_crlf(
+    process: impl Fn(&str) -> ProcessingResult,
+    input: &str,
+    expected: &str,
+) -> String {
+    let input = read_crlf(input);
+    let expected = read_crlf(expected);
+
+    let result = process(&input);
+    assert!(
+        !result.has_errors(),
+        "Processing failed: {:?}",
+        result.errors
+    );
+    assert_eq!(result.output, expected);
+    assert_eq!(
+        result.output.matches('\n').count(),
+        result.output.matches("\r\n").count()
+    );
+    result.output
+}
+
+#[test]
+fn test_format_tables_keeps_crlf() {
+    let output = assert_keeps_crlf(
+        format_tables,
+        "tests/table/fixtures/all_functions_input.md",
+        "tests/table/fixtures/all_functions_expected.md",
+    );
+    // Idempotency check: the CRLF output is left as it is
+    assert_eq!(format_tables(&output).output, output);
+}
+
+#[test]
+fn test_transpose_tables_keeps_crlf() {
+    let output = assert_keeps_crlf(
+        transpose_tables,
+        "tests/table/fixtures/transpose_table_input.md",
+        "tests/table/fixtures/transpose_table_expected.md",
+    );
+    // Transposing back gives the formatted original, still with CRLF
+    assert!(transpose_tables(&output)
+        .output
+        .contains("| North  | 120 |"));
+    assert!(!transpose_tables(&output)
+        .output
+        .replace("\r\n", "")
+        .contains('\n'));
+}
+
+#[test]
+fn test_process_toc_keeps_crlf() {
+    let output = assert_keeps_crlf(
+        process_toc,
+        "tests/toc/fixtures/complex_input.md",
+        "tests/toc/fixtures/complex_expected.md",
+    );
+    assert_eq!(process_toc(&output).output, output);
+}
+
+#[test]
+fn test_process_done_keeps_crlf() {
+    assert_keeps_crlf(
+        |input| process_done_with_timestamp(input, "2024-01-15 14:30:00"),
+        "tests/done/fixtures/mixed_content_input.md",
+        "tests/done/fixtures/mixed_content_expected.md",
+    );
+}
+
+#[test]
+fn test_process_code_blocks_keeps_crlf() {
+    let output = assert_keeps_crlf(
+        process_code_blocks,
+        "tests/code/fixtures/basic_bash_input.md",
+        "tests/code/fixtures/basic_bash_expected.md",
+    );
+    // The stored hash is the same as for LF input, so the block is not rerun
+    assert_eq!(process_code_blocks(&output).output, output);
+}
+
+#[test]
+fn test_mixed_line_endings_follow_the_majority() {
+    let mostly_crlf = "| A | B |\r\n|---|---|\r\n| 1 | 2 |\n";
+    assert_eq!(
+        format_tables(mostly_crlf).output,
+        "| A   | B   |\r\n| --- | --- |\r\n| 1   | 2   |\r\n"
+    );
+
+    let mostly_lf = "- [ ] one\r\n- [ ] two\n- [x] three\n";
+    assert_eq!(
+        process_done_with_timestamp(mostly_lf, "2024-01-15 14:30:00").output,
+        "- [x] ~~one~~ `COMPLETED: 2024-01-15 14:30:00`\n\
+         - [x] ~~two~~ `COMPLETED: 2024-01-15 14:30:00`\n\
+         - [x] three\n"
+    );
+}
@@ -0,0 +1,57 @@
+use mdutils::{format_tables, lint_document, process_toc};
+/// Integration tests for the lint command
+/// Tests use fixture files in tests/lint/fixtures/ directory
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_lint_reports_problems() {
+    let input = fs::read_to_string("tests/lint/fixtures/broken_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/lint/fixtures/broken_expected.txt")
+        .expect("Failed to read expected fixture");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["lint", "tests/lint/fixtures/broken_input.md"])
+        .output()
+        .expect("Failed to run md");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+
+    // The broken formula is reported through the same errors md table collects
+    let findings = lint_document(&input);
+    let formula_errors: Vec<_> = format_tables(&input).errors;
+    assert_eq!(formula_errors.len(), 1);
+    assert!(findings
+        .iter()
+        .any(|finding| finding.to_string() == formula_errors[0].to_string()));
+    assert!(formula_errors[0].message.contains("sum(B1:B2"));
+}
+
+#[test]
+fn test_lint_clean_document_from_stdin() {
+    let doc = process_toc(
+        "# Notes\n\n<!-- md-toc: -->\n<!-- md-toc: end -->\n\n## Totals\n\n\
+         | A | B |\n|---|---|\n| 1 | 2 |\n<!-- md-table: B1 = A1 * 2 -->\n",
+    )
+    .output;
+    assert!(lint_document(&doc).is_empty());
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md"))
+        .arg("lint")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(doc.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,170 @@
+use mdutils::{export_csv, export_json, format_tables, import_csv, import_json};
+/// Integration tests for the new command
+/// Tests use fixture files in tests/new/fixtures/ directory
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs `md new SPEC` and returns (exit code, stdout, stderr)
+fn run_md_new(spec: &str) -> (Option<i32>, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["new", spec])
+        .output()
+        .expect("Failed to run md");
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_new_table_with_dimensions() {
+    let (code, stdout, _) = run_md_new("table:2:3");
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        "|     |     |     |\n| --- | --- | --- |\n|     |     |     |\n|     |     |     |"
+    );
+}
+
+#[test]
+fn test_new_table_with_headers() {
+    let expected = fs::read_to_string("tests/new/fixtures/spec_headers_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let (code, stdout, _) = run_md_new("table:3:\"Name, Inc\",Price,Qty");
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, expected);
+
+    // The generated table is already formatted
+    let formatted = format_tables(&stdout);
+    assert_eq!(formatted.output, stdout);
+
+    let (code, stdout, _) = run_md_new("table:1:Name,Price");
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        "| Name | Price |\n| ---- | ----- |\n|      |       |"
+    );
+}
+
+#[test]
+fn test_new_table_invalid_spec() {
+    for spec in [
+        "table:2",
+        "table:0:3",
+        "table:2:0",
+        "table:x:Name",
+        "table:2:\"Name",
+    ] {
+        let (code, stdout, stderr) = run_md_new(spec);
+        assert_eq!(code, Some(64), "spec {}", spec);
+        assert!(stdout.is_empty());
+        assert!(stderr.starts_with("Error: "), "spec {}", spec);
+    }
+}
+
+#[test]
+fn test_new_from_csv() {
+    let input = fs::read_to_string("tests/new/fixtures/csv_input.csv")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/new/fixtures/csv_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = import_csv(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // The generated table is already formatted
+    let formatted = format_tables(&result.output);
+    assert_eq!(formatted.output, result.output);
+}
+
+#[test]
+fn test_new_from_tsv_with_delimiter() {
+    let input = fs::read_to_string("tests/new/fixtures/tsv_input.tsv")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/new/fixtures/csv_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["new", "csv", "--delimiter", "tab"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn test_new_delimiter_requires_csv_spec() {
+    let output = Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["new", "table:2:3", "--delimiter", ";"])
+        .output()
+        .expect("Failed to run md");
+    assert_eq!(output.status.code(), Some(64));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_csv_round_trip_through_export() {
+    let input = fs::read_to_string("tests/new/fixtures/csv_input.csv")
+        .expect("Failed to read input fixture");
+
+    let table = import_csv(&input);
+    let exported = export_csv(&table.output);
+    assert!(!exported.has_errors());
+    assert_eq!(exported.output, input);
+
+    // And back again: re-importing the export gives the same table
+    assert_eq!(import_csv(&exported.output).output, table.output);
+}
+
+#[test]
+fn test_new_from_json() {
+    let input = fs::read_to_string("tests/new/fixtures/json_input.json")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/new/fixtures/json_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md"))
+        .args(["new", "json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn test_json_round_trip_through_export() {
+    let input = fs::read_to_string("tests/new/fixtures/json_input.json")
+        .expect("Failed to read input fixture");
+
+    let table = import_json(&input);
+    assert!(!table.has_errors());
+    assert_eq!(format_tables(&table.output).output, table.output);
+
+    let exported = export_json(&table.output);
+    assert!(!exported.has_errors());
+    assert_eq!(exported.output, input);
+}
@@ -0,0 +1,61 @@
+use mdutils::{document_stats, DocumentStats};
+/// Integration tests for the stats command
+/// Tests use fixture files in tests/stats/fixtures/ directory
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_stats_counts() {
+    let input = fs::read_to_string("tests/stats/fixtures/document.md")
+        .expect("Failed to read input fixture");
+
+    // Elements inside the ~~~ example block are not counted
+    assert_eq!(
+        document_stats(&input),
+        DocumentStats {
+            headers: [1, 3, 2, 0, 0, 0],
+            tables: 2,
+            code_blocks: 3,
+            md_code_blocks: Some(1),
+            open_items: 3,
+            done_items: 3,
+        }
+    );
+}
+
+#[test]
+fn test_stats_command_output() {
+    for (args, expected) in [
+        (vec![], "tests/stats/fixtures/document_expected.txt"),
+        (
+            vec!["--json"],
+            "tests/stats/fixtures/document_expected.json",
+        ),
+    ] {
+        let expected = fs::read_to_string(expected).expect("Failed to read expected fixture");
+        let output = Command::new(env!("CARGO_BIN_EXE_md"))
+            .arg("stats")
+            .args(args)
+            .arg("tests/stats/fixtures/document.md")
+            .output()
+            .expect("Failed to run md");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_stats_with_invalid_code_directives() {
+    // Duplicate ids stop md code, but the blocks are still counted
+    let doc = "# Doc\n\n```sh\necho a\n```\n<!-- md-code: id=\"x\"; bin=\"sh\" -->\n\n\
+               ```sh\necho b\n```\n<!-- md-code: id=\"x\"; bin=\"sh\" -->\n";
+    let stats = document_stats(doc);
+    assert_eq!(stats.headers[0], 1);
+    assert_eq!(stats.code_blocks, 2);
+    assert_eq!(stats.md_code_blocks, Some(2));
+
+    let unclosed = document_stats("# Doc\n\n```sh\necho a\n");
+    assert_eq!(unclosed.md_code_blocks, None);
+    assert!(!unclosed.to_text().contains("md-code"));
+    assert!(unclosed.to_json().contains("\"md_code\": null"));
+}
@@ -1,7 +1,33 @@
-use mdutils::format_tables;
+use mdutils::{format_tables, format_tables_with_options, transpose_tables, FormatOptions, Table};
 /// Integration tests for table formatting and formula evaluation
 /// Tests use fixture files in tests/table/fixtures/ directory
 use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs `md table ARGS` on `input` and returns (exit code, stdout, stderr)
+fn run_md_table(args: &[&str], input: &str) -> (Option<i32>, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md"))
+        .arg("table")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run md");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
 
 #[test]
 fn test_vector_addition() {
@@ -79,6 +105,26 @@ fn test_transpose_operator() {
     );
 }
 
+#[test]
+fn test_formula_comments() {
+    let input = fs::read_to_string("tests/table/fixtures/formula_comments_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/formula_comments_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output.trim(), expected.trim());
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(
+        result2.output.trim(),
+        expected.trim(),
+        "Not idempotent: running on expected output produced different result"
+    );
+}
+
 #[test]
 fn test_multiple_formulas() {
     let input = fs::read_to_string("tests/table/fixtures/multiple_formulas_input.md")
@@ -167,6 +213,8 @@ fn test_formula_parse_error() {
     // Verify error comment is present
     assert!(result.output.contains("md-error:"));
     assert!(result.output.contains("Failed to parse statement"));
+    // Error identifies the failing formula
+    assert!(result.output.contains("formula 1 \"this is invalid\""));
 }
 
 #[test]
@@ -182,6 +230,14 @@ fn test_formula_eval_error() {
     assert!(result.output.contains("md-error:"));
     // First formula should succeed
     assert!(result.output.contains("| 1   | 2   | 3   |"));
+    // Error identifies the second formula and still shows the position indicator
+    assert!(result
+        .output
+        .contains("<!-- md-error: formula 2 \"D_ = X_ + Y_\": Failed to evaluate expression:"));
+    assert!(result.output.contains("X_ + Y_\n^^ -->"));
+    assert!(result.errors[0]
+        .message
+        .starts_with("formula 2 \"D_ = X_ + Y_\""));
 }
 
 #[test]
@@ -269,6 +325,72 @@ fn test_error_elementwise_dimension() {
     assert!(result.output.contains("matching dimensions"));
 }
 
+#[test]
+fn test_formula_dependency_order() {
+    let input = fs::read_to_string("tests/table/fixtures/formula_dependency_order_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/formula_dependency_order_expected.md")
+        .expect("Failed to read expected fixture");
+
+    // Formulas are written in reverse dependency order (the let comes last)
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    assert!(result
+        .output
+        .contains("| Sum    |       |     | 2100     | 210.0 | 2310.0 |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_error_circular_reference() {
+    let input = fs::read_to_string("tests/table/fixtures/error_circular_reference_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/error_circular_reference_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output, expected);
+    // The self-reference and both formulas of the D2/B2 cycle are reported
+    assert_eq!(result.errors.len(), 3);
+    assert!(result.output.contains(
+        "formula 3 \"D3 = D3 + 1\": Circular reference in 'D3 = D3 + 1': the formula reads the cells it assigns"
+    ));
+    // Formulas outside the cycle still run
+    assert!(result.output.contains("| Pen   | 2     | 5   | 10    |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_error_circular_reference_through_variable() {
+    let input =
+        fs::read_to_string("tests/table/fixtures/error_circular_reference_variable_input.md")
+            .expect("Failed to read input fixture");
+    let expected =
+        fs::read_to_string("tests/table/fixtures/error_circular_reference_variable_expected.md")
+            .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output, expected);
+    // The let/A1/B1 cycle and the fill reading its own column are reported
+    assert_eq!(result.errors.len(), 4);
+    assert!(result.output.contains(
+        "formula 2 \"A1 = sum(x)\": Circular reference in 'A1 = sum(x)': the formula depends on its own result through formulas 1, 3"
+    ));
+    // Formulas outside the cycles still run
+    assert!(result.output.contains("| 2   | 0   | 4   | 0   |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
 #[test]
 fn test_error_unmatched_paren() {
     let input = fs::read_to_string("tests/table/fixtures/error_unmatched_paren_input.md")
@@ -306,6 +428,7 @@ fn test_error_unknown_function() {
     assert_eq!(result.output.trim(), expected.trim());
     assert!(result.output.contains("md-error:"));
     assert!(result.output.contains("unknown function"));
+    assert!(result.output.contains("formula 1 \"B1 = foo(A_)\""));
 }
 
 #[test]
@@ -426,6 +549,62 @@ fn test_cross_table_missing_id() {
     assert!(result.output.contains("table 'nonexistent' not found"));
 }
 
+#[test]
+fn test_cross_table_write() {
+    let input = fs::read_to_string("tests/table/fixtures/cross_table_write_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/cross_table_write_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // Written both into a table before the source and into one after it
+    assert!(result.output.contains("| Total cost    | 16.00 |"));
+    assert!(result.output.contains("| Plums  | 2.50 | 2.750    |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_cross_table_write_errors() {
+    let input = fs::read_to_string("tests/table/fixtures/cross_table_write_error_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/cross_table_write_error_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    assert_eq!(result.errors.len(), 2);
+    assert!(result.output.contains("cell index out of bounds"));
+    assert!(result.output.contains("table 'summry' not found"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output.trim(), expected.trim());
+}
+
+#[test]
+fn test_duplicate_table_id() {
+    let input = fs::read_to_string("tests/table/fixtures/duplicate_table_id_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/duplicate_table_id_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output, expected);
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].line, 7);
+    // from() reads the first table with the ID
+    assert!(result.output.contains("| 180   |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
 #[test]
 fn test_assignment_row_vector() {
     let input = fs::read_to_string("tests/table/fixtures/assignment_row_vector_input.md")
@@ -623,3 +802,852 @@ fn test_skip_code_blocks() {
     // No errors should be present
     assert!(!result.output.contains("md-error:"));
 }
+
+#[test]
+fn test_tables_in_code_fences_pass_through() {
+    let input = fs::read_to_string("tests/table/fixtures/code_fence_tables_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/code_fence_tables_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Everything up to the real table is untouched, fences and all
+    let fenced_part = input.split("| Real |").next().unwrap();
+    assert!(result.output.starts_with(fenced_part));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_stale_error_removed_when_formula_fixed() {
+    let input = fs::read_to_string("tests/table/fixtures/stale_error_fixed_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/stale_error_fixed_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    // Old error comment should be gone and no new errors reported
+    assert!(!result.output.contains("md-error:"));
+    assert!(!result.has_errors());
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(
+        result2.output.trim(),
+        expected.trim(),
+        "Not idempotent: running on expected output produced different result"
+    );
+}
+
+#[test]
+fn test_stale_error_replaced_when_formula_still_fails() {
+    let input = fs::read_to_string("tests/table/fixtures/stale_error_still_failing_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/stale_error_still_failing_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    // Duplicate stale errors collapse into the single error from this run
+    assert_eq!(result.output.matches("md-error:").count(), 1);
+    assert_eq!(result.errors.len(), 1);
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(
+        result2.output.trim(),
+        expected.trim(),
+        "Not idempotent: running on expected output produced different result"
+    );
+}
+
+#[test]
+fn test_unclosed_error_comment_keeps_the_rest_of_the_document() {
+    let input = "| A | B |\n| --- | --- |\n| 1 | |\n<!-- md-table: B1 = A1 * 2 -->\n\
+                 <!-- md-error: never closed\n\n\
+                 More prose.\n\n\
+                 | C |\n| --- |\n| 3 |\n";
+    let result = format_tables(input);
+    assert!(!result.has_errors(), "{:?}", result.errors);
+    assert!(result.output.contains("| 1   | 2   |"));
+    assert!(result.output.contains("<!-- md-error: never closed\n"));
+    assert!(result.output.contains("\nMore prose.\n"));
+    assert!(result.output.contains("| C   |\n| --- |\n| 3   |"));
+
+    // Running again leaves the output as it is
+    assert_eq!(format_tables(&result.output).output, result.output);
+}
+
+#[test]
+fn test_error_identifies_formula_in_multi_formula_comment() {
+    let input = fs::read_to_string("tests/table/fixtures/error_multi_formula_index_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/error_multi_formula_index_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    // Only the third formula fails, and the error says so
+    assert_eq!(result.errors.len(), 1);
+    assert!(result
+        .output
+        .contains("<!-- md-error: formula 3 \"D4 = sum(E_)\":"));
+    assert!(result.output.contains("column vector E_ is out of bounds"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(
+        result2.output.trim(),
+        expected.trim(),
+        "Not idempotent: running on expected output produced different result"
+    );
+}
+
+#[test]
+fn test_overwrite_text_cell_warns() {
+    let input = fs::read_to_string("tests/table/fixtures/overwrite_warning_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/overwrite_warning_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    // The assignment still happens and the warning does not fail the run
+    assert!(result.output.contains("| 1650 |"));
+    assert!(result.output.contains("<!-- md-warning: formula 1 \"A3 = sum(B1:B2)\": overwrote non-numeric cell A3 (\"TOTAL\") -->"));
+    assert!(!result.has_errors());
+
+    // Once the label is gone the stale warning is dropped
+    let result2 = format_tables(&expected);
+    assert!(!result2.output.contains("md-warning:"));
+}
+
+#[test]
+fn test_overwrite_allow_silences_warning() {
+    let input = fs::read_to_string("tests/table/fixtures/overwrite_allow_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/overwrite_allow_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    assert!(!result.output.contains("md-warning:"));
+    assert!(!result.has_errors());
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output.trim(), expected.trim());
+}
+
+#[test]
+fn test_column_alignment() {
+    let input = fs::read_to_string("tests/table/fixtures/alignment_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/alignment_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_align_auto() {
+    let input = fs::read_to_string("tests/table/fixtures/align_auto_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/align_auto_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // The formula-filled Total column is numeric once the formulas have run
+    assert!(result
+        .output
+        .contains("| ------ | ----- | ---: | :----- | ------: |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_paren_negatives() {
+    let input = fs::read_to_string("tests/table/fixtures/paren_negatives_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/paren_negatives_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Idempotency check: parenthesized negatives are read back as negative numbers
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_pipeless_tables_normalized() {
+    let input = fs::read_to_string("tests/table/fixtures/pipeless_normalize_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/pipeless_normalize_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_pipeless_table_without_spaces() {
+    let result = format_tables("A | B\n---|---\n1 | 22\n");
+    assert!(!result.has_errors());
+    assert_eq!(
+        result.output,
+        "| A   | B   |\n| --- | --- |\n| 1   | 22  |\n"
+    );
+}
+
+#[test]
+fn test_pipeless_tables_preserved() {
+    let input = fs::read_to_string("tests/table/fixtures/pipeless_preserve_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/pipeless_preserve_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    assert!(result.output.contains("Apple |    3 |  1.50 |  4.50\n"));
+    // Formulas address the same columns in both styles
+    assert!(result.output.contains("| Apple |    3 |  1.50 |  4.50 |\n"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_max_col_width() {
+    let input = fs::read_to_string("tests/table/fixtures/max_col_width_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/max_col_width_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // Overflowing cells keep their full text
+    assert!(result
+        .output
+        .contains("| Roll out the new release to staging, then production after sign-off |"));
+
+    // The ellipsis cuts the display only; the full text is kept after the directive
+    assert!(result
+        .output
+        .contains("| Deploy | Ann   | Roll out th… |\n"));
+    assert!(result.output.contains(
+        "<!-- md-cell: C1 = Roll out the new release to staging, then production after sign-off -->"
+    ));
+
+    // Idempotency check over repeated runs: truncated cells are not cut further
+    let result2 = format_tables(&result.output);
+    assert_eq!(result2.output, expected);
+    let result3 = format_tables(&result2.output);
+    assert_eq!(result3.output, expected);
+
+    // The full text is still there after three runs: dropping the ellipsis shows it again
+    let result4 = format_tables(&result3.output.replace("; wrap=ellipsis", ""));
+    assert!(!result4.output.contains("md-cell"));
+    assert_eq!(
+        result4
+            .output
+            .matches(
+                "| Deploy | Ann   | Roll out the new release to staging, then production after sign-off |\n"
+            )
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn test_previous_row_references() {
+    let input = fs::read_to_string("tests/table/fixtures/previous_row_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/previous_row_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    assert!(result.output.contains("| Thu  | -20    | 95.50   |\n"));
+
+    // Idempotency check: balances are recomputed from the top, not accumulated
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_compact_style() {
+    let input = fs::read_to_string("tests/table/fixtures/compact_style_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/compact_style_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    assert!(result.output.contains("|---|:---:|---:|---|\n"));
+    assert!(result.output.contains("| Watermelon | 1 | 4.25 | 4.25 |\n"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_compact_default_option() {
+    let input = fs::read_to_string("tests/table/fixtures/compact_style_input.md")
+        .expect("Failed to read input fixture");
+    let options = FormatOptions {
+        compact: true,
+        ..FormatOptions::default()
+    };
+
+    // Unmarked tables become compact; style=aligned still wins
+    let result = format_tables_with_options(&input, &options);
+    assert!(!result.has_errors());
+    assert!(result
+        .output
+        .contains("## Aligned by default\n\n| a | bb |\n|---|---|\n| long cell | x |\n"));
+    assert!(result
+        .output
+        .contains("| long cell | x   |\n<!-- md-table: style=aligned -->"));
+}
+
+#[test]
+fn test_pipeless_default_option() {
+    let input = "| Item | Qty |\n|---|---|\n| Fig | 2 |\n";
+    let options = FormatOptions {
+        pipeless: true,
+        ..FormatOptions::default()
+    };
+
+    let result = format_tables_with_options(input, &options);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, "Item | Qty\n---- | ---\nFig  | 2\n");
+    assert_eq!(
+        format_tables_with_options(&result.output, &options).output,
+        result.output
+    );
+}
+
+#[test]
+fn test_max_col_width_default_option() {
+    let input =
+        "| Task | Notes |\n|---|---|\n| Deploy | Roll out the new release |\n| Test | Smoke |\n";
+    let options = FormatOptions {
+        max_col_width: Some(8),
+        ..FormatOptions::default()
+    };
+
+    // Tables without a directive use the caller's cap
+    let result = format_tables_with_options(input, &options);
+    assert!(!result.has_errors());
+    assert_eq!(
+        result.output,
+        "| Task   | Notes    |\n| ------ | -------- |\n| Deploy | Roll out the new release |\n| Test   | Smoke    |\n"
+    );
+
+    // With an ellipsis the full text is kept, so formatting again changes nothing
+    let options = FormatOptions {
+        ellipsis: true,
+        ..options
+    };
+    let result = format_tables_with_options(input, &options);
+    assert_eq!(
+        result.output,
+        "| Task   | Notes    |\n| ------ | -------- |\n| Deploy | Roll ou… |\n| Test   | Smoke    |\n<!-- md-cell: B1 = Roll out the new release -->\n"
+    );
+    let result2 = format_tables_with_options(&result.output, &options);
+    assert_eq!(result2.output, result.output);
+}
+
+#[test]
+fn test_ragged_rows_padded() {
+    let input = fs::read_to_string("tests/table/fixtures/ragged_rows_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/ragged_rows_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // The short row's missing cells read as empty, so the column formula succeeds
+    assert!(result.output.contains("| Pear  | 2   |       | 0     |\n"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_unicode_display_width() {
+    let input = fs::read_to_string("tests/table/fixtures/unicode_width_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/unicode_width_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_pipes_inside_code_spans() {
+    let input = fs::read_to_string("tests/table/fixtures/code_span_pipes_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/code_span_pipes_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // The numeric columns after the code span are still addressable by formulas
+    assert!(result
+        .output
+        .contains("| `ls | wc -l`        | 3    | 0.5     | 1.5   |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_sort_directive() {
+    let input = fs::read_to_string("tests/table/fixtures/sort_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/sort_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_sort_multiple_keys() {
+    let input = fs::read_to_string("tests/table/fixtures/sort_multi_key_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/sort_multi_key_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // E1 is written to the first row after sorting
+    assert!(result
+        .output
+        .contains("| East   | Eve | 6     | 1100    | 1    |\n"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_filter_rows() {
+    let input = fs::read_to_string("tests/table/fixtures/filter_rows_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/filter_rows_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // Filtered rows are gone for good, not hidden
+    assert!(!result.output.contains("Bolts"));
+    assert!(!result.output.contains("Review"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_totals_row() {
+    let input = fs::read_to_string("tests/table/fixtures/totals_row_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/totals_row_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // The stale totals row was replaced rather than kept alongside the new one
+    assert_eq!(result.output.matches("| Grand total |").count(), 1);
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_transpose_directive() {
+    let input = fs::read_to_string("tests/table/fixtures/transpose_directive_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/transpose_directive_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Not idempotent by design: every run transposes again, so a second run
+    // restores the original layout
+    let result2 = format_tables(&expected);
+    assert!(!result2.has_errors());
+    assert_eq!(result2.output, input);
+}
+
+#[test]
+fn test_column_edits() {
+    let input = fs::read_to_string("tests/table/fixtures/column_edits_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/column_edits_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+    // The edits were consumed; only the formulas remain
+    assert!(!result.output.contains("addcol="));
+    assert!(!result.output.contains("dropcol="));
+    assert!(!result.output.contains("movecol="));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_outer_product_range_assignment() {
+    let input = fs::read_to_string("tests/table/fixtures/outer_product_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/outer_product_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // A 3×1 column @ a 1×3 row fills the whole 3×3 range
+    let (_, quantities) = result.output.split_once("in every size:").unwrap();
+    let prices = Table::parse(quantities).unwrap();
+    assert_eq!(prices.cell(0, 1), Some("1.5"));
+    assert_eq!(prices.cell(1, 2), Some("4"));
+    assert_eq!(prices.cell(2, 3), Some("7.5"));
+
+    let (_, squares) = result.output.split_once("own transpose:").unwrap();
+    let squares = Table::parse(squares).unwrap();
+    assert_eq!(squares.cell(0, 1), Some("1"));
+    assert_eq!(squares.cell(1, 3), Some("-6"));
+    assert_eq!(squares.cell(2, 3), Some("9"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output, expected);
+}
+
+#[test]
+fn test_transpose_table() {
+    let input = fs::read_to_string("tests/table/fixtures/transpose_table_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/transpose_table_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = transpose_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output, expected);
+
+    // Transposing twice restores the original layout (with the padded cell)
+    let result2 = transpose_tables(&result.output);
+    assert!(result2.output.contains("| North  | 120 | 135 | 150 |"));
+    assert!(result2.output.contains("| South  | 80  | 95  |     |"));
+}
+
+#[test]
+fn test_document_constants_shared_across_tables() {
+    let input = fs::read_to_string("tests/table/fixtures/constants_shared_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/constants_shared_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output.trim(), expected.trim());
+    // Both tables use the shared tax rate; the second shadows shipping with a let
+    assert!(result.output.contains("| Lamp | 40    | 48.20  |"));
+    assert!(result.output.contains("| Chair | 50    | 54.00 |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output.trim(), expected.trim());
+}
+
+#[test]
+fn test_document_constant_redefinition_error() {
+    let input = fs::read_to_string("tests/table/fixtures/constants_redefined_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/constants_redefined_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    assert_eq!(result.errors.len(), 1);
+    assert_eq!(result.errors[0].line, 8);
+    // The error follows the second definition and the first value is kept
+    assert!(result.output.contains(
+        "<!-- md-table-const: rate = 10; bonus = rate + 1 -->\n<!-- md-error: constant 'rate' is already defined -->"
+    ));
+    assert!(result.output.contains("| 3   | 6   |"));
+
+    // Idempotency check: the error comment is regenerated, not duplicated
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output.trim(), expected.trim());
+}
+
+#[test]
+fn test_variables_from_slice() {
+    let input = fs::read_to_string("tests/table/fixtures/variables_from_slice_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/variables_from_slice_expected.md")
+        .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert!(!result.has_errors());
+    assert_eq!(result.output.trim(), expected.trim());
+    // Slices use the matrix's own coordinates, not the source table's
+    assert!(result.output.contains("| Q1 total    | 120   |"));
+    assert!(result.output.contains("| South Q2:Q3 | 110   |"));
+    assert!(result.output.contains("| Corner      | 90    |"));
+
+    // Idempotency check: command(expected) should equal expected
+    let result2 = format_tables(&expected);
+    assert_eq!(result2.output.trim(), expected.trim());
+}
+
+#[test]
+fn test_variables_from_slice_out_of_bounds() {
+    let input = fs::read_to_string("tests/table/fixtures/variables_from_slice_error_input.md")
+        .expect("Failed to read input fixture");
+    let expected =
+        fs::read_to_string("tests/table/fixtures/variables_from_slice_error_expected.md")
+            .expect("Failed to read expected fixture");
+
+    let result = format_tables(&input);
+    assert_eq!(result.output.trim(), expected.trim());
+    assert!(result
+        .output
+        .contains("range is out of bounds for variable 'm' with shape (2×2)"));
+}
+
+#[test]
+fn test_check_formatted_input() {
+    let formatted = fs::read_to_string("tests/table/fixtures/totals_row_expected.md")
+        .expect("Failed to read expected fixture");
+
+    for flag in ["--check", "--diff"] {
+        let (code, stdout, stderr) = run_md_table(&[flag], &formatted);
+        assert_eq!(code, Some(0), "{}", flag);
+        assert_eq!(stdout, "");
+        assert_eq!(stderr, "");
+    }
+}
+
+#[test]
+fn test_check_reports_changed_tables() {
+    let input = fs::read_to_string("tests/table/fixtures/outer_product_input.md")
+        .expect("Failed to read input fixture");
+
+    let (code, stdout, _) = run_md_table(&["--check"], &input);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "3 tables would be reformatted\n");
+
+    // Only the table with a stale formula result needs updating
+    let formatted = fs::read_to_string("tests/table/fixtures/outer_product_expected.md")
+        .expect("Failed to read expected fixture");
+    let stale = formatted.replacen("| 3   | 3   | -6  | 9   |", "| 3   | 3   | -6  | 0   |", 1);
+    let (code, stdout, _) = run_md_table(&["--check"], &stale);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "1 table would be reformatted\n");
+}
+
+#[test]
+fn test_check_diff() {
+    let input = fs::read_to_string("tests/table/fixtures/totals_row_input.md")
+        .expect("Failed to read input fixture");
+    let expected = fs::read_to_string("tests/table/fixtures/check_totals_row_expected.diff")
+        .expect("Failed to read expected fixture");
+
+    let (code, stdout, _) = run_md_table(&["--diff"], &input);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, expected);
+}
+
+#[test]
+fn test_check_formula_errors() {
+    let input = fs::read_to_string("tests/table/fixtures/error_unknown_function_expected.md")
+        .expect("Failed to read expected fixture");
+
+    // The document already shows the error, but it still fails the check
+    let (code, stdout, stderr) = run_md_table(&["--check"], &input);
+    assert_eq!(code, Some(65));
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("unknown function: 'foo'"));
+}
+
+/// Cell texts that a number-aware formatter could be tempted to normalize
+const TRICKY_CELLS: &[&str] = &[
+    "007",
+    "1.50",
+    "+3",
+    "-0",
+    "1e3",
+    "0.000",
+    "(12)",
+    "8%",
+    "$1,200.00",
+    "1_000",
+    ".5",
+    "1.",
+    "0x1F",
+    "NaN",
+    "Ünïcödé",
+    "日本語",
+    "🎉 party",
+    "a \\| b",
+    "`x | y`",
+    "**bold**",
+    "two  spaces",
+    "",
+];
+
+/// A small deterministic xorshift generator, so failures are reproducible
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+/// Builds a random table of tricky cells padded with random spaces, along with
+/// the cells it contains (header row first, separator row left out)
+fn random_table(rng: &mut Xorshift) -> (String, Vec<Vec<String>>) {
+    let cols = 1 + rng.below(4);
+    let rows = 1 + rng.below(5);
+    let mut cells = Vec::new();
+    let mut text = String::new();
+
+    for row in 0..=rows {
+        let mut line = String::from("|");
+        let mut row_cells = Vec::new();
+        for _ in 0..cols {
+            let cell = TRICKY_CELLS[rng.below(TRICKY_CELLS.len())];
+            let left = " ".repeat(rng.below(3));
+            let right = " ".repeat(rng.below(4));
+            line.push_str(&format!("{}{}{}|", left, cell, right));
+            row_cells.push(cell.to_string());
+        }
+        text.push_str(&line);
+        text.push('\n');
+        if row == 0 {
+            text.push_str(&"|---".repeat(cols));
+            text.push_str("|\n");
+        }
+        cells.push(row_cells);
+    }
+
+    (text, cells)
+}
+
+/// Header and data cells of a formatted table, in order
+fn table_cells(text: &str) -> Vec<Vec<String>> {
+    let table = Table::parse(text).expect("formatted output should contain a table");
+    std::iter::once(table.headers().to_vec())
+        .chain(table.rows().iter().cloned())
+        .collect()
+}
+
+#[test]
+fn test_untouched_cells_are_preserved() {
+    let mut rng = Xorshift(0x5eed_1327);
+
+    for _ in 0..300 {
+        let (input, cells) = random_table(&mut rng);
+        let result = format_tables(&input);
+        assert!(!result.has_errors(), "input:\n{}", input);
+
+        // With no formulas every cell keeps its text exactly (as a multiset
+        // and, since nothing reorders rows, in place)
+        let formatted = table_cells(&result.output);
+        let mut before: Vec<&String> = cells.iter().flatten().collect();
+        let mut after: Vec<&String> = formatted.iter().flatten().collect();
+        assert_eq!(formatted, cells, "input:\n{}", input);
+        before.sort();
+        after.sort();
+        assert_eq!(after, before);
+    }
+}
+
+#[test]
+fn test_only_formula_targets_are_rewritten() {
+    let mut rng = Xorshift(0x1327_f00d);
+
+    for _ in 0..300 {
+        let (table, mut cells) = random_table(&mut rng);
+        let cols = cells[0].len();
+        // Write the first data cell and (in wider tables) the whole last column
+        let mut formulas = vec!["A1 = 0.50 * 3".to_string()];
+        if cols > 1 {
+            let last_col = (b'A' + cols as u8 - 1) as char;
+            formulas.push(format!("{}_ = A_ * 0 + 7", last_col));
+            for row in cells.iter_mut().skip(1) {
+                row[cols - 1] = "7".to_string();
+            }
+        }
+        cells[1][0] = "1.50".to_string();
+        let input = format!("{}<!-- md-table: {} -->\n", table, formulas.join("; "));
+
+        let result = format_tables(&input);
+        assert!(!result.has_errors(), "input:\n{}", input);
+        assert_eq!(table_cells(&result.output), cells, "input:\n{}", input);
+    }
+}